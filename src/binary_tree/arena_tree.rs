@@ -0,0 +1,483 @@
+use super::*;
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArenaTreeError {
+    #[error("node {0} has no parent and cannot be pruned")]
+    NotPrunable(usize),
+
+    #[error("node {0} is not the root of a pruned subtree")]
+    NotDetached(usize),
+
+    #[error("node {0} is not part of the tree")]
+    NotInTree(usize),
+}
+
+#[derive(Debug, Clone)]
+struct ArenaNode {
+    parent: Option<usize>,
+    children: Option<(usize, usize)>,
+    label: Option<Label>,
+    hash: u64,
+}
+
+/// Mutable binary tree stored as an array of nodes with parent pointers, intended for local
+/// search. It supports subtree prune and regraft (SPR) moves, split into [`ArenaTree::prune`]
+/// and [`ArenaTree::attach`], and maintains a Merkle-style hash of every subtree: the hash of a
+/// leaf depends only on its label and the hash of an inner node only on the (unordered) hashes
+/// of its children. Hence, two trees with the same topology (regardless of the order of
+/// children) have the same [`ArenaTree::hash`], while different trees collide only with
+/// probability about `2^-64`.
+///
+/// After each modification only the hashes on the path to the root are recomputed, so a move
+/// takes time proportional to the depth of the modified node and the hash of the resulting
+/// tree is available in constant time, e.g. to look it up in a tabu set.
+///
+/// Nodes are addressed by their index, which stays valid across modifications; the inner
+/// node removed by [`ArenaTree::prune`] is reused by the next [`ArenaTree::attach`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+/// use std::collections::HashSet;
+///
+/// let tree: BinTree = "((1,2),(3,4));".parse().unwrap();
+/// let mut arena = ArenaTree::new(tree.top_down());
+/// let mut tabu = HashSet::from([arena.hash()]);
+///
+/// // move leaf 3 next to leaf 1
+/// let (l1, l3) = (arena.leaf(Label(1)).unwrap(), arena.leaf(Label(3)).unwrap());
+/// arena.spr(l3, l1).unwrap();
+/// assert_eq!(arena.top_down().to_newick_string(), "(((1,3),2),4);");
+/// assert!(tabu.insert(arena.hash()));
+///
+/// // moving it back onto the edge above leaf 4 restores the original topology
+/// arena.spr(l3, arena.leaf(Label(4)).unwrap()).unwrap();
+/// assert_eq!(arena.top_down().to_newick_string(), "((1,2),(4,3));");
+/// assert!(!tabu.insert(arena.hash()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ArenaTree {
+    nodes: Vec<ArenaNode>,
+    leaves: HashMap<Label, usize>,
+    free: Vec<usize>,
+    root: usize,
+}
+
+impl ArenaTree {
+    /// Copies the tree below `root`; nodes are numbered in preorder, so `root` has index 0
+    pub fn new<C: TopDownCursor>(root: C) -> Self {
+        let mut nodes: Vec<ArenaNode> = Vec::new();
+        let mut leaves = HashMap::new();
+
+        let mut stack: Vec<(C, Option<(usize, bool)>)> = vec![(root, None)];
+        while let Some((cursor, parent)) = stack.pop() {
+            let id = nodes.len();
+            if let Some((p, is_left)) = parent {
+                let children = nodes[p].children.get_or_insert((usize::MAX, usize::MAX));
+                if is_left {
+                    children.0 = id;
+                } else {
+                    children.1 = id;
+                }
+            }
+
+            nodes.push(ArenaNode {
+                parent: parent.map(|(p, _)| p),
+                children: None,
+                label: None,
+                hash: 0,
+            });
+
+            match cursor.visit() {
+                NodeType::Inner(l, r) => {
+                    stack.push((r, Some((id, false))));
+                    stack.push((l, Some((id, true))));
+                }
+                NodeType::Leaf(label) => {
+                    nodes[id].label = Some(label);
+                    leaves.insert(label, id);
+                }
+            }
+        }
+
+        let mut tree = Self {
+            nodes,
+            leaves,
+            free: Vec::new(),
+            root: 0,
+        };
+
+        // in preorder every parent precedes its children
+        for u in (0..tree.nodes.len()).rev() {
+            tree.nodes[u].hash = tree.compute_hash(u);
+        }
+
+        tree
+    }
+
+    /// Index of the root node
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    /// Returns a cursor pointing to the root
+    pub fn top_down(&self) -> ArenaCursor<'_> {
+        self.cursor(self.root)
+    }
+
+    /// Returns a cursor pointing to node `u`, which may also be the root of a pruned subtree;
+    /// visiting the cursor panics if `u` is not a node of the arena
+    pub fn cursor(&self, u: usize) -> ArenaCursor<'_> {
+        ArenaCursor {
+            tree: self,
+            node: u,
+        }
+    }
+
+    /// Returns the node of the leaf with `label`, if any
+    pub fn leaf(&self, label: Label) -> Option<usize> {
+        self.leaves.get(&label).copied()
+    }
+
+    /// Returns the parent of `u`, which is `None` for the root and roots of pruned subtrees;
+    /// panics if `u` is not a node of the arena
+    pub fn parent(&self, u: usize) -> Option<usize> {
+        self.nodes[u].parent
+    }
+
+    /// Returns the children iff `u` is an inner node and `None` otherwise; panics if `u` is not
+    /// a node of the arena
+    pub fn children(&self, u: usize) -> Option<(usize, usize)> {
+        self.nodes[u].children
+    }
+
+    /// Returns the label iff `u` is a leaf and `None` otherwise; panics if `u` is not a node of
+    /// the arena
+    pub fn label(&self, u: usize) -> Option<Label> {
+        self.nodes[u].label
+    }
+
+    /// Hash of the tree, i.e. [`ArenaTree::subtree_hash`] of the root
+    pub fn hash(&self) -> u64 {
+        self.nodes[self.root].hash
+    }
+
+    /// Hash of the subtree below `u`, which is independent of the order of children; panics if
+    /// `u` is not a node of the arena
+    pub fn subtree_hash(&self, u: usize) -> u64 {
+        self.nodes[u].hash
+    }
+
+    /// Detaches the subtree below `u` and splices out its former parent, i.e. the sibling of
+    /// `u` takes the place of the parent. Afterwards `u` is the root of a separate subtree that
+    /// has to be reinserted with [`ArenaTree::attach`]. Fails if `u` is not a node of the arena
+    /// or has no parent.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let tree: BinTree = "((1,2),3);".parse().unwrap();
+    /// let mut arena = ArenaTree::new(tree.top_down());
+    ///
+    /// let leaf = arena.leaf(Label(2)).unwrap();
+    /// arena.prune(leaf).unwrap();
+    /// assert_eq!(arena.top_down().to_newick_string(), "(1,3);");
+    /// assert!(arena.prune(leaf).is_err());
+    /// ```
+    pub fn prune(&mut self, u: usize) -> Result<(), ArenaTreeError> {
+        let node = self.nodes.get(u).ok_or(ArenaTreeError::NotInTree(u))?;
+        let p = node.parent.ok_or(ArenaTreeError::NotPrunable(u))?;
+        let (l, r) = self.nodes[p].children.unwrap();
+        let sibling = if l == u { r } else { l };
+        let grandparent = self.nodes[p].parent;
+
+        self.replace_child(grandparent, p, sibling);
+        self.nodes[u].parent = None;
+        self.nodes[p].parent = None;
+        self.nodes[p].children = None;
+        self.free.push(p);
+
+        if let Some(g) = grandparent {
+            self.update_hashes(g);
+        }
+        Ok(())
+    }
+
+    /// Reinserts the subtree below `u`, which has to be detached by [`ArenaTree::prune`] before,
+    /// by subdividing the edge above node `v` (or placing a new root above `v`). The new inner
+    /// node has children `v` and `u` (in this order).
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let tree: BinTree = "((1,2),3);".parse().unwrap();
+    /// let mut arena = ArenaTree::new(tree.top_down());
+    ///
+    /// let leaf = arena.leaf(Label(2)).unwrap();
+    /// arena.prune(leaf).unwrap();
+    /// arena.attach(leaf, arena.root()).unwrap();
+    /// assert_eq!(arena.top_down().to_newick_string(), "((1,3),2);");
+    /// ```
+    pub fn attach(&mut self, u: usize, v: usize) -> Result<(), ArenaTreeError> {
+        if u >= self.nodes.len()
+            || self.nodes[u].parent.is_some()
+            || u == self.root
+            || self.is_free(u)
+        {
+            return Err(ArenaTreeError::NotDetached(u));
+        }
+        if !self.in_tree(v) {
+            return Err(ArenaTreeError::NotInTree(v));
+        }
+
+        let parent = self.nodes[v].parent;
+        let node = ArenaNode {
+            parent,
+            children: Some((v, u)),
+            label: None,
+            hash: 0,
+        };
+        let w = match self.free.pop() {
+            Some(w) => {
+                self.nodes[w] = node;
+                w
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+
+        self.replace_child(parent, v, w);
+        self.nodes[u].parent = Some(w);
+        self.nodes[v].parent = Some(w);
+        self.update_hashes(w);
+        Ok(())
+    }
+
+    /// Subtree prune and regraft: moves the subtree below `u` onto the edge above `v`, which
+    /// must not be in the subtree below `u` nor be the parent of `u` (as it is spliced out).
+    /// The tree is left unchanged if the move is invalid.
+    pub fn spr(&mut self, u: usize, v: usize) -> Result<(), ArenaTreeError> {
+        let node = self.nodes.get(u).ok_or(ArenaTreeError::NotInTree(u))?;
+        let p = node.parent.ok_or(ArenaTreeError::NotPrunable(u))?;
+        if v == p || !self.in_tree(v) || self.is_ancestor(u, v) {
+            return Err(ArenaTreeError::NotInTree(v));
+        }
+
+        self.prune(u)?;
+        self.attach(u, v)
+    }
+
+    fn is_free(&self, u: usize) -> bool {
+        self.nodes[u].parent.is_none()
+            && self.nodes[u].children.is_none()
+            && self.nodes[u].label.is_none()
+    }
+
+    /// Returns `true` iff `u` is an ancestor of (or equal to) `v`
+    fn is_ancestor(&self, u: usize, mut v: usize) -> bool {
+        loop {
+            if u == v {
+                return true;
+            }
+            match self.nodes[v].parent {
+                Some(p) => v = p,
+                None => return false,
+            }
+        }
+    }
+
+    /// Returns `true` iff `v` is connected to the root (and not part of a pruned subtree)
+    fn in_tree(&self, v: usize) -> bool {
+        v < self.nodes.len() && self.is_ancestor(self.root, v)
+    }
+
+    fn replace_child(&mut self, parent: Option<usize>, old: usize, new: usize) {
+        self.nodes[new].parent = parent;
+        match parent {
+            Some(p) => {
+                let (l, r) = self.nodes[p].children.as_mut().unwrap();
+                if *l == old {
+                    *l = new;
+                } else {
+                    *r = new;
+                }
+            }
+            None if old == self.root => self.root = new,
+            None => {}
+        }
+    }
+
+    fn update_hashes(&mut self, mut u: usize) {
+        loop {
+            self.nodes[u].hash = self.compute_hash(u);
+            match self.nodes[u].parent {
+                Some(p) => u = p,
+                None => break,
+            }
+        }
+    }
+
+    fn compute_hash(&self, u: usize) -> u64 {
+        const LEAF_SEED: u64 = 0x243f_6a88_85a3_08d3;
+        const INNER_SEED: u64 = 0x1319_8a2e_0370_7344;
+
+        match self.nodes[u].children {
+            Some((l, r)) => {
+                let (a, b) = (self.nodes[l].hash, self.nodes[r].hash);
+                let (a, b) = (a.min(b), a.max(b));
+                mix64(mix64(a ^ INNER_SEED).wrapping_add(b))
+            }
+            None => {
+                // the cast is a no-op if the feature `u64-labels` is enabled
+                #[allow(clippy::unnecessary_cast)]
+                let label = self.nodes[u].label.unwrap().0 as u64;
+                mix64(label ^ LEAF_SEED)
+            }
+        }
+    }
+}
+
+/// Finalizer of SplitMix64
+fn mix64(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// [`TopDownCursor`] of [`ArenaTree`]
+#[derive(Debug, Clone, Copy)]
+pub struct ArenaCursor<'a> {
+    tree: &'a ArenaTree,
+    node: usize,
+}
+
+impl ArenaCursor<'_> {
+    /// Index of the node the cursor points to
+    pub fn node(&self) -> usize {
+        self.node
+    }
+}
+
+impl TopDownCursor for ArenaCursor<'_> {
+    fn children(&self) -> Option<(Self, Self)> {
+        let (l, r) = self.tree.children(self.node)?;
+        Some((self.tree.cursor(l), self.tree.cursor(r)))
+    }
+
+    fn leaf_label(&self) -> Option<Label> {
+        self.tree.label(self.node)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::NewickWriter;
+    use rand::{Rng, SeedableRng, seq::SliceRandom};
+    use rand_pcg::Pcg64Mcg;
+
+    fn arena(text: &str) -> ArenaTree {
+        let tree: BinTree = text.parse().unwrap();
+        ArenaTree::new(tree.top_down())
+    }
+
+    #[test]
+    fn hash_ignores_child_order() {
+        let a = arena("((1,2),(3,(4,5)));");
+        assert_eq!(a.hash(), arena("(((5,4),3),(2,1));").hash());
+        assert_ne!(a.hash(), arena("((1,3),(2,(4,5)));").hash());
+        assert_ne!(a.hash(), arena("((1,2),((3,4),5));").hash());
+        assert_ne!(arena("1;").hash(), arena("2;").hash());
+    }
+
+    #[test]
+    fn invalid_moves() {
+        let mut a = arena("((1,2),(3,4));");
+        let hash = a.hash();
+        let (l1, l3) = (a.leaf(Label(1)).unwrap(), a.leaf(Label(3)).unwrap());
+        let p1 = a.parent(l1).unwrap();
+
+        assert_eq!(a.prune(a.root()), Err(ArenaTreeError::NotPrunable(0)));
+        assert_eq!(a.spr(p1, l1), Err(ArenaTreeError::NotInTree(l1)));
+        assert_eq!(a.spr(l1, p1), Err(ArenaTreeError::NotInTree(p1)));
+        assert_eq!(a.attach(l1, l3), Err(ArenaTreeError::NotDetached(l1)));
+        assert_eq!(a.prune(100), Err(ArenaTreeError::NotInTree(100)));
+        assert_eq!(a.spr(100, l3), Err(ArenaTreeError::NotInTree(100)));
+        assert_eq!(a.spr(l1, 100), Err(ArenaTreeError::NotInTree(100)));
+        assert_eq!(a.hash(), hash);
+
+        a.prune(l1).unwrap();
+        assert_eq!(a.attach(l3, l1), Err(ArenaTreeError::NotDetached(l3)));
+        assert_eq!(a.attach(l1, l1), Err(ArenaTreeError::NotInTree(l1)));
+        assert_eq!(a.attach(p1, l3), Err(ArenaTreeError::NotDetached(p1)));
+        a.attach(l1, l3).unwrap();
+        assert_eq!(a.top_down().to_newick_string(), "(2,((3,1),4));");
+    }
+
+    #[test]
+    fn prune_below_root() {
+        let mut a = arena("(1,(2,3));");
+        let l1 = a.leaf(Label(1)).unwrap();
+        a.prune(l1).unwrap();
+        assert_eq!(a.top_down().to_newick_string(), "(2,3);");
+        assert_eq!(a.hash(), arena("(2,3);").hash());
+
+        a.attach(l1, a.leaf(Label(3)).unwrap()).unwrap();
+        assert_eq!(a.top_down().to_newick_string(), "(2,(3,1));");
+        assert_eq!(a.nodes.len(), 5);
+    }
+
+    #[test]
+    fn random_spr_moves() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0x5eed);
+        for _ in 0..20 {
+            let n = rng.random_range(3..30);
+            let mut subtrees: Vec<String> = (1..=n).map(|l| l.to_string()).collect();
+            while subtrees.len() > 1 {
+                subtrees.shuffle(&mut rng);
+                let (a, b) = (subtrees.pop().unwrap(), subtrees.pop().unwrap());
+                subtrees.push(format!("({a},{b})"));
+            }
+            let mut a = arena(&format!("{};", subtrees[0]));
+
+            for _ in 0..50 {
+                let u = rng.random_range(0..a.nodes.len());
+                let v = rng.random_range(0..a.nodes.len());
+                if a.spr(u, v).is_err() {
+                    continue;
+                }
+
+                let newick = a.top_down().to_newick_string();
+                let rebuilt = arena(&newick);
+                assert_eq!(a.hash(), rebuilt.hash(), "{newick}");
+                assert_eq!(rebuilt.nodes.len(), a.nodes.len());
+                for u in 0..a.nodes.len() {
+                    assert_eq!(a.subtree_hash(u), a.compute_hash(u));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deep_caterpillar() {
+        const N: LabelValue = 100_000;
+        let children_of = |&u: &LabelValue| (u < N).then(|| (u + 1, N + 1 + u));
+        let label_of = |&u: &LabelValue| (u >= N).then(|| Label(u - N + 1));
+        let mut a = ArenaTree::new(FnCursor::new(0, &children_of, &label_of));
+
+        // moving a leaf of the deepest cherry to the top and back restores the hash
+        let hash = a.hash();
+        let (deepest, top) = (a.leaf(Label(1)).unwrap(), a.leaf(Label(2)).unwrap());
+        let sibling = a.leaf(Label(N + 1)).unwrap();
+        a.spr(deepest, top).unwrap();
+        assert_ne!(a.hash(), hash);
+        a.spr(deepest, sibling).unwrap();
+        assert_eq!(a.hash(), hash);
+    }
+}
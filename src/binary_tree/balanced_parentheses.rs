@@ -0,0 +1,135 @@
+use super::*;
+
+/// Succinct representation of a binary tree as a sequence of balanced parentheses: each node
+/// contributes an opening parenthesis (`1` bit) when it is entered in preorder and a closing
+/// one (`0` bit) after its subtree. Hence a tree with `k` nodes requires `2k` bits plus the
+/// leaf labels (in left-to-right order). Use [`crate::newick::parse_newick_to_bp`] to
+/// create it without an intermediate pointer-based tree.
+///
+/// Navigation uses linear scans: computing the children of a node takes time proportional
+/// to the size of its left subtree. There are no inner node ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BalancedParentheses {
+    words: Vec<u64>,
+    len: usize,
+    labels: Vec<Label>,
+}
+
+impl BalancedParentheses {
+    /// Appends an opening (`true`) or closing (`false`) parenthesis
+    pub fn push(&mut self, open: bool) {
+        if self.len.is_multiple_of(64) {
+            self.words.push(0);
+        }
+        if open {
+            self.words[self.len / 64] |= 1 << (self.len % 64);
+        }
+        self.len += 1;
+    }
+
+    /// Appends a leaf, i.e. `()` and its label
+    pub fn push_leaf(&mut self, label: Label) {
+        self.push(true);
+        self.push(false);
+        self.labels.push(label);
+    }
+
+    /// Returns `true` iff the parenthesis at position `i` is an opening one
+    pub fn is_open(&self, i: usize) -> bool {
+        assert!(i < self.len);
+        (self.words[i / 64] >> (i % 64)) & 1 == 1
+    }
+
+    /// Number of parentheses, i.e. twice the number of nodes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Leaf labels from left to right
+    pub fn leaf_labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// Returns a cursor pointing to the root; the tree must not be empty.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let bp = parse_newick_to_bp("((1,2),3);").unwrap();
+    /// assert_eq!(bp.len(), 10);
+    /// assert_eq!(bp.root().to_newick_string(), "((1,2),3);");
+    /// ```
+    pub fn root(&self) -> BpCursor<'_> {
+        assert!(!self.is_empty());
+        BpCursor {
+            tree: self,
+            pos: 0,
+            leaf_rank: 0,
+        }
+    }
+
+    /// Returns the position of the parenthesis closing the one opened at `open` and the number
+    /// of leaves in between
+    fn find_close(&self, open: usize) -> (usize, usize) {
+        let mut excess = 0usize;
+        let mut leaves = 0;
+        for i in open..self.len {
+            if self.is_open(i) {
+                excess += 1;
+            } else {
+                if self.is_open(i - 1) {
+                    leaves += 1;
+                }
+                excess -= 1;
+                if excess == 0 {
+                    return (i, leaves);
+                }
+            }
+        }
+        panic!("unbalanced parentheses");
+    }
+}
+
+/// [`TopDownCursor`] of [`BalancedParentheses`]
+#[derive(Debug, Clone, Copy)]
+pub struct BpCursor<'a> {
+    tree: &'a BalancedParentheses,
+    pos: usize,
+    leaf_rank: usize,
+}
+
+impl BpCursor<'_> {
+    /// Position of the opening parenthesis of the node
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+}
+
+impl TopDownCursor for BpCursor<'_> {
+    fn children(&self) -> Option<(Self, Self)> {
+        if !self.tree.is_open(self.pos + 1) {
+            return None;
+        }
+
+        let left = Self {
+            pos: self.pos + 1,
+            ..*self
+        };
+        let (close, leaves) = self.tree.find_close(left.pos);
+        let right = Self {
+            pos: close + 1,
+            leaf_rank: self.leaf_rank + leaves,
+            ..*self
+        };
+        Some((left, right))
+    }
+
+    fn leaf_label(&self) -> Option<Label> {
+        (!self.tree.is_open(self.pos + 1)).then(|| self.tree.labels[self.leaf_rank])
+    }
+}
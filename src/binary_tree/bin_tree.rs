@@ -2,6 +2,7 @@ use super::*;
 
 /// Minimalistic implementation of a binary tree without any meta information
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinTree {
     Node(Box<(BinTree, BinTree)>),
     Leaf(Label),
@@ -29,7 +30,52 @@ impl TopDownCursor for &BinTree {
     }
 }
 
-#[derive(Default)]
+impl std::str::FromStr for BinTree {
+    type Err = crate::newick::ParserError;
+
+    /// Parses a single tree in strict Newick format
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::binary_tree::*;
+    ///
+    /// let tree: BinTree = "(1,(2,3));".parse().unwrap();
+    /// assert_eq!(tree.top_down().left_child().unwrap().leaf_label(), Some(Label(1)));
+    /// assert!("(1,(2,3))".parse::<BinTree>().is_err());
+    /// ```
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_single_tree::<BinTreeBuilder>(text)
+    }
+}
+
+/// Writes the tree as minimal Newick string, e.g. `((1,2),3);`
+impl std::fmt::Display for BinTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use crate::newick::NewickWriter;
+        self.top_down().write_newick_fmt(f)
+    }
+}
+
+/// Parses a [`BinTree`] from a Newick string and panics if it is invalid; intended for
+/// tests and examples.
+///
+/// # Example
+/// ```
+/// use pace26io::{bin_tree, binary_tree::*, newick::NewickWriter};
+///
+/// let tree = bin_tree!("((1,2),3);");
+/// assert_eq!(tree.top_down().to_newick_string(), "((1,2),3);");
+/// ```
+#[macro_export]
+macro_rules! bin_tree {
+    ($text:expr) => {
+        $text
+            .parse::<$crate::binary_tree::BinTree>()
+            .expect("valid Newick string")
+    };
+}
+
+#[derive(Debug, Default)]
 pub struct BinTreeBuilder();
 
 impl TreeBuilder for BinTreeBuilder {
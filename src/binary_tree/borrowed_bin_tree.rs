@@ -0,0 +1,99 @@
+use crate::newick::lexer::unquote_label;
+use std::borrow::Cow;
+
+/// Binary tree whose leaves are taxon names borrowed from the input it was parsed from; use
+/// [`crate::newick::parse_newick_borrowed`] to create it. In contrast to interning names (see
+/// [`crate::newick::LabelKind::Names`]), no label is copied, which suits read-only analyses
+/// of large inputs. Each leaf stores its label as written in the input, i.e. including the
+/// quotes and escapes of quoted labels; [`BorrowedBinTree::leaf_name`] decodes them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BorrowedBinTree<'a> {
+    Node(Box<(BorrowedBinTree<'a>, BorrowedBinTree<'a>)>),
+    Leaf(&'a str),
+}
+
+impl<'a> BorrowedBinTree<'a> {
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, BorrowedBinTree::Leaf(_))
+    }
+
+    pub fn children(&self) -> Option<(&Self, &Self)> {
+        match self {
+            BorrowedBinTree::Node(children) => Some((&children.0, &children.1)),
+            BorrowedBinTree::Leaf(_) => None,
+        }
+    }
+
+    /// Returns the taxon name of a leaf, where quotes are removed and escaped quotes decoded;
+    /// only names containing escaped quotes are copied
+    pub fn leaf_name(&self) -> Option<Cow<'a, str>> {
+        match self {
+            BorrowedBinTree::Leaf(text) => Some(unquote_label(text)),
+            BorrowedBinTree::Node(_) => None,
+        }
+    }
+
+    /// Returns the labels of all leaves from left to right as written in the input
+    pub fn leaves(&self) -> impl Iterator<Item = &'a str> + '_ {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            loop {
+                match stack.pop()? {
+                    BorrowedBinTree::Node(children) => {
+                        stack.push(&children.1);
+                        stack.push(&children.0);
+                    }
+                    BorrowedBinTree::Leaf(text) => return Some(*text),
+                }
+            }
+        })
+    }
+}
+
+/// Drops the nodes iteratively, since the parser supports trees of arbitrary depth
+impl Drop for BorrowedBinTree<'_> {
+    fn drop(&mut self) {
+        let detach = |children: &mut (Self, Self)| {
+            std::mem::replace(children, (Self::Leaf(""), Self::Leaf("")))
+        };
+
+        let BorrowedBinTree::Node(children) = self else {
+            return;
+        };
+        let mut stack = vec![detach(children)];
+        while let Some((left, right)) = stack.pop() {
+            for mut child in [left, right] {
+                if let BorrowedBinTree::Node(children) = &mut child {
+                    stack.push(detach(children));
+                }
+            }
+        }
+    }
+}
+
+/// Writes the tree as Newick string with the labels as written in the input,
+/// e.g. `(('Homo sapiens',Pan),Gorilla);`
+impl std::fmt::Display for BorrowedBinTree<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        enum Step<'t, 'a> {
+            Visit(&'t BorrowedBinTree<'a>),
+            Text(&'static str),
+        }
+
+        let mut stack = vec![Step::Text(";"), Step::Visit(self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Visit(BorrowedBinTree::Node(children)) => {
+                    f.write_str("(")?;
+                    stack.push(Step::Text(")"));
+                    stack.push(Step::Visit(&children.1));
+                    stack.push(Step::Text(","));
+                    stack.push(Step::Visit(&children.0));
+                }
+                Step::Visit(BorrowedBinTree::Leaf(text)) => f.write_str(text)?,
+                Step::Text(text) => f.write_str(text)?,
+            }
+        }
+        Ok(())
+    }
+}
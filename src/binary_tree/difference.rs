@@ -0,0 +1,174 @@
+use super::*;
+use crate::newick::NewickWriter;
+
+/// Location of the first structural difference of two trees; see [`first_difference()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeDifference {
+    /// Children (`0` = left, `1` = right) taken from the root of the first tree to the
+    /// divergence point; all subtrees along the path have the same leaf sets in both trees
+    pub path_a: Vec<usize>,
+
+    /// Same as `path_a`, but for the second tree
+    pub path_b: Vec<usize>,
+
+    /// Subtree of the first tree rooted at the divergence point in Newick format
+    pub subtree_a: String,
+
+    /// Subtree of the second tree rooted at the divergence point in Newick format
+    pub subtree_b: String,
+}
+
+/// Searches the first difference of two trees on the same leaf set, ignoring the order of
+/// children. Starting at the roots, the function descends into pairs of subtrees with equal
+/// leaf sets and stops at the first pair (in preorder of `a`) whose children cannot be
+/// matched by their leaf sets. Returns `None` if the trees are equal up to the order of
+/// children.
+///
+/// If the leaf sets of the trees differ, the roots are reported as divergence point.
+///
+/// # Example
+/// ```
+/// use pace26io::binary_tree::*;
+///
+/// let a: BinTree = "(((1,2),3),(4,(5,6)));".parse().unwrap();
+/// let b: BinTree = "((4,(6,5)),((1,3),2));".parse().unwrap();
+///
+/// let diff = first_difference(&a, &b).unwrap();
+/// assert_eq!(diff.path_a, vec![0]);
+/// assert_eq!(diff.path_b, vec![1]);
+/// assert_eq!(diff.subtree_a, "((1,2),3);");
+/// assert_eq!(diff.subtree_b, "((1,3),2);");
+///
+/// assert_eq!(first_difference(&a, &a), None);
+/// ```
+pub fn first_difference<A, B>(a: A, b: B) -> Option<TreeDifference>
+where
+    A: TopDownCursor + Clone,
+    B: TopDownCursor + Clone,
+{
+    // pairs of subtrees with the same leaf sets together with the index of their parent
+    // pair and the children taken from it; the stack holds indices into `pairs`
+    let mut pairs = vec![(a, b, usize::MAX, 0, 0)];
+    let mut stack = vec![0];
+
+    while let Some(index) = stack.pop() {
+        let (a, b, ..) = &pairs[index];
+        let matched = match (a.visit(), b.visit()) {
+            (NodeType::Leaf(x), NodeType::Leaf(y)) if x == y => Some(Vec::new()),
+            (NodeType::Inner(al, ar), NodeType::Inner(bl, br)) => {
+                let (sal, sar) = (sorted_leaves(&al), sorted_leaves(&ar));
+                let (sbl, sbr) = (sorted_leaves(&bl), sorted_leaves(&br));
+                if sal == sbl && sar == sbr {
+                    Some(vec![(al, bl, 0, 0), (ar, br, 1, 1)])
+                } else if sal == sbr && sar == sbl {
+                    Some(vec![(al, br, 0, 1), (ar, bl, 1, 0)])
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        let Some(children) = matched else {
+            return Some(difference_at(&pairs, index));
+        };
+
+        // push in reverse to visit the left child of `a` first
+        for (a, b, step_a, step_b) in children.into_iter().rev() {
+            stack.push(pairs.len());
+            pairs.push((a, b, index, step_a, step_b));
+        }
+    }
+
+    None
+}
+
+fn sorted_leaves<C: TopDownCursor + Clone>(cursor: &C) -> Vec<Label> {
+    let mut leaves = leaf_order(cursor.clone());
+    leaves.sort_unstable();
+    leaves
+}
+
+fn difference_at<A: TopDownCursor, B: TopDownCursor>(
+    pairs: &[(A, B, usize, usize, usize)],
+    index: usize,
+) -> TreeDifference {
+    let (a, b, ..) = &pairs[index];
+    let mut path_a = Vec::new();
+    let mut path_b = Vec::new();
+
+    let mut current = index;
+    while current != 0 {
+        let (_, _, parent, step_a, step_b) = pairs[current];
+        path_a.push(step_a);
+        path_b.push(step_b);
+        current = parent;
+    }
+    path_a.reverse();
+    path_b.reverse();
+
+    TreeDifference {
+        path_a,
+        path_b,
+        subtree_a: a.to_newick_string(),
+        subtree_b: b.to_newick_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(a: &str, b: &str) -> Option<(Vec<usize>, Vec<usize>, String, String)> {
+        let a: BinTree = a.parse().unwrap();
+        let b: BinTree = b.parse().unwrap();
+        first_difference(&a, &b).map(|d| (d.path_a, d.path_b, d.subtree_a, d.subtree_b))
+    }
+
+    #[test]
+    fn equal_trees() {
+        assert_eq!(diff("1;", "1;"), None);
+        assert_eq!(diff("((1,2),(3,4));", "((4,3),(2,1));"), None);
+    }
+
+    #[test]
+    fn differences() {
+        assert_eq!(
+            diff("1;", "2;"),
+            Some((vec![], vec![], "1;".into(), "2;".into()))
+        );
+        assert_eq!(
+            diff("((1,2),3);", "(1,(2,3));"),
+            Some((vec![], vec![], "((1,2),3);".into(), "(1,(2,3));".into()))
+        );
+        assert_eq!(
+            diff("(5,(4,((1,2),3)));", "((4,(2,(1,3))),5);"),
+            Some((
+                vec![1, 1],
+                vec![0, 1],
+                "((1,2),3);".into(),
+                "(2,(1,3));".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn deep_caterpillar() {
+        let n = 2000;
+        let caterpillar = |last: u32| {
+            let mut text = String::from("1");
+            for i in 2..n {
+                text = format!("({text},{i})");
+            }
+            format!("({text},{last});")
+        };
+
+        let a: BinTree = caterpillar(n).parse().unwrap();
+        let b: BinTree = caterpillar(n + 1).parse().unwrap();
+        assert_eq!(first_difference(&a, &a), None);
+        assert_eq!(
+            first_difference(&a, &b).unwrap().path_a,
+            Vec::<usize>::new()
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use super::*;
+
+/// Child endpoint of an edge emitted by [`EdgeListBuilder`]; also the node type of the builder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeTarget {
+    Inner(NodeIdx),
+    Leaf(Label),
+}
+
+/// A [`TreeBuilder`] that does not construct a tree, but passes each edge `(parent, child)`
+/// to a callback as soon as the parent is created. Since the parser creates a node after
+/// all of its descendants, edges are emitted in postorder, the left edge of each node
+/// before its right edge. The node returned by the parser is the root.
+///
+/// This is useful to, e.g., fill CSR-style adjacency arrays without an intermediate tree.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::BinaryTreeParser};
+///
+/// let mut edges = Vec::new();
+/// let root = EdgeListBuilder::new(|parent, child| edges.push((parent, child)))
+///     .parse_newick_from_str("((1,2),3);", NodeIdx::new(4))
+///     .unwrap();
+///
+/// assert_eq!(root, EdgeTarget::Inner(NodeIdx::new(4)));
+/// assert_eq!(
+///     edges,
+///     vec![
+///         (NodeIdx::new(5), EdgeTarget::Leaf(Label(1))),
+///         (NodeIdx::new(5), EdgeTarget::Leaf(Label(2))),
+///         (NodeIdx::new(4), EdgeTarget::Inner(NodeIdx::new(5))),
+///         (NodeIdx::new(4), EdgeTarget::Leaf(Label(3))),
+///     ]
+/// );
+/// ```
+pub struct EdgeListBuilder<F: FnMut(NodeIdx, EdgeTarget)> {
+    on_edge: F,
+}
+
+impl<F: FnMut(NodeIdx, EdgeTarget)> EdgeListBuilder<F> {
+    pub fn new(on_edge: F) -> Self {
+        Self { on_edge }
+    }
+
+    pub fn into_inner(self) -> F {
+        self.on_edge
+    }
+}
+
+impl<F: FnMut(NodeIdx, EdgeTarget)> TreeBuilder for EdgeListBuilder<F> {
+    type Node = EdgeTarget;
+
+    fn new_inner(&mut self, id: NodeIdx, left: Self::Node, right: Self::Node) -> Self::Node {
+        (self.on_edge)(id, left);
+        (self.on_edge)(id, right);
+        EdgeTarget::Inner(id)
+    }
+
+    fn new_leaf(&mut self, label: Label) -> Self::Node {
+        EdgeTarget::Leaf(label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::newick::BinaryTreeParser;
+
+    #[test]
+    fn same_edges_as_tree() {
+        let text = "(((4,2),(7,1)),(8,(3,(5,6))));";
+        let mut edges = Vec::new();
+        let root = EdgeListBuilder::new(|parent, child| edges.push((parent, child)))
+            .parse_newick_from_str(text, NodeIdx::new(9))
+            .unwrap();
+        assert_eq!(root, EdgeTarget::Inner(NodeIdx::new(9)));
+        assert_eq!(edges.len(), 2 * 7);
+
+        let tree = IndexedBinTreeBuilder::default()
+            .parse_newick_from_str(text, NodeIdx::new(9))
+            .unwrap();
+        let mut expected = Vec::new();
+        let mut stack = vec![tree.top_down()];
+        while let Some(node) = stack.pop() {
+            if let Some((left, right)) = node.children() {
+                for child in [&left, &right] {
+                    let target = match child.leaf_label() {
+                        Some(label) => EdgeTarget::Leaf(label),
+                        None => EdgeTarget::Inner(child.node_idx()),
+                    };
+                    expected.push((node.node_idx(), target));
+                }
+                stack.extend([left, right]);
+            }
+        }
+
+        edges.sort_unstable_by_key(|&(parent, child)| (parent, format!("{child:?}")));
+        expected.sort_unstable_by_key(|&(parent, child)| (parent, format!("{child:?}")));
+        assert_eq!(edges, expected);
+
+        let single = EdgeListBuilder::new(|_, _| panic!("a leaf has no edges"))
+            .parse_newick_from_str("1;", NodeIdx::new(2))
+            .unwrap();
+        assert_eq!(single, EdgeTarget::Leaf(Label(1)));
+    }
+}
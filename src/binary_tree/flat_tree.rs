@@ -0,0 +1,138 @@
+use super::*;
+
+pub(crate) struct FlatNode {
+    pub parent: Option<usize>,
+    pub children: Option<(usize, usize)>,
+    pub label: Option<Label>,
+    pub size: u64,
+    pub depth: usize,
+}
+
+/// Array representation of a tree in which the nodes are initially stored in preorder.
+/// Used internally by algorithms that need parent pointers or random access.
+///
+/// Algorithms modifying the topology only maintain `parent`, `children`, and `root`;
+/// afterwards `size`, `depth`, `leaves`, and the preorder guarantee are stale.
+pub(crate) struct FlatTree {
+    pub nodes: Vec<FlatNode>,
+    pub leaves: Vec<(Label, usize)>,
+    pub root: usize,
+}
+
+impl FlatTree {
+    pub fn new<C: TopDownCursor>(root: C) -> Self {
+        let mut nodes: Vec<FlatNode> = Vec::new();
+        let mut leaves = Vec::new();
+
+        let mut stack: Vec<(C, Option<(usize, bool)>)> = vec![(root, None)];
+        while let Some((cursor, parent)) = stack.pop() {
+            let id = nodes.len();
+            let mut depth = 0;
+            if let Some((p, is_left)) = parent {
+                depth = nodes[p].depth + 1;
+                let children = nodes[p].children.get_or_insert((usize::MAX, usize::MAX));
+                if is_left {
+                    children.0 = id;
+                } else {
+                    children.1 = id;
+                }
+            }
+
+            let label = cursor.leaf_label();
+            nodes.push(FlatNode {
+                parent: parent.map(|(p, _)| p),
+                children: None,
+                label,
+                size: 1,
+                depth,
+            });
+
+            match cursor.visit() {
+                NodeType::Inner(l, r) => {
+                    stack.push((r, Some((id, false))));
+                    stack.push((l, Some((id, true))));
+                }
+                NodeType::Leaf(label) => leaves.push((label, id)),
+            }
+        }
+
+        // in preorder every parent precedes its children
+        for u in (0..nodes.len()).rev() {
+            if let Some((l, r)) = nodes[u].children {
+                nodes[u].size = nodes[l].size + nodes[r].size;
+            }
+        }
+
+        Self {
+            nodes,
+            leaves,
+            root: 0,
+        }
+    }
+
+    /// Nodes ordered such that children are visited before their parent
+    pub fn post_order(&self) -> impl Iterator<Item = usize> {
+        (0..self.nodes.len()).rev()
+    }
+
+    /// Returns the lowest common ancestor of nodes `u` and `v`
+    pub fn lca(&self, mut u: usize, mut v: usize) -> usize {
+        while self.nodes[u].depth > self.nodes[v].depth {
+            u = self.nodes[u].parent.unwrap();
+        }
+        while self.nodes[v].depth > self.nodes[u].depth {
+            v = self.nodes[v].parent.unwrap();
+        }
+        while u != v {
+            u = self.nodes[u].parent.unwrap();
+            v = self.nodes[v].parent.unwrap();
+        }
+        u
+    }
+
+    /// Builds a copy of the tree where the children of inner node `u` are swapped iff `swap(u)`.
+    /// Inner nodes receive ids in preorder starting with `root_id` (as assigned by the Newick
+    /// parser). Also returns the leaf labels of the new tree from left to right.
+    pub fn build<B: TreeBuilder>(
+        &self,
+        builder: &mut B,
+        root_id: NodeIdx,
+        mut swap: impl FnMut(usize) -> bool,
+    ) -> (B::Node, Vec<Label>) {
+        enum Step {
+            Enter(usize),
+            Exit(NodeIdx),
+        }
+
+        let mut leaf_order = Vec::with_capacity(self.leaves.len());
+        let mut next_id = root_id;
+        let mut nodes: Vec<B::Node> = Vec::new();
+        let mut stack = vec![Step::Enter(self.root)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(u) => match self.nodes[u].children {
+                    Some((l, r)) => {
+                        let (first, second) = if swap(u) { (r, l) } else { (l, r) };
+                        stack.push(Step::Exit(next_id));
+                        stack.push(Step::Enter(second));
+                        stack.push(Step::Enter(first));
+                        next_id = next_id.incremented();
+                    }
+                    None => {
+                        let label = self.nodes[u].label.unwrap();
+                        leaf_order.push(label);
+                        nodes.push(builder.new_leaf(label));
+                    }
+                },
+                Step::Exit(id) => {
+                    let right = nodes.pop().unwrap();
+                    let left = nodes.pop().unwrap();
+                    nodes.push(builder.new_inner(id, left, right));
+                }
+            }
+        }
+
+        let root = nodes.pop().unwrap();
+        (builder.make_root(root), leaf_order)
+    }
+}
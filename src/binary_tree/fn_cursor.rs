@@ -0,0 +1,100 @@
+use super::*;
+
+/// Adapter implementing [`TopDownCursor`] for an arbitrary tree representation given by two
+/// closures: `children_of(node)` returns the children of an inner node (and `None` for leaves)
+/// and `label_of(node)` returns the label of a leaf (and `None` for inner nodes).
+/// This allows, e.g., writing Newick strings or computing metrics of trees stored in
+/// user-defined data structures without converting them first.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// // tree ((1,2),3) stored as an array of child indices; leaves store their label
+/// enum Node { Inner(usize, usize), Leaf(LabelValue) }
+/// let nodes = [Node::Inner(1, 4), Node::Inner(2, 3), Node::Leaf(1), Node::Leaf(2), Node::Leaf(3)];
+///
+/// let children_of = |&u: &usize| match nodes[u] {
+///     Node::Inner(l, r) => Some((l, r)),
+///     Node::Leaf(_) => None,
+/// };
+/// let label_of = |&u: &usize| match nodes[u] {
+///     Node::Inner(..) => None,
+///     Node::Leaf(l) => Some(Label(l)),
+/// };
+///
+/// let cursor = FnCursor::new(0, &children_of, &label_of);
+/// assert_eq!(cursor.to_newick_string(), "((1,2),3);");
+/// ```
+pub struct FnCursor<'a, N, C, L> {
+    node: N,
+    children_of: &'a C,
+    label_of: &'a L,
+}
+
+impl<'a, N, C, L> FnCursor<'a, N, C, L>
+where
+    C: Fn(&N) -> Option<(N, N)>,
+    L: Fn(&N) -> Option<Label>,
+{
+    pub fn new(node: N, children_of: &'a C, label_of: &'a L) -> Self {
+        Self {
+            node,
+            children_of,
+            label_of,
+        }
+    }
+
+    /// Returns the node of the underlying representation the cursor points to
+    pub fn node(&self) -> &N {
+        &self.node
+    }
+
+    fn with_node(&self, node: N) -> Self {
+        Self::new(node, self.children_of, self.label_of)
+    }
+}
+
+impl<N, C, L> TopDownCursor for FnCursor<'_, N, C, L>
+where
+    C: Fn(&N) -> Option<(N, N)>,
+    L: Fn(&N) -> Option<Label>,
+{
+    fn children(&self) -> Option<(Self, Self)> {
+        let (left, right) = (self.children_of)(&self.node)?;
+        Some((self.with_node(left), self.with_node(right)))
+    }
+
+    fn leaf_label(&self) -> Option<Label> {
+        (self.label_of)(&self.node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::quartet_distance, newick::*};
+
+    #[test]
+    fn parent_array() {
+        // tree (((1,2),3),(4,5)) given by parent pointers; nodes 0..5 are leaves with label u+1
+        let parent = [5, 5, 6, 7, 7, 6, 8, 8, usize::MAX];
+        let num_leaves = 5;
+        let root = 8;
+
+        let children_of = |&u: &usize| {
+            let mut children = (0..parent.len()).filter(|&v| parent[v] == u);
+            Some((children.next()?, children.next()?))
+        };
+        let label_of = |&u: &usize| (u < num_leaves).then(|| Label(u as LabelValue + 1));
+
+        let cursor = FnCursor::new(root, &children_of, &label_of);
+        assert_eq!(cursor.to_newick_string(), "((3,(1,2)),(4,5));");
+        assert_eq!(*cursor.left_child().unwrap().node(), 6);
+
+        let tree = BinTreeBuilder::default()
+            .parse_newick_from_str("(((1,2),3),(4,5));", NodeIdx::new(0))
+            .unwrap();
+        assert_eq!(quartet_distance(&tree, cursor), Ok(0));
+    }
+}
@@ -2,6 +2,7 @@ use super::*;
 
 /// Minimalistic implementation of a binary tree without any meta information
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum IndexedBinTree {
     Node(Box<(NodeIdx, IndexedBinTree, IndexedBinTree)>),
     Leaf(Label),
@@ -11,6 +12,17 @@ impl IndexedBinTree {
     pub fn top_down(&self) -> &Self {
         self
     }
+
+    /// Returns the Newick string of the tree, where each inner node is labeled by its
+    /// [`NodeIdx`], e.g. `((1,2)8,3)7;`; see [`InnerNodeIndices`](crate::newick::InnerNodeIndices)
+    pub fn to_newick_string_with_indices(&self) -> String {
+        use crate::newick::{InnerNodeIndices, NumericLabels, write_newick_annotated};
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write_newick_annotated(self, &NumericLabels, &InnerNodeIndices, &(), &mut buffer)
+            .expect("The writer should not fail");
+        String::from_utf8(buffer).expect("The writer should not produce invalid strings")
+    }
 }
 
 impl TopDownCursor for &IndexedBinTree {
@@ -33,12 +45,42 @@ impl TreeWithNodeIdx for IndexedBinTree {
     fn node_idx(&self) -> NodeIdx {
         match self {
             IndexedBinTree::Node(b) => b.0,
-            IndexedBinTree::Leaf(label) => (*label).into(),
+            IndexedBinTree::Leaf(label) => {
+                NodeIdx::try_from(*label).expect("leaf label exceeds the range of node ids")
+            }
         }
     }
 }
 
-#[derive(Default)]
+/// Writes the tree as minimal Newick string without node indices, e.g. `((1,2),3);`; see
+/// [`IndexedBinTree::to_newick_string_with_indices`] to include them
+impl std::fmt::Display for IndexedBinTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use crate::newick::NewickWriter;
+        self.top_down().write_newick_fmt(f)
+    }
+}
+
+impl std::str::FromStr for IndexedBinTree {
+    type Err = crate::newick::ParserError;
+
+    /// Parses a single tree in strict Newick format; inner nodes receive ids in preorder
+    /// starting with `n + 1`, where `n` is the number of leaves
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::binary_tree::*;
+    ///
+    /// let tree: IndexedBinTree = "(1,(2,3));".parse().unwrap();
+    /// assert_eq!(tree.node_idx(), NodeIdx::new(4));
+    /// assert_eq!(tree.top_down().right_child().unwrap().node_idx(), NodeIdx::new(5));
+    /// ```
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        parse_single_tree::<IndexedBinTreeBuilder>(text)
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct IndexedBinTreeBuilder();
 
 impl TreeBuilder for IndexedBinTreeBuilder {
@@ -52,3 +94,21 @@ impl TreeBuilder for IndexedBinTreeBuilder {
         IndexedBinTree::Leaf(label)
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serde_roundtrip() {
+        let text = "(((4,2),(7,1)),8);";
+
+        let tree: IndexedBinTree = text.parse().unwrap();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(serde_json::from_str::<IndexedBinTree>(&json).unwrap(), tree);
+
+        let tree: BinTree = text.parse().unwrap();
+        let json = serde_json::to_string(&tree).unwrap();
+        assert_eq!(serde_json::from_str::<BinTree>(&json).unwrap(), tree);
+    }
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+/// Returns the leaf labels of the tree below `cursor` from left to right.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let tree = BinTreeBuilder::default()
+///     .parse_newick_from_str("((3,1),(4,2));", NodeIdx::new(0))
+///     .unwrap();
+/// assert_eq!(leaf_order(&tree), vec![Label(3), Label(1), Label(4), Label(2)]);
+/// ```
+pub fn leaf_order<C: TopDownCursor>(cursor: C) -> Vec<Label> {
+    leaves_in_order(cursor).collect()
+}
+
+/// Lazily iterates over the leaf labels below `cursor` from left to right.
+/// In contrast to [`leaf_order()`], no vector of all labels is materialized.
+pub fn leaves_in_order<C: TopDownCursor>(cursor: C) -> impl Iterator<Item = Label> {
+    cursor.dfs().filter_map(|c| c.leaf_label())
+}
+
+/// Returns `true` iff both trees have the same leaf labels in the same left-to-right order.
+/// The comparison stops at the first difference and does not materialize the leaf orders.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let a = builder.parse_newick_from_str("((1,2),3);", NodeIdx::new(0)).unwrap();
+/// let b = builder.parse_newick_from_str("(1,(2,3));", NodeIdx::new(0)).unwrap();
+/// let c = builder.parse_newick_from_str("(1,(3,2));", NodeIdx::new(0)).unwrap();
+///
+/// assert!(same_leaf_order(&a, &b));
+/// assert!(!same_leaf_order(&a, &c));
+/// ```
+pub fn same_leaf_order<A: TopDownCursor, B: TopDownCursor>(a: A, b: B) -> bool {
+    leaves_in_order(a).eq(leaves_in_order(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::newick::BinaryTreeParser;
+
+    #[test]
+    fn orders() {
+        let mut builder = IndexedBinTreeBuilder::default();
+        let mut parse = |t| builder.parse_newick_from_str(t, NodeIdx::new(0)).unwrap();
+
+        let a = parse("5;");
+        let b = parse("(((4,2),(7,1)),8);");
+        let c = parse("(4,(2,(7,(1,8))));");
+        let d = parse("(4,(2,(7,1)));");
+
+        assert_eq!(leaf_order(&a), vec![Label(5)]);
+        assert_eq!(leaf_order(&b), [4, 2, 7, 1, 8].map(Label).to_vec());
+        assert!(same_leaf_order(&b, &c));
+        assert!(!same_leaf_order(&b, &d));
+        assert!(!same_leaf_order(&d, &b));
+        assert!(!same_leaf_order(&a, &d));
+    }
+}
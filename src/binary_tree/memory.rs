@@ -0,0 +1,73 @@
+use super::*;
+use std::mem::size_of;
+
+/// Memory consumption of tree representations, e.g., to choose a representation
+/// before loading large instances.
+pub trait MemoryFootprint: Sized {
+    /// Returns the number of bytes allocated on the heap and owned by `self`.
+    /// The total memory usage hence is `size_of::<Self>() + self.heap_size()`.
+    fn heap_size(&self) -> usize;
+
+    /// Returns the (expected) heap size of a binary tree with `num_leaves` leaves
+    /// without building it, e.g., using the counts of the `#p` header.
+    fn estimate_heap_size(num_leaves: usize) -> usize;
+}
+
+fn count_inner_nodes<C: TopDownCursor>(root: C) -> usize {
+    root.dfs().filter(|c| c.is_inner()).count()
+}
+
+impl MemoryFootprint for BinTree {
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let tree = BinTreeBuilder::default()
+    ///     .parse_newick_from_str("((1,2),(3,4));", NodeIdx::new(0))
+    ///     .unwrap();
+    /// assert_eq!(tree.heap_size(), BinTree::estimate_heap_size(4));
+    /// ```
+    fn heap_size(&self) -> usize {
+        count_inner_nodes(self) * size_of::<(BinTree, BinTree)>()
+    }
+
+    fn estimate_heap_size(num_leaves: usize) -> usize {
+        num_leaves.saturating_sub(1) * size_of::<(BinTree, BinTree)>()
+    }
+}
+
+impl MemoryFootprint for IndexedBinTree {
+    fn heap_size(&self) -> usize {
+        count_inner_nodes(self) * size_of::<(NodeIdx, IndexedBinTree, IndexedBinTree)>()
+    }
+
+    fn estimate_heap_size(num_leaves: usize) -> usize {
+        num_leaves.saturating_sub(1) * size_of::<(NodeIdx, IndexedBinTree, IndexedBinTree)>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::newick::BinaryTreeParser;
+
+    #[test]
+    fn heap_size_matches_estimate() {
+        for (text, n) in [("7;", 1), ("(1,2);", 2), ("(((1,2),3),(4,(5,6)));", 6)] {
+            let tree = BinTreeBuilder::default()
+                .parse_newick_from_str(text, NodeIdx::new(0))
+                .unwrap();
+            assert_eq!(tree.heap_size(), BinTree::estimate_heap_size(n));
+
+            let tree = IndexedBinTreeBuilder::default()
+                .parse_newick_from_str(text, NodeIdx::new(0))
+                .unwrap();
+            assert_eq!(tree.heap_size(), IndexedBinTree::estimate_heap_size(n));
+        }
+
+        assert_eq!(
+            BinTree::estimate_heap_size(3),
+            2 * size_of::<(BinTree, BinTree)>()
+        );
+    }
+}
@@ -3,10 +3,32 @@ pub use bin_tree::*;
 pub mod indexed_bin_tree;
 pub use indexed_bin_tree::*;
 
+pub mod arena_tree;
+pub use arena_tree::{ArenaCursor, ArenaTree, ArenaTreeError};
+pub mod balanced_parentheses;
+pub use balanced_parentheses::{BalancedParentheses, BpCursor};
+pub mod borrowed_bin_tree;
+pub use borrowed_bin_tree::BorrowedBinTree;
 pub mod depth_first_search;
 pub use depth_first_search::DepthFirstSearch;
+pub mod difference;
+pub use difference::{TreeDifference, first_difference};
+pub mod edge_list;
+pub use edge_list::{EdgeListBuilder, EdgeTarget};
+pub mod fn_cursor;
+pub use fn_cursor::FnCursor;
+pub mod leaf_order;
+pub use leaf_order::{leaf_order, leaves_in_order, same_leaf_order};
+pub mod memory;
+pub use memory::MemoryFootprint;
+pub mod wire;
+pub mod zip_dfs;
+pub use zip_dfs::{ZipDfs, ZipNode, zip_dfs};
+
+pub(crate) mod flat_tree;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NodeIdx(pub u32);
 
 impl NodeIdx {
@@ -19,15 +41,50 @@ impl NodeIdx {
     }
 }
 
+/// Integer type of leaf labels; `u32` unless the feature `u64-labels` is enabled
+#[cfg(not(feature = "u64-labels"))]
+pub type LabelValue = u32;
+
+/// Integer type of leaf labels; `u32` unless the feature `u64-labels` is enabled
+#[cfg(feature = "u64-labels")]
+pub type LabelValue = u64;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Label(pub u32);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Label(pub LabelValue);
+
+/// Error of converting a [`Label`] that does not fit into a [`NodeIdx`]; only possible
+/// if the feature `u64-labels` is enabled
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("label {0} exceeds the range of node indices")]
+pub struct LabelOutOfRange(pub LabelValue);
 
-impl From<Label> for NodeIdx {
-    fn from(value: Label) -> Self {
-        NodeIdx(value.0)
+impl TryFrom<Label> for NodeIdx {
+    type Error = LabelOutOfRange;
+
+    // infallible unless the feature `u64-labels` is enabled
+    #[allow(clippy::useless_conversion)]
+    fn try_from(value: Label) -> Result<Self, Self::Error> {
+        u32::try_from(value.0)
+            .map(NodeIdx)
+            .map_err(|_| LabelOutOfRange(value.0))
     }
 }
 
+/// Parses a single tree in strict Newick format with a default-constructed builder. Inner
+/// nodes receive the ids of the first tree of a PACE instance (see
+/// [`crate::pace::numbering::NodeNumbering`]), i.e. ids in preorder starting with `n + 1`,
+/// where `n` is the number of leaves.
+fn parse_single_tree<B: TreeBuilder + Default>(
+    text: &str,
+) -> Result<B::Node, crate::newick::ParserError> {
+    use crate::newick::BinaryTreeParser;
+
+    // each inner node of a binary tree separates its children by exactly one comma
+    let num_leaves = text.matches(',').count() + 1;
+    B::default().parse_newick_from_str(text, NodeIdx::new(num_leaves as u32 + 1))
+}
+
 /// Generic interface to build binary trees required by Newick parser.
 pub trait TreeBuilder {
     type Node;
@@ -62,6 +119,79 @@ pub trait TreeBuilder {
     /// ```    
     fn new_leaf(&mut self, label: Label) -> Self::Node;
 
+    /// Same as [`TreeBuilder::new_inner`], but invoked by the Newick parser if the node
+    /// carries a branch length `(...):<length>`. The default implementation ignores the length.
+    fn new_inner_with_length(
+        &mut self,
+        id: NodeIdx,
+        left: Self::Node,
+        right: Self::Node,
+        _length: f64,
+    ) -> Self::Node {
+        self.new_inner(id, left, right)
+    }
+
+    /// Same as [`TreeBuilder::new_inner`], but invoked by the Newick parser for inner nodes
+    /// with a label `(...)<label>` if inner labels are enabled (see
+    /// [`crate::newick::NewickParserOptions::inner_labels`]). `length` is the branch length
+    /// `(...)<label>:<length>` of the node, if any. The default implementation ignores the
+    /// label and forwards the length to [`TreeBuilder::new_inner_with_length`].
+    fn new_inner_labeled(
+        &mut self,
+        id: NodeIdx,
+        left: Self::Node,
+        right: Self::Node,
+        _label: &str,
+        length: Option<f64>,
+    ) -> Self::Node {
+        match length {
+            Some(length) => self.new_inner_with_length(id, left, right, length),
+            None => self.new_inner(id, left, right),
+        }
+    }
+
+    /// Same as [`TreeBuilder::new_leaf`], but invoked by the Newick parser if the leaf
+    /// carries a branch length `<label>:<length>`. The default implementation ignores the length.
+    fn new_leaf_with_length(&mut self, label: Label, _length: f64) -> Self::Node {
+        self.new_leaf(label)
+    }
+
+    /// Invoked by the Newick parser after creating `node` if it is followed by an NHX
+    /// annotation `[&&NHX:key=value:...]` and NHX parsing is enabled (see
+    /// [`crate::newick::NewickParserOptions::nhx`]); `tags` contains the key-value pairs
+    /// in order. The returned node replaces `node`. The default implementation ignores
+    /// the annotation.
+    fn annotate_nhx(&mut self, node: Self::Node, _tags: &[(String, String)]) -> Self::Node {
+        node
+    }
+
+    /// Invoked by the Newick parser after creating (and annotating) `node` if spans are
+    /// enabled (see [`crate::newick::NewickParserOptions::spans`]), e.g., to point error
+    /// messages of downstream tools to the exact bytes of the input. The span of a leaf
+    /// covers its label and the span of an inner node ranges from its opening to its closing
+    /// parenthesis; inner labels, branch lengths, and comments following a node are not
+    /// included. The returned node replaces `node`. The default implementation ignores the span.
+    fn annotate_span(&mut self, node: Self::Node, _span: crate::newick::Span) -> Self::Node {
+        node
+    }
+
+    /// Hints that `num_inner` inner nodes and `num_leaves` leaves are about to be created
+    /// (in total over all trees), e.g., to allow builders backed by a `Vec` to allocate
+    /// their storage once. The default implementation does nothing.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::binary_tree::*;
+    ///
+    /// let mut builder = BinTreeBuilder::default();
+    /// builder.reserve(1, 2);
+    /// let l1 = builder.new_leaf(Label(1));
+    /// let l2 = builder.new_leaf(Label(2));
+    /// let root = builder.new_inner(NodeIdx::new(0), l1, l2);
+    /// assert!(root.top_down().is_inner());
+    /// ```
+    fn reserve(&mut self, _num_inner: usize, _num_leaves: usize) {}
+
     /// Declares a node a root. Depending on the tree
     /// implementation this may be a no-op, or may trigger
     /// the computation of meta information.
@@ -226,5 +356,9 @@ pub trait TopDownCursor: Sized {
 pub trait TreeWithNodeIdx {
     /// Returns the index of the node. If the node is a leaf,
     /// the leaf label is converted into a node index.
+    ///
+    /// # Panics
+    /// If the label of a leaf exceeds the range of [`NodeIdx`], which is only possible if the
+    /// feature `u64-labels` is enabled
     fn node_idx(&self) -> NodeIdx;
 }
@@ -0,0 +1,234 @@
+//! Compact binary encoding of trees, e.g., to exchange trees between processes.
+//!
+//! A tree with `n` leaves is encoded as
+//!  - the number of leaves `n` as varint,
+//!  - the structure as `2n - 1` bits in preorder (`1` for inner nodes, `0` for leaves),
+//!    packed least-significant bit first into `ceil((2n - 1) / 8)` bytes,
+//!  - the leaf labels in preorder as zigzag-encoded varints of the (wrapping) differences
+//!    between consecutive labels (starting from 0) in [`LabelValue`].
+//!
+//! Varints use 7 bits per byte (least significant group first), where the highest bit
+//! indicates that another byte follows. Inner node ids are not stored; as in the Newick
+//! parser, [`decode_tree`] assigns them in preorder.
+use super::*;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WireError {
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+
+    #[error("varint exceeds the range of its type")]
+    VarintOverflow,
+
+    #[error("structure bits do not describe a binary tree")]
+    InvalidStructure,
+}
+
+/// Encodes the tree below `root` and appends it to `out`.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::{wire::*, *}, newick::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let tree = builder.parse_newick_from_str("((1,2),(3,4));", NodeIdx::new(0)).unwrap();
+///
+/// let mut bytes = Vec::new();
+/// encode_tree(tree.top_down(), &mut bytes);
+/// assert_eq!(bytes.len(), 6);
+///
+/// let (decoded, rest) = decode_tree(&bytes, &mut builder, NodeIdx::new(0)).unwrap();
+/// assert_eq!(decoded, tree);
+/// assert!(rest.is_empty());
+/// ```
+pub fn encode_tree<C: TopDownCursor>(root: C, out: &mut Vec<u8>) {
+    let mut structure = Vec::new();
+    let mut labels = Vec::new();
+
+    let mut num_nodes = 0usize;
+    let mut stack = vec![root];
+    while let Some(cursor) = stack.pop() {
+        if num_nodes.is_multiple_of(8) {
+            structure.push(0u8);
+        }
+
+        match cursor.visit() {
+            NodeType::Inner(l, r) => {
+                *structure.last_mut().unwrap() |= 1 << (num_nodes % 8);
+                stack.push(r);
+                stack.push(l);
+            }
+            NodeType::Leaf(label) => labels.push(label),
+        }
+        num_nodes += 1;
+    }
+
+    write_varint(out, labels.len() as u64);
+    out.extend_from_slice(&structure);
+
+    let mut prev: LabelValue = 0;
+    for Label(label) in labels {
+        // zigzag encoding of the delta interpreted as signed integer
+        let delta = label.wrapping_sub(prev);
+        let sign = (delta >> (LabelValue::BITS - 1)).wrapping_neg();
+        write_varint(out, ((delta << 1) ^ sign) as u64);
+        prev = label;
+    }
+}
+
+/// Decodes a tree encoded by [`encode_tree`] from the beginning of `bytes` and returns it
+/// together with the remaining bytes. Inner nodes receive ids in preorder starting with `root_id`.
+pub fn decode_tree<'a, B: TreeBuilder>(
+    bytes: &'a [u8],
+    builder: &mut B,
+    root_id: NodeIdx,
+) -> Result<(B::Node, &'a [u8]), WireError> {
+    let (num_leaves, mut bytes) = read_varint::<u32>(bytes)?;
+    if num_leaves == 0 {
+        return Err(WireError::InvalidStructure);
+    }
+
+    let num_nodes = 2 * num_leaves as usize - 1;
+    let structure_len = num_nodes.div_ceil(8);
+    if bytes.len() < structure_len {
+        return Err(WireError::UnexpectedEnd);
+    }
+    let (structure, rest) = bytes.split_at(structure_len);
+    bytes = rest;
+    let is_inner = |u: usize| structure[u / 8] & (1 << (u % 8)) != 0;
+
+    // each entry is an inner node waiting for its children: (id, left child if decoded)
+    let mut open_nodes: Vec<(NodeIdx, Option<B::Node>)> = Vec::new();
+    let mut next_id = root_id;
+    let mut prev_label: LabelValue = 0;
+
+    for u in 0..num_nodes {
+        if is_inner(u) {
+            open_nodes.push((next_id, None));
+            next_id = next_id.incremented();
+            continue;
+        }
+
+        let (zigzag, rest) = read_varint::<LabelValue>(bytes)?;
+        bytes = rest;
+        prev_label = prev_label.wrapping_add((zigzag >> 1) ^ (zigzag & 1).wrapping_neg());
+
+        let mut node = builder.new_leaf(Label(prev_label));
+        loop {
+            match open_nodes.last_mut() {
+                None if u + 1 == num_nodes => return Ok((builder.make_root(node), bytes)),
+                None => return Err(WireError::InvalidStructure),
+                Some((_, left @ None)) => {
+                    *left = Some(node);
+                    break;
+                }
+                Some((_, Some(_))) => {
+                    let (id, left) = open_nodes.pop().unwrap();
+                    node = builder.new_inner(id, left.unwrap(), node);
+                }
+            }
+        }
+    }
+
+    Err(WireError::InvalidStructure)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a varint of at most `T::BITS` bits
+fn read_varint<T: TryFrom<u128>>(bytes: &[u8]) -> Result<(T, &[u8]), WireError> {
+    let max_len = (8 * size_of::<T>()).div_ceil(7);
+    let mut value = 0u128;
+    for (i, &byte) in bytes.iter().enumerate().take(max_len) {
+        value |= ((byte & 0x7f) as u128) << (7 * i);
+        if byte & 0x80 == 0 {
+            let value = T::try_from(value).map_err(|_| WireError::VarintOverflow)?;
+            return Ok((value, &bytes[i + 1..]));
+        }
+    }
+
+    if bytes.len() < max_len {
+        Err(WireError::UnexpectedEnd)
+    } else {
+        Err(WireError::VarintOverflow)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::*;
+
+    #[test]
+    fn roundtrip() {
+        let mut builder = IndexedBinTreeBuilder::default();
+        let mut bytes = Vec::new();
+        let mut trees = Vec::new();
+        for text in [
+            "7;",
+            "(1,2);",
+            "((4000000000,1),(3,(5,2)));",
+            "(((((1,2),3),4),5),(6,(7,(8,9))));",
+        ] {
+            let tree = builder
+                .parse_newick_from_str(text, NodeIdx::new(100))
+                .unwrap();
+            encode_tree(tree.top_down(), &mut bytes);
+            trees.push(tree);
+        }
+
+        // trees can be concatenated
+        let mut rest = bytes.as_slice();
+        for tree in trees {
+            let (decoded, r) = decode_tree(rest, &mut builder, NodeIdx::new(100)).unwrap();
+            assert_eq!(decoded, tree);
+            rest = r;
+        }
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn varints() {
+        for value in [0, 1, 127, 128, 300, u32::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value as u64);
+            assert_eq!(read_varint::<u32>(&bytes), Ok((value, &[][..])));
+        }
+
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, u64::MAX);
+        assert_eq!(read_varint::<u64>(&bytes), Ok((u64::MAX, &[][..])));
+
+        assert_eq!(read_varint::<u32>(&[0x80]), Err(WireError::UnexpectedEnd));
+        assert_eq!(
+            read_varint::<u32>(&[0xff, 0xff, 0xff, 0xff, 0x7f]),
+            Err(WireError::VarintOverflow)
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut builder = BinTreeBuilder::default();
+        let mut decode = |bytes: &[u8]| {
+            decode_tree(bytes, &mut builder, NodeIdx::new(0))
+                .err()
+                .unwrap()
+        };
+
+        assert_eq!(decode(&[]), WireError::UnexpectedEnd);
+        assert_eq!(decode(&[0]), WireError::InvalidStructure);
+        // two leaves, but the structure starts with a leaf
+        assert_eq!(decode(&[2, 0b000, 2, 2, 2]), WireError::InvalidStructure);
+        // two leaves, but all nodes are inner
+        assert_eq!(decode(&[2, 0b111]), WireError::InvalidStructure);
+        // labels are missing
+        assert_eq!(decode(&[2, 0b001, 2]), WireError::UnexpectedEnd);
+    }
+}
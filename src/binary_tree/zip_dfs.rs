@@ -0,0 +1,129 @@
+use super::*;
+
+/// Item reported by [`zip_dfs()`]; each variant holds the cursors of all trees at the
+/// current position (in input order).
+pub enum ZipNode<C> {
+    /// All trees have an inner node at this position; the traversal descends into the children.
+    Inner(Vec<C>),
+
+    /// All trees have a leaf with the same label at this position.
+    Leaf(Label, Vec<C>),
+
+    /// The trees disagree at this position, i.e. some have an inner node and some a leaf,
+    /// or the leaf labels differ. The traversal does not descend below a divergence point.
+    Diverged(Vec<C>),
+}
+
+/// Traverses several trees simultaneously in depth-first order (left child first) as long as
+/// they agree structurally. Children are compared in the order given by the cursors, so
+/// the trees `(1,2);` and `(2,1);` diverge at both leaves.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let a = builder.parse_newick_from_str("((1,2),(3,4));", NodeIdx::new(0)).unwrap();
+/// let b = builder.parse_newick_from_str("((1,2),((3,5),4));", NodeIdx::new(0)).unwrap();
+///
+/// let mut num_common_leaves = 0;
+/// let mut divergence_points = Vec::new();
+/// for node in zip_dfs([a.top_down(), b.top_down()]) {
+///     match node {
+///         ZipNode::Leaf(..) => num_common_leaves += 1,
+///         ZipNode::Diverged(cursors) => divergence_points.push(cursors[0].leaf_label()),
+///         ZipNode::Inner(_) => {}
+///     }
+/// }
+///
+/// assert_eq!(num_common_leaves, 3);
+/// assert_eq!(divergence_points, vec![Some(Label(3))]);
+/// ```
+pub fn zip_dfs<C: TopDownCursor>(cursors: impl IntoIterator<Item = C>) -> ZipDfs<C> {
+    let cursors: Vec<C> = cursors.into_iter().collect();
+    ZipDfs {
+        stack: if cursors.is_empty() {
+            Vec::new()
+        } else {
+            vec![cursors]
+        },
+    }
+}
+
+/// Iterator returned by [`zip_dfs()`]
+pub struct ZipDfs<C> {
+    stack: Vec<Vec<C>>,
+}
+
+impl<C: TopDownCursor> Iterator for ZipDfs<C> {
+    type Item = ZipNode<C>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cursors = self.stack.pop()?;
+
+        let first_label = cursors[0].leaf_label();
+        if cursors.iter().any(|c| c.leaf_label() != first_label) {
+            return Some(ZipNode::Diverged(cursors));
+        }
+
+        if let Some(label) = first_label {
+            return Some(ZipNode::Leaf(label, cursors));
+        }
+
+        let (left, right): (Vec<C>, Vec<C>) = cursors
+            .iter()
+            .map(|c| c.children().expect("inner node"))
+            .unzip();
+        self.stack.push(right);
+        self.stack.push(left);
+
+        Some(ZipNode::Inner(cursors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::newick::BinaryTreeParser;
+
+    fn summary(texts: &[&str]) -> String {
+        let mut builder = IndexedBinTreeBuilder::default();
+        let trees: Vec<_> = texts
+            .iter()
+            .map(|t| builder.parse_newick_from_str(t, NodeIdx::new(10)).unwrap())
+            .collect();
+
+        zip_dfs(trees.iter().map(|t| t.top_down()))
+            .map(|node| match node {
+                ZipNode::Inner(cursors) => format!("i{}", cursors[0].node_idx().0),
+                ZipNode::Leaf(Label(l), _) => format!("l{l}"),
+                ZipNode::Diverged(cursors) => format!("d{}", cursors.len()),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn identical() {
+        assert_eq!(
+            summary(&["((1,2),3);", "((1,2),3);", "((1,2),3);"]),
+            "i10 i11 l1 l2 l3"
+        );
+        assert_eq!(summary(&["((1,2),3);"]), "i10 i11 l1 l2 l3");
+    }
+
+    #[test]
+    fn divergence() {
+        assert_eq!(summary(&["((1,2),3);", "((2,1),3);"]), "i10 i11 d2 d2 l3");
+        assert_eq!(summary(&["((1,2),3);", "(1,(2,3));"]), "i10 d2 d2");
+        assert_eq!(
+            summary(&["(((1,2),3),(4,5));", "((4,3),(4,5));"]),
+            "i10 i11 d2 l3 i13 l4 l5"
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert!(zip_dfs(Vec::<&BinTree>::new()).next().is_none());
+    }
+}
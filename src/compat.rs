@@ -0,0 +1,347 @@
+//! Named dialects of the input format. Each [`CompatProfile`] bundles the tolerance flags
+//! of the Newick lexer, the parser, and the instance reader into a [`CompatFlags`] preset,
+//! so tools can state exactly which inputs they accept.
+use crate::newick::lexer::Lexer;
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Key of the stride line `#s format-version 1.0` declaring the version of the input format
+pub const FORMAT_VERSION_KEY: &str = "format-version";
+
+/// Named presets of [`CompatFlags`]
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, compat::*, newick::*};
+///
+/// let text = "((1, 2)[support] , 3)root;";
+/// let mut builder = BinTreeBuilder::default();
+/// assert!(builder
+///     .parse_newick_from_str_with(text, NodeIdx::new(4), &mut CompatProfile::Pace26Strict.into())
+///     .is_err());
+///
+/// let tree = builder
+///     .parse_newick_from_str_with(text, NodeIdx::new(4), &mut CompatProfile::Lenient.into())
+///     .unwrap();
+/// assert_eq!(tree.top_down().to_newick_string(), "((1,2),3);");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompatProfile {
+    /// Exactly the PACE 2026 format, i.e. all flags disabled
+    #[default]
+    Pace26Strict,
+
+    /// Accepts common extensions of Newick and unknown `#x` parameters, i.e. all flags enabled
+    Lenient,
+}
+
+impl CompatProfile {
+    pub fn flags(self) -> CompatFlags {
+        match self {
+            CompatProfile::Pace26Strict => CompatFlags {
+                whitespace: false,
+                comments: false,
+                inner_labels: false,
+                unknown_parameters: false,
+                out_of_range_labels: false,
+                bom_and_crlf: false,
+                branch_lengths: false,
+            },
+            CompatProfile::Lenient => CompatFlags {
+                whitespace: true,
+                comments: true,
+                inner_labels: true,
+                unknown_parameters: true,
+                out_of_range_labels: true,
+                bom_and_crlf: true,
+                branch_lengths: true,
+            },
+        }
+    }
+}
+
+/// Tolerance flags of the lexer, parser, and reader. Each flag enables the acceptance of
+/// some input that is not part of the PACE 2026 format. Start from a [`CompatProfile`] to
+/// customize individual flags, e.g.
+/// `CompatFlags { whitespace: true, ..CompatProfile::Pace26Strict.flags() }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompatFlags {
+    /// Whitespace between Newick tokens
+    pub whitespace: bool,
+
+    /// Bracket comments `[...]` in Newick strings; they are skipped silently
+    pub comments: bool,
+
+    /// Labels of inner nodes, e.g. `(1,2)95`; they are passed to
+    /// [`TreeBuilder::new_inner_labeled`](crate::binary_tree::TreeBuilder::new_inner_labeled)
+    pub inner_labels: bool,
+
+    /// Unknown `#x` parameter lines; they are treated as unrecognized lines
+    /// instead of raising [`ReaderError::UnknownParameter`](crate::pace::reader::ReaderError::UnknownParameter)
+    pub unknown_parameters: bool,
+
+    /// Leaf labels outside of `1..=n`, where `n` is the number of leaves declared in the
+    /// header, instead of raising
+    /// [`SimplifiedReaderError::LabelOutOfRange`](crate::pace::simplified::SimplifiedReaderError::LabelOutOfRange);
+    /// see [`Instance::compact_labels`](crate::pace::simplified::Instance::compact_labels)
+    /// to repair such instances
+    pub out_of_range_labels: bool,
+
+    /// A byte order mark at the beginning of the input and Windows line endings `\r\n`,
+    /// as produced by many Windows tools; see [`Lexer::tolerate_bom_and_crlf`]
+    pub bom_and_crlf: bool,
+
+    /// Branch lengths, e.g. `(1:0.5,2)`; they are passed to
+    /// [`TreeBuilder::new_leaf_with_length`](crate::binary_tree::TreeBuilder::new_leaf_with_length)
+    /// and [`TreeBuilder::new_inner_with_length`](crate::binary_tree::TreeBuilder::new_inner_with_length)
+    pub branch_lengths: bool,
+}
+
+impl Default for CompatFlags {
+    fn default() -> Self {
+        CompatProfile::default().flags()
+    }
+}
+
+impl From<CompatProfile> for CompatFlags {
+    fn from(profile: CompatProfile) -> Self {
+        profile.flags()
+    }
+}
+
+impl CompatFlags {
+    /// Returns the flags enabled in `self` or `other`, i.e. the tolerance of both
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            whitespace: self.whitespace || other.whitespace,
+            comments: self.comments || other.comments,
+            inner_labels: self.inner_labels || other.inner_labels,
+            unknown_parameters: self.unknown_parameters || other.unknown_parameters,
+            out_of_range_labels: self.out_of_range_labels || other.out_of_range_labels,
+            bom_and_crlf: self.bom_and_crlf || other.bom_and_crlf,
+            branch_lengths: self.branch_lengths || other.branch_lengths,
+        }
+    }
+
+    /// Applies the lexer-level flags to `lexer`
+    pub(crate) fn configure_lexer(&self, lexer: &mut Lexer) {
+        if self.whitespace {
+            lexer.allow_whitespaces();
+        }
+        if self.comments {
+            lexer.skip_comments();
+        }
+        if self.inner_labels {
+            lexer.allow_inner_labels();
+        }
+        if self.bom_and_crlf {
+            lexer.tolerate_bom_and_crlf();
+        }
+        if self.branch_lengths {
+            lexer.allow_branch_lengths();
+        }
+    }
+}
+
+/// Version `major.minor` of the input format as declared by `#s format-version`.
+/// Minor revisions are backwards compatible; inputs with a different major version are rejected.
+/// The reader parses all trees following the declaration with the flags of
+/// [`FormatVersion::profile`] in addition to the flags requested by the caller.
+///
+/// # Example
+/// ```
+/// use pace26io::compat::*;
+///
+/// let version: FormatVersion = "1.2".parse().unwrap();
+/// assert_eq!(version.profile(), Ok(CompatProfile::Pace26Strict));
+///
+/// let future: FormatVersion = "2".parse().unwrap();
+/// assert_eq!(future.to_string(), "2.0");
+/// assert!(future.profile().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FormatVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl FormatVersion {
+    /// Version implemented by this crate
+    pub const CURRENT: Self = Self { major: 1, minor: 0 };
+
+    pub fn is_supported(self) -> bool {
+        self.major == Self::CURRENT.major
+    }
+
+    /// Returns the profile describing the dialect of this version; this is the single place
+    /// mapping versions to dialects, so new revisions of the format only need an entry here
+    pub fn profile(self) -> Result<CompatProfile, FormatVersionError> {
+        match self.major {
+            1 => Ok(CompatProfile::Pace26Strict),
+            _ => Err(FormatVersionError::Unsupported(self)),
+        }
+    }
+}
+
+impl Default for FormatVersion {
+    fn default() -> Self {
+        Self::CURRENT
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for FormatVersion {
+    type Err = FormatVersionError;
+
+    /// Parses `major` or `major.minor`, optionally enclosed in double quotes
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || FormatVersionError::Invalid(s.to_owned());
+        let text = s.trim().trim_matches('"');
+        let (major, minor) = text.split_once('.').unwrap_or((text, "0"));
+        Ok(Self {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FormatVersionError {
+    #[error("invalid format version {0:?}; expected major.minor")]
+    Invalid(String),
+
+    #[error("unsupported format version {0}; supported are versions {major}.x", major = FormatVersion::CURRENT.major)]
+    Unsupported(FormatVersion),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binary_tree::*, newick::BinaryTreeParser, pace::simplified::*};
+
+    #[test]
+    fn instance_profiles() {
+        let input = "#p 2 3\n#x unknown 1\n((1, 2), 3);\n((1,3)[x],2);\n";
+        let read = |flags: CompatFlags| {
+            Instance::try_read_compat(input.as_bytes(), &mut BinTreeBuilder::default(), flags)
+        };
+
+        assert!(read(CompatProfile::Pace26Strict.into()).is_err());
+        assert!(
+            read(CompatFlags {
+                unknown_parameters: true,
+                ..CompatProfile::Pace26Strict.flags()
+            })
+            .is_err()
+        );
+        assert_eq!(read(CompatProfile::Lenient.into()).unwrap().trees.len(), 2);
+    }
+
+    #[test]
+    fn branch_lengths() {
+        let text = "((1:0.5,2):1,3);";
+        let parse = |flags: CompatFlags| {
+            BinTreeBuilder::default().parse_newick_from_str_with(
+                text,
+                NodeIdx::new(4),
+                &mut flags.into(),
+            )
+        };
+        assert!(parse(CompatProfile::Pace26Strict.into()).is_err());
+        assert!(parse(CompatProfile::Lenient.into()).is_ok());
+        assert!(
+            parse(CompatFlags {
+                branch_lengths: true,
+                ..CompatProfile::Pace26Strict.flags()
+            })
+            .is_ok()
+        );
+
+        let input = format!("#p 1 3\n{text}\n");
+        let read = |flags: CompatFlags| {
+            Instance::try_read_compat(input.as_bytes(), &mut BinTreeBuilder::default(), flags)
+        };
+        assert!(read(CompatProfile::Pace26Strict.into()).is_err());
+        assert_eq!(read(CompatProfile::Lenient.into()).unwrap().trees.len(), 1);
+        assert!(Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).is_err());
+    }
+
+    #[test]
+    fn union() {
+        let strict = CompatProfile::Pace26Strict.flags();
+        let lenient = CompatProfile::Lenient.flags();
+        assert_eq!(strict.union(strict), strict);
+        assert_eq!(strict.union(lenient), lenient);
+
+        let whitespace = CompatFlags {
+            whitespace: true,
+            ..strict
+        };
+        let comments = CompatFlags {
+            comments: true,
+            ..strict
+        };
+        assert_eq!(
+            whitespace.union(comments),
+            CompatFlags {
+                whitespace: true,
+                comments: true,
+                ..strict
+            }
+        );
+    }
+
+    #[test]
+    fn format_versions() {
+        let parse = |s: &str| s.parse::<FormatVersion>();
+        assert_eq!(parse("1.0"), Ok(FormatVersion::CURRENT));
+        assert_eq!(parse("\"1.3\""), Ok(FormatVersion { major: 1, minor: 3 }));
+        assert!(parse("1.3").unwrap() > FormatVersion::CURRENT);
+        assert!(parse("1.3").unwrap().is_supported());
+        assert!(!parse("0.9").unwrap().is_supported());
+        for invalid in ["", "a", "1.", "1.2.3", "-1"] {
+            assert_eq!(
+                parse(invalid),
+                Err(FormatVersionError::Invalid(invalid.into()))
+            );
+        }
+
+        let read = |input: &str| {
+            Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default())
+                .map(|instance| instance.format_version)
+        };
+        assert_eq!(read("#p 1 2\n(1,2);\n").unwrap(), None);
+        assert_eq!(
+            read("#s format-version 1.1\n#p 1 2\n(1,2);\n").unwrap(),
+            Some(FormatVersion { major: 1, minor: 1 })
+        );
+
+        // the profile of the version extends the flags of the caller rather than replacing them
+        let input = "#s format-version 1.1\n#p 1 2\n(1, 2);\n";
+        let instance = Instance::try_read_compat(
+            input.as_bytes(),
+            &mut BinTreeBuilder::default(),
+            CompatProfile::Lenient,
+        )
+        .unwrap();
+        assert_eq!(instance.trees.len(), 1);
+        assert!(read(input).is_err());
+        assert!(matches!(
+            read("#s format-version 2.0\n#p 1 2\n(1,2);\n"),
+            Err(SimplifiedReaderError::FormatVersion(
+                FormatVersionError::Unsupported(_)
+            ))
+        ));
+        assert!(matches!(
+            read("#s format-version latest\n#p 1 2\n(1,2);\n"),
+            Err(SimplifiedReaderError::FormatVersion(
+                FormatVersionError::Invalid(_)
+            ))
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+//! Reusable test suites for implementors of [`TreeBuilder`] and [`TopDownCursor`].
+//! The Newick parser, the writers, and the algorithms of this crate rely on the invariants
+//! checked here; call these functions from the tests of custom tree implementations.
+//! All functions panic with a descriptive message if an invariant is violated.
+use crate::{binary_tree::*, newick::*};
+
+/// Trees covering leaves, cherries, unbalanced and balanced shapes, as well as extreme labels
+const TREES: [&str; 6] = [
+    "1;",
+    "(1,2);",
+    "(2,1);",
+    "((1,2),3);",
+    "((((1,2),3),4),(5,(6,(7,8))));",
+    "((0,4294967295),(123456,(7,99)));",
+];
+
+/// Checks that trees built by `B` expose exactly the structure passed to the builder:
+///  - leaves keep their labels, including `0` and `u32::MAX`,
+///  - inner nodes keep the order of their children,
+///  - [`TreeBuilder::make_root`], [`TreeBuilder::reserve`], and the hooks for branch lengths
+///    and inner labels do not alter the structure,
+///  - trees built earlier are unaffected by building further trees with the same builder,
+///  - all trees satisfy [`assert_top_down_cursor_conformance`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, conformance::*};
+///
+/// assert_tree_builder_conformance::<BinTreeBuilder>();
+/// assert_tree_builder_conformance::<IndexedBinTreeBuilder>();
+/// ```
+pub fn assert_tree_builder_conformance<B>()
+where
+    B: TreeBuilder + Default,
+    for<'a> &'a B::Node: TopDownCursor,
+{
+    let mut builder = B::default();
+
+    // manual construction
+    builder.reserve(2, 3);
+    let l1 = builder.new_leaf(Label(1));
+    let l2 = builder.new_leaf_with_length(Label(2), 0.5);
+    let cherry = builder.new_inner_with_length(NodeIdx::new(5), l1, l2, 1.5);
+    let l3 = builder.new_leaf(Label(3));
+    let root = builder.new_inner_labeled(NodeIdx::new(4), cherry, l3, "root", Some(0.0));
+    let root = builder.make_root(root);
+    assert_newick(&root, "((1,2),3);", "manually built tree");
+
+    // parsed trees; all of them are kept alive to detect interference between trees
+    let trees: Vec<B::Node> = TREES
+        .iter()
+        .map(|text| {
+            builder
+                .parse_newick_from_str(text, NodeIdx::new(1 << 20))
+                .unwrap_or_else(|err| panic!("failed to parse {text}: {err}"))
+        })
+        .collect();
+
+    // a deep caterpillar to detect recursion in the builder or cursor
+    let mut caterpillar = builder.new_leaf(Label(1));
+    for i in 2..=1000 {
+        let leaf = builder.new_leaf(Label(i));
+        caterpillar = builder.new_inner(NodeIdx::try_from(Label(i)).unwrap(), caterpillar, leaf);
+    }
+    let caterpillar = builder.make_root(caterpillar);
+
+    for (tree, text) in trees.iter().zip(TREES) {
+        assert_newick(tree, text, "parsed tree");
+    }
+    assert_newick(&root, "((1,2),3);", "manually built tree after parsing");
+
+    let mut depth = 0;
+    let mut cursor = &caterpillar;
+    while let Some((left, right)) = cursor.children() {
+        assert_eq!(
+            right.leaf_label(),
+            Some(Label(1000 - depth)),
+            "caterpillar: unexpected right child at depth {depth}"
+        );
+        cursor = left;
+        depth += 1;
+    }
+    assert_eq!(depth, 999, "caterpillar: unexpected depth");
+    assert_eq!(cursor.leaf_label(), Some(Label(1)));
+}
+
+/// Checks that the accessors of [`TopDownCursor`] agree with each other on every node
+/// below `root`, i.e. each node is either a leaf or an inner node, and
+/// [`TopDownCursor::visit`], [`TopDownCursor::left_child`], [`TopDownCursor::right_child`],
+/// [`TopDownCursor::is_leaf`], and [`TopDownCursor::is_inner`] are consistent with
+/// [`TopDownCursor::children`] and [`TopDownCursor::leaf_label`]. Additionally, the
+/// Newick string written for `root` has to parse into the same structure.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, conformance::*, newick::*};
+///
+/// let bp = parse_newick_to_bp("((1,2),(3,4));").unwrap();
+/// assert_top_down_cursor_conformance(bp.root());
+/// ```
+pub fn assert_top_down_cursor_conformance<C: TopDownCursor>(root: C) {
+    let mut newick = None;
+    let mut expected = Vec::new();
+
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        let children = node.children();
+        let label = node.leaf_label();
+        assert!(
+            children.is_some() != label.is_some(),
+            "node has to be either inner node or leaf"
+        );
+        assert_eq!(
+            node.is_leaf(),
+            label.is_some(),
+            "is_leaf disagrees with leaf_label"
+        );
+        assert_eq!(
+            node.is_inner(),
+            children.is_some(),
+            "is_inner disagrees with children"
+        );
+        assert_eq!(node.left_child().is_some(), children.is_some());
+        assert_eq!(node.right_child().is_some(), children.is_some());
+
+        // the writer relies on the checks above, so only write once the root passed them
+        newick.get_or_insert_with(|| node.to_newick_string());
+
+        match (node.visit(), label) {
+            (NodeType::Leaf(visited), Some(label)) => {
+                assert_eq!(visited, label, "visit reports a different label");
+                expected.push(Some(label));
+            }
+            (NodeType::Inner(left, right), None) => {
+                let (l, r) = children.unwrap();
+                assert_eq!(
+                    (left.leaf_label(), right.leaf_label()),
+                    (l.leaf_label(), r.leaf_label()),
+                    "visit reports different children"
+                );
+                assert_eq!(
+                    node.left_child().unwrap().leaf_label(),
+                    l.leaf_label(),
+                    "left_child disagrees with children"
+                );
+                assert_eq!(
+                    node.right_child().unwrap().leaf_label(),
+                    r.leaf_label(),
+                    "right_child disagrees with children"
+                );
+                expected.push(None);
+                stack.push(r);
+                stack.push(l);
+            }
+            _ => panic!("visit disagrees with children/leaf_label"),
+        }
+    }
+
+    // compare the preorder of the written tree with the traversal above
+    let newick = newick.unwrap();
+    let bp = parse_newick_to_bp(&newick)
+        .unwrap_or_else(|err| panic!("written tree {newick} cannot be parsed: {err}"));
+    let mut written = Vec::new();
+    let mut stack = vec![bp.root()];
+    while let Some(node) = stack.pop() {
+        match node.visit() {
+            NodeType::Inner(l, r) => {
+                written.push(None);
+                stack.push(r);
+                stack.push(l);
+            }
+            NodeType::Leaf(label) => written.push(Some(label)),
+        }
+    }
+    assert_eq!(
+        written, expected,
+        "written tree {newick} differs from cursor"
+    );
+}
+
+fn assert_newick<N>(tree: &N, expected: &str, context: &str)
+where
+    for<'a> &'a N: TopDownCursor,
+{
+    assert_eq!(tree.to_newick_string(), expected, "{context}");
+    assert_top_down_cursor_conformance(tree);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn builders() {
+        assert_tree_builder_conformance::<BinTreeBuilder>();
+        assert_tree_builder_conformance::<IndexedBinTreeBuilder>();
+    }
+
+    #[test]
+    #[should_panic(expected = "inner node or leaf")]
+    fn broken_cursor() {
+        struct Broken;
+        impl TopDownCursor for Broken {
+            fn children(&self) -> Option<(Self, Self)> {
+                None
+            }
+
+            fn leaf_label(&self) -> Option<Label> {
+                None
+            }
+        }
+
+        assert_top_down_cursor_conformance(Broken);
+    }
+}
@@ -0,0 +1,315 @@
+//! Consensus trees summarize a collection of trees on the same leaf set by the
+//! clusters (i.e. leaf sets of subtrees) they share. Since the result is
+//! in general not binary, it is constructed using a [`MultifurcatingTreeBuilder`].
+use crate::{binary_tree::*, multi_tree::MultifurcatingTreeBuilder};
+use std::collections::HashMap;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ConsensusError {
+    #[error("at least one tree is required")]
+    NoTrees,
+
+    #[error("trees are not defined on the same leaf set")]
+    DifferentLeafSets,
+
+    #[error("label {0:?} appears multiple times in a tree")]
+    DuplicateLabel(Label),
+
+    #[error("threshold {0} is outside the range [0.5, 1]")]
+    InvalidThreshold(f64),
+
+    #[error("trees do not share any leaf")]
+    NoCommonLeaves,
+}
+
+/// Computes the strict consensus tree, i.e. the tree containing exactly those
+/// clusters that appear in all input trees.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, consensus, multi_tree::*, newick::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let trees = vec![
+///     builder.parse_newick_from_str("(((1,2),3),(4,5));", NodeIdx::new(0)).unwrap(),
+///     builder.parse_newick_from_str("((1,(2,3)),(4,5));", NodeIdx::new(0)).unwrap(),
+/// ];
+///
+/// let consensus = consensus::strict(&trees, &mut MultiTreeBuilder::default()).unwrap();
+/// assert_eq!((&consensus).to_newick_string(), "((1,2,3),(4,5));");
+/// ```
+pub fn strict<C, B>(
+    trees: impl IntoIterator<Item = C>,
+    builder: &mut B,
+) -> Result<B::Node, ConsensusError>
+where
+    C: TopDownCursor,
+    B: MultifurcatingTreeBuilder,
+{
+    let counts = ClusterCounts::new(trees)?;
+    let num_trees = counts.num_trees;
+    Ok(counts.build(builder, |count| count == num_trees))
+}
+
+/// Computes the majority-rule consensus tree, i.e. the tree containing exactly
+/// those clusters that appear in more than `threshold * trees.len()` input trees.
+/// The threshold has to be within `[0.5, 1]`, which guarantees that the selected
+/// clusters are compatible. Clusters contained in all trees are always included,
+/// so `majority(trees, 1.0, builder)` is the strict consensus.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, consensus, multi_tree::*, newick::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let trees = vec![
+///     builder.parse_newick_from_str("(((1,2),3),(4,5));", NodeIdx::new(0)).unwrap(),
+///     builder.parse_newick_from_str("((1,(2,3)),(4,5));", NodeIdx::new(0)).unwrap(),
+///     builder.parse_newick_from_str("(((2,1),4),(3,5));", NodeIdx::new(0)).unwrap(),
+/// ];
+///
+/// let consensus = consensus::majority(&trees, 0.5, &mut MultiTreeBuilder::default()).unwrap();
+/// assert_eq!((&consensus).to_newick_string(), "(((1,2),3),(4,5));");
+/// ```
+pub fn majority<C, B>(
+    trees: impl IntoIterator<Item = C>,
+    threshold: f64,
+    builder: &mut B,
+) -> Result<B::Node, ConsensusError>
+where
+    C: TopDownCursor,
+    B: MultifurcatingTreeBuilder,
+{
+    if !(0.5..=1.0).contains(&threshold) {
+        return Err(ConsensusError::InvalidThreshold(threshold));
+    }
+
+    let counts = ClusterCounts::new(trees)?;
+    let num_trees = counts.num_trees;
+    let min_count = threshold * num_trees as f64;
+    Ok(counts.build(builder, |count| {
+        count == num_trees || count as f64 > min_count
+    }))
+}
+
+/// Bitset over the dense leaf indices
+type Cluster = Vec<u64>;
+
+pub(crate) struct ClusterCounts {
+    pub(crate) num_trees: usize,
+    labels: Vec<Label>,
+    counts: HashMap<Cluster, usize>,
+}
+
+impl ClusterCounts {
+    fn new<C: TopDownCursor>(trees: impl IntoIterator<Item = C>) -> Result<Self, ConsensusError> {
+        Self::from_leaf_ranges(trees.into_iter().map(leaf_ranges))
+    }
+
+    /// Counts the clusters given as output of [`leaf_ranges`]; each cluster may appear at most once per tree
+    pub(crate) fn from_leaf_ranges(
+        trees: impl IntoIterator<Item = (Vec<Label>, Vec<(usize, usize)>)>,
+    ) -> Result<Self, ConsensusError> {
+        let mut result = Self {
+            num_trees: 0,
+            labels: Vec::new(),
+            counts: HashMap::new(),
+        };
+        let mut dense = HashMap::new();
+
+        for (leaves, ranges) in trees {
+            if result.num_trees == 0 {
+                for &label in &leaves {
+                    if dense.insert(label, dense.len()).is_some() {
+                        return Err(ConsensusError::DuplicateLabel(label));
+                    }
+                }
+                result.labels = leaves.clone();
+            }
+            result.num_trees += 1;
+
+            // translate the leaf order into dense indices and check that the leaf sets agree
+            let words = dense.len().div_ceil(64);
+            let mut seen = vec![0u64; words];
+            let mut indices = Vec::with_capacity(leaves.len());
+            for label in leaves {
+                let e = *dense.get(&label).ok_or(ConsensusError::DifferentLeafSets)?;
+                if (seen[e / 64] >> (e % 64)) & 1 == 1 {
+                    return Err(ConsensusError::DuplicateLabel(label));
+                }
+                seen[e / 64] |= 1 << (e % 64);
+                indices.push(e);
+            }
+            if indices.len() != dense.len() {
+                return Err(ConsensusError::DifferentLeafSets);
+            }
+
+            for (begin, end) in ranges {
+                let mut cluster = vec![0u64; words];
+                for &e in &indices[begin..end] {
+                    cluster[e / 64] |= 1 << (e % 64);
+                }
+                *result.counts.entry(cluster).or_default() += 1;
+            }
+        }
+
+        if result.num_trees == 0 {
+            return Err(ConsensusError::NoTrees);
+        }
+
+        Ok(result)
+    }
+
+    /// Builds the tree of all selected clusters; the caller has to ensure that they are compatible
+    pub(crate) fn build<B: MultifurcatingTreeBuilder>(
+        self,
+        builder: &mut B,
+        mut select: impl FnMut(usize) -> bool,
+    ) -> B::Node {
+        let n = self.labels.len();
+        let mut clusters: Vec<(usize, Cluster)> = self
+            .counts
+            .into_iter()
+            .filter(|(_, count)| select(*count))
+            .map(|(cluster, _)| {
+                (
+                    cluster.iter().map(|w| w.count_ones() as usize).sum(),
+                    cluster,
+                )
+            })
+            .collect();
+        clusters.sort_unstable();
+
+        // Processing the laminar family bottom-up, the children of a cluster are the
+        // largest clusters (or leaves) processed so far that contain one of its elements.
+        let mut owner: Vec<Option<usize>> = vec![None; n];
+        let mut nodes: Vec<Option<B::Node>> = (0..clusters.len()).map(|_| None).collect();
+        for (id, (_, cluster)) in clusters.iter().enumerate() {
+            let mut children = Vec::new();
+            for e in iter_bits(cluster) {
+                match owner[e] {
+                    None => children.push(builder.new_leaf(self.labels[e])),
+                    Some(child) => {
+                        if let Some(node) = nodes[child].take() {
+                            children.push(node);
+                        }
+                    }
+                }
+                owner[e] = Some(id);
+            }
+            nodes[id] = Some(builder.new_inner(children));
+        }
+
+        // every tree contains the trivial cluster of all leaves, except for single-leaf trees
+        let root = match nodes.pop().flatten() {
+            Some(root) => root,
+            None => builder.new_leaf(self.labels[0]),
+        };
+        builder.make_root(root)
+    }
+}
+
+fn iter_bits(cluster: &Cluster) -> impl Iterator<Item = usize> + '_ {
+    cluster.iter().enumerate().flat_map(|(i, &word)| {
+        let mut word = word;
+        std::iter::from_fn(move || {
+            (word != 0).then(|| {
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                i * 64 + bit
+            })
+        })
+    })
+}
+
+/// Returns the leaf labels in left-to-right order and, for each inner node,
+/// the range of this order covered by its subtree.
+pub(crate) fn leaf_ranges<C: TopDownCursor>(root: C) -> (Vec<Label>, Vec<(usize, usize)>) {
+    enum Step<C> {
+        Enter(C),
+        Exit(usize),
+    }
+
+    let mut leaves = Vec::new();
+    let mut ranges = Vec::new();
+    let mut stack = vec![Step::Enter(root)];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Enter(node) => match node.visit() {
+                NodeType::Inner(l, r) => {
+                    stack.push(Step::Exit(leaves.len()));
+                    stack.push(Step::Enter(r));
+                    stack.push(Step::Enter(l));
+                }
+                NodeType::Leaf(label) => leaves.push(label),
+            },
+            Step::Exit(begin) => ranges.push((begin, leaves.len())),
+        }
+    }
+
+    (leaves, ranges)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{multi_tree::MultiTreeBuilder, newick::*};
+
+    fn parse(texts: &[&str]) -> Vec<BinTree> {
+        let mut builder = BinTreeBuilder::default();
+        texts
+            .iter()
+            .map(|t| builder.parse_newick_from_str(t, NodeIdx::new(0)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn identical_trees() {
+        let trees = parse(&["(((4,2),(7,1)),8);", "(8,((2,4),(1,7)));"]);
+        let tree = strict(&trees, &mut MultiTreeBuilder::default()).unwrap();
+        assert_eq!((&tree).to_newick_string(), "(((4,2),(7,1)),8);");
+    }
+
+    #[test]
+    fn single_leaf() {
+        let trees = parse(&["3;", "3;"]);
+        let tree = strict(&trees, &mut MultiTreeBuilder::default()).unwrap();
+        assert_eq!((&tree).to_newick_string(), "3;");
+    }
+
+    #[test]
+    fn star() {
+        let trees = parse(&["((1,2),(3,4));", "((1,3),(2,4));"]);
+        let tree = strict(&trees, &mut MultiTreeBuilder::default()).unwrap();
+        assert_eq!((&tree).to_newick_string(), "(1,2,3,4);");
+
+        let tree = majority(&trees, 1.0, &mut MultiTreeBuilder::default()).unwrap();
+        assert_eq!((&tree).to_newick_string(), "(1,2,3,4);");
+    }
+
+    #[test]
+    fn errors() {
+        let mut builder = MultiTreeBuilder::default();
+        assert_eq!(
+            strict(Vec::<&BinTree>::new(), &mut builder),
+            Err(ConsensusError::NoTrees)
+        );
+        assert_eq!(
+            strict(&parse(&["(1,2);", "(1,3);"]), &mut builder),
+            Err(ConsensusError::DifferentLeafSets)
+        );
+        assert_eq!(
+            strict(&parse(&["(1,2);", "(1,(2,3));"]), &mut builder),
+            Err(ConsensusError::DifferentLeafSets)
+        );
+        assert_eq!(
+            strict(&parse(&["(1,2);", "(1,1);"]), &mut builder),
+            Err(ConsensusError::DuplicateLabel(Label(1)))
+        );
+        assert_eq!(
+            majority(&parse(&["(1,2);"]), 0.3, &mut builder),
+            Err(ConsensusError::InvalidThreshold(0.3))
+        );
+    }
+}
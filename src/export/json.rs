@@ -0,0 +1,521 @@
+use crate::binary_tree::*;
+use serde_core::de::Error as _;
+use serde_json::{Value, json};
+
+/// Structured JSON representation of a tree, where a leaf is written as `{"leaf": 5}` and an
+/// inner node as `{"inner": [left, right]}`. This allows, e.g., web front-ends to consume
+/// trees without a Newick parser. All conversions use explicit stacks, so arbitrarily deep
+/// trees are supported. However, [`serde_json::Value`] is dropped recursively, so deep trees
+/// should be converted with [`to_json_string`] and [`JsonTree::from_json_str`] rather than
+/// through a `Value`.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, export::json::JsonTree};
+///
+/// let tree: BinTree = "((1,2),3);".parse().unwrap();
+/// let json = JsonTree::from_cursor(tree.top_down()).to_json_value().to_string();
+/// assert_eq!(json, r#"{"inner":[{"inner":[{"leaf":1},{"leaf":2}]},{"leaf":3}]}"#);
+///
+/// let parsed = JsonTree::from_json_value(&serde_json::from_str(&json).unwrap()).unwrap();
+/// let rebuilt = parsed.build(&mut BinTreeBuilder::default(), NodeIdx::new(4));
+/// assert_eq!(rebuilt, tree);
+/// ```
+///
+/// With the `serde` feature, `JsonTree` also implements `Serialize` and `Deserialize` with the
+/// same representation; being derived, they recurse once per level of the tree.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum JsonTree {
+    Inner(Box<(JsonTree, JsonTree)>),
+    Leaf(LabelValue),
+}
+
+impl JsonTree {
+    /// Copies the tree below `node`
+    pub fn from_cursor<C: TopDownCursor>(node: C) -> Self {
+        enum Step<C> {
+            Enter(C),
+            Exit,
+        }
+
+        let mut subtrees = Vec::new();
+        let mut stack = vec![Step::Enter(node)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(node) => match node.visit() {
+                    NodeType::Inner(left, right) => {
+                        stack.push(Step::Exit);
+                        stack.push(Step::Enter(right));
+                        stack.push(Step::Enter(left));
+                    }
+                    NodeType::Leaf(label) => subtrees.push(JsonTree::Leaf(label.0)),
+                },
+                Step::Exit => {
+                    let right = subtrees.pop().unwrap();
+                    let left = subtrees.pop().unwrap();
+                    subtrees.push(JsonTree::Inner(Box::new((left, right))));
+                }
+            }
+        }
+
+        subtrees.pop().unwrap()
+    }
+
+    /// Returns the JSON representation, e.g. `{"inner":[{"leaf":1},{"leaf":2}]}`
+    pub fn to_json_value(&self) -> Value {
+        enum Step<'a> {
+            Enter(&'a JsonTree),
+            Exit,
+        }
+
+        let mut values = Vec::new();
+        let mut stack = vec![Step::Enter(self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(JsonTree::Inner(children)) => {
+                    stack.push(Step::Exit);
+                    stack.push(Step::Enter(&children.1));
+                    stack.push(Step::Enter(&children.0));
+                }
+                Step::Enter(JsonTree::Leaf(label)) => values.push(json!({ "leaf": label })),
+                Step::Exit => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(json!({ "inner": [left, right] }));
+                }
+            }
+        }
+
+        values.pop().unwrap()
+    }
+
+    /// Inverse of [`JsonTree::to_json_value`]; fails if `value` is not a valid representation
+    pub fn from_json_value(value: &Value) -> Result<Self, serde_json::Error> {
+        enum Step<'a> {
+            Enter(&'a Value),
+            Exit,
+        }
+
+        let mut subtrees = Vec::new();
+        let mut stack = vec![Step::Enter(value)];
+        while let Some(step) = stack.pop() {
+            let value = match step {
+                Step::Enter(value) => value,
+                Step::Exit => {
+                    let right = subtrees.pop().unwrap();
+                    let left = subtrees.pop().unwrap();
+                    subtrees.push(JsonTree::Inner(Box::new((left, right))));
+                    continue;
+                }
+            };
+
+            let invalid = || serde_json::Error::custom(format!("invalid tree node {value}"));
+            let Some(object) = value.as_object().filter(|o| o.len() == 1) else {
+                return Err(invalid());
+            };
+
+            if let Some(label) = object.get("leaf") {
+                let label = label
+                    .as_u64()
+                    .and_then(|l| LabelValue::try_from(l).ok())
+                    .ok_or_else(invalid)?;
+                subtrees.push(JsonTree::Leaf(label));
+                continue;
+            }
+
+            match object
+                .get("inner")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+            {
+                Some([left, right]) => {
+                    stack.push(Step::Exit);
+                    stack.push(Step::Enter(right));
+                    stack.push(Step::Enter(left));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(subtrees.pop().unwrap())
+    }
+
+    /// Parses the representation written by [`to_json_string`] (with arbitrary whitespace
+    /// between tokens). Since the schema is fixed, the text is parsed directly with an explicit
+    /// stack, so, unlike `serde_json::from_str`, there is no limit on the depth of the tree.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::export::json::JsonTree;
+    ///
+    /// let tree = JsonTree::from_json_str(r#"{"inner": [{"leaf": 1}, {"leaf": 2}]}"#).unwrap();
+    /// assert_eq!(tree, JsonTree::Inner(Box::new((JsonTree::Leaf(1), JsonTree::Leaf(2)))));
+    /// assert!(JsonTree::from_json_str(r#"{"leaf": 1, "inner": []}"#).is_err());
+    /// ```
+    pub fn from_json_str(text: &str) -> Result<Self, serde_json::Error> {
+        let mut parser = SchemaParser {
+            text: text.as_bytes(),
+            pos: 0,
+        };
+
+        // inner nodes whose `[` was read; holds the left child once it is complete
+        let mut pending: Vec<Option<JsonTree>> = Vec::new();
+        loop {
+            parser.expect(b'{')?;
+            let key = parser.key()?;
+            parser.expect(b':')?;
+            let mut node = match key {
+                b"leaf" => {
+                    let label = parser.label()?;
+                    parser.expect(b'}')?;
+                    JsonTree::Leaf(label)
+                }
+                b"inner" => {
+                    parser.expect(b'[')?;
+                    pending.push(None);
+                    continue;
+                }
+                _ => return Err(parser.error("expected key \"leaf\" or \"inner\"")),
+            };
+
+            // attach the completed node to its parent, completing ancestors on the way
+            loop {
+                match pending.last_mut() {
+                    None => {
+                        parser.skip_whitespace();
+                        if parser.pos < parser.text.len() {
+                            return Err(parser.error("trailing characters"));
+                        }
+                        return Ok(node);
+                    }
+                    Some(left @ None) => {
+                        *left = Some(node);
+                        parser.expect(b',')?;
+                        break;
+                    }
+                    Some(Some(_)) => {
+                        let left = pending.pop().unwrap().unwrap();
+                        parser.expect(b']')?;
+                        parser.expect(b'}')?;
+                        node = JsonTree::Inner(Box::new((left, node)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds the tree with `builder`, where inner nodes receive ids in preorder starting with
+    /// `root_id`, as assigned by the Newick parser
+    pub fn build<B: TreeBuilder>(&self, builder: &mut B, root_id: NodeIdx) -> B::Node {
+        enum Step<'a> {
+            Enter(&'a JsonTree),
+            Exit(NodeIdx),
+        }
+
+        let mut next_id = root_id;
+        let mut nodes = Vec::new();
+        let mut stack = vec![Step::Enter(self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(JsonTree::Inner(children)) => {
+                    stack.push(Step::Exit(next_id));
+                    stack.push(Step::Enter(&children.1));
+                    stack.push(Step::Enter(&children.0));
+                    next_id = next_id.incremented();
+                }
+                Step::Enter(JsonTree::Leaf(label)) => nodes.push(builder.new_leaf(Label(*label))),
+                Step::Exit(id) => {
+                    let right = nodes.pop().unwrap();
+                    let left = nodes.pop().unwrap();
+                    nodes.push(builder.new_inner(id, left, right));
+                }
+            }
+        }
+
+        let root = nodes.pop().unwrap();
+        builder.make_root(root)
+    }
+}
+
+/// Compares deep trees without recursion
+impl PartialEq for JsonTree {
+    fn eq(&self, other: &Self) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (JsonTree::Inner(a), JsonTree::Inner(b)) => {
+                    stack.push((&a.1, &b.1));
+                    stack.push((&a.0, &b.0));
+                }
+                (JsonTree::Leaf(a), JsonTree::Leaf(b)) if a == b => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl Eq for JsonTree {}
+
+/// Copies deep trees without recursion
+impl Clone for JsonTree {
+    fn clone(&self) -> Self {
+        enum Step<'a> {
+            Enter(&'a JsonTree),
+            Exit,
+        }
+
+        let mut subtrees = Vec::new();
+        let mut stack = vec![Step::Enter(self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(JsonTree::Inner(children)) => {
+                    stack.push(Step::Exit);
+                    stack.push(Step::Enter(&children.1));
+                    stack.push(Step::Enter(&children.0));
+                }
+                Step::Enter(JsonTree::Leaf(label)) => subtrees.push(JsonTree::Leaf(*label)),
+                Step::Exit => {
+                    let right = subtrees.pop().unwrap();
+                    let left = subtrees.pop().unwrap();
+                    subtrees.push(JsonTree::Inner(Box::new((left, right))));
+                }
+            }
+        }
+        subtrees.pop().unwrap()
+    }
+}
+
+/// Drops deep trees without recursion
+impl Drop for JsonTree {
+    fn drop(&mut self) {
+        let detach = |children: &mut (Self, Self)| {
+            std::mem::replace(children, (Self::Leaf(0), Self::Leaf(0)))
+        };
+
+        let JsonTree::Inner(children) = self else {
+            return;
+        };
+        let mut stack = vec![detach(children)];
+        while let Some((left, right)) = stack.pop() {
+            for mut child in [left, right] {
+                if let JsonTree::Inner(children) = &mut child {
+                    stack.push(detach(children));
+                }
+            }
+        }
+    }
+}
+
+/// Reads the tokens of the fixed [`JsonTree`] schema
+struct SchemaParser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SchemaParser<'a> {
+    fn error(&self, message: &str) -> serde_json::Error {
+        serde_json::Error::custom(format!("{message} at byte {}", self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|c| matches!(c, b' ' | b'\t' | b'\n' | b'\r'))
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), serde_json::Error> {
+        self.skip_whitespace();
+        if self.text.get(self.pos) != Some(&c) {
+            return Err(self.error(&format!("expected '{}'", c as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Reads a string without escape sequences
+    fn key(&mut self) -> Result<&'a [u8], serde_json::Error> {
+        self.expect(b'"')?;
+        let begin = self.pos;
+        let len = self.text[begin..]
+            .iter()
+            .position(|&c| c == b'"' || c == b'\\')
+            .ok_or_else(|| self.error("unterminated key"))?;
+        self.pos += len;
+        self.expect(b'"')?;
+        Ok(&self.text[begin..begin + len])
+    }
+
+    fn label(&mut self) -> Result<LabelValue, serde_json::Error> {
+        self.skip_whitespace();
+        let begin = self.pos;
+        let len = self.text[begin..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let digits = &self.text[begin..begin + len];
+        if digits.is_empty() || (digits.len() > 1 && digits[0] == b'0') {
+            return Err(self.error("expected label"));
+        }
+        self.pos += len;
+
+        // digits are ASCII, so the conversion cannot fail
+        std::str::from_utf8(digits)
+            .unwrap()
+            .parse()
+            .map_err(|_| self.error("label out of range"))
+    }
+}
+
+/// Returns the [`JsonTree`] representation of the tree below `root` as JSON string; the tree
+/// is traversed with an explicit stack and written directly
+pub fn to_json_string(root: impl TopDownCursor) -> String {
+    use std::fmt::Write;
+
+    enum Step<C> {
+        Visit(C),
+        Text(&'static str),
+    }
+
+    let mut out = String::new();
+    let mut stack = vec![Step::Visit(root)];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Visit(node) => match node.visit() {
+                NodeType::Inner(left, right) => {
+                    out.push_str(r#"{"inner":["#);
+                    stack.push(Step::Text("]}"));
+                    stack.push(Step::Visit(right));
+                    stack.push(Step::Text(","));
+                    stack.push(Step::Visit(left));
+                }
+                NodeType::Leaf(label) => write!(out, r#"{{"leaf":{}}}"#, label.0).unwrap(),
+            },
+            Step::Text(text) => out.push_str(text),
+        }
+    }
+    out
+}
+
+/// Parses a tree in [`JsonTree`] representation (see [`JsonTree::from_json_str`]) and builds
+/// it as in [`JsonTree::build`]
+pub fn from_json_str<B: TreeBuilder>(
+    text: &str,
+    builder: &mut B,
+    root_id: NodeIdx,
+) -> Result<B::Node, serde_json::Error> {
+    Ok(JsonTree::from_json_str(text)?.build(builder, root_id))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::BinaryTreeParser;
+
+    #[test]
+    fn roundtrip() {
+        for text in ["1;", "(((4,2),(7,1)),(8,(3,(5,6))));"] {
+            let mut builder = IndexedBinTreeBuilder::default();
+            let tree = builder
+                .parse_newick_from_str(text, NodeIdx::new(9))
+                .unwrap();
+            let json = to_json_string(tree.top_down());
+            assert_eq!(
+                from_json_str(&json, &mut builder, NodeIdx::new(9)).unwrap(),
+                tree
+            );
+        }
+
+        let mut builder = BinTreeBuilder::default();
+        for invalid in [
+            "",
+            "{\"leaf\":-1}",
+            "{\"inner\":[{\"leaf\":1}]}",
+            "{\"node\":1}",
+            "{\"leaf\":1,\"inner\":[]}",
+        ] {
+            assert!(from_json_str(invalid, &mut builder, NodeIdx::new(0)).is_err());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_derive_matches() {
+        let tree: BinTree = "((1,2),3);".parse().unwrap();
+        let json_tree = JsonTree::from_cursor(tree.top_down());
+        assert_eq!(
+            serde_json::to_string(&json_tree).unwrap(),
+            to_json_string(tree.top_down())
+        );
+        assert_eq!(
+            serde_json::from_value::<JsonTree>(json_tree.to_json_value()).unwrap(),
+            json_tree
+        );
+    }
+
+    #[test]
+    fn deep_roundtrip() {
+        // node u < N is inner with children (u + 1, N + 1 + u); nodes u >= N are leaves
+        const N: LabelValue = 100_000;
+        let children_of = |&u: &LabelValue| (u < N).then(|| (u + 1, N + 1 + u));
+        let label_of = |&u: &LabelValue| (u >= N).then(|| Label(u - N + 1));
+        let json = to_json_string(FnCursor::new(0, &children_of, &label_of));
+        assert!(json.starts_with(r#"{"inner":[{"inner":["#));
+
+        let tree = JsonTree::from_json_str(&json).unwrap();
+        assert_eq!(
+            tree,
+            JsonTree::from_cursor(FnCursor::new(0, &children_of, &label_of))
+        );
+        assert_eq!(tree.clone(), tree);
+
+        // build into an edge list and write it again
+        let mut children = std::collections::HashMap::new();
+        let root = from_json_str(
+            &json,
+            &mut EdgeListBuilder::new(|parent, child| {
+                children.entry(parent).or_insert_with(Vec::new).push(child)
+            }),
+            NodeIdx::new(0),
+        )
+        .unwrap();
+        let children_of = |u: &EdgeTarget| match u {
+            EdgeTarget::Inner(id) => Some((children[id][0], children[id][1])),
+            EdgeTarget::Leaf(_) => None,
+        };
+        let label_of = |u: &EdgeTarget| match u {
+            EdgeTarget::Inner(_) => None,
+            EdgeTarget::Leaf(label) => Some(*label),
+        };
+        assert_eq!(
+            to_json_string(FnCursor::new(root, &children_of, &label_of)),
+            json
+        );
+    }
+
+    #[test]
+    fn whitespace_and_errors() {
+        let text = " { \"inner\" : [ {\"leaf\":1} ,\n{\"leaf\":2}] } ";
+        assert_eq!(
+            JsonTree::from_json_str(text).unwrap(),
+            JsonTree::from_json_value(&serde_json::from_str(text).unwrap()).unwrap()
+        );
+
+        for invalid in [
+            r#"{"leaf":01}"#,
+            r#"{"leaf":1.5}"#,
+            r#"{"leaf":1}x"#,
+            r#"{"le\u0061f":1}"#,
+            r#"{"leaf":99999999999999999999999}"#,
+            r#"{"inner":[{"leaf":1},{"leaf":2},{"leaf":3}]}"#,
+        ] {
+            assert!(JsonTree::from_json_str(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! Exporters into formats intended for humans and other tools rather than solvers.
+pub mod json;
+pub mod svg;
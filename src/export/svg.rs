@@ -0,0 +1,346 @@
+use crate::{
+    binary_tree::{flat_tree::FlatTree, *},
+    network::{Network, NetworkError},
+};
+use std::io::{self, Write};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SvgError {
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+
+    #[error(transparent)]
+    IO(#[from] io::Error),
+}
+
+/// Geometry of the drawings produced by [`write_svg`] and [`write_network_svg`]; all values
+/// are in pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgStyle {
+    /// Horizontal distance between neighboring leaves
+    pub leaf_spacing: f64,
+
+    /// Vertical distance between two levels of the layout
+    pub level_height: f64,
+
+    /// Empty space around the drawing
+    pub margin: f64,
+
+    pub font_size: f64,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        Self {
+            leaf_spacing: 30.0,
+            level_height: 30.0,
+            margin: 20.0,
+            font_size: 12.0,
+        }
+    }
+}
+
+/// Writes a standalone SVG drawing of the tree using a simple layered layout:
+/// All leaves are placed (in left-to-right order) on a common baseline and labelled
+/// below it. Each inner node is placed above its highest child, centered over its children.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, export::svg::*, newick::*};
+///
+/// let tree = BinTreeBuilder::default()
+///     .parse_newick_from_str("((1,2),3);", NodeIdx::new(0))
+///     .unwrap();
+///
+/// let svg = to_svg_string(tree.top_down(), &SvgStyle::default());
+/// assert!(svg.starts_with("<svg"));
+/// assert_eq!(svg.matches("<line").count(), 4);
+/// assert_eq!(svg.matches("<text").count(), 3);
+/// ```
+pub fn write_svg(
+    tree: impl TopDownCursor,
+    style: &SvgStyle,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    let tree = FlatTree::new(tree);
+
+    // height[u] is the number of levels between u and its deepest descendant
+    let mut height = vec![0usize; tree.nodes.len()];
+    let mut x = vec![0.0f64; tree.nodes.len()];
+
+    // leaves are stored in preorder, i.e., from left to right
+    for (i, &(_, u)) in tree.leaves.iter().enumerate() {
+        x[u] = style.margin + i as f64 * style.leaf_spacing;
+    }
+    for u in tree.post_order() {
+        if let Some((l, r)) = tree.nodes[u].children {
+            height[u] = height[l].max(height[r]) + 1;
+            x[u] = (x[l] + x[r]) / 2.0;
+        }
+    }
+
+    let levels = height.first().copied().unwrap_or(0);
+    let edges = tree
+        .nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(u, node)| Some((node.parent?, u, false)));
+    let leaves = tree.leaves.iter().map(|&(label, u)| (u, Some(label)));
+    write_layout(&x, &height, levels, edges, leaves, style, writer)
+}
+
+/// Writes a standalone SVG drawing of a rooted network using the layered layout of
+/// [`write_svg`]: leaves are placed in the order of their first occurrence in a depth-first
+/// traversal (which visits each reticulation once) and each other node above its highest child,
+/// centered over its children. Edges entering a reticulation are drawn dashed. Fails if the
+/// network is invalid (see [`Network::root`]).
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::Label, export::svg::*, network::Network};
+///
+/// // ((1,(2)#H1),(#H1,3))
+/// let mut network = Network::default();
+/// let [root, a, b, h] = [(); 4].map(|_| network.add_node());
+/// let [l1, l2, l3] = [1, 2, 3].map(|l| network.add_leaf(Label(l)));
+/// for (u, v) in [(root, a), (root, b), (a, l1), (a, h), (b, h), (b, l3), (h, l2)] {
+///     network.add_edge(u, v);
+/// }
+///
+/// let svg = network_to_svg_string(&network, &SvgStyle::default()).unwrap();
+/// assert_eq!(svg.matches("<line").count(), 7);
+/// assert_eq!(svg.matches("stroke-dasharray").count(), 2);
+/// ```
+pub fn write_network_svg(
+    network: &Network,
+    style: &SvgStyle,
+    writer: &mut impl Write,
+) -> Result<(), SvgError> {
+    let root = network.root()?;
+    let n = network.num_nodes();
+
+    // iterative depth-first search yielding edges and leaves in order and nodes in postorder
+    let mut visited = vec![false; n];
+    let mut edges = Vec::new();
+    let mut leaves = Vec::new();
+    let mut post_order = Vec::with_capacity(n);
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((u, next_child)) = stack.pop() {
+        let children = network.children(u);
+        if children.is_empty() {
+            leaves.push((u, network.label(u)));
+        }
+        match children.get(next_child) {
+            Some(&v) => {
+                stack.push((u, next_child + 1));
+                edges.push((u, v, network.is_reticulation(v)));
+                if !visited[v] {
+                    visited[v] = true;
+                    stack.push((v, 0));
+                }
+            }
+            None => post_order.push(u),
+        }
+    }
+
+    let mut height = vec![0usize; n];
+    let mut x = vec![0.0f64; n];
+    for (i, &(u, _)) in leaves.iter().enumerate() {
+        x[u] = style.margin + i as f64 * style.leaf_spacing;
+    }
+    for &u in &post_order {
+        let children = network.children(u);
+        if children.is_empty() {
+            continue;
+        }
+        height[u] = children.iter().map(|&v| height[v]).max().unwrap() + 1;
+        let (min, max) = children
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+                (min.min(x[v]), max.max(x[v]))
+            });
+        x[u] = (min + max) / 2.0;
+    }
+
+    write_layout(
+        &x,
+        &height,
+        height[root],
+        edges.into_iter(),
+        leaves.iter().copied(),
+        style,
+        writer,
+    )?;
+    Ok(())
+}
+
+/// Same as [`write_network_svg`] but returns the drawing as a `String`.
+pub fn network_to_svg_string(network: &Network, style: &SvgStyle) -> Result<String, NetworkError> {
+    let mut buffer: Vec<u8> = Vec::new();
+    match write_network_svg(network, style, &mut buffer) {
+        Ok(()) => {
+            Ok(String::from_utf8(buffer).expect("The writer should not produce invalid strings"))
+        }
+        Err(SvgError::Network(e)) => Err(e),
+        Err(SvgError::IO(e)) => unreachable!("writing into a Vec cannot fail: {e}"),
+    }
+}
+
+/// Writes the SVG document for nodes with horizontal positions `x` and `height[u]` levels
+/// above the baseline; `edges` are `(parent, child, dashed)` and `leaves` are labelled in order
+fn write_layout(
+    x: &[f64],
+    height: &[usize],
+    levels: usize,
+    edges: impl Iterator<Item = (usize, usize, bool)>,
+    leaves: impl ExactSizeIterator<Item = (usize, Option<Label>)>,
+    style: &SvgStyle,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let baseline = style.margin + levels as f64 * style.level_height;
+    let y = |u: usize| baseline - height[u] as f64 * style.level_height;
+
+    let width = 2.0 * style.margin + leaves.len().saturating_sub(1) as f64 * style.leaf_spacing;
+    let total_height = baseline + style.margin + 1.5 * style.font_size;
+
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{total_height}" viewBox="0 0 {width} {total_height}">"#
+    )?;
+
+    writeln!(writer, r#"<g stroke="black" fill="none">"#)?;
+    for (p, u, dashed) in edges {
+        writeln!(
+            writer,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}"{}/>"#,
+            x[p],
+            y(p),
+            x[u],
+            y(u),
+            if dashed {
+                r#" stroke-dasharray="4 2""#
+            } else {
+                ""
+            }
+        )?;
+    }
+    writeln!(writer, "</g>")?;
+
+    writeln!(
+        writer,
+        r#"<g font-family="sans-serif" font-size="{}" text-anchor="middle">"#,
+        style.font_size
+    )?;
+    for (u, label) in leaves {
+        if let Some(Label(label)) = label {
+            writeln!(
+                writer,
+                r#"<text x="{}" y="{}">{label}</text>"#,
+                x[u],
+                baseline + 1.2 * style.font_size
+            )?;
+        }
+    }
+    writeln!(writer, "</g>")?;
+
+    writeln!(writer, "</svg>")
+}
+
+/// Same as [`write_svg`] but returns the drawing as a `String`.
+pub fn to_svg_string(tree: impl TopDownCursor, style: &SvgStyle) -> String {
+    let mut buffer: Vec<u8> = Vec::new();
+    write_svg(tree, style, &mut buffer).expect("The writer should not fail");
+    String::from_utf8(buffer).expect("The writer should not produce invalid strings")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::BinaryTreeParser;
+
+    #[test]
+    fn leaf() {
+        let tree = BinTreeBuilder::default().new_leaf(Label(42));
+        let svg = to_svg_string(tree.top_down(), &SvgStyle::default());
+        assert_eq!(svg.matches("<line").count(), 0);
+        assert!(svg.contains(r#"<text x="20" y="34.4">42</text>"#), "{svg}");
+    }
+
+    #[test]
+    fn layout() {
+        let tree = BinTreeBuilder::default()
+            .parse_newick_from_str("((1,2),3);", NodeIdx::new(0))
+            .unwrap();
+        let svg = to_svg_string(tree.top_down(), &SvgStyle::default());
+
+        // root is centered above (1,2) and 3 at the top level
+        assert!(
+            svg.contains(r#"<line x1="57.5" y1="20" x2="35" y2="50"/>"#),
+            "{svg}"
+        );
+        assert!(
+            svg.contains(r#"<line x1="57.5" y1="20" x2="80" y2="80"/>"#),
+            "{svg}"
+        );
+        assert!(
+            svg.contains(r#"<line x1="35" y1="50" x2="20" y2="80"/>"#),
+            "{svg}"
+        );
+        assert!(svg.contains(r#"<text x="80" y="94.4">3</text>"#), "{svg}");
+    }
+
+    #[test]
+    fn network_matches_tree() {
+        let tree = BinTreeBuilder::default()
+            .parse_newick_from_str("(((4,2),(7,1)),8);", NodeIdx::new(0))
+            .unwrap();
+        let network = Network::from_tree(tree.top_down());
+        let style = SvgStyle::default();
+        assert_eq!(
+            network_to_svg_string(&network, &style).unwrap(),
+            to_svg_string(tree.top_down(), &style)
+        );
+    }
+
+    #[test]
+    fn dashed_reticulation_edges() {
+        // ((1,(2)#H1),(#H1,3)); the reticulation is placed above leaf 2
+        let mut network = Network::default();
+        let [root, a, b, h] = [(); 4].map(|_| network.add_node());
+        let [l1, l2, l3] = [1, 2, 3].map(|l| network.add_leaf(Label(l)));
+        for (u, v) in [
+            (root, a),
+            (root, b),
+            (a, l1),
+            (a, h),
+            (b, h),
+            (b, l3),
+            (h, l2),
+        ] {
+            network.add_edge(u, v);
+        }
+
+        let svg = network_to_svg_string(&network, &SvgStyle::default()).unwrap();
+        assert_eq!(svg.matches("<line").count(), 7);
+        assert_eq!(svg.matches("<text").count(), 3);
+
+        // b is centered over the reticulation (x = 50) and leaf 3 (x = 80)
+        assert!(
+            svg.contains(r#"<line x1="35" y1="50" x2="50" y2="80" stroke-dasharray="4 2"/>"#),
+            "{svg}"
+        );
+        assert!(
+            svg.contains(r#"<line x1="65" y1="50" x2="50" y2="80" stroke-dasharray="4 2"/>"#),
+            "{svg}"
+        );
+        assert!(
+            svg.contains(r#"<line x1="50" y1="80" x2="50" y2="110"/>"#),
+            "{svg}"
+        );
+
+        network.add_edge(l3, root);
+        assert!(network_to_svg_string(&network, &SvgStyle::default()).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+//! Forests, i.e. ordered collections of trees over a shared label universe `1..=num_leaves`,
+//! as they appear, e.g., as solutions of agreement forest problems.
+use crate::{
+    binary_tree::*,
+    newick::{
+        BinaryTreeParser, LabelInterner, NewickStyle, NewickWriter, ParserError,
+        write_newick_with_names,
+    },
+};
+use std::io::Write;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ForestError {
+    #[error(transparent)]
+    Parser(#[from] ParserError),
+
+    #[error("label {0:?} is outside the universe")]
+    LabelOutOfRange(Label),
+
+    #[error("label {0:?} appears multiple times")]
+    DuplicateLabel(Label),
+
+    #[error("label {0:?} does not appear in any tree")]
+    MissingLabel(Label),
+}
+
+#[derive(Debug, Clone)]
+pub struct Forest<B: TreeBuilder> {
+    /// The trees are defined over the labels `1..=num_leaves`
+    pub num_leaves: usize,
+    pub trees: Vec<B::Node>,
+}
+
+impl<B: TreeBuilder> Forest<B> {
+    pub fn new(num_leaves: usize) -> Self {
+        Self {
+            num_leaves,
+            trees: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, tree: B::Node) {
+        self.trees.push(tree);
+    }
+
+    pub fn len(&self) -> usize {
+        self.trees.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
+    }
+
+    /// Parses a list of Newick trees, each terminated by `;` and separated by arbitrary
+    /// whitespace (e.g. one tree per line). Inner nodes of the `i`-th tree are numbered in
+    /// preorder starting with `root_id(i)`.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, forest::*};
+    ///
+    /// let mut builder = BinTreeBuilder::default();
+    /// let forest: Forest<BinTreeBuilder> =
+    ///     Forest::parse_newick("((1,3),4);\n2;\n", 4, &mut builder, |_| NodeIdx::new(0)).unwrap();
+    /// assert_eq!(forest.len(), 2);
+    /// assert_eq!(forest.to_newick_string(), "((1,3),4);\n2;\n");
+    /// assert!(forest.validate_partition().is_ok());
+    /// ```
+    pub fn parse_newick(
+        text: &str,
+        num_leaves: usize,
+        builder: &mut B,
+        mut root_id: impl FnMut(usize) -> NodeIdx,
+    ) -> Result<Self, ParserError> {
+        let mut forest = Self::new(num_leaves);
+        let mut lexer = crate::newick::lexer::Lexer::new(text);
+        lexer.allow_whitespaces();
+        while !lexer.is_exhausted() {
+            let tree = builder.parse_newick_from_lexer(&mut lexer, root_id(forest.len()))?;
+            forest.push(tree);
+        }
+        Ok(forest)
+    }
+}
+
+impl<B: TreeBuilder> Forest<B>
+where
+    for<'a> &'a B::Node: TopDownCursor,
+{
+    /// Writes each tree in Newick format on its own line
+    pub fn write_newick(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        self.write_newick_styled(&NewickStyle::COMPACT, writer)
+    }
+
+    /// Same as [`Forest::write_newick`], but emits whitespace according to `style`; each tree
+    /// is followed by exactly one newline regardless of [`NewickStyle::trailing_newline`]
+    pub fn write_newick_styled(
+        &self,
+        style: &NewickStyle,
+        writer: &mut impl Write,
+    ) -> std::io::Result<()> {
+        for tree in &self.trees {
+            tree.write_newick_inner_styled(style, writer)?;
+            writeln!(writer, ";")?;
+        }
+        Ok(())
+    }
+
+    /// Same as [`Forest::write_newick`], but writes the name of each label if `names` is
+    /// provided; see [`write_newick_with_names`]
+    pub fn write_newick_with_names(
+        &self,
+        names: Option<&LabelInterner>,
+        writer: &mut impl Write,
+    ) -> std::io::Result<()> {
+        for tree in &self.trees {
+            write_newick_with_names(tree, names, writer)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    pub fn to_newick_string(&self) -> String {
+        let mut buffer: Vec<u8> = Vec::new();
+        self.write_newick(&mut buffer)
+            .expect("The writer should not fail");
+        String::from_utf8(buffer).unwrap()
+    }
+
+    /// Checks that the leaf sets of the trees partition the universe `1..=num_leaves`,
+    /// i.e. that each label appears in exactly one tree and exactly once.
+    pub fn validate_partition(&self) -> Result<(), ForestError> {
+        let mut seen = vec![false; self.num_leaves];
+        for tree in &self.trees {
+            claim_leaves(&mut seen, tree)?;
+        }
+        first_missing(&seen)
+    }
+
+    /// Builds a forest from `trees` and fails unless their leaf sets partition
+    /// `1..=num_leaves`; see [`ForestBuilder`] to add trees one at a time.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, forest::*};
+    ///
+    /// let trees: Vec<BinTree> = ["(1,3);", "2;"].map(|t| t.parse().unwrap()).into();
+    /// assert!(Forest::<BinTreeBuilder>::from_trees(3, trees.clone()).is_ok());
+    /// assert_eq!(
+    ///     Forest::<BinTreeBuilder>::from_trees(4, trees).unwrap_err(),
+    ///     ForestError::MissingLabel(Label(4))
+    /// );
+    /// ```
+    pub fn from_trees(
+        num_leaves: usize,
+        trees: impl IntoIterator<Item = B::Node>,
+    ) -> Result<Self, ForestError> {
+        let mut builder = ForestBuilder::new(num_leaves);
+        for tree in trees {
+            builder.push(tree)?;
+        }
+        builder.build()
+    }
+}
+
+/// Assembles a [`Forest`] tree by tree while checking that no label lies outside of
+/// `1..=num_leaves` or appears twice; [`ForestBuilder::build`] additionally checks that no
+/// label is missing. A rejected tree is not added and leaves the builder unchanged.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, forest::*};
+///
+/// let mut builder = ForestBuilder::<BinTreeBuilder>::new(3);
+/// builder.push("(1,3);".parse().unwrap()).unwrap();
+/// assert_eq!(
+///     builder.push("(2,3);".parse().unwrap()),
+///     Err(ForestError::DuplicateLabel(Label(3)))
+/// );
+/// builder.push("2;".parse().unwrap()).unwrap();
+/// assert_eq!(builder.build().unwrap().len(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ForestBuilder<B: TreeBuilder> {
+    trees: Vec<B::Node>,
+
+    /// `seen[i]` is `true` iff label `i + 1` appears in `trees`
+    seen: Vec<bool>,
+}
+
+impl<B: TreeBuilder> ForestBuilder<B>
+where
+    for<'a> &'a B::Node: TopDownCursor,
+{
+    pub fn new(num_leaves: usize) -> Self {
+        Self {
+            trees: Vec::new(),
+            seen: vec![false; num_leaves],
+        }
+    }
+
+    /// Appends `tree` unless one of its labels is out of range or was already added
+    pub fn push(&mut self, tree: B::Node) -> Result<(), ForestError> {
+        claim_leaves(&mut self.seen, &tree)?;
+        self.trees.push(tree);
+        Ok(())
+    }
+
+    /// Returns the forest, or the smallest label that does not appear in any tree
+    pub fn build(self) -> Result<Forest<B>, ForestError> {
+        first_missing(&self.seen)?;
+        Ok(Forest {
+            num_leaves: self.seen.len(),
+            trees: self.trees,
+        })
+    }
+}
+
+/// Marks the leaves of `tree` in `seen`; on error, `seen` is left unchanged
+fn claim_leaves<C: TopDownCursor>(seen: &mut [bool], tree: C) -> Result<(), ForestError> {
+    let mut claimed = Vec::new();
+    let result = leaves_in_order(tree).try_for_each(|label| {
+        let idx = (label.0 as usize)
+            .checked_sub(1)
+            .filter(|&i| i < seen.len())
+            .ok_or(ForestError::LabelOutOfRange(label))?;
+        if std::mem::replace(&mut seen[idx], true) {
+            return Err(ForestError::DuplicateLabel(label));
+        }
+        claimed.push(idx);
+        Ok(())
+    });
+
+    if result.is_err() {
+        for idx in claimed {
+            seen[idx] = false;
+        }
+    }
+    result
+}
+
+fn first_missing(seen: &[bool]) -> Result<(), ForestError> {
+    match seen.iter().position(|&s| !s) {
+        Some(i) => Err(ForestError::MissingLabel(Label(i as LabelValue + 1))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(text: &str, n: usize) -> Forest<IndexedBinTreeBuilder> {
+        Forest::parse_newick(text, n, &mut IndexedBinTreeBuilder::default(), |i| {
+            NodeIdx::new(10 * i as u32)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_and_write() {
+        let forest = parse(" (1,2);(3,(4,5));\n\n 6;  ", 6);
+        assert_eq!(forest.len(), 3);
+        assert_eq!(forest.trees[1].node_idx(), NodeIdx::new(10));
+        assert_eq!(forest.to_newick_string(), "(1,2);\n(3,(4,5));\n6;\n");
+
+        assert!(parse("", 0).is_empty());
+        assert!(
+            Forest::<BinTreeBuilder>::parse_newick(
+                "(1,2);(3",
+                3,
+                &mut BinTreeBuilder::default(),
+                |_| NodeIdx::new(0)
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn partition() {
+        assert_eq!(parse("(1,2);(3,(4,5));", 5).validate_partition(), Ok(()));
+        assert_eq!(
+            parse("(1,2);(3,(4,2));", 5).validate_partition(),
+            Err(ForestError::DuplicateLabel(Label(2)))
+        );
+        assert_eq!(
+            parse("(1,2);(3,(4,6));", 5).validate_partition(),
+            Err(ForestError::LabelOutOfRange(Label(6)))
+        );
+        assert_eq!(
+            parse("(1,2);(4,0);", 5).validate_partition(),
+            Err(ForestError::LabelOutOfRange(Label(0)))
+        );
+        assert_eq!(
+            parse("(1,2);(4,5);", 5).validate_partition(),
+            Err(ForestError::MissingLabel(Label(3)))
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let tree = |text: &str| -> IndexedBinTree { text.parse().unwrap() };
+
+        let mut builder = ForestBuilder::<IndexedBinTreeBuilder>::new(5);
+        builder.push(tree("(1,2);")).unwrap();
+        assert_eq!(
+            builder.push(tree("(3,(4,1));")),
+            Err(ForestError::DuplicateLabel(Label(1)))
+        );
+        assert_eq!(
+            builder.push(tree("(3,6);")),
+            Err(ForestError::LabelOutOfRange(Label(6)))
+        );
+
+        // rejected trees leave no trace
+        builder.push(tree("(3,4);")).unwrap();
+        builder.push(tree("5;")).unwrap();
+        assert_eq!(
+            builder.build().unwrap().to_newick_string(),
+            "(1,2);\n(3,4);\n5;\n"
+        );
+
+        assert_eq!(
+            Forest::<IndexedBinTreeBuilder>::from_trees(5, [tree("(1,2);"), tree("(3,4);")])
+                .unwrap_err(),
+            ForestError::MissingLabel(Label(5))
+        );
+        assert!(Forest::<IndexedBinTreeBuilder>::from_trees(0, []).is_ok());
+    }
+}
@@ -1,5 +1,22 @@
 #![doc = include_str!("../README.md")]
 
 pub mod binary_tree;
+pub mod cancellation;
+pub mod compat;
+pub mod conformance;
+pub mod consensus;
+pub mod export;
+pub mod forest;
+pub mod metrics;
+pub mod multi_tree;
+pub mod network;
 pub mod newick;
+pub mod nexus;
 pub mod pace;
+#[cfg(feature = "rand")]
+pub mod perturbation;
+pub mod progress;
+pub mod seed;
+pub mod supertree;
+pub mod tanglegram;
+pub mod testing;
@@ -0,0 +1,14 @@
+use crate::binary_tree::*;
+use thiserror::Error;
+
+pub mod quartet_distance;
+pub use quartet_distance::*;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MetricsError {
+    #[error("trees are not defined on the same leaf set")]
+    DifferentLeafSets,
+
+    #[error("label {0:?} appears multiple times in a tree")]
+    DuplicateLabel(Label),
+}
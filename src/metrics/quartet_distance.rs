@@ -0,0 +1,632 @@
+use super::*;
+use crate::binary_tree::flat_tree::FlatTree;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Computes the quartet distance between two binary trees on the same leaf set,
+/// i.e. the number of four-element leaf sets whose induced (unrooted) topologies differ.
+/// The rooting of the input trees is ignored.
+///
+/// The implementation follows the approach of Brodal, Fagerberg, and Pedersen: it counts the
+/// butterflies (i.e. quartets `ab|cd`) shared by both trees by coloring the leaves according
+/// to each inner node of the first tree (smaller half trick) and maintaining the number of
+/// matching butterflies of the second tree in a heavy path decomposition. It runs in time
+/// `O(n log^3 n)` and space `O(n)` for `n` leaves.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, metrics::*, newick::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let a = builder.parse_newick_from_str("((1,2),(3,(4,5)));", NodeIdx::new(0)).unwrap();
+/// let b = builder.parse_newick_from_str("((1,3),(2,(4,5)));", NodeIdx::new(0)).unwrap();
+///
+/// assert_eq!(quartet_distance(a.top_down(), a.top_down()), Ok(0));
+/// assert_eq!(quartet_distance(a.top_down(), b.top_down()), Ok(2));
+/// ```
+pub fn quartet_distance(
+    a: impl TopDownCursor,
+    b: impl TopDownCursor,
+) -> Result<u128, MetricsError> {
+    let tree_a = FlatTree::new(a);
+    let tree_b = FlatTree::new(b);
+
+    // map labels to dense indices, such that each leaf in `tree_b` knows its
+    // counterpart in `tree_a`
+    let mut dense = HashMap::with_capacity(tree_a.leaves.len());
+    for &(label, _) in &tree_a.leaves {
+        if dense.insert(label, dense.len()).is_some() {
+            return Err(MetricsError::DuplicateLabel(label));
+        }
+    }
+
+    let mut leaf_of_b = vec![usize::MAX; dense.len()];
+    for &(label, node) in &tree_b.leaves {
+        let idx = *dense.get(&label).ok_or(MetricsError::DifferentLeafSets)?;
+        if leaf_of_b[idx] != usize::MAX {
+            return Err(MetricsError::DuplicateLabel(label));
+        }
+        leaf_of_b[idx] = node;
+    }
+
+    if tree_a.leaves.len() != tree_b.leaves.len() {
+        return Err(MetricsError::DifferentLeafSets);
+    }
+
+    let n = tree_a.leaves.len() as u128;
+    if n < 4 {
+        return Ok(0);
+    }
+
+    let mut partner = vec![usize::MAX; tree_a.nodes.len()];
+    for &(label, node) in &tree_a.leaves {
+        partner[node] = leaf_of_b[dense[&label]];
+    }
+
+    // computed stepwise to avoid overflows of n^4 for large n
+    let num_quartets = n * (n - 1) / 2 * (n - 2) / 3 * (n - 3) / 4;
+    let four_times_shared = count_shared_butterflies(&tree_a, &partner, &tree_b);
+    Ok(num_quartets - (four_times_shared / 4) as u128)
+}
+
+const A: u8 = 0;
+const B: u8 = 1;
+const C: u8 = 2;
+
+/// Returns four times the number of butterflies shared by both trees, where `partner` maps
+/// each leaf of `tree_a` to the leaf of `tree_b` with the same label.
+///
+/// Each butterfly `ab|cd` of an unrooted tree is claimed by two inner nodes: the node separating
+/// `a` and `b` while `c` and `d` lie in its third direction, and vice versa. For each inner
+/// node `v` of `tree_a`, we color the leaves below its children with `A` and `B`, and all
+/// other leaves with `C`. Then the butterflies of `tree_b` whose claimed pair has two distinct
+/// colors while the other pair has the third color are exactly the shared butterflies
+/// claimed at `v`. Visiting the smaller child first and keeping the colors of the larger
+/// child, each leaf changes its color `O(log n)` times.
+fn count_shared_butterflies(tree_a: &FlatTree, partner: &[usize], tree_b: &FlatTree) -> i128 {
+    enum Step {
+        Enter { u: usize, keep: bool },
+        Exit { u: usize, light: usize, keep: bool },
+    }
+
+    // nodes are stored in preorder, so the subtree of u spans 2 * size - 1 consecutive nodes
+    let leaves_below = |u: usize| {
+        (u..u + 2 * tree_a.nodes[u].size as usize - 1)
+            .filter(|&x| tree_a.nodes[x].children.is_none())
+            .map(|x| partner[x])
+    };
+
+    let mut colored = ColoredTree::new(tree_b);
+    let mut total = 0;
+    let mut stack = vec![Step::Enter {
+        u: tree_a.root,
+        keep: true,
+    }];
+
+    // Invariant: when entering u, all leaves are colored C; after leaving u, the leaves
+    // below u are colored B if `keep` and C otherwise.
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Enter { u, keep } => match tree_a.nodes[u].children {
+                None if keep => colored.recolor(partner[u], B),
+                None => {}
+                Some((l, r)) => {
+                    let (heavy, light) = if tree_a.nodes[l].size >= tree_a.nodes[r].size {
+                        (l, r)
+                    } else {
+                        (r, l)
+                    };
+                    stack.push(Step::Exit { u, light, keep });
+                    stack.push(Step::Enter {
+                        u: heavy,
+                        keep: true,
+                    });
+                    stack.push(Step::Enter {
+                        u: light,
+                        keep: false,
+                    });
+                }
+            },
+            Step::Exit { u, light, keep } => {
+                for leaf in leaves_below(light) {
+                    colored.recolor(leaf, A);
+                }
+                total += colored.matching_butterflies();
+                for leaf in leaves_below(light) {
+                    colored.recolor(leaf, B);
+                }
+                if !keep {
+                    for leaf in leaves_below(u) {
+                        colored.recolor(leaf, C);
+                    }
+                }
+            }
+        }
+    }
+
+    total
+}
+
+type Counts = [i128; 3];
+
+/// Twice the number of butterflies claimed by the nodes of a segment of a heavy path (and the
+/// subtrees below their light children) as polynomial in the color counts `X` of the leaves
+/// above and `Y` of the leaves below the segment. It only stores the monomials that occur,
+/// i.e. those of degree at most two in each of `X` and `Y`, where squares are limited to a
+/// single color.
+#[derive(Debug, Clone, Copy, Default)]
+struct Poly {
+    c: i128,
+    x: Counts,
+    y: Counts,
+    xx: Counts,
+    yy: Counts,
+    /// `xy[a][b]` is the coefficient of `X_a Y_b`
+    xy: [Counts; 3],
+    /// `xxy[a][b]` is the coefficient of `X_a^2 Y_b`
+    xxy: [Counts; 3],
+    /// `xyy[a][b]` is the coefficient of `X_a Y_b^2`
+    xyy: [Counts; 3],
+}
+
+impl Poly {
+    /// Twice the number of butterflies claimed by a node with the color counts `X` above,
+    /// `Y` below its heavy child, and `light` below its light child
+    fn claimed_by_node(light: &Counts) -> Self {
+        let mut poly = Self::default();
+        for c3 in 0..3 {
+            let (c1, c2) = ((c3 + 1) % 3, (c3 + 2) % 3);
+            // pair of color c3 above, separated pair below the heavy and light child
+            poly.xxy[c3][c1] += light[c2];
+            poly.xxy[c3][c2] += light[c1];
+            poly.xy[c3][c1] -= light[c2];
+            poly.xy[c3][c2] -= light[c1];
+            // pair of color c3 below the heavy child
+            poly.xyy[c1][c3] += light[c2];
+            poly.xyy[c2][c3] += light[c1];
+            poly.xy[c1][c3] -= light[c2];
+            poly.xy[c2][c3] -= light[c1];
+            // pair of color c3 below the light child
+            let pairs = light[c3] * light[c3] - light[c3];
+            poly.xy[c1][c2] += pairs;
+            poly.xy[c2][c1] += pairs;
+        }
+        poly
+    }
+
+    /// Interprets the restriction `P(X, 0)` of a whole heavy path as polynomial `Q` of a
+    /// light subtree hanging below a path node, i.e. returns `Q(X, Y) = P(X + Y, 0)`
+    fn hanging(&self) -> Self {
+        let mut poly = Self {
+            c: self.c,
+            x: self.x,
+            y: self.x,
+            xx: self.xx,
+            yy: self.xx,
+            ..Self::default()
+        };
+        for a in 0..3 {
+            poly.xy[a][a] = 2 * self.xx[a];
+        }
+        poly
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.c += other.c;
+        for a in 0..3 {
+            self.x[a] += other.x[a];
+            self.y[a] += other.y[a];
+            self.xx[a] += other.xx[a];
+            self.yy[a] += other.yy[a];
+            for b in 0..3 {
+                self.xy[a][b] += other.xy[a][b];
+                self.xxy[a][b] += other.xxy[a][b];
+                self.xyy[a][b] += other.xyy[a][b];
+            }
+        }
+    }
+
+    /// Returns `P(X + s, Y)`
+    fn shift_x(&self, s: &Counts) -> Self {
+        let mut poly = *self;
+        for (a, &s) in s.iter().enumerate() {
+            poly.c += s * (self.x[a] + s * self.xx[a]);
+            poly.x[a] += 2 * s * self.xx[a];
+            for b in 0..3 {
+                poly.y[b] += s * (self.xy[a][b] + s * self.xxy[a][b]);
+                poly.yy[b] += s * self.xyy[a][b];
+                poly.xy[a][b] += 2 * s * self.xxy[a][b];
+            }
+        }
+        poly
+    }
+
+    /// Returns `P(X, Y + t)`
+    fn shift_y(&self, t: &Counts) -> Self {
+        let mut poly = *self;
+        for (b, &t) in t.iter().enumerate() {
+            poly.c += t * (self.y[b] + t * self.yy[b]);
+            poly.y[b] += 2 * t * self.yy[b];
+            for a in 0..3 {
+                poly.x[a] += t * (self.xy[a][b] + t * self.xyy[a][b]);
+                poly.xx[a] += t * self.xxy[a][b];
+                poly.xy[a][b] += 2 * t * self.xyy[a][b];
+            }
+        }
+        poly
+    }
+}
+
+/// Consecutive nodes of a heavy path together with the subtrees below their light children
+#[derive(Debug, Clone, Copy, Default)]
+struct Segment {
+    poly: Poly,
+    counts: Counts,
+}
+
+impl Segment {
+    /// Combines the segment `upper` with the segment `lower` directly below it
+    fn merge(upper: &Self, lower: &Self) -> Self {
+        let mut poly = upper.poly.shift_y(&lower.counts);
+        poly.add(&lower.poly.shift_x(&upper.counts));
+        let mut counts = upper.counts;
+        for (c, l) in counts.iter_mut().zip(&lower.counts) {
+            *c += l;
+        }
+        Self { poly, counts }
+    }
+}
+
+struct HeavyPath {
+    /// Nodes from top to bottom; the last one is a leaf
+    nodes: Vec<usize>,
+    /// Position of the segment tree in [`ColoredTree::segments`]
+    offset: usize,
+    /// Path and position of the parent of the topmost node
+    parent: Option<(usize, usize)>,
+}
+
+/// Leaf-colored tree maintaining the number of butterflies whose claimed pair has two distinct
+/// colors while the other pair has the third color. Each heavy path stores a segment tree over
+/// its nodes, where the segment `[lo, hi]` at index `i` has its children at `i + 1` and
+/// `i + 2 * (mid - lo + 1)`.
+struct ColoredTree<'a> {
+    tree: &'a FlatTree,
+    color: Vec<u8>,
+    light_child: Vec<usize>,
+    path_of: Vec<(usize, usize)>,
+    paths: Vec<HeavyPath>,
+    segments: Vec<Segment>,
+    /// Positions of each path to update; paths are processed by decreasing id, i.e. each
+    /// path after all paths below it
+    dirty: Vec<Vec<usize>>,
+    pending: BinaryHeap<usize>,
+}
+
+impl<'a> ColoredTree<'a> {
+    /// Decomposes `tree` into heavy paths, where all leaves are colored `C`
+    fn new(tree: &'a FlatTree) -> Self {
+        let num_nodes = tree.nodes.len();
+        let mut this = Self {
+            tree,
+            color: vec![C; num_nodes],
+            light_child: vec![usize::MAX; num_nodes],
+            path_of: vec![(usize::MAX, usize::MAX); num_nodes],
+            paths: Vec::new(),
+            segments: Vec::with_capacity(2 * num_nodes),
+            dirty: Vec::new(),
+            pending: BinaryHeap::new(),
+        };
+
+        // paths below another path are discovered after it
+        let mut heads = vec![(tree.root, None)];
+        while let Some((head, parent)) = heads.pop() {
+            let id = this.paths.len();
+            let mut nodes = Vec::new();
+            let mut u = head;
+            loop {
+                this.path_of[u] = (id, nodes.len());
+                nodes.push(u);
+                let Some((l, r)) = tree.nodes[u].children else {
+                    break;
+                };
+                let (heavy, light) = if tree.nodes[l].size >= tree.nodes[r].size {
+                    (l, r)
+                } else {
+                    (r, l)
+                };
+                this.light_child[u] = light;
+                heads.push((light, Some((id, nodes.len() - 1))));
+                u = heavy;
+            }
+
+            this.paths.push(HeavyPath {
+                nodes,
+                offset: 0,
+                parent,
+            });
+        }
+
+        this.dirty = vec![Vec::new(); this.paths.len()];
+        for id in (0..this.paths.len()).rev() {
+            this.paths[id].offset = this.segments.len();
+            let len = this.paths[id].nodes.len();
+            this.segments
+                .resize(this.segments.len() + 2 * len - 1, Segment::default());
+            this.build(id, 0, 0, len - 1);
+        }
+
+        this
+    }
+
+    /// Returns twice the number of butterflies described in [`ColoredTree`]
+    fn matching_butterflies(&mut self) -> i128 {
+        while let Some(id) = self.pending.pop() {
+            let mut positions = std::mem::take(&mut self.dirty[id]);
+            positions.sort_unstable();
+            positions.dedup();
+            for &pos in &positions {
+                self.update(id, pos);
+            }
+            // keep the allocation
+            positions.clear();
+            self.dirty[id] = positions;
+
+            if let Some(parent) = self.paths[id].parent {
+                self.mark_dirty(parent);
+            }
+        }
+
+        self.segments[self.paths[0].offset].poly.c
+    }
+
+    /// Changes the color of `leaf`; the update is deferred to the next query
+    fn recolor(&mut self, leaf: usize, color: u8) {
+        self.color[leaf] = color;
+        self.mark_dirty(self.path_of[leaf]);
+    }
+
+    fn mark_dirty(&mut self, (id, pos): (usize, usize)) {
+        if self.dirty[id].is_empty() {
+            self.pending.push(id);
+        }
+        self.dirty[id].push(pos);
+    }
+
+    /// Returns the segment consisting of the node at `pos` of path `id` and the subtree
+    /// below its light child
+    fn element(&self, id: usize, pos: usize) -> Segment {
+        let u = self.paths[id].nodes[pos];
+        if self.tree.nodes[u].children.is_none() {
+            let mut counts = Counts::default();
+            counts[self.color[u] as usize] = 1;
+            return Segment {
+                poly: Poly::default(),
+                counts,
+            };
+        }
+
+        let light = self.path_of[self.light_child[u]].0;
+        let below = &self.segments[self.paths[light].offset];
+        let mut poly = Poly::claimed_by_node(&below.counts);
+        poly.add(&below.poly.hanging());
+        Segment {
+            poly,
+            counts: below.counts,
+        }
+    }
+
+    fn build(&mut self, id: usize, idx: usize, lo: usize, hi: usize) {
+        let offset = self.paths[id].offset;
+        self.segments[offset + idx] = if lo == hi {
+            self.element(id, lo)
+        } else {
+            let mid = (lo + hi) / 2;
+            let (left, right) = (idx + 1, idx + 2 * (mid - lo + 1));
+            self.build(id, left, lo, mid);
+            self.build(id, right, mid + 1, hi);
+            Segment::merge(
+                &self.segments[offset + left],
+                &self.segments[offset + right],
+            )
+        };
+    }
+
+    fn update(&mut self, id: usize, pos: usize) {
+        let offset = self.paths[id].offset;
+        let mut trail = [(0, 0); usize::BITS as usize];
+        let mut depth = 0;
+        let (mut idx, mut lo, mut hi) = (0, 0, self.paths[id].nodes.len() - 1);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            let right = idx + 2 * (mid - lo + 1);
+            trail[depth] = (idx, right);
+            depth += 1;
+            if pos <= mid {
+                idx += 1;
+                hi = mid;
+            } else {
+                idx = right;
+                lo = mid + 1;
+            }
+        }
+
+        self.segments[offset + idx] = self.element(id, pos);
+        for &(idx, right) in trail[..depth].iter().rev() {
+            self.segments[offset + idx] = Segment::merge(
+                &self.segments[offset + idx + 1],
+                &self.segments[offset + right],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::BinaryTreeParser;
+    use rand::{Rng, SeedableRng, seq::SliceRandom};
+    use rand_pcg::Pcg64Mcg;
+
+    fn random_tree(rng: &mut impl Rng, n: LabelValue) -> BinTree {
+        fn build(rng: &mut impl Rng, labels: &[LabelValue]) -> BinTree {
+            if labels.len() == 1 {
+                return BinTree::Leaf(Label(labels[0]));
+            }
+            let split = rng.random_range(1..labels.len());
+            BinTree::Node(Box::new((
+                build(rng, &labels[..split]),
+                build(rng, &labels[split..]),
+            )))
+        }
+
+        let mut labels: Vec<LabelValue> = (1..=n).collect();
+        labels.shuffle(rng);
+        build(rng, &labels)
+    }
+
+    /// Computes the pairwise distances between all leaves, indexed by their labels
+    fn leaf_distances(tree: &BinTree, n: usize) -> Vec<Vec<u32>> {
+        fn collect(tree: &BinTree, path: &mut Vec<u8>, out: &mut Vec<(LabelValue, Vec<u8>)>) {
+            match tree.top_down().visit() {
+                NodeType::Leaf(Label(l)) => out.push((l, path.clone())),
+                NodeType::Inner(l, r) => {
+                    path.push(0);
+                    collect(l, path, out);
+                    path.pop();
+                    path.push(1);
+                    collect(r, path, out);
+                    path.pop();
+                }
+            }
+        }
+
+        let mut paths = Vec::new();
+        collect(tree, &mut Vec::new(), &mut paths);
+
+        let mut dist = vec![vec![0; n + 1]; n + 1];
+        for (a, pa) in &paths {
+            for (b, pb) in &paths {
+                let common = pa.iter().zip(pb).take_while(|(x, y)| x == y).count();
+                dist[*a as usize][*b as usize] = (pa.len() + pb.len() - 2 * common) as u32;
+            }
+        }
+        dist
+    }
+
+    /// Returns 0, 1, or 2 for the topologies ab|cd, ac|bd, ad|bc using the four point condition
+    fn quartet_topology(d: &[Vec<u32>], q: [usize; 4]) -> usize {
+        let [a, b, c, e] = q;
+        let sums = [d[a][b] + d[c][e], d[a][c] + d[b][e], d[a][e] + d[b][c]];
+        (0..3).min_by_key(|&i| sums[i]).unwrap()
+    }
+
+    fn naive_quartet_distance(a: &BinTree, b: &BinTree, n: usize) -> u128 {
+        let da = leaf_distances(a, n);
+        let db = leaf_distances(b, n);
+
+        let mut dist = 0;
+        for x0 in 1..=n {
+            for x1 in x0 + 1..=n {
+                for x2 in x1 + 1..=n {
+                    for x3 in x2 + 1..=n {
+                        let q = [x0, x1, x2, x3];
+                        dist += (quartet_topology(&da, q) != quartet_topology(&db, q)) as u128;
+                    }
+                }
+            }
+        }
+        dist
+    }
+
+    #[test]
+    fn rooting_is_ignored() {
+        let mut builder = BinTreeBuilder::default();
+        let a = builder
+            .parse_newick_from_str("((1,2),((3,4),5));", NodeIdx::new(0))
+            .unwrap();
+        let b = builder
+            .parse_newick_from_str("(1,(2,((3,4),5)));", NodeIdx::new(0))
+            .unwrap();
+        assert_eq!(quartet_distance(a.top_down(), b.top_down()), Ok(0));
+    }
+
+    #[test]
+    fn errors() {
+        let mut builder = BinTreeBuilder::default();
+        let a = builder
+            .parse_newick_from_str("((1,2),(3,4));", NodeIdx::new(0))
+            .unwrap();
+        let b = builder
+            .parse_newick_from_str("((1,2),(3,5));", NodeIdx::new(0))
+            .unwrap();
+        let c = builder
+            .parse_newick_from_str("((1,2),(3,3));", NodeIdx::new(0))
+            .unwrap();
+        assert_eq!(
+            quartet_distance(a.top_down(), b.top_down()),
+            Err(MetricsError::DifferentLeafSets)
+        );
+        assert_eq!(
+            quartet_distance(c.top_down(), a.top_down()),
+            Err(MetricsError::DuplicateLabel(Label(3)))
+        );
+    }
+
+    #[test]
+    fn random_against_naive() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0x5eed);
+        for _ in 0..200 {
+            let n = rng.random_range(1..12);
+            let a = random_tree(&mut rng, n);
+            let b = random_tree(&mut rng, n);
+
+            assert_eq!(
+                quartet_distance(a.top_down(), b.top_down()),
+                Ok(naive_quartet_distance(&a, &b, n as usize)),
+                "{a:?} {b:?}"
+            );
+        }
+
+        for _ in 0..5 {
+            let n = rng.random_range(20..40);
+            let a = random_tree(&mut rng, n);
+            let b = random_tree(&mut rng, n);
+            assert_eq!(
+                quartet_distance(a.top_down(), b.top_down()),
+                Ok(naive_quartet_distance(&a, &b, n as usize))
+            );
+        }
+    }
+
+    #[test]
+    fn deep_caterpillars() {
+        // node u < N - 1 is inner with children (N + u, u + 1), where the last one is replaced
+        // by the leaf N + u + 1; node N + i is the leaf at position i
+        const N: usize = 5_000;
+        let children_of = |&u: &usize| match u {
+            _ if u + 2 < N => Some((N + u, u + 1)),
+            _ if u + 2 == N => Some((N + u, N + u + 1)),
+            _ => None,
+        };
+        let distance = |order_a: &[LabelValue], order_b: &[LabelValue]| {
+            let label_a = |&u: &usize| (u >= N).then(|| Label(order_a[u - N]));
+            let label_b = |&u: &usize| (u >= N).then(|| Label(order_b[u - N]));
+            quartet_distance(
+                FnCursor::new(0, &children_of, &label_a),
+                FnCursor::new(0, &children_of, &label_b),
+            )
+        };
+
+        // reversing the order of a caterpillar yields the same unrooted tree
+        let order: Vec<LabelValue> = (1..=N as LabelValue).collect();
+        let reversed: Vec<LabelValue> = order.iter().rev().copied().collect();
+        assert_eq!(distance(&order, &reversed), Ok(0));
+
+        // swapping both ends changes all quartets containing at least one of them
+        let mut swapped = order.clone();
+        swapped.swap(0, N - 1);
+        let inner = N as u128 - 2;
+        let choose = |k: u128| (0..k).fold(1, |acc, i| acc * (inner - i) / (i + 1));
+        assert_eq!(distance(&order, &swapped), Ok(2 * choose(3) + choose(2)));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{
+    binary_tree::{Label, NodeIdx, TreeBuilder},
+    newick::{NewickStyle, NewickWriter},
+};
+use std::io::Write;
+
+/// Generic interface to build trees in which inner nodes may have an arbitrary number of children.
+/// It is the counterpart of [`crate::binary_tree::TreeBuilder`] for algorithms whose output
+/// is not necessarily binary (e.g., consensus trees).
+pub trait MultifurcatingTreeBuilder {
+    type Node;
+
+    /// Creates a new inner node with the children provided.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::Label, multi_tree::*};
+    ///
+    /// let mut builder = MultiTreeBuilder::default();
+    /// let children = (1..=3).map(|l| builder.new_leaf(Label(l))).collect();
+    /// let root = builder.new_inner(children);
+    ///
+    /// assert_eq!(root.children().len(), 3);
+    /// ```
+    fn new_inner(&mut self, children: Vec<Self::Node>) -> Self::Node;
+
+    /// Creates a new leaf node with the label provided.
+    fn new_leaf(&mut self, label: Label) -> Self::Node;
+
+    /// Declares a node a root; see [`crate::binary_tree::TreeBuilder::make_root`].
+    fn make_root(&mut self, root: Self::Node) -> Self::Node {
+        root
+    }
+}
+
+/// Minimalistic implementation of a rooted tree with inner nodes of arbitrary degree
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MultiTree {
+    Node(Vec<MultiTree>),
+    Leaf(Label),
+}
+
+impl MultiTree {
+    /// Returns the children of an inner node, or an empty slice for a leaf
+    pub fn children(&self) -> &[MultiTree] {
+        match self {
+            MultiTree::Node(children) => children,
+            MultiTree::Leaf(_) => &[],
+        }
+    }
+
+    /// Returns the label iff self is a leaf node and `None` otherwise.
+    pub fn leaf_label(&self) -> Option<Label> {
+        match self {
+            MultiTree::Leaf(l) => Some(*l),
+            MultiTree::Node(_) => None,
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, MultiTree::Leaf(_))
+    }
+}
+
+impl NewickWriter for &MultiTree {
+    fn write_newick_inner_styled(
+        &self,
+        style: &NewickStyle,
+        writer: &mut impl Write,
+    ) -> std::io::Result<()> {
+        match self {
+            MultiTree::Node(children) => {
+                style.write_open(writer)?;
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        style.write_comma(writer)?;
+                    }
+                    child.write_newick_inner_styled(style, writer)?;
+                }
+                style.write_close(writer)
+            }
+            MultiTree::Leaf(Label(label)) => write!(writer, "{label}"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MultiTreeBuilder();
+
+impl MultifurcatingTreeBuilder for MultiTreeBuilder {
+    type Node = MultiTree;
+
+    fn new_inner(&mut self, children: Vec<Self::Node>) -> Self::Node {
+        MultiTree::Node(children)
+    }
+
+    fn new_leaf(&mut self, label: Label) -> Self::Node {
+        MultiTree::Leaf(label)
+    }
+}
+
+/// Policy of [`ResolvingTreeBuilder`] to resolve an inner node with more than two children
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// `(a,b,c,d)` becomes `(((a,b),c),d)`
+    LeftComb,
+
+    /// `(a,b,c,d)` becomes `((a,b),(c,d))`; for an odd number of children, the right
+    /// subtree receives the additional child
+    Balanced,
+
+    /// Repeatedly joins two children chosen uniformly at random; the choices are determined
+    /// by the seed (independently of the platform)
+    Random(u64),
+}
+
+/// Adapter to build binary trees with a [`TreeBuilder`] from the output of algorithms
+/// producing multifurcating trees (e.g., [`crate::consensus`]). Inner nodes with more than two
+/// children are resolved according to a [`Resolution`]; inner nodes with a single child are
+/// contracted. Inner nodes receive consecutive ids in the order of their creation (i.e.
+/// bottom-up) starting with the id passed to [`ResolvingTreeBuilder::new`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, multi_tree::*, newick::NewickWriter};
+///
+/// let mut builder = ResolvingTreeBuilder::new(BinTreeBuilder::default(), Resolution::Balanced, NodeIdx::new(6));
+/// let children = (1..=4).map(|l| builder.new_leaf(Label(l))).collect();
+/// let tree = builder.new_inner(children);
+/// assert_eq!(tree.top_down().to_newick_string(), "((1,2),(3,4));");
+///
+/// let mut builder = ResolvingTreeBuilder::new(BinTreeBuilder::default(), Resolution::LeftComb, NodeIdx::new(6));
+/// let children = (1..=4).map(|l| builder.new_leaf(Label(l))).collect();
+/// let tree = builder.new_inner(children);
+/// assert_eq!(tree.top_down().to_newick_string(), "(((1,2),3),4);");
+/// ```
+pub struct ResolvingTreeBuilder<B> {
+    builder: B,
+    resolution: Resolution,
+    next_id: NodeIdx,
+
+    /// State of the SplitMix64 generator used by [`Resolution::Random`]
+    rng_state: u64,
+}
+
+impl<B: TreeBuilder> ResolvingTreeBuilder<B> {
+    pub fn new(builder: B, resolution: Resolution, first_id: NodeIdx) -> Self {
+        let rng_state = match resolution {
+            Resolution::Random(seed) => seed,
+            _ => 0,
+        };
+        Self {
+            builder,
+            resolution,
+            next_id: first_id,
+            rng_state,
+        }
+    }
+
+    /// Returns the id the next inner node will receive
+    pub fn next_id(&self) -> NodeIdx {
+        self.next_id
+    }
+
+    pub fn into_inner(self) -> B {
+        self.builder
+    }
+
+    fn join(&mut self, left: B::Node, right: B::Node) -> B::Node {
+        let id = self.next_id;
+        self.next_id = id.incremented();
+        self.builder.new_inner(id, left, right)
+    }
+
+    fn join_balanced(&mut self, mut nodes: Vec<B::Node>) -> B::Node {
+        if nodes.len() == 1 {
+            return nodes.pop().unwrap();
+        }
+        let right = nodes.split_off(nodes.len() / 2);
+        let left = self.join_balanced(nodes);
+        let right = self.join_balanced(right);
+        self.join(left, right)
+    }
+
+    /// Returns a uniform random number in `0..n` (with negligible bias)
+    fn random_below(&mut self, n: usize) -> usize {
+        self.rng_state = self.rng_state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let x = crate::seed::mix(self.rng_state);
+        ((x as u128 * n as u128) >> 64) as usize
+    }
+}
+
+impl<B: TreeBuilder> MultifurcatingTreeBuilder for ResolvingTreeBuilder<B> {
+    type Node = B::Node;
+
+    /// # Panics
+    /// Panics if `children` is empty
+    fn new_inner(&mut self, mut children: Vec<Self::Node>) -> Self::Node {
+        assert!(!children.is_empty(), "inner node without children");
+
+        match self.resolution {
+            Resolution::LeftComb => {
+                let mut children = children.into_iter();
+                let first = children.next().unwrap();
+                children.fold(first, |left, right| self.join(left, right))
+            }
+            Resolution::Balanced => self.join_balanced(children),
+            Resolution::Random(_) => {
+                while children.len() > 1 {
+                    let left = children.swap_remove(self.random_below(children.len()));
+                    let right = children.swap_remove(self.random_below(children.len()));
+                    let node = self.join(left, right);
+                    children.push(node);
+                }
+                children.pop().unwrap()
+            }
+        }
+    }
+
+    fn new_leaf(&mut self, label: Label) -> Self::Node {
+        self.builder.new_leaf(label)
+    }
+
+    fn make_root(&mut self, root: Self::Node) -> Self::Node {
+        self.builder.make_root(root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn write_newick() {
+        let mut builder = MultiTreeBuilder::default();
+        let inner = vec![builder.new_leaf(Label(2)), builder.new_leaf(Label(3))];
+        let children = vec![
+            builder.new_leaf(Label(1)),
+            builder.new_inner(inner),
+            builder.new_leaf(Label(4)),
+        ];
+        let tree = builder.new_inner(children);
+
+        assert_eq!((&tree).to_newick_string(), "(1,(2,3),4);");
+    }
+
+    #[test]
+    fn resolve() {
+        use crate::binary_tree::*;
+
+        let resolve = |resolution, text: &[&[u32]]| {
+            let mut builder = ResolvingTreeBuilder::new(
+                IndexedBinTreeBuilder::default(),
+                resolution,
+                NodeIdx::new(10),
+            );
+            let groups = text
+                .iter()
+                .map(|group| {
+                    let leaves = group
+                        .iter()
+                        .map(|&l| builder.new_leaf(Label(l as LabelValue)))
+                        .collect();
+                    builder.new_inner(leaves)
+                })
+                .collect();
+            let root = builder.new_inner(groups);
+            (builder.make_root(root), builder.next_id())
+        };
+
+        // single children are contracted
+        let (tree, next_id) = resolve(Resolution::LeftComb, &[&[1], &[2, 3, 4]]);
+        assert_eq!(tree.top_down().to_newick_string(), "(1,((2,3),4));");
+        assert_eq!(tree.node_idx(), NodeIdx::new(12));
+        assert_eq!(next_id, NodeIdx::new(13));
+
+        let (tree, _) = resolve(Resolution::Balanced, &[&[1, 2, 3], &[4, 5]]);
+        assert_eq!(tree.top_down().to_newick_string(), "((1,(2,3)),(4,5));");
+
+        for seed in 0..20 {
+            let (tree, next_id) = resolve(Resolution::Random(seed), &[&[1, 2, 3, 4, 5, 6]]);
+            assert_eq!(next_id, NodeIdx::new(15));
+            let mut leaves: Vec<_> = leaves_in_order(tree.top_down()).collect();
+            leaves.sort();
+            assert_eq!(leaves, (1..=6).map(Label).collect::<Vec<_>>());
+            assert_eq!(
+                resolve(Resolution::Random(seed), &[&[1, 2, 3, 4, 5, 6]]).0,
+                tree
+            );
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! Rooted phylogenetic networks, i.e. directed acyclic graphs with a single root in which
+//! nodes with multiple parents (reticulations) model hybridization events. Networks are
+//! written in extended Newick format with [`crate::newick::write_enewick`].
+use crate::binary_tree::{Label, NodeType, TopDownCursor};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkError {
+    #[error("network has no nodes")]
+    Empty,
+
+    #[error("nodes {0} and {1} both have no parent")]
+    MultipleRoots(usize, usize),
+
+    #[error("network contains a cycle")]
+    Cycle,
+
+    #[error("leaf {0} has children")]
+    LeafWithChildren(usize),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct NetworkNode {
+    children: Vec<usize>,
+    num_parents: usize,
+    label: Option<Label>,
+}
+
+/// Minimal representation of a rooted network as adjacency lists. Nodes are addressed by the
+/// index returned when adding them; the order of children is the order in which edges were
+/// added and determines the output of [`crate::newick::write_enewick`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::Label, network::Network};
+///
+/// // ((1,(2)#H1),(#H1,3)) where leaf 2 has two parents
+/// let mut network = Network::default();
+/// let [root, a, b, h] = [(); 4].map(|_| network.add_node());
+/// let [l1, l2, l3] = [1, 2, 3].map(|l| network.add_leaf(Label(l)));
+/// for (u, v) in [(root, a), (root, b), (a, l1), (a, h), (b, h), (b, l3), (h, l2)] {
+///     network.add_edge(u, v);
+/// }
+///
+/// assert_eq!(network.root(), Ok(root));
+/// assert!(network.is_reticulation(h));
+/// assert_eq!(network.num_reticulations(), 1);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Network {
+    nodes: Vec<NetworkNode>,
+}
+
+impl Network {
+    /// Copies the tree below `root`, where children keep their order
+    pub fn from_tree<C: TopDownCursor>(root: C) -> Self {
+        let mut network = Self::default();
+        let mut stack = vec![(root, None)];
+        while let Some((cursor, parent)) = stack.pop() {
+            let u = match cursor.visit() {
+                NodeType::Inner(left, right) => {
+                    let u = network.add_node();
+                    stack.push((right, Some(u)));
+                    stack.push((left, Some(u)));
+                    u
+                }
+                NodeType::Leaf(label) => network.add_leaf(label),
+            };
+            if let Some(p) = parent {
+                network.add_edge(p, u);
+            }
+        }
+        network
+    }
+
+    /// Adds an unlabelled node and returns its index
+    pub fn add_node(&mut self) -> usize {
+        self.nodes.push(NetworkNode::default());
+        self.nodes.len() - 1
+    }
+
+    /// Adds a leaf with `label` and returns its index
+    pub fn add_leaf(&mut self, label: Label) -> usize {
+        let u = self.add_node();
+        self.nodes[u].label = Some(label);
+        u
+    }
+
+    /// Adds an edge from `parent` to `child`; `child` becomes the last child of `parent`
+    pub fn add_edge(&mut self, parent: usize, child: usize) {
+        self.nodes[parent].children.push(child);
+        self.nodes[child].num_parents += 1;
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn children(&self, u: usize) -> &[usize] {
+        &self.nodes[u].children
+    }
+
+    /// Returns the label iff `u` was added with [`Network::add_leaf`]
+    pub fn label(&self, u: usize) -> Option<Label> {
+        self.nodes[u].label
+    }
+
+    /// Returns `true` iff `u` has at least two parents
+    pub fn is_reticulation(&self, u: usize) -> bool {
+        self.nodes[u].num_parents >= 2
+    }
+
+    pub fn num_reticulations(&self) -> usize {
+        (0..self.nodes.len())
+            .filter(|&u| self.is_reticulation(u))
+            .count()
+    }
+
+    /// Returns the unique node without parents, and fails if the network is empty, has
+    /// multiple such nodes, contains a cycle, or a leaf with children. Takes linear time.
+    pub fn root(&self) -> Result<usize, NetworkError> {
+        let mut roots = (0..self.nodes.len()).filter(|&u| self.nodes[u].num_parents == 0);
+        let root = roots.next().ok_or(if self.nodes.is_empty() {
+            NetworkError::Empty
+        } else {
+            NetworkError::Cycle
+        })?;
+        if let Some(other) = roots.next() {
+            return Err(NetworkError::MultipleRoots(root, other));
+        }
+
+        if let Some(u) = (0..self.nodes.len())
+            .find(|&u| self.nodes[u].label.is_some() && !self.nodes[u].children.is_empty())
+        {
+            return Err(NetworkError::LeafWithChildren(u));
+        }
+
+        // Kahn's algorithm: with a single source, all nodes are reached iff there is no cycle
+        let mut remaining: Vec<usize> = self.nodes.iter().map(|n| n.num_parents).collect();
+        let mut stack = vec![root];
+        let mut visited = 0;
+        while let Some(u) = stack.pop() {
+            visited += 1;
+            for &v in &self.nodes[u].children {
+                remaining[v] -= 1;
+                if remaining[v] == 0 {
+                    stack.push(v);
+                }
+            }
+        }
+
+        if visited < self.nodes.len() {
+            return Err(NetworkError::Cycle);
+        }
+
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binary_tree::BinTree;
+
+    #[test]
+    fn invalid_networks() {
+        let mut network = Network::default();
+        assert_eq!(network.root(), Err(NetworkError::Empty));
+
+        let [a, b, c] = [(); 3].map(|_| network.add_node());
+        network.add_edge(a, b);
+        assert_eq!(network.root(), Err(NetworkError::MultipleRoots(a, c)));
+
+        network.add_edge(b, c);
+        network.add_edge(c, b);
+        assert_eq!(network.root(), Err(NetworkError::Cycle));
+
+        let mut network = Network::default();
+        let leaf = network.add_leaf(Label(1));
+        let child = network.add_leaf(Label(2));
+        network.add_edge(leaf, child);
+        assert_eq!(network.root(), Err(NetworkError::LeafWithChildren(leaf)));
+    }
+
+    #[test]
+    fn from_tree() {
+        let tree: BinTree = "((1,2),3);".parse().unwrap();
+        let network = Network::from_tree(tree.top_down());
+        assert_eq!(network.num_nodes(), 5);
+        assert_eq!(network.root(), Ok(0));
+        assert_eq!(network.num_reticulations(), 0);
+        assert_eq!(network.children(0), &[1, 4]);
+        assert_eq!(network.label(2), Some(Label(1)));
+    }
+}
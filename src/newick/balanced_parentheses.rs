@@ -0,0 +1,142 @@
+use super::{
+    binary_tree_parser::{ParserError, next_token},
+    lexer::*,
+};
+use crate::binary_tree::{BalancedParentheses, Label};
+
+/// Converts a Newick string directly into [`BalancedParentheses`] while validating it with the
+/// same grammar as [`crate::newick::BinaryTreeParser`]. In contrast to building a pointer-based
+/// tree first, the peak memory is proportional to the output (plus a stack of the tree's depth).
+/// Branch lengths are accepted and dropped.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let bp = parse_newick_to_bp("(1,(2,3));").unwrap();
+/// let parens: String = (0..bp.len()).map(|i| if bp.is_open(i) { '(' } else { ')' }).collect();
+/// assert_eq!(parens, "(()(()()))");
+/// assert_eq!(bp.leaf_labels(), &[Label(1), Label(2), Label(3)]);
+/// ```
+pub fn parse_newick_to_bp(text: &str) -> Result<BalancedParentheses, ParserError> {
+    let mut lexer = Lexer::new(text);
+    parse_bp_from_lexer(&mut lexer)
+}
+
+fn parse_bp_from_lexer(lexer: &mut Lexer) -> Result<BalancedParentheses, ParserError> {
+    let mut bp = BalancedParentheses::default();
+
+    // number of completed children of each open inner node
+    let mut open_nodes: Vec<u8> = Vec::new();
+    loop {
+        // begin of a node
+        let token = next_token(lexer)?;
+        match token.token_type {
+            TokenType::ParOpen => {
+                bp.push(true);
+                open_nodes.push(0);
+                continue;
+            }
+            TokenType::Number(x) | TokenType::Name(x) => {
+                bp.push_leaf(Label(x));
+                lexer.try_branch_length().transpose()?;
+            }
+            _ => {
+                return Err(ParserError::ExpectedNodeBegin {
+                    span: lexer.token_span(&token),
+                    token,
+                });
+            }
+        }
+
+        // a node was completed; close all inner nodes whose second child it is
+        loop {
+            let Some(num_children) = open_nodes.last_mut() else {
+                let token = next_token(lexer)?;
+                if token.token_type != TokenType::Semicolon {
+                    return Err(ParserError::ExpectedEnd {
+                        span: lexer.token_span(&token),
+                        token,
+                    });
+                }
+                return Ok(bp);
+            };
+
+            *num_children += 1;
+            let token = next_token(lexer)?;
+            if *num_children == 1 {
+                if token.token_type != TokenType::Comma {
+                    return Err(ParserError::ExpectedComma {
+                        span: lexer.token_span(&token),
+                        token,
+                    });
+                }
+                break;
+            }
+
+            if token.token_type != TokenType::ParClose {
+                return Err(ParserError::ExpectedClosing {
+                    span: lexer.token_span(&token),
+                    token,
+                });
+            }
+            bp.push(false);
+            open_nodes.pop();
+            lexer.try_branch_length().transpose()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        binary_tree::{BinTreeBuilder, LabelValue, NodeIdx, TopDownCursor},
+        newick::*,
+    };
+
+    #[test]
+    fn roundtrip() {
+        for text in ["1;", "(1,2);", "(1,(5,91234));", "(((4,2),(7,1)),8);"] {
+            let bp = parse_newick_to_bp(text).unwrap();
+            assert_eq!(bp.root().to_newick_string(), text);
+        }
+    }
+
+    #[test]
+    fn deep_caterpillar() {
+        let n = 100_000;
+        let mut text = "(".repeat(n - 1);
+        text.push('1');
+        for i in 2..=n {
+            text.push_str(&format!(",{i})"));
+        }
+        text.push(';');
+
+        let bp = parse_newick_to_bp(&text).unwrap();
+        assert_eq!(bp.len(), 2 * (2 * n - 1));
+        assert_eq!(bp.leaf_labels().len(), n);
+        assert_eq!(
+            bp.root().right_child().unwrap().leaf_label(),
+            Some(Label(n as LabelValue))
+        );
+    }
+
+    #[test]
+    fn same_errors_as_parser() {
+        for text in [
+            "123",
+            "123,",
+            "(123)",
+            "(123,)",
+            "(123,123,23)",
+            "(1,2));",
+            ")",
+        ] {
+            let expected = BinTreeBuilder::default()
+                .parse_newick_from_str(text, NodeIdx::new(0))
+                .unwrap_err();
+            assert_eq!(parse_newick_to_bp(text).unwrap_err(), expected, "{text}");
+        }
+    }
+}
@@ -1,28 +1,278 @@
 use thiserror::Error;
 
-use super::{super::binary_tree::*, lexer::*};
+use super::{super::binary_tree::*, label_interner::LabelInterner, lexer::*};
+use crate::compat::*;
+use std::{collections::HashSet, io::BufRead};
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum ParserError {
-    #[error("unexpected end of token stream")]
-    UnexpectedEnd,
+    #[error("unexpected end of token stream at {span}")]
+    UnexpectedEnd { span: Span },
 
-    #[error("Expected begin of node definition, i.e. label or opening parenthesis. Got: {token:?}")]
-    ExpectedNodeBegin { token: Token },
+    #[error(
+        "Expected begin of node definition, i.e. label or opening parenthesis, at {span}. Got: {token:?}"
+    )]
+    ExpectedNodeBegin { token: Token, span: Span },
 
-    #[error("Expected comma. Got: {token:?}")]
-    ExpectedComma { token: Token },
+    #[error("Expected comma at {span}. Got: {token:?}")]
+    ExpectedComma { token: Token, span: Span },
 
-    #[error("Expected closing parenthesis. Got {token:?}")]
-    ExpectedClosing { token: Token },
+    #[error("Expected closing parenthesis at {span}. Got {token:?}")]
+    ExpectedClosing { token: Token, span: Span },
 
-    #[error("Expected end of expression, i.e. ';'. Got: {token:?}")]
-    ExpectedEnd { token: Token },
+    #[error("Expected end of expression, i.e. ';', at {span}. Got: {token:?}")]
+    ExpectedEnd { token: Token, span: Span },
+
+    #[error("leaf label {} appears multiple times; again at {span}", label.0)]
+    DuplicateLabel { label: Label, span: Span },
+
+    #[error("failed to read input at {span}: {message}")]
+    Read { message: String, span: Span },
+
+    #[error("unexpected whitespace after ';' at {span}")]
+    TrailingWhitespace { span: Span },
+
+    #[error("{limit:?} limit of {max} exceeded at {span}")]
+    LimitExceeded {
+        limit: Limit,
+        max: usize,
+        offset: usize,
+        span: Span,
+    },
 
     #[error(transparent)]
     Lexer(#[from] LexerError),
 }
 
+impl ParserError {
+    /// Returns the location of the error within the input
+    pub fn span(&self) -> &Span {
+        match self {
+            ParserError::UnexpectedEnd { span }
+            | ParserError::ExpectedNodeBegin { span, .. }
+            | ParserError::ExpectedComma { span, .. }
+            | ParserError::ExpectedClosing { span, .. }
+            | ParserError::ExpectedEnd { span, .. }
+            | ParserError::DuplicateLabel { span, .. }
+            | ParserError::Read { span, .. }
+            | ParserError::TrailingWhitespace { span }
+            | ParserError::LimitExceeded { span, .. } => span,
+            ParserError::Lexer(err) => err.span(),
+        }
+    }
+}
+
+/// Resource limited by [`ParserLimits`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Limit {
+    Depth,
+    Nodes,
+    InputBytes,
+}
+
+/// Upper bounds on the resources a single tree may consume; exceeding any of them
+/// yields [`ParserError::LimitExceeded`]. All limits are unbounded by default.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let limits = ParserLimits { max_depth: 1, ..Default::default() };
+/// let mut options = NewickParserOptions { limits, ..Default::default() };
+/// let mut builder = BinTreeBuilder::default();
+/// assert!(builder.parse_newick_from_str_with("(1,2);", NodeIdx::new(0), &mut options).is_ok());
+/// assert!(matches!(
+///     builder.parse_newick_from_str_with("((1,2),3);", NodeIdx::new(0), &mut options),
+///     Err(ParserError::LimitExceeded { limit: Limit::Depth, max: 1, offset: 1, .. })
+/// ));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ParserLimits {
+    /// Maximum number of nested inner nodes, i.e. a leaf has depth 0 and a cherry depth 1
+    pub max_depth: usize,
+
+    /// Maximum number of nodes (inner nodes and leaves)
+    pub max_nodes: usize,
+
+    /// Maximum length of the input text in bytes
+    pub max_input_bytes: usize,
+}
+
+impl Default for ParserLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_nodes: usize::MAX,
+            max_input_bytes: usize::MAX,
+        }
+    }
+}
+
+/// Deviations from the Newick syntax tolerated by [`NewickParserOptions::leniency`]. Each
+/// tolerated deviation is reported as [`ParserWarning`]; by default, none are tolerated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Leniency {
+    /// Accept a tree that ends without `;` instead of failing with [`ParserError::UnexpectedEnd`]
+    pub missing_semicolon: bool,
+
+    /// Accept whitespace at the end of the input instead of failing with
+    /// [`ParserError::TrailingWhitespace`] or [`LexerError::UnexpectedChar`]
+    pub trailing_whitespace: bool,
+
+    /// Accept a root with more than two children, as common for unrooted trees, instead of
+    /// failing with [`ParserError::ExpectedClosing`]. The root `(a,b,c)` is binarized into
+    /// `((a,b),c)`, where the artificial inner node receives the next unused id, i.e. the ids
+    /// are no longer in preorder.
+    pub multifurcating_root: bool,
+}
+
+impl Leniency {
+    /// Tolerates all deviations
+    pub fn all() -> Self {
+        Self {
+            missing_semicolon: true,
+            trailing_whitespace: true,
+            multifurcating_root: true,
+        }
+    }
+}
+
+/// Interpretation of leaf labels; see [`NewickParserOptions::labels`]
+#[derive(Default)]
+pub enum LabelKind<'a> {
+    /// Decimal numbers, which are the labels themselves
+    #[default]
+    Numbers,
+
+    /// Taxon names (e.g. `Homo_sapiens`), which are mapped to labels by the interner.
+    /// Numeric names are treated as names as well, i.e. `"7"` is not necessarily mapped to
+    /// `Label(7)`. See [`LabelInterner`] for an example.
+    Names(&'a mut LabelInterner),
+
+    /// Decimal numbers passed through the mapper, e.g. to renumber sparse labels on the fly.
+    /// All further processing (including the builder) only sees the mapped labels.
+    Mapped(&'a mut dyn LabelMapper),
+}
+
+/// Callback of [`NewickParserOptions::on_comment`]
+pub type CommentCallback<'a> = &'a mut dyn FnMut(usize, &str);
+
+/// Callback of [`NewickParserOptions::on_leaf`]
+pub type LeafCallback<'a> = &'a mut dyn FnMut(Label, &[NodeIdx]);
+
+/// Settings of [`BinaryTreeParser::parse_newick_from_str_with`]; the defaults correspond
+/// to the strict PACE format.
+#[derive(Default)]
+pub struct NewickParserOptions<'a> {
+    /// Whitespace between tokens
+    pub whitespace: bool,
+
+    /// Bracket comments `[...]` between tokens; they are skipped silently
+    pub comments: bool,
+
+    /// Labels of inner nodes, which are passed to [`TreeBuilder::new_inner_labeled`]
+    pub inner_labels: bool,
+
+    /// Branch lengths `:<float>` following a node, which are passed to
+    /// [`TreeBuilder::new_leaf_with_length`] and [`TreeBuilder::new_inner_with_length`];
+    /// otherwise, a colon is an unexpected character
+    pub branch_lengths: bool,
+
+    /// NHX annotations `[&&NHX:key=value:...]` following a node, which are passed to
+    /// [`TreeBuilder::annotate_nhx`]; other bracket comments are skipped
+    pub nhx: bool,
+
+    /// A byte order mark at the beginning and a line terminator `\n` or `\r\n` at the
+    /// end of the text; see [`Lexer::tolerate_bom_and_crlf`]
+    pub bom_and_crlf: bool,
+
+    /// Fail with [`ParserError::DuplicateLabel`] if a leaf label appears multiple times
+    /// within the tree
+    pub reject_duplicate_labels: bool,
+
+    /// Pass the source span of each node to [`TreeBuilder::annotate_span`]
+    pub spans: bool,
+
+    pub limits: ParserLimits,
+    pub leniency: Leniency,
+
+    /// Interpretation of leaf labels; numbers by default
+    pub labels: LabelKind<'a>,
+
+    /// Receives the offset of the opening bracket and the text of each bracket comment
+    /// between tokens; implies [`NewickParserOptions::comments`]
+    pub on_comment: Option<CommentCallback<'a>>,
+
+    /// Receives each leaf label in the order of appearance together with the ids of all
+    /// ancestors of the leaf starting with the root. Since inner nodes are created bottom-up,
+    /// these nodes do not exist yet when the callback is invoked.
+    pub on_leaf: Option<LeafCallback<'a>>,
+
+    /// Receives each deviation tolerated due to [`NewickParserOptions::leniency`]
+    pub on_warning: Option<&'a mut dyn FnMut(ParserWarning)>,
+}
+
+impl NewickParserOptions<'_> {
+    /// Applies the whitespace, comment, label, and limit settings to `lexer`
+    pub(crate) fn configure_lexer(&self, lexer: &mut Lexer) {
+        if self.whitespace {
+            lexer.allow_whitespaces();
+        }
+        if self.comments {
+            lexer.skip_comments();
+        }
+        if self.inner_labels {
+            lexer.allow_inner_labels();
+        }
+        if self.branch_lengths {
+            lexer.allow_branch_lengths();
+        }
+        if self.nhx {
+            lexer.parse_nhx();
+        }
+        if self.bom_and_crlf {
+            lexer.tolerate_bom_and_crlf();
+        }
+        lexer.set_limits(self.limits);
+    }
+}
+
+/// Takes over the lexer-level flags of a [`CompatFlags`] preset; all other settings keep
+/// their defaults
+impl From<CompatFlags> for NewickParserOptions<'_> {
+    fn from(flags: CompatFlags) -> Self {
+        Self {
+            whitespace: flags.whitespace,
+            comments: flags.comments,
+            inner_labels: flags.inner_labels,
+            bom_and_crlf: flags.bom_and_crlf,
+            branch_lengths: flags.branch_lengths,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<CompatProfile> for NewickParserOptions<'_> {
+    fn from(profile: CompatProfile) -> Self {
+        profile.flags().into()
+    }
+}
+
+/// Deviations tolerated due to [`Leniency`]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParserWarning {
+    #[error("missing ';' at {span}")]
+    MissingSemicolon { span: Span },
+
+    #[error("trailing whitespace at {span}")]
+    TrailingWhitespace { span: Span },
+
+    /// An artificial inner node was inserted below the root for the child following the
+    /// comma at `span`
+    #[error("binarized root with more than two children at {span}")]
+    BinarizedRoot { span: Span },
+}
+
 pub trait BinaryTreeParser: TreeBuilder + Sized {
     fn parse_newick_from_lexer(
         &mut self,
@@ -30,6 +280,9 @@ pub trait BinaryTreeParser: TreeBuilder + Sized {
         root_id: NodeIdx,
     ) -> Result<Self::Node, ParserError>;
 
+    /// Parses `text` in the strict PACE format; this is equivalent to
+    /// [`BinaryTreeParser::parse_newick_from_str_with`] with the default
+    /// [`NewickParserOptions`]
     fn parse_newick_from_str(
         &mut self,
         text: &str,
@@ -38,48 +291,634 @@ pub trait BinaryTreeParser: TreeBuilder + Sized {
         let mut lexer = Lexer::new(text);
         self.parse_newick_from_lexer(&mut lexer, root_id)
     }
+
+    /// Same as [`BinaryTreeParser::parse_newick_from_str`], but operates directly on bytes,
+    /// e.g., of a memory-mapped file, without UTF-8 validation. Error offsets are byte offsets.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let tree = BinTreeBuilder::default()
+    ///     .parse_newick_from_bytes(b"((1,2),3);", NodeIdx::new(4))
+    ///     .unwrap();
+    /// assert_eq!(tree.top_down().to_newick_string(), "((1,2),3);");
+    /// ```
+    fn parse_newick_from_bytes(
+        &mut self,
+        bytes: &[u8],
+        root_id: NodeIdx,
+    ) -> Result<Self::Node, ParserError> {
+        let mut lexer = Lexer::from_bytes(bytes);
+        self.parse_newick_from_lexer(&mut lexer, root_id)
+    }
+
+    /// Same as [`BinaryTreeParser::parse_newick_from_bytes`], but reads the tree incrementally
+    /// from `reader`, so at most the buffer of the reader is held in memory; use
+    /// [`std::io::BufReader::with_capacity`] to choose the window size. This allows to parse
+    /// tree lines exceeding the available memory. The reader is left directly after the `;`,
+    /// so subsequent lines can be read from it. Spans assume the tree is on a single line
+    /// and [`ParserLimits::max_input_bytes`] is not enforced. Errors of the reader are
+    /// reported as [`ParserError::Read`].
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    /// use std::io::{BufRead, BufReader};
+    ///
+    /// let mut reader = BufReader::with_capacity(4, "((1,2),(3,4));\n#s next line\n".as_bytes());
+    /// let tree = BinTreeBuilder::default()
+    ///     .parse_newick_from_reader(&mut reader, NodeIdx::new(5))
+    ///     .unwrap();
+    /// assert_eq!(tree.top_down().to_newick_string(), "((1,2),(3,4));");
+    ///
+    /// let rest: Vec<_> = reader.lines().collect::<Result<_, _>>().unwrap();
+    /// assert_eq!(rest, vec!["", "#s next line"]);
+    /// ```
+    fn parse_newick_from_reader(
+        &mut self,
+        reader: &mut impl BufRead,
+        root_id: NodeIdx,
+    ) -> Result<Self::Node, ParserError> {
+        let mut lexer = Lexer::from_reader(reader);
+        let result = self.parse_newick_from_lexer(&mut lexer, root_id);
+        lexer.release_reader();
+        match lexer.take_read_error() {
+            Some(err) => Err(ParserError::Read {
+                message: err.to_string(),
+                span: lexer.end_span(),
+            }),
+            None => result,
+        }
+    }
+
+    /// Same as [`BinaryTreeParser::parse_newick_from_str`], but configured by `options`;
+    /// e.g., `(1, 2);` can be parsed by only enabling [`NewickParserOptions::whitespace`].
+    /// The callbacks of `options` are invoked while parsing.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, compat::*, newick::*};
+    ///
+    /// let mut whitespace = NewickParserOptions { whitespace: true, ..Default::default() };
+    /// let mut builder = BinTreeBuilder::default();
+    /// assert!(builder.parse_newick_from_str("(1, 2);", NodeIdx::new(3)).is_err());
+    /// assert!(builder.parse_newick_from_str_with("(1, 2);", NodeIdx::new(3), &mut whitespace).is_ok());
+    ///
+    /// // options can also be derived from a compatibility profile
+    /// let mut lenient = NewickParserOptions::from(CompatProfile::Lenient);
+    /// assert!(builder.parse_newick_from_str_with("(1, [comment] 2);\r\n", NodeIdx::new(3), &mut lenient).is_ok());
+    ///
+    /// let mut comments = Vec::new();
+    /// let mut leaves = Vec::new();
+    /// let mut warnings = Vec::new();
+    /// let mut options = NewickParserOptions {
+    ///     inner_labels: true,
+    ///     leniency: Leniency { missing_semicolon: true, ..Default::default() },
+    ///     on_comment: Some(&mut |offset, text: &str| comments.push((offset, text.to_owned()))),
+    ///     on_leaf: Some(&mut |label, path: &[NodeIdx]| leaves.push((label.0, path.len()))),
+    ///     on_warning: Some(&mut |warning| warnings.push(warning)),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// let tree = BinTreeBuilder::default()
+    ///     .parse_newick_from_str_with("((1,2)95,[x]3)", NodeIdx::new(4), &mut options)
+    ///     .unwrap();
+    /// drop(options);
+    ///
+    /// assert_eq!(tree.top_down().to_newick_string(), "((1,2),3);");
+    /// assert_eq!(comments, vec![(9, "x".to_owned())]);
+    /// assert_eq!(leaves, vec![(1, 2), (2, 2), (3, 1)]);
+    /// assert!(matches!(warnings[..], [ParserWarning::MissingSemicolon { .. }]));
+    /// ```
+    fn parse_newick_from_str_with(
+        &mut self,
+        text: &str,
+        root_id: NodeIdx,
+        options: &mut NewickParserOptions,
+    ) -> Result<Self::Node, ParserError> {
+        let mut lexer = Lexer::new(text);
+        parse_with_options(self, &mut lexer, root_id, options)
+    }
+
+    /// Parses a sequence of trees that are concatenated without separators, e.g. `(1,2);(3,4);`.
+    /// The `i`-th tree (0-based) receives the root id `root_id(i)`. The iterator stops after
+    /// the end of the input or the first error.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let mut builder = BinTreeBuilder::default();
+    /// let trees: Vec<_> = builder
+    ///     .parse_many_from_str("((1,2),3);(1,(2,3));", |i| NodeIdx::new(4 + 2 * i as u32))
+    ///     .collect::<Result<_, _>>()
+    ///     .unwrap();
+    ///
+    /// assert_eq!(trees.len(), 2);
+    /// assert_eq!(trees[1].top_down().to_newick_string(), "(1,(2,3));");
+    /// ```
+    fn parse_many_from_str<'a, F: FnMut(usize) -> NodeIdx>(
+        &'a mut self,
+        text: &'a str,
+        root_id: F,
+    ) -> ManyTrees<'a, Self, F> {
+        ManyTrees {
+            builder: self,
+            lexer: Lexer::new(text),
+            root_id,
+            num_trees: 0,
+            failed: false,
+        }
+    }
+}
+
+/// Iterator over concatenated trees; see [`BinaryTreeParser::parse_many_from_str`]
+pub struct ManyTrees<'a, B, F> {
+    builder: &'a mut B,
+    lexer: Lexer<'a>,
+    root_id: F,
+    num_trees: usize,
+    failed: bool,
+}
+
+impl<B: TreeBuilder, F: FnMut(usize) -> NodeIdx> Iterator for ManyTrees<'_, B, F> {
+    type Item = Result<B::Node, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.lexer.is_exhausted() {
+            return None;
+        }
+
+        let root_id = (self.root_id)(self.num_trees);
+        self.num_trees += 1;
+
+        let result = self
+            .builder
+            .parse_newick_from_lexer(&mut self.lexer, root_id);
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Maps labels as read from the input to the labels of the tree; see [`LabelKind::Mapped`].
+/// Implemented for closures.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+/// use std::collections::HashMap;
+///
+/// // assign dense labels in order of first appearance
+/// let mut dense = HashMap::new();
+/// let mut mapper = |raw: LabelValue| {
+///     let next = dense.len() as LabelValue + 1;
+///     Label(*dense.entry(raw).or_insert(next))
+/// };
+///
+/// let mut options = NewickParserOptions {
+///     labels: LabelKind::Mapped(&mut mapper),
+///     ..Default::default()
+/// };
+/// let tree = BinTreeBuilder::default()
+///     .parse_newick_from_str_with("((1000,20),7);", NodeIdx::new(4), &mut options)
+///     .unwrap();
+/// assert_eq!(tree.top_down().to_newick_string(), "((1,2),3);");
+/// ```
+pub trait LabelMapper {
+    fn map(&mut self, raw: LabelValue) -> Label;
+}
+
+impl<F: FnMut(LabelValue) -> Label> LabelMapper for F {
+    fn map(&mut self, raw: LabelValue) -> Label {
+        self(raw)
+    }
+}
+
+/// Hooks invoked by the parser while descending into the tree
+pub(crate) trait ParserHook {
+    fn enter_inner(&mut self, _id: NodeIdx) {}
+    fn leave_inner(&mut self) {}
+    fn visit_leaf(&mut self, _label: Label) {}
+
+    /// Invoked for each leaf before any other hook to obtain its label
+    fn map_label(&mut self, raw: LabelValue) -> Label {
+        Label(raw)
+    }
+
+    /// Invoked if the root is followed by a further child at the comma at `span`; the
+    /// root is binarized if `true` is returned
+    fn binarize_root(&mut self, _span: &Span) -> bool {
+        false
+    }
+
+    /// Invoked for each leaf; the parser fails with [`ParserError::DuplicateLabel`] if
+    /// `true` is returned
+    fn is_duplicate(&mut self, _label: Label) -> bool {
+        false
+    }
+}
+
+impl ParserHook for () {}
+
+/// Receives the span of each node created by [`parse_node`]; the span is only computed on
+/// demand, so the no-op implementation of `()` does not incur any costs
+trait SpanSink<B: TreeBuilder> {
+    fn annotate(
+        &mut self,
+        _builder: &mut B,
+        node: B::Node,
+        _span: impl FnOnce() -> Span,
+    ) -> B::Node {
+        node
+    }
+}
+
+impl<B: TreeBuilder> SpanSink<B> for () {}
+
+/// Passes the spans to [`TreeBuilder::annotate_span`]
+struct ReportSpans;
+
+impl<B: TreeBuilder> SpanSink<B> for ReportSpans {
+    fn annotate(&mut self, builder: &mut B, node: B::Node, span: impl FnOnce() -> Span) -> B::Node {
+        builder.annotate_span(node, span())
+    }
+}
+
+/// Implements the checks, tolerances, and callbacks of [`NewickParserOptions`] that are
+/// not handled by the lexer
+struct OptionsHook<'a> {
+    leniency: Leniency,
+    on_warning: Option<&'a mut dyn FnMut(ParserWarning)>,
+
+    /// Leaf labels seen so far if duplicates are rejected
+    labels: Option<HashSet<Label>>,
+
+    mapper: Option<&'a mut dyn LabelMapper>,
+    leaf_paths: Option<LeafPathHook<LeafCallback<'a>>>,
+}
+
+impl OptionsHook<'_> {
+    fn warn(&mut self, warning: ParserWarning) {
+        if let Some(on_warning) = self.on_warning.as_mut() {
+            on_warning(warning);
+        }
+    }
 }
 
-fn assert_next_token_else(
+impl ParserHook for OptionsHook<'_> {
+    fn enter_inner(&mut self, id: NodeIdx) {
+        if let Some(leaf_paths) = self.leaf_paths.as_mut() {
+            leaf_paths.enter_inner(id);
+        }
+    }
+
+    fn leave_inner(&mut self) {
+        if let Some(leaf_paths) = self.leaf_paths.as_mut() {
+            leaf_paths.leave_inner();
+        }
+    }
+
+    fn visit_leaf(&mut self, label: Label) {
+        if let Some(leaf_paths) = self.leaf_paths.as_mut() {
+            leaf_paths.visit_leaf(label);
+        }
+    }
+
+    fn map_label(&mut self, raw: LabelValue) -> Label {
+        match self.mapper.as_mut() {
+            Some(mapper) => mapper.map(raw),
+            None => Label(raw),
+        }
+    }
+
+    fn is_duplicate(&mut self, label: Label) -> bool {
+        self.labels
+            .as_mut()
+            .is_some_and(|labels| !labels.insert(label))
+    }
+
+    fn binarize_root(&mut self, span: &Span) -> bool {
+        if self.leniency.multifurcating_root {
+            self.warn(ParserWarning::BinarizedRoot { span: span.clone() });
+        }
+        self.leniency.multifurcating_root
+    }
+}
+
+/// Keeps track of the ancestors of the current node and reports them for each leaf
+pub(crate) struct LeafPathHook<F> {
+    pub(crate) path: Vec<NodeIdx>,
+    pub(crate) on_leaf: F,
+}
+
+impl<F: FnMut(Label, &[NodeIdx])> ParserHook for LeafPathHook<F> {
+    fn enter_inner(&mut self, id: NodeIdx) {
+        self.path.push(id);
+    }
+
+    fn leave_inner(&mut self) {
+        self.path.pop();
+    }
+
+    fn visit_leaf(&mut self, label: Label) {
+        (self.on_leaf)(label, &self.path);
+    }
+}
+
+pub(crate) fn next_token(lexer: &mut Lexer) -> Result<Token, ParserError> {
+    match lexer.next() {
+        Some(token) => Ok(token?),
+        None => Err(ParserError::UnexpectedEnd {
+            span: lexer.end_span(),
+        }),
+    }
+}
+
+pub(crate) fn assert_next_token_else(
     lexer: &mut Lexer,
     expected: TokenType,
-    error: impl FnOnce(Token) -> ParserError,
+    error: impl FnOnce(Token, Span) -> ParserError,
 ) -> Result<(), ParserError> {
-    let token = lexer.next().ok_or(ParserError::UnexpectedEnd)??;
+    let token = next_token(lexer)?;
     if token.token_type == expected {
         Ok(())
     } else {
-        Err(error(token))
+        Err(error(token, lexer.token_span(&token)))
     }
 }
 
-fn parse_inner<B: TreeBuilder>(
+/// Parses the node starting at the next token. To support arbitrarily deep trees, the
+/// recursion is replaced by an explicit stack of open inner nodes, each storing its id
+/// and, once parsed, its left child.
+fn parse_node<B: TreeBuilder>(
     builder: &mut B,
     lexer: &mut Lexer,
-    own_id: NodeIdx,
-) -> Result<(B::Node, NodeIdx), ParserError> {
-    let token = lexer.next().ok_or(ParserError::UnexpectedEnd)??;
+    root_id: NodeIdx,
+    hook: &mut impl ParserHook,
+    spans: &mut impl SpanSink<B>,
+) -> Result<B::Node, ParserError> {
+    let limits = *lexer.limits();
+    if lexer.input_bytes() > limits.max_input_bytes {
+        return Err(ParserError::LimitExceeded {
+            limit: Limit::InputBytes,
+            max: limits.max_input_bytes,
+            offset: 0,
+            span: lexer.input_span(),
+        });
+    }
+
+    // id, left child (once parsed), and opening parenthesis of each open node
+    let mut open_nodes: Vec<(NodeIdx, Option<B::Node>, Token)> = Vec::new();
+    let mut next_id = root_id;
+    let mut num_nodes = 0usize;
 
-    match token.token_type {
-        TokenType::ParOpen => {
-            let (left_child, next_id) = parse_inner(builder, lexer, own_id.incremented())?;
+    loop {
+        let token = next_token(lexer)?;
+        lexer.discard_nhx();
 
-            assert_next_token_else(lexer, TokenType::Comma, |token| {
-                ParserError::ExpectedComma { token }
-            })?;
+        num_nodes += 1;
+        if num_nodes > limits.max_nodes {
+            return Err(ParserError::LimitExceeded {
+                limit: Limit::Nodes,
+                max: limits.max_nodes,
+                offset: token.offset,
+                span: lexer.token_span(&token),
+            });
+        }
 
-            let (right_child, next_id) = parse_inner(builder, lexer, next_id)?;
+        let mut node = match token.token_type {
+            TokenType::ParOpen => {
+                if open_nodes.len() >= limits.max_depth {
+                    return Err(ParserError::LimitExceeded {
+                        limit: Limit::Depth,
+                        max: limits.max_depth,
+                        offset: token.offset,
+                        span: lexer.token_span(&token),
+                    });
+                }
 
-            assert_next_token_else(lexer, TokenType::ParClose, |token| {
-                ParserError::ExpectedClosing { token }
-            })?;
+                hook.enter_inner(next_id);
+                open_nodes.push((next_id, None, token));
+                next_id = next_id.incremented();
+                continue;
+            }
 
-            Ok((builder.new_inner(own_id, left_child, right_child), next_id))
+            TokenType::Number(x) | TokenType::Name(x) => {
+                let label = hook.map_label(x);
+                if hook.is_duplicate(label) {
+                    return Err(ParserError::DuplicateLabel {
+                        label,
+                        span: lexer.token_span(&token),
+                    });
+                }
+                hook.visit_leaf(label);
+                let leaf = match lexer.try_branch_length().transpose()? {
+                    Some(length) => builder.new_leaf_with_length(label, length),
+                    None => builder.new_leaf(label),
+                };
+                let leaf = annotate_nhx(builder, lexer, leaf)?;
+                spans.annotate(builder, leaf, || lexer.token_span(&token))
+            }
+
+            _ => {
+                return Err(ParserError::ExpectedNodeBegin {
+                    span: lexer.token_span(&token),
+                    token,
+                });
+            }
+        };
+
+        // `node` is complete; attach it to its parent and close all inner nodes
+        // whose right child has been parsed
+        loop {
+            let Some((_, left_child, _)) = open_nodes.last_mut() else {
+                return Ok(node);
+            };
+
+            if left_child.is_none() {
+                *left_child = Some(node);
+                assert_next_token_else(lexer, TokenType::Comma, |token, span| {
+                    ParserError::ExpectedComma { token, span }
+                })?;
+                break;
+            }
+
+            let (own_id, left_child, begin) = open_nodes.pop().unwrap();
+            let token = next_token(lexer)?;
+            match token.token_type {
+                TokenType::ParClose => {}
+                TokenType::Comma
+                    if open_nodes.is_empty() && hook.binarize_root(&lexer.token_span(&token)) =>
+                {
+                    // combine the children parsed so far and continue with the next child
+                    let inner = builder.new_inner(next_id, left_child.unwrap(), node);
+                    next_id = next_id.incremented();
+                    open_nodes.push((own_id, Some(inner), begin));
+                    break;
+                }
+                _ => {
+                    return Err(ParserError::ExpectedClosing {
+                        span: lexer.token_span(&token),
+                        token,
+                    });
+                }
+            }
+            hook.leave_inner();
+
+            node = finish_inner(builder, lexer, own_id, left_child.unwrap(), node)?;
+            node = spans.annotate(builder, node, || lexer.span_between(&begin, &token));
+        }
+    }
+}
+
+/// Creates an inner node after its closing parenthesis, consuming an optional label and
+/// branch length
+fn finish_inner<B: TreeBuilder>(
+    builder: &mut B,
+    lexer: &mut Lexer,
+    own_id: NodeIdx,
+    left_child: B::Node,
+    right_child: B::Node,
+) -> Result<B::Node, ParserError> {
+    let label = lexer.try_inner_label().transpose()?;
+    let length = lexer.try_branch_length().transpose()?;
+    let node = match (label, length) {
+        (Some(label), length) => {
+            builder.new_inner_labeled(own_id, left_child, right_child, &label, length)
+        }
+        (None, Some(length)) => {
+            builder.new_inner_with_length(own_id, left_child, right_child, length)
+        }
+        (None, None) => builder.new_inner(own_id, left_child, right_child),
+    };
+    annotate_nhx(builder, lexer, node)
+}
+
+/// Passes the NHX annotation following `node` (if any) to the builder
+fn annotate_nhx<B: TreeBuilder>(
+    builder: &mut B,
+    lexer: &mut Lexer,
+    node: B::Node,
+) -> Result<B::Node, ParserError> {
+    Ok(match lexer.try_nhx().transpose()? {
+        Some(tags) => builder.annotate_nhx(node, &tags),
+        None => node,
+    })
+}
+
+/// Configures `lexer` by `options` and parses a tree, applying the checks, tolerances, and
+/// callbacks enabled in `options`
+fn parse_with_options<'l, B: TreeBuilder>(
+    builder: &mut B,
+    lexer: &mut Lexer<'l>,
+    root_id: NodeIdx,
+    options: &'l mut NewickParserOptions,
+) -> Result<B::Node, ParserError> {
+    options.configure_lexer(lexer);
+    if let Some(on_comment) = options.on_comment.as_deref_mut() {
+        lexer.report_comments(on_comment);
+    }
+
+    let mut mapper = None;
+    match &mut options.labels {
+        LabelKind::Numbers => {}
+        LabelKind::Names(interner) => lexer.intern_names(interner),
+        LabelKind::Mapped(label_mapper) => {
+            mapper = Some(&mut **label_mapper as &mut dyn LabelMapper)
         }
+    }
+
+    let mut hook = OptionsHook {
+        leniency: options.leniency,
+        on_warning: options
+            .on_warning
+            .as_deref_mut()
+            .map(|on_warning| on_warning as &mut dyn FnMut(ParserWarning)),
+        labels: options.reject_duplicate_labels.then(HashSet::new),
+        mapper,
+        leaf_paths: options.on_leaf.as_deref_mut().map(|on_leaf| LeafPathHook {
+            path: Vec::new(),
+            on_leaf: on_leaf as LeafCallback,
+        }),
+    };
+
+    if options.spans {
+        parse_lenient(builder, lexer, root_id, &mut hook, &mut ReportSpans)
+    } else {
+        parse_lenient(builder, lexer, root_id, &mut hook, &mut ())
+    }
+}
 
-        TokenType::Number(x) => Ok((builder.new_leaf(Label(x)), own_id)),
-        _ => Err(ParserError::ExpectedNodeBegin { token }),
+/// Parses a tree, tolerating the deviations enabled in the leniency of `hook`
+fn parse_lenient<B: TreeBuilder>(
+    builder: &mut B,
+    lexer: &mut Lexer,
+    root_id: NodeIdx,
+    hook: &mut OptionsHook,
+    spans: &mut impl SpanSink<B>,
+) -> Result<B::Node, ParserError> {
+    let leniency = hook.leniency;
+    let tree = parse_node(builder, lexer, root_id, hook, spans)?;
+
+    if leniency.trailing_whitespace
+        && let Some(span) = lexer.skip_trailing_whitespace()?
+    {
+        hook.warn(ParserWarning::TrailingWhitespace { span });
     }
+
+    match lexer.next() {
+        None if leniency.missing_semicolon => hook.warn(ParserWarning::MissingSemicolon {
+            span: lexer.end_span(),
+        }),
+        None => {
+            return Err(ParserError::UnexpectedEnd {
+                span: lexer.end_span(),
+            });
+        }
+        Some(token) => {
+            let token = token?;
+            if token.token_type != TokenType::Semicolon {
+                return Err(ParserError::ExpectedEnd {
+                    span: lexer.token_span(&token),
+                    token,
+                });
+            }
+            if let Some(span) = lexer.skip_trailing_whitespace()? {
+                if !leniency.trailing_whitespace {
+                    return Err(ParserError::TrailingWhitespace { span });
+                }
+                hook.warn(ParserWarning::TrailingWhitespace { span });
+            }
+        }
+    }
+
+    Ok(builder.make_root(tree))
+}
+
+pub(crate) fn parse_tree<B: TreeBuilder>(
+    builder: &mut B,
+    lexer: &mut Lexer,
+    root_id: NodeIdx,
+    hook: &mut impl ParserHook,
+) -> Result<B::Node, ParserError> {
+    parse_tree_with_spans(builder, lexer, root_id, hook, &mut ())
+}
+
+fn parse_tree_with_spans<B: TreeBuilder>(
+    builder: &mut B,
+    lexer: &mut Lexer,
+    root_id: NodeIdx,
+    hook: &mut impl ParserHook,
+    spans: &mut impl SpanSink<B>,
+) -> Result<B::Node, ParserError> {
+    let tree = parse_node(builder, lexer, root_id, hook, spans)?;
+
+    assert_next_token_else(lexer, TokenType::Semicolon, |token, span| {
+        ParserError::ExpectedEnd { token, span }
+    })?;
+
+    Ok(builder.make_root(tree))
 }
 
 impl<B: TreeBuilder> BinaryTreeParser for B {
@@ -88,13 +927,7 @@ impl<B: TreeBuilder> BinaryTreeParser for B {
         lexer: &mut Lexer,
         root_id: NodeIdx,
     ) -> Result<Self::Node, ParserError> {
-        let (tree, _) = parse_inner(self, lexer, root_id)?;
-
-        assert_next_token_else(lexer, TokenType::Semicolon, |token| {
-            ParserError::ExpectedEnd { token }
-        })?;
-
-        Ok(self.make_root(tree))
+        parse_tree(self, lexer, root_id, &mut ())
     }
 }
 
@@ -187,6 +1020,736 @@ mod test {
         test_string("(((4,2),(7,1)),8);");
     }
 
+    #[test]
+    fn leaf_paths_match_tree() {
+        let mut paths = Vec::new();
+        let mut on_leaf = |l, p: &[NodeIdx]| paths.push((l, p.to_vec()));
+        let tree = IndexedBinTreeBuilder::default()
+            .parse_newick_from_str_with(
+                "((1,2),(3,(5,4)));",
+                NodeIdx::new(6),
+                &mut NewickParserOptions {
+                    on_leaf: Some(&mut on_leaf),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(paths.len(), 5);
+        for (label, path) in paths {
+            // follow the reported ancestors from the root down to the leaf
+            let mut cursor = tree.top_down();
+            assert_eq!(cursor.node_idx(), path[0]);
+            for id in &path[1..] {
+                let (l, r) = cursor.children().unwrap();
+                cursor = if l.node_idx() == *id { l } else { r };
+                assert_eq!(cursor.node_idx(), *id);
+            }
+
+            let (l, r) = cursor.children().unwrap();
+            assert!(l.leaf_label() == Some(label) || r.leaf_label() == Some(label));
+        }
+
+        let mut count = 0;
+        let mut on_leaf = |l, p: &[NodeIdx]| {
+            assert_eq!(l, Label(7));
+            assert!(p.is_empty());
+            count += 1;
+        };
+        BinTreeBuilder::default()
+            .parse_newick_from_str_with(
+                "7;",
+                NodeIdx::new(0),
+                &mut NewickParserOptions {
+                    on_leaf: Some(&mut on_leaf),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn branch_lengths() {
+        #[derive(Default)]
+        struct LengthBuilder(Vec<(Option<u32>, f64)>);
+
+        impl TreeBuilder for LengthBuilder {
+            type Node = ();
+
+            fn new_inner(&mut self, _id: NodeIdx, _left: (), _right: ()) {}
+            fn new_leaf(&mut self, _label: Label) {}
+
+            fn new_inner_with_length(&mut self, id: NodeIdx, _l: (), _r: (), length: f64) {
+                self.0.push((Some(id.0), length));
+            }
+
+            fn new_leaf_with_length(&mut self, label: Label, length: f64) {
+                self.0.push((None, label.0 as f64 + length));
+            }
+        }
+
+        let mut options = NewickParserOptions {
+            branch_lengths: true,
+            ..Default::default()
+        };
+        let mut builder = LengthBuilder::default();
+        builder
+            .parse_newick_from_str_with("((1:0.5,2):1.25,3:2e1):0;", NodeIdx::new(4), &mut options)
+            .unwrap();
+        assert_eq!(
+            builder.0,
+            vec![(None, 1.5), (Some(5), 1.25), (None, 23.0), (Some(4), 0.0)]
+        );
+
+        // builders without support ignore lengths
+        let tree = BinTreeBuilder::default()
+            .parse_newick_from_str_with("((1:0.5,2):1.25,3:2e1);", NodeIdx::new(0), &mut options)
+            .unwrap();
+        assert_eq!(tree.top_down().to_newick_string(), "((1,2),3);");
+
+        // branch lengths are rejected by default
+        for text in ["((1:0.5,2):1,3);", "((1,2):1,3);"] {
+            assert!(matches!(
+                BinTreeBuilder::default().parse_newick_from_str(text, NodeIdx::new(0)),
+                Err(ParserError::Lexer(LexerError::UnexpectedChar {
+                    character: ':',
+                    ..
+                }))
+            ));
+        }
+
+        assert!(matches!(
+            BinTreeBuilder::default().parse_newick_from_str_with(
+                "(1:,2);",
+                NodeIdx::new(0),
+                &mut options
+            ),
+            Err(ParserError::Lexer(LexerError::InvalidBranchLength {
+                offset: 2,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn inner_labels() {
+        #[derive(Default)]
+        struct LabelBuilder(Vec<(u32, String, Option<f64>)>);
+
+        impl TreeBuilder for LabelBuilder {
+            type Node = ();
+
+            fn new_inner(&mut self, id: NodeIdx, _left: (), _right: ()) {
+                self.0.push((id.0, String::new(), None));
+            }
+            fn new_leaf(&mut self, _label: Label) {}
+
+            fn new_inner_with_length(&mut self, id: NodeIdx, _l: (), _r: (), length: f64) {
+                self.0.push((id.0, String::new(), Some(length)));
+            }
+
+            fn new_inner_labeled(
+                &mut self,
+                id: NodeIdx,
+                _l: (),
+                _r: (),
+                label: &str,
+                length: Option<f64>,
+            ) {
+                self.0.push((id.0, label.to_owned(), length));
+            }
+        }
+
+        let mut options = NewickParserOptions {
+            inner_labels: true,
+            branch_lengths: true,
+            ..Default::default()
+        };
+        let mut builder = LabelBuilder::default();
+        builder
+            .parse_newick_from_str_with(
+                "(((1,2)a:0.5,3):2,(4,5)0.95)root;",
+                NodeIdx::new(6),
+                &mut options,
+            )
+            .unwrap();
+        assert_eq!(
+            builder.0,
+            vec![
+                (8, "a".into(), Some(0.5)),
+                (7, "".into(), Some(2.0)),
+                (9, "0.95".into(), None),
+                (6, "root".into(), None)
+            ]
+        );
+
+        assert!(matches!(
+            builder.parse_newick_from_str_with("((1,2)'open,3);", NodeIdx::new(4), &mut options),
+            Err(ParserError::Lexer(LexerError::UnterminatedQuote {
+                offset: 6,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn comments() {
+        let mut comments = Vec::new();
+        let mut on_comment = |offset, text: &str| comments.push((offset, text.to_owned()));
+        let mut lexer = Lexer::new("[&R]((1[a],2):[b]1.5,3)[c];");
+        lexer.report_comments(&mut on_comment);
+        lexer.allow_branch_lengths();
+        let tree = BinTreeBuilder::default()
+            .parse_newick_from_lexer(&mut lexer, NodeIdx::new(4))
+            .unwrap();
+        drop(lexer);
+        assert_eq!(tree.top_down().to_newick_string(), "((1,2),3);");
+        assert_eq!(
+            comments,
+            vec![
+                (0, "&R".to_owned()),
+                (7, "a".to_owned()),
+                (14, "b".to_owned()),
+                (23, "c".to_owned())
+            ]
+        );
+
+        let result = BinTreeBuilder::default().parse_newick_from_str_with(
+            "(1,2)[open;",
+            NodeIdx::new(0),
+            &mut NewickParserOptions {
+                on_comment: Some(&mut |_, _| {}),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(
+            result,
+            Err(ParserError::Lexer(LexerError::UnterminatedComment {
+                offset: 5,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn deep_caterpillar() {
+        /// Computes the depth bottom-up, so no deep recursive structure needs to be dropped
+        struct DepthBuilder;
+
+        impl TreeBuilder for DepthBuilder {
+            type Node = usize;
+
+            fn new_inner(&mut self, _id: NodeIdx, left: usize, right: usize) -> usize {
+                left.max(right) + 1
+            }
+            fn new_leaf(&mut self, _label: Label) -> usize {
+                0
+            }
+        }
+
+        const N: usize = 200_000;
+        let mut text = "(".repeat(N - 1);
+        text.push('1');
+        for i in 2..=N {
+            text.push_str(&format!(",{i})"));
+        }
+        text.push(';');
+
+        let depth = DepthBuilder
+            .parse_newick_from_str(&text, NodeIdx::new(N as u32 + 1))
+            .unwrap();
+        assert_eq!(depth, N - 1);
+
+        text.pop();
+        assert!(matches!(
+            DepthBuilder.parse_newick_from_str(&text, NodeIdx::new(0)),
+            Err(ParserError::UnexpectedEnd { .. })
+        ));
+    }
+
+    #[test]
+    fn error_spans() {
+        let span = |text| {
+            BinTreeBuilder::default()
+                .parse_newick_from_str(text, NodeIdx::new(0))
+                .unwrap_err()
+                .span()
+                .clone()
+        };
+
+        assert_eq!(span("(1,2)3;").byte_range, 5..6);
+        assert_eq!(span("(1,2").byte_range, 4..4);
+        assert_eq!(span("(1:x,2);").byte_range, 2..3);
+
+        let mut lexer = Lexer::new("((1,\n  2)\n  3);");
+        lexer.allow_whitespaces();
+        let err = BinTreeBuilder::default()
+            .parse_newick_from_lexer(&mut lexer, NodeIdx::new(0))
+            .unwrap_err();
+        assert_eq!((err.span().line, err.span().column), (2, 2));
+        assert!(
+            err.to_string()
+                .starts_with("Expected comma at line 3, column 3.")
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let text = "((1,2),(3,(4,5)));";
+        let parse = |limits| {
+            BinTreeBuilder::default().parse_newick_from_str_with(
+                text,
+                NodeIdx::new(6),
+                &mut NewickParserOptions {
+                    limits,
+                    ..Default::default()
+                },
+            )
+        };
+
+        assert!(parse(ParserLimits::default()).is_ok());
+        assert!(
+            parse(ParserLimits {
+                max_depth: 3,
+                max_nodes: 9,
+                max_input_bytes: text.len()
+            })
+            .is_ok()
+        );
+
+        let exceeded = |limits| match parse(limits) {
+            Err(ParserError::LimitExceeded { limit, offset, .. }) => Some((limit, offset)),
+            _ => None,
+        };
+        assert_eq!(
+            exceeded(ParserLimits {
+                max_depth: 2,
+                ..Default::default()
+            }),
+            Some((Limit::Depth, 10))
+        );
+        assert_eq!(
+            exceeded(ParserLimits {
+                max_nodes: 8,
+                ..Default::default()
+            }),
+            Some((Limit::Nodes, 13))
+        );
+        assert_eq!(
+            exceeded(ParserLimits {
+                max_input_bytes: 10,
+                ..Default::default()
+            }),
+            Some((Limit::InputBytes, 0))
+        );
+    }
+
+    #[test]
+    fn leniency() {
+        let mut builder = BinTreeBuilder::default();
+        let mut parse = |text: &str, leniency: Leniency| {
+            let mut warnings = Vec::new();
+            let mut on_warning = |w| warnings.push(w);
+            builder
+                .parse_newick_from_str_with(
+                    text,
+                    NodeIdx::new(0),
+                    &mut NewickParserOptions {
+                        leniency,
+                        on_warning: Some(&mut on_warning),
+                        ..Default::default()
+                    },
+                )
+                .map(|_| warnings)
+        };
+
+        assert_eq!(parse("(1,2);", Leniency::default()), Ok(vec![]));
+        assert!(matches!(
+            parse("(1,2)", Leniency::default()),
+            Err(ParserError::UnexpectedEnd { .. })
+        ));
+        assert!(matches!(
+            parse("(1,2); ", Leniency::default()),
+            Err(ParserError::TrailingWhitespace { .. })
+        ));
+        assert!(parse("(1,2) ;", Leniency::all()).is_err());
+        assert!(matches!(
+            parse("(1,2)3", Leniency::all()),
+            Err(ParserError::ExpectedEnd { .. })
+        ));
+
+        let missing = Leniency {
+            missing_semicolon: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse("(1,2)", missing),
+            Ok(vec![ParserWarning::MissingSemicolon {
+                span: Span {
+                    line: 0,
+                    column: 5,
+                    byte_range: 5..5
+                }
+            }])
+        );
+
+        let whitespace = Leniency {
+            trailing_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse("(1,2);\r\n", whitespace),
+            Ok(vec![ParserWarning::TrailingWhitespace {
+                span: Span {
+                    line: 0,
+                    column: 6,
+                    byte_range: 6..8
+                }
+            }])
+        );
+    }
+
+    #[test]
+    fn multifurcating_root() {
+        let leniency = Leniency {
+            multifurcating_root: true,
+            ..Default::default()
+        };
+        let mut builder = IndexedBinTreeBuilder::default();
+        let mut warnings = Vec::new();
+        let mut on_warning = |w| warnings.push(w);
+        let tree = builder
+            .parse_newick_from_str_with(
+                "((1,2),3,(4,5),6);",
+                NodeIdx::new(10),
+                &mut NewickParserOptions {
+                    leniency,
+                    on_warning: Some(&mut on_warning),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(tree.top_down().to_newick_string(), "((((1,2),3),(4,5)),6);");
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0],
+            ParserWarning::BinarizedRoot {
+                span: Span {
+                    line: 0,
+                    column: 8,
+                    byte_range: 8..9
+                }
+            }
+        );
+
+        // ids of the original nodes are unchanged, artificial nodes are appended
+        assert_eq!(tree.top_down().node_idx(), NodeIdx::new(10));
+        let ids: Vec<u32> = navigate(tree.top_down(), "l")
+            .into_iter()
+            .chain(navigate(tree.top_down(), "ll"))
+            .chain(navigate(tree.top_down(), "lll"))
+            .chain(navigate(tree.top_down(), "lr"))
+            .map(|node| node.node_idx().0)
+            .collect();
+        assert_eq!(ids, vec![14, 12, 11, 13]);
+
+        // only the root may have more than two children
+        assert!(matches!(
+            builder.parse_newick_from_str_with(
+                "((1,2,3),4);",
+                NodeIdx::new(0),
+                &mut NewickParserOptions {
+                    leniency,
+                    ..Default::default()
+                }
+            ),
+            Err(ParserError::ExpectedClosing { .. })
+        ));
+        assert!(matches!(
+            builder.parse_newick_from_str("(1,2,3);", NodeIdx::new(0)),
+            Err(ParserError::ExpectedClosing { .. })
+        ));
+    }
+
+    #[test]
+    fn options() {
+        let mut builder = BinTreeBuilder::default();
+        let mut parse = |text: &str, mut options: NewickParserOptions| {
+            builder
+                .parse_newick_from_str_with(text, NodeIdx::new(0), &mut options)
+                .map(|tree| tree.top_down().to_newick_string())
+        };
+
+        let strict = NewickParserOptions::default;
+        assert_eq!(parse("((1,2),3);", strict()).as_deref(), Ok("((1,2),3);"));
+        assert!(parse("((1,2), 3);", strict()).is_err());
+        assert!(parse("((1,2)[x],3);", strict()).is_err());
+        assert!(parse("((1,2),3);\n", strict()).is_err());
+
+        let windows = || NewickParserOptions {
+            bom_and_crlf: true,
+            ..strict()
+        };
+        assert_eq!(
+            parse("\u{FEFF}((1,2),3);\r\n", windows()).as_deref(),
+            Ok("((1,2),3);")
+        );
+        assert!(parse("\u{FEFF}((1,2),3);\r\n", strict()).is_err());
+        assert!(parse("((1,2),3);\r\n;", windows()).is_err());
+
+        let relaxed = || NewickParserOptions {
+            whitespace: true,
+            comments: true,
+            ..strict()
+        };
+        assert_eq!(
+            parse(" ( (1,2) [x], 3) ;\n", relaxed()).as_deref(),
+            Ok("((1,2),3);")
+        );
+        assert!(parse("((1,2),3)", relaxed()).is_err());
+        assert_eq!(
+            parse(
+                "((1,2),3) \n",
+                NewickParserOptions {
+                    leniency: Leniency::all(),
+                    ..relaxed()
+                }
+            )
+            .as_deref(),
+            Ok("((1,2),3);")
+        );
+
+        let limited = || NewickParserOptions {
+            limits: ParserLimits {
+                max_nodes: 3,
+                ..Default::default()
+            },
+            ..strict()
+        };
+        assert!(parse("(1,2);", limited()).is_ok());
+        assert!(matches!(
+            parse("((1,2),3);", limited()),
+            Err(ParserError::LimitExceeded {
+                limit: Limit::Nodes,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn duplicate_labels() {
+        let mut options = NewickParserOptions {
+            reject_duplicate_labels: true,
+            ..Default::default()
+        };
+        let mut builder = BinTreeBuilder::default();
+        assert!(
+            builder
+                .parse_newick_from_str("((1,2),1);", NodeIdx::new(0))
+                .is_ok()
+        );
+        assert!(
+            builder
+                .parse_newick_from_str_with("((1,2),3);", NodeIdx::new(0), &mut options)
+                .is_ok()
+        );
+        assert_eq!(
+            builder
+                .parse_newick_from_str_with("((1,2),1);", NodeIdx::new(0), &mut options)
+                .unwrap_err(),
+            ParserError::DuplicateLabel {
+                label: Label(1),
+                span: Span {
+                    line: 0,
+                    column: 7,
+                    byte_range: 7..8
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn nhx() {
+        /// Writes nodes as strings with annotations in braces
+        struct Annotating;
+        impl TreeBuilder for Annotating {
+            type Node = String;
+
+            fn new_inner(&mut self, _id: NodeIdx, left: String, right: String) -> String {
+                format!("({left},{right})")
+            }
+
+            fn new_leaf(&mut self, label: Label) -> String {
+                label.0.to_string()
+            }
+
+            fn annotate_nhx(&mut self, node: String, tags: &[(String, String)]) -> String {
+                let tags: Vec<_> = tags.iter().map(|(k, v)| format!("{k}={v}")).collect();
+                format!("{node}{{{}}}", tags.join(","))
+            }
+        }
+
+        let mut options = NewickParserOptions {
+            nhx: true,
+            branch_lengths: true,
+            ..Default::default()
+        };
+        let parse = |text: &str, options: &mut NewickParserOptions| {
+            Annotating.parse_newick_from_str_with(text, NodeIdx::new(0), options)
+        };
+
+        assert_eq!(
+            parse(
+                "((1:0.5[&&NHX:S=human:E=1.1.1],2[comment])[&&NHX:D=Y],3)[&&NHX:S=root];",
+                &mut options
+            )
+            .unwrap(),
+            "((1{S=human,E=1.1.1},2){D=Y},3){S=root}"
+        );
+
+        // annotations preceding a node are dropped
+        assert_eq!(parse("([&&NHX:S=x]1,2);", &mut options).unwrap(), "(1,2)");
+
+        assert!(matches!(
+            parse("(1[&&NHX:S],2);", &mut options),
+            Err(ParserError::Lexer(LexerError::InvalidNhx { offset: 2, .. }))
+        ));
+        assert!(parse("(1[&&NHX:S=x],2);", &mut NewickParserOptions::default()).is_err());
+    }
+
+    #[test]
+    fn spans() {
+        /// Writes nodes as strings followed by `@line:column:bytes`
+        struct Spanned;
+        impl TreeBuilder for Spanned {
+            type Node = String;
+
+            fn new_inner(&mut self, _id: NodeIdx, left: String, right: String) -> String {
+                format!("({left},{right})")
+            }
+
+            fn new_leaf(&mut self, label: Label) -> String {
+                label.0.to_string()
+            }
+
+            fn annotate_span(&mut self, node: String, span: Span) -> String {
+                let std::ops::Range { start, end } = span.byte_range;
+                format!("{node}@{}:{}:{start}-{end}", span.line, span.column)
+            }
+        }
+
+        let mut options = NewickParserOptions {
+            spans: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Spanned
+                .parse_newick_from_str_with("((1,23),4);", NodeIdx::new(0), &mut options)
+                .unwrap(),
+            "((1@0:2:2-3,23@0:4:4-6)@0:1:1-7,4@0:8:8-9)@0:0:0-10"
+        );
+
+        let mut options = NewickParserOptions {
+            whitespace: true,
+            inner_labels: true,
+            spans: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            Spanned
+                .parse_newick_from_str_with(
+                    "(\n  (1,2)label,\n  3\n);",
+                    NodeIdx::new(0),
+                    &mut options
+                )
+                .unwrap(),
+            "((1@1:3:5-6,2@1:5:7-8)@1:2:4-9,3@2:2:18-19)@0:0:0-21"
+        );
+
+        assert_eq!(
+            Spanned
+                .parse_newick_from_str("((1,23),4);", NodeIdx::new(0))
+                .unwrap(),
+            "((1,23),4)"
+        );
+    }
+
+    #[test]
+    fn from_reader() {
+        use std::io::{BufReader, Read};
+
+        // a caterpillar with small windows
+        let n = 10_000;
+        let mut text = "(".repeat(n - 1) + "1";
+        for i in 2..=n {
+            text += &format!(",{i})");
+        }
+        text += ";(1,2);";
+
+        let mut builder = BinTreeBuilder::default();
+        let mut reader = BufReader::with_capacity(16, text.as_bytes());
+        let tree = builder
+            .parse_newick_from_reader(&mut reader, NodeIdx::new(0))
+            .unwrap();
+        assert_eq!(leaves_in_order(tree.top_down()).count(), n);
+        let second = builder
+            .parse_newick_from_reader(&mut reader, NodeIdx::new(0))
+            .unwrap();
+        assert_eq!(second.top_down().to_newick_string(), "(1,2);");
+
+        // offsets refer to the position within the stream
+        let mut reader = BufReader::with_capacity(2, "((1,2),x);".as_bytes());
+        assert!(matches!(
+            builder.parse_newick_from_reader(&mut reader, NodeIdx::new(0)),
+            Err(ParserError::Lexer(LexerError::UnexpectedChar {
+                offset: 7,
+                ..
+            }))
+        ));
+
+        // errors of the reader are reported
+        let failing = "((1,2),".as_bytes().chain(FailingReader);
+        let err = builder
+            .parse_newick_from_reader(&mut BufReader::new(failing), NodeIdx::new(0))
+            .unwrap_err();
+        assert!(matches!(&err, ParserError::Read { span, .. } if span.byte_range == (7..7)));
+
+        struct FailingReader;
+        impl Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+        }
+    }
+
+    #[test]
+    fn many_trees() {
+        let mut builder = BinTreeBuilder::default();
+        let parse = |builder: &mut BinTreeBuilder, text: &str| {
+            builder
+                .parse_many_from_str(text, |_| NodeIdx::new(0))
+                .map(|r| r.map(|t| t.top_down().to_newick_string()))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(parse(&mut builder, "").is_empty());
+        assert_eq!(
+            parse(&mut builder, "1;(2,3);((4,5),6);"),
+            vec![
+                Ok("1;".to_string()),
+                Ok("(2,3);".to_string()),
+                Ok("((4,5),6);".to_string())
+            ]
+        );
+
+        // the iterator stops after the first error
+        let results = parse(&mut builder, "(1,2);(3,4)(5,6);(7,8);");
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[1], Err(ParserError::ExpectedEnd { .. })));
+    }
+
     #[test]
     fn parser_indexed_bintree() {
         let tree = IndexedBinTreeBuilder::default()
@@ -2,14 +2,18 @@ use super::{super::binary_tree::*, *};
 use std::io::Write;
 
 impl<B: TopDownCursor> NewickWriter for B {
-    fn write_newick_inner(&self, writer: &mut impl Write) -> std::io::Result<()> {
+    fn write_newick_inner_styled(
+        &self,
+        style: &NewickStyle,
+        writer: &mut impl Write,
+    ) -> std::io::Result<()> {
         match self.visit() {
             NodeType::Inner(left, right) => {
-                write!(writer, "(")?;
-                left.write_newick_inner(writer)?;
-                write!(writer, ",")?;
-                right.write_newick_inner(writer)?;
-                write!(writer, ")")
+                style.write_open(writer)?;
+                left.write_newick_inner_styled(style, writer)?;
+                style.write_comma(writer)?;
+                right.write_newick_inner_styled(style, writer)?;
+                style.write_close(writer)
             }
             NodeType::Leaf(Label(label)) => {
                 write!(writer, "{label}")
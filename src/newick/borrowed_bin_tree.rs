@@ -0,0 +1,151 @@
+use super::{
+    binary_tree_parser::{ParserError, assert_next_token_else, next_token},
+    lexer::*,
+};
+use crate::binary_tree::BorrowedBinTree;
+
+/// Parses a single tree whose leaves are arbitrary taxon names (quoted or unquoted) into a
+/// [`BorrowedBinTree`] that borrows each label from `text` instead of copying it. The
+/// grammar is the same as for [`crate::newick::BinaryTreeParser`] with its default options.
+///
+/// # Example
+/// ```
+/// use pace26io::newick::*;
+///
+/// let text = String::from("(('Homo sapiens',Pan),'Gorilla ''gorilla''');");
+/// let tree = parse_newick_borrowed(&text).unwrap();
+///
+/// let leaves: Vec<&str> = tree.leaves().collect();
+/// assert_eq!(leaves, ["'Homo sapiens'", "Pan", "'Gorilla ''gorilla'''"]);
+/// assert_eq!(tree.children().unwrap().1.leaf_name().unwrap(), "Gorilla 'gorilla'");
+/// assert_eq!(tree.to_string(), text);
+/// ```
+pub fn parse_newick_borrowed(text: &str) -> Result<BorrowedBinTree<'_>, ParserError> {
+    let mut lexer = Lexer::new(text);
+    lexer.raw_labels();
+
+    // left child of each open inner node, once parsed
+    let mut open_nodes: Vec<Option<BorrowedBinTree>> = Vec::new();
+    loop {
+        // begin of a node
+        let token = next_token(&mut lexer)?;
+        let mut node = match token.token_type {
+            TokenType::ParOpen => {
+                open_nodes.push(None);
+                continue;
+            }
+            TokenType::RawLabel => {
+                let label = lexer
+                    .label_slice(&token)
+                    .expect("the lexer reads from text and returned a label");
+                BorrowedBinTree::Leaf(label)
+            }
+            _ => {
+                return Err(ParserError::ExpectedNodeBegin {
+                    span: lexer.token_span(&token),
+                    token,
+                });
+            }
+        };
+
+        // a node was completed; close all inner nodes whose second child it is
+        loop {
+            let Some(left_child) = open_nodes.last_mut() else {
+                assert_next_token_else(&mut lexer, TokenType::Semicolon, |token, span| {
+                    ParserError::ExpectedEnd { token, span }
+                })?;
+                return Ok(node);
+            };
+
+            if left_child.is_none() {
+                *left_child = Some(node);
+                assert_next_token_else(&mut lexer, TokenType::Comma, |token, span| {
+                    ParserError::ExpectedComma { token, span }
+                })?;
+                break;
+            }
+
+            let left_child = open_nodes.pop().unwrap().unwrap();
+            assert_next_token_else(&mut lexer, TokenType::ParClose, |token, span| {
+                ParserError::ExpectedClosing { token, span }
+            })?;
+            node = BorrowedBinTree::Node(Box::new((left_child, node)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        binary_tree::{BinTreeBuilder, NodeIdx},
+        newick::BinaryTreeParser,
+    };
+
+    #[test]
+    fn zero_copy() {
+        let text = "((12,'a b'),(x,''''));";
+        let tree = parse_newick_borrowed(text).unwrap();
+        assert_eq!(tree.to_string(), text);
+
+        let range = text.as_bytes().as_ptr_range();
+        for leaf in tree.leaves() {
+            assert!(range.contains(&leaf.as_ptr()));
+        }
+
+        let names: Vec<_> = tree
+            .children()
+            .into_iter()
+            .flat_map(|(l, r)| [l, r])
+            .flat_map(|child| {
+                let (l, r) = child.children().unwrap();
+                [l.leaf_name().unwrap(), r.leaf_name().unwrap()]
+            })
+            .collect();
+        assert_eq!(names, ["12", "a b", "x", "'"]);
+        assert!(!tree.is_leaf());
+        assert_eq!(tree.leaf_name(), None);
+    }
+
+    #[test]
+    fn deep_caterpillar() {
+        let n = 100_000;
+        let mut text = "(".repeat(n - 1);
+        text.push('1');
+        for i in 2..=n {
+            text.push_str(&format!(",t{i})"));
+        }
+        text.push(';');
+
+        let tree = parse_newick_borrowed(&text).unwrap();
+        assert_eq!(tree.leaves().count(), n);
+        assert_eq!(
+            tree.children().unwrap().1.leaf_name().unwrap(),
+            format!("t{n}")
+        );
+        assert_eq!(tree.to_string(), text);
+    }
+
+    #[test]
+    fn same_errors_as_parser() {
+        for text in [
+            "123",
+            "123,",
+            "(123)",
+            "(123,)",
+            "(123,123,23)",
+            "(1,2));",
+            ")",
+        ] {
+            let expected = BinTreeBuilder::default()
+                .parse_newick_from_str(text, NodeIdx::new(0))
+                .unwrap_err();
+            assert_eq!(parse_newick_borrowed(text).unwrap_err(), expected, "{text}");
+        }
+
+        assert!(matches!(
+            parse_newick_borrowed("('abc,d);"),
+            Err(ParserError::Lexer(LexerError::UnterminatedQuote { .. }))
+        ));
+    }
+}
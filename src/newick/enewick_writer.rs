@@ -0,0 +1,223 @@
+use super::NewickStyle;
+use crate::network::{Network, NetworkError};
+use std::io::{self, Write};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ENewickWriterError {
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+
+    #[error(transparent)]
+    IO(#[from] io::Error),
+}
+
+enum Step {
+    Visit(usize),
+    Comma,
+    Close,
+    Tag(usize),
+}
+
+/// Writes `network` in extended Newick format (followed by a semicolon). Each reticulation
+/// is written in full at its first occurrence, followed by a tag `#Hk`, while all further
+/// occurrences consist of the tag only. Reticulations are numbered `1, 2, ...` in the order of
+/// their first occurrence in the output, which only depends on the order of children; hence
+/// the output does not depend on the node indices of `network`.
+///
+/// The network is validated with [`Network::root`] before anything is written. The traversal
+/// uses an explicit stack, so deep networks do not overflow the call stack.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::Label, network::Network, newick::*};
+///
+/// let mut network = Network::default();
+/// let [root, a, b, h] = [(); 4].map(|_| network.add_node());
+/// let [l1, l2, l3] = [1, 2, 3].map(|l| network.add_leaf(Label(l)));
+/// for (u, v) in [(root, a), (root, b), (a, l1), (a, h), (b, h), (b, l3), (h, l2)] {
+///     network.add_edge(u, v);
+/// }
+///
+/// let mut buffer = Vec::new();
+/// write_enewick(&network, &mut buffer).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "((1,(2)#H1),(#H1,3));");
+/// ```
+pub fn write_enewick(network: &Network, writer: &mut impl Write) -> Result<(), ENewickWriterError> {
+    write_enewick_styled(network, &NewickStyle::COMPACT, writer)
+}
+
+/// Same as [`write_enewick`], but emits whitespace according to `style`
+pub fn write_enewick_styled(
+    network: &Network,
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> Result<(), ENewickWriterError> {
+    let root = network.root()?;
+
+    // tag of each reticulation once its first occurrence was written
+    let mut tags = vec![None; network.num_nodes()];
+    let mut num_tags = 0;
+
+    let mut stack = vec![Step::Visit(root)];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Visit(u) => {
+                if network.is_reticulation(u) {
+                    if let Some(tag) = tags[u] {
+                        write!(writer, "#H{tag}")?;
+                        continue;
+                    }
+                    num_tags += 1;
+                    tags[u] = Some(num_tags);
+                    stack.push(Step::Tag(num_tags));
+                }
+
+                if let Some(label) = network.label(u) {
+                    write!(writer, "{}", label.0)?;
+                    continue;
+                }
+
+                style.write_open(writer)?;
+                stack.push(Step::Close);
+                for (i, &v) in network.children(u).iter().enumerate().rev() {
+                    stack.push(Step::Visit(v));
+                    if i > 0 {
+                        stack.push(Step::Comma);
+                    }
+                }
+            }
+            Step::Comma => style.write_comma(writer)?,
+            Step::Close => style.write_close(writer)?,
+            Step::Tag(tag) => write!(writer, "#H{tag}")?,
+        }
+    }
+
+    style.write_end(writer)?;
+    Ok(())
+}
+
+/// Returns the output of [`write_enewick`] as a string
+pub fn to_enewick_string(network: &Network) -> Result<String, NetworkError> {
+    let mut buffer = Vec::new();
+    match write_enewick(network, &mut buffer) {
+        Ok(()) => Ok(String::from_utf8(buffer).unwrap()),
+        Err(ENewickWriterError::Network(e)) => Err(e),
+        Err(ENewickWriterError::IO(e)) => unreachable!("writing into a Vec cannot fail: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binary_tree::*;
+
+    #[test]
+    fn trees_as_newick() {
+        for text in ["1;", "(1,2);", "(((4,2),(7,1)),8);"] {
+            let tree: BinTree = text.parse().unwrap();
+            let network = Network::from_tree(tree.top_down());
+            assert_eq!(to_enewick_string(&network).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn deterministic_numbering() {
+        // leaf 5 and the parent of (1,2) are reticulations; node indices differ from the
+        // order of first occurrence, which determines the numbering
+        let mut network = Network::default();
+        let h2 = network.add_node();
+        let h1 = network.add_leaf(Label(5));
+        let [root, a, b] = [(); 3].map(|_| network.add_node());
+        let [l1, l2] = [1, 2].map(|l| network.add_leaf(Label(l)));
+        for (u, v) in [
+            (root, a),
+            (root, b),
+            (a, h1),
+            (a, h2),
+            (b, h2),
+            (b, h1),
+            (h2, l1),
+            (h2, l2),
+        ] {
+            network.add_edge(u, v);
+        }
+
+        assert_eq!(
+            to_enewick_string(&network).unwrap(),
+            "((5#H1,(1,2)#H2),(#H2,#H1));"
+        );
+
+        let style = NewickStyle {
+            space_after_comma: true,
+            trailing_newline: true,
+            ..Default::default()
+        };
+        let mut buffer = Vec::new();
+        write_enewick_styled(&network, &style, &mut buffer).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "((5#H1, (1, 2)#H2), (#H2, #H1));\n"
+        );
+    }
+
+    #[test]
+    fn nested_reticulations() {
+        // reticulation `inner` lies below reticulation `outer` and is reached again from the root
+        let mut network = Network::default();
+        let [root, inner, outer] = [(); 3].map(|_| network.add_node());
+        let [l1, l2] = [1, 2].map(|l| network.add_leaf(Label(l)));
+        for (u, v) in [
+            (root, outer),
+            (root, inner),
+            (root, outer),
+            (outer, inner),
+            (inner, l1),
+            (inner, l2),
+        ] {
+            network.add_edge(u, v);
+        }
+        assert_eq!(
+            to_enewick_string(&network).unwrap(),
+            "(((1,2)#H2)#H1,#H2,#H1);"
+        );
+    }
+
+    #[test]
+    fn invalid_network_writes_nothing() {
+        let mut network = Network::default();
+        let [a, b] = [(); 2].map(|_| network.add_node());
+        network.add_edge(a, b);
+        network.add_edge(b, a);
+
+        let mut buffer = Vec::new();
+        assert!(matches!(
+            write_enewick(&network, &mut buffer),
+            Err(ENewickWriterError::Network(NetworkError::Cycle))
+        ));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn deep_network() {
+        // caterpillar where every inner node also points to a shared reticulation leaf
+        const N: usize = 100_000;
+        let mut network = Network::default();
+        let shared = network.add_leaf(Label(1));
+        let mut u = network.add_node();
+        for i in 2..=N as LabelValue + 1 {
+            let leaf = network.add_leaf(Label(i));
+            let v = network.add_node();
+            network.add_edge(u, leaf);
+            network.add_edge(u, shared);
+            network.add_edge(u, v);
+            u = v;
+        }
+        let last = network.add_leaf(Label(N as LabelValue + 2));
+        network.add_edge(u, last);
+
+        let text = to_enewick_string(&network).unwrap();
+        assert!(text.starts_with("(2,1#H1,(3,#H1,("));
+        assert_eq!(text.matches("#H1").count(), N);
+    }
+}
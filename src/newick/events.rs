@@ -0,0 +1,123 @@
+//! Event-driven (SAX-style) Newick parsing, which reports the structure of a tree without
+//! building it. This is useful to compute statistics or to construct custom data structures
+//! that do not fit the bottom-up [`TreeBuilder`] interface.
+use super::{
+    binary_tree_parser::{ParserError, ParserHook, parse_tree},
+    lexer::Lexer,
+};
+use crate::binary_tree::{Label, NodeIdx, TreeBuilder};
+
+/// Callbacks invoked by [`parse_newick_events`] in the order of the input. All methods
+/// default to doing nothing.
+pub trait NewickEventHandler {
+    /// Invoked for each opening parenthesis, i.e. when entering an inner node
+    fn on_open(&mut self) {}
+
+    fn on_leaf(&mut self, _label: Label) {}
+
+    /// Invoked for each closing parenthesis, i.e. after both children of an inner node
+    fn on_close(&mut self) {}
+
+    /// Invoked once after the final `;`
+    fn on_end(&mut self) {}
+}
+
+/// Parses the tree in `text` and reports its structure to `handler` without allocating nodes.
+/// Events up to an error are reported; `on_end` is only invoked for valid trees.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::events::*};
+///
+/// #[derive(Default)]
+/// struct Depths { depth: usize, leaf_depths: Vec<(LabelValue, usize)> }
+///
+/// impl NewickEventHandler for Depths {
+///     fn on_open(&mut self) { self.depth += 1; }
+///     fn on_close(&mut self) { self.depth -= 1; }
+///     fn on_leaf(&mut self, label: Label) { self.leaf_depths.push((label.0, self.depth)); }
+/// }
+///
+/// let mut handler = Depths::default();
+/// parse_newick_events("((1,2),3);", &mut handler).unwrap();
+/// assert_eq!(handler.leaf_depths, vec![(1, 2), (2, 2), (3, 1)]);
+/// ```
+pub fn parse_newick_events(
+    text: &str,
+    handler: &mut impl NewickEventHandler,
+) -> Result<(), ParserError> {
+    let mut lexer = Lexer::new(text);
+    parse_tree(
+        &mut Discard,
+        &mut lexer,
+        NodeIdx::new(0),
+        &mut EventHook(&mut *handler),
+    )?;
+    handler.on_end();
+    Ok(())
+}
+
+/// Forwards the parser hooks to a [`NewickEventHandler`]
+struct EventHook<'a, H>(&'a mut H);
+
+impl<H: NewickEventHandler> ParserHook for EventHook<'_, H> {
+    fn enter_inner(&mut self, _id: NodeIdx) {
+        self.0.on_open();
+    }
+
+    fn leave_inner(&mut self) {
+        self.0.on_close();
+    }
+
+    fn visit_leaf(&mut self, label: Label) {
+        self.0.on_leaf(label);
+    }
+}
+
+/// Builder that does not construct anything
+struct Discard;
+
+impl TreeBuilder for Discard {
+    type Node = ();
+
+    fn new_inner(&mut self, _id: NodeIdx, _left: (), _right: ()) {}
+
+    fn new_leaf(&mut self, _label: Label) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder(String);
+
+    impl NewickEventHandler for Recorder {
+        fn on_open(&mut self) {
+            self.0.push('(');
+        }
+
+        fn on_leaf(&mut self, label: Label) {
+            self.0 += &label.0.to_string();
+        }
+
+        fn on_close(&mut self) {
+            self.0.push(')');
+        }
+
+        fn on_end(&mut self) {
+            self.0.push(';');
+        }
+    }
+
+    #[test]
+    fn events() {
+        let mut recorder = Recorder::default();
+        parse_newick_events("((1,2),(3,(4,5)));", &mut recorder).unwrap();
+        assert_eq!(recorder.0, "((12)(3(45)));");
+
+        let mut recorder = Recorder::default();
+        assert!(parse_newick_events("((1,2),3", &mut recorder).is_err());
+        assert_eq!(recorder.0, "((12)3");
+    }
+}
@@ -0,0 +1,138 @@
+//! Classification of Newick strings into spans, e.g., for syntax highlighting.
+use super::{label_interner::LabelInterner, lexer::*};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    /// Opening parenthesis; `depth` is the number of enclosing parentheses
+    Open { depth: usize },
+
+    /// Closing parenthesis matching an [`SpanKind::Open`] with the same `depth`
+    Close { depth: usize },
+
+    /// Leaf label (numeric or taxon name)
+    Label,
+
+    /// Branch length including the leading colon, e.g. `:0.5`
+    BranchLength,
+
+    /// Comma or semicolon
+    Separator,
+
+    /// Characters that cannot be tokenized, unmatched closing parentheses,
+    /// and malformed branch lengths
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub kind: SpanKind,
+
+    /// Byte range within the input
+    pub range: Range<usize>,
+}
+
+/// Splits `text` into classified spans using the crate's Newick tokenizer. Whitespace is not
+/// reported, and the input does not have to be a valid tree; e.g., tokens following an error
+/// are still classified. Each span covers a byte range of `text`, so it can be sliced directly.
+///
+/// # Example
+/// ```
+/// use pace26io::newick::highlight::*;
+///
+/// let text = "((1,Pan):0.5,'Homo sapiens');";
+/// let spans = classify_spans(text);
+/// let kinds: Vec<_> = spans.iter().map(|s| (&text[s.range.clone()], s.kind)).collect();
+/// assert_eq!(kinds, vec![
+///     ("(", SpanKind::Open { depth: 0 }),
+///     ("(", SpanKind::Open { depth: 1 }),
+///     ("1", SpanKind::Label),
+///     (",", SpanKind::Separator),
+///     ("Pan", SpanKind::Label),
+///     (")", SpanKind::Close { depth: 1 }),
+///     (":0.5", SpanKind::BranchLength),
+///     (",", SpanKind::Separator),
+///     ("'Homo sapiens'", SpanKind::Label),
+///     (")", SpanKind::Close { depth: 0 }),
+///     (";", SpanKind::Separator),
+/// ]);
+/// ```
+pub fn classify_spans(text: &str) -> Vec<Span> {
+    let mut interner = LabelInterner::default();
+    let mut lexer = Lexer::new(text);
+    lexer.allow_whitespaces();
+    lexer.allow_branch_lengths();
+    lexer.intern_names(&mut interner);
+
+    let mut spans = Vec::new();
+    let mut depth = 0;
+    loop {
+        if let Some((range, length)) = lexer.try_branch_length_with_range() {
+            let kind = match length {
+                Ok(_) => SpanKind::BranchLength,
+                Err(_) => SpanKind::Error,
+            };
+            spans.push(Span { kind, range });
+            continue;
+        }
+
+        let Some(token) = lexer.next() else {
+            break;
+        };
+
+        let (kind, range) = match token {
+            Ok(token) => {
+                let kind = match token.token_type {
+                    TokenType::ParOpen => {
+                        depth += 1;
+                        SpanKind::Open { depth: depth - 1 }
+                    }
+                    TokenType::ParClose if depth > 0 => {
+                        depth -= 1;
+                        SpanKind::Close { depth }
+                    }
+                    TokenType::ParClose => SpanKind::Error,
+                    TokenType::Comma | TokenType::Semicolon => SpanKind::Separator,
+                    TokenType::Number(_) | TokenType::Name(_) | TokenType::RawLabel => {
+                        SpanKind::Label
+                    }
+                };
+                (kind, token.byte_range())
+            }
+            Err(err) => (SpanKind::Error, err.span().byte_range.clone()),
+        };
+        spans.push(Span { kind, range });
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn kinds(text: &str) -> Vec<(&str, SpanKind)> {
+        classify_spans(text)
+            .into_iter()
+            .map(|s| (&text[s.range], s.kind))
+            .collect()
+    }
+
+    #[test]
+    fn errors_and_unicode() {
+        assert_eq!(
+            kinds(") (Ä, 12 :x]"),
+            vec![
+                (")", SpanKind::Error),
+                ("(", SpanKind::Open { depth: 0 }),
+                ("Ä", SpanKind::Label),
+                (",", SpanKind::Separator),
+                ("12", SpanKind::Label),
+                (":", SpanKind::Error),
+                ("x", SpanKind::Label),
+                ("]", SpanKind::Error),
+            ]
+        );
+        assert!(classify_spans("  ").is_empty());
+    }
+}
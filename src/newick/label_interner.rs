@@ -0,0 +1,101 @@
+use crate::binary_tree::{Label, LabelValue};
+use std::collections::HashMap;
+
+/// Maps taxon names (e.g. `Homo_sapiens`) to dense labels `1, 2, 3, ...` in order of their
+/// first appearance and allows to query the reverse mapping afterwards.
+/// Pass it as [`LabelKind::Names`](crate::newick::LabelKind::Names) in the
+/// [`NewickParserOptions`](crate::newick::NewickParserOptions) to read trees with named leaves.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let mut interner = LabelInterner::default();
+/// let mut options = NewickParserOptions {
+///     labels: LabelKind::Names(&mut interner),
+///     ..Default::default()
+/// };
+/// let mut builder = BinTreeBuilder::default();
+/// let a = builder
+///     .parse_newick_from_str_with("((Homo_sapiens,Pan),Gorilla);", NodeIdx::new(0), &mut options)
+///     .unwrap();
+/// let b = builder
+///     .parse_newick_from_str_with("(Gorilla,(Pan,Homo_sapiens));", NodeIdx::new(0), &mut options)
+///     .unwrap();
+///
+/// assert_eq!(a.top_down().to_newick_string(), "((1,2),3);");
+/// assert_eq!(b.top_down().to_newick_string(), "(3,(2,1));");
+/// assert_eq!(interner.name(Label(1)), Some("Homo_sapiens"));
+/// assert_eq!(interner.label("Gorilla"), Some(Label(3)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LabelInterner {
+    names: Vec<String>,
+    labels: HashMap<String, Label>,
+}
+
+impl LabelInterner {
+    /// Returns the label of `name`, assigning the next free label if it is new.
+    pub fn intern(&mut self, name: &str) -> Label {
+        if let Some(&label) = self.labels.get(name) {
+            return label;
+        }
+
+        self.names.push(name.to_owned());
+        let label = Label(self.names.len() as LabelValue);
+        self.labels.insert(name.to_owned(), label);
+        label
+    }
+
+    /// Returns the label of `name` if it has been interned before.
+    pub fn label(&self, name: &str) -> Option<Label> {
+        self.labels.get(name).copied()
+    }
+
+    /// Returns the name of `label` if it has been assigned by this interner.
+    pub fn name(&self, label: Label) -> Option<&str> {
+        let idx = (label.0 as usize).checked_sub(1)?;
+        self.names.get(idx).map(String::as_str)
+    }
+
+    /// Number of distinct names, i.e. the labels `1..=len()` are assigned.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Iterates over all `(label, name)` pairs in increasing label order.
+    pub fn iter(&self) -> impl Iterator<Item = (Label, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (Label(i as LabelValue + 1), name.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intern() {
+        let mut interner = LabelInterner::default();
+        assert!(interner.is_empty());
+        assert_eq!(interner.intern("b"), Label(1));
+        assert_eq!(interner.intern("a"), Label(2));
+        assert_eq!(interner.intern("b"), Label(1));
+        assert_eq!(interner.len(), 2);
+
+        assert_eq!(interner.name(Label(0)), None);
+        assert_eq!(interner.name(Label(2)), Some("a"));
+        assert_eq!(interner.name(Label(3)), None);
+        assert_eq!(interner.label("c"), None);
+        assert_eq!(
+            interner.iter().collect::<Vec<_>>(),
+            vec![(Label(1), "b"), (Label(2), "a")]
+        );
+    }
+}
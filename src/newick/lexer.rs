@@ -8,63 +8,1077 @@
 ///
 /// Returns a [`LexerError`] if an unexpected character is encountered in the input.
 use std::{
-    iter::{Enumerate, Peekable},
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    io::{self, BufRead},
+    iter::Enumerate,
+    ops::Range,
+    rc::Rc,
+    slice,
     str::Chars,
 };
 
 use thiserror::Error;
 
+use super::{binary_tree_parser::ParserLimits, label_interner::LabelInterner};
+use crate::{
+    binary_tree::LabelValue,
+    progress::{Progress, ProgressReporter},
+};
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum TokenType {
     ParOpen,
     ParClose,
     Comma,
     Semicolon,
-    Number(u32),
+    Number(LabelValue),
+
+    /// Taxon name (quoted or unquoted), reported as the label assigned by the interner
+    Name(LabelValue),
+
+    /// Unconverted label (quoted or unquoted) if [`Lexer::raw_labels`] is set; its text is the
+    /// input within [`Token::byte_range`] and available via [`Lexer::label_text`]
+    RawLabel,
+}
+
+/// Location of a token or error within the input
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Span {
+    /// Line (0-based) of the first character
+    pub line: usize,
+
+    /// Column (0-based, in characters) of the first character within its line
+    pub column: usize,
+
+    /// Byte range within the input
+    pub byte_range: Range<usize>,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line + 1, self.column + 1)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Token {
+    /// Character offset of the token within the input
     pub offset: usize,
+
+    /// Byte offset of the token within the input
+    pub byte_offset: usize,
+
+    /// Length of the token in bytes
+    pub byte_len: usize,
+
     pub token_type: TokenType,
 }
 
-#[derive(Error, Debug, PartialEq, Eq)]
+impl Token {
+    /// Returns the byte range of the token within the input; see [`Lexer::token_span`]
+    /// for its line and column
+    pub fn byte_range(&self) -> Range<usize> {
+        self.byte_offset..self.byte_offset + self.byte_len
+    }
+
+    fn begin(&self) -> Position {
+        Position {
+            offset: self.offset,
+            byte: self.byte_offset,
+        }
+    }
+}
+
+/// Character and byte offset of a character within the input
+#[derive(Debug, Clone, Copy, Default)]
+struct Position {
+    offset: usize,
+    byte: usize,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum LexerError {
-    #[error("unexpected character {character} at {offset}")]
-    UnexpectedChar { character: char, offset: usize },
+    #[error("unexpected character {character} at {span}")]
+    UnexpectedChar {
+        character: char,
+        offset: usize,
+        span: Span,
+    },
+
+    #[error("invalid branch length at {span}")]
+    InvalidBranchLength { offset: usize, span: Span },
+
+    #[error("unterminated quoted label starting at {span}")]
+    UnterminatedQuote { offset: usize, span: Span },
+
+    #[error("non-numeric label at {span} requires a label interner")]
+    NameWithoutInterner { offset: usize, span: Span },
+
+    #[error("unterminated comment starting at {span}")]
+    UnterminatedComment { offset: usize, span: Span },
+
+    #[error("invalid NHX annotation at {span}; expected [&&NHX:key=value:...]")]
+    InvalidNhx { offset: usize, span: Span },
+
+    #[error("label at {span} exceeds the range of label values")]
+    NumberOverflow { offset: usize, span: Span },
+}
+
+impl LexerError {
+    pub fn span(&self) -> &Span {
+        match self {
+            LexerError::UnexpectedChar { span, .. }
+            | LexerError::InvalidBranchLength { span, .. }
+            | LexerError::UnterminatedQuote { span, .. }
+            | LexerError::NameWithoutInterner { span, .. }
+            | LexerError::UnterminatedComment { span, .. }
+            | LexerError::InvalidNhx { span, .. }
+            | LexerError::NumberOverflow { span, .. } => span,
+        }
+    }
+}
+
+/// Characters of the input together with their character offsets
+enum Input<'a> {
+    Chars(Enumerate<Chars<'a>>),
+
+    /// Each byte is a character; non-ASCII bytes are interpreted as Latin-1
+    Bytes(Enumerate<slice::Iter<'a, u8>>),
+
+    /// Same as `Bytes`, but read incrementally
+    Stream(StreamInput<'a>),
+}
+
+/// Progress of a [`StreamInput`], shared with the lexer
+#[derive(Default)]
+struct StreamState {
+    /// Number of bytes returned so far
+    num_bytes: Cell<usize>,
+    error: RefCell<Option<io::Error>>,
+}
+
+/// Reads bytes from the buffer of a [`BufRead`]. A byte is only consumed from the reader
+/// once the next byte is requested, so the reader is positioned after the last byte the
+/// lexer processed (rather than the one it peeked at).
+struct StreamInput<'a> {
+    reader: &'a mut dyn BufRead,
+    state: Rc<StreamState>,
+    pending: bool,
+}
+
+impl StreamInput<'_> {
+    /// Consumes the last byte returned from the reader
+    fn commit(&mut self) {
+        if std::mem::take(&mut self.pending) {
+            self.reader.consume(1);
+        }
+    }
+}
+
+impl Iterator for StreamInput<'_> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.commit();
+
+        loop {
+            match self.reader.fill_buf() {
+                Ok([]) => return None,
+                Ok(buffer) => {
+                    let offset = self.state.num_bytes.get();
+                    self.state.num_bytes.set(offset + 1);
+                    self.pending = true;
+                    return Some((offset, buffer[0] as char));
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    *self.state.error.borrow_mut() = Some(err);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Input<'_> {
+    type Item = (usize, char);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Input::Chars(chars) => chars.next(),
+            Input::Bytes(bytes) => bytes.next().map(|(i, &b)| (i, b as char)),
+            Input::Stream(stream) => stream.next(),
+        }
+    }
+}
+
+impl Input<'_> {
+    /// Returns the number of bytes `c` occupies in the input
+    #[inline]
+    fn encoded_len(&self, c: char) -> usize {
+        match self {
+            Input::Chars(_) => c.len_utf8(),
+            Input::Bytes(_) | Input::Stream(_) => 1,
+        }
+    }
+}
+
+/// Characters of the input with a lookahead of one character; tracks the position of the
+/// next character not consumed yet
+struct Cursor<'a> {
+    input: Input<'a>,
+    peeked: Option<Option<(usize, char)>>,
+    position: Position,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: Input<'a>) -> Self {
+        Self {
+            input,
+            peeked: None,
+            position: Position::default(),
+        }
+    }
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, char)> {
+        let next = match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.input.next(),
+        };
+        if let Some((offset, c)) = next {
+            self.position = Position {
+                offset: offset + 1,
+                byte: self.position.byte + self.input.encoded_len(c),
+            };
+        }
+        next
+    }
+
+    #[inline]
+    fn peek(&mut self) -> Option<&(usize, char)> {
+        let input = &mut self.input;
+        self.peeked.get_or_insert_with(|| input.next()).as_ref()
+    }
+
+    #[inline]
+    fn next_if(&mut self, func: impl FnOnce(&(usize, char)) -> bool) -> Option<(usize, char)> {
+        if self.peek().is_some_and(func) {
+            self.next()
+        } else {
+            None
+        }
+    }
+}
+
+/// Input retained to compute spans
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    Text(&'a str),
+    Bytes(&'a [u8]),
+
+    /// Streamed inputs are not retained; their spans assume a single line
+    Stream,
 }
 
 pub struct Lexer<'a> {
-    input: Peekable<Enumerate<Chars<'a>>>,
+    input: Cursor<'a>,
+    source: Source<'a>,
     allow_whitespace: bool,
+    allow_inner_labels: bool,
+    allow_branch_lengths: bool,
+    interner: Option<&'a mut LabelInterner>,
+    comments: Comments<'a>,
+    input_bytes: usize,
+    limits: ParserLimits,
+
+    /// Position of the first character of each line before `lines_scanned`; extended on demand
+    /// when spans are requested, so that each character is scanned at most once
+    line_starts: RefCell<Vec<Position>>,
+    lines_scanned: Cell<Position>,
+
+    /// Progress of streamed inputs, whose length is not known in advance
+    stream: Option<Rc<StreamState>>,
+
+    /// Tokens read by [`Lexer::peek_n`] but not yet returned
+    peeked: VecDeque<Result<Token, LexerError>>,
+
+    /// If NHX annotations are parsed: positions, end byte offsets, and texts of the
+    /// annotations skipped since the last call of [`Lexer::try_nhx`]
+    nhx: Option<Vec<(Position, usize, String)>>,
+
+    /// Number of characters of the byte order mark at the beginning of the input (if any)
+    bom_chars: usize,
+
+    /// Accept a line terminator `\n` or `\r\n` after the tree
+    allow_line_terminator: bool,
+
+    /// Report labels as [`TokenType::RawLabel`] instead of converting them
+    raw_labels: bool,
+
+    progress: Option<ProgressReporter<'a>>,
+
+    /// Number of tokens beginning a node (opening parentheses and labels) lexed so far
+    num_nodes: usize,
+}
+
+/// Treatment of bracket comments `[...]`
+enum Comments<'a> {
+    Reject,
+    Skip,
+    Report(&'a mut dyn FnMut(usize, &str)),
+}
+
+/// UTF-8 encoding of the byte order mark U+FEFF
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Prefix of bracket comments holding NHX annotations
+const NHX_PREFIX: &str = "&&NHX";
+
+/// Removes the quotes of a quoted label and decodes its escaped quotes (`''`); other labels
+/// are returned unchanged
+pub(crate) fn unquote_label(text: &str) -> Cow<'_, str> {
+    match text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')) {
+        Some(quoted) if quoted.contains("''") => Cow::Owned(quoted.replace("''", "'")),
+        Some(quoted) => Cow::Borrowed(quoted),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Characters that may not appear in unquoted taxon names
+fn is_name_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '(' | ')' | ',' | ';' | ':' | '[' | ']' | '\'' | '"')
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
+        let mut lexer = Self::with_input(
+            Input::Chars(input.chars().enumerate()),
+            Source::Text(input),
+            input.len(),
+        );
+        lexer.bom_chars = usize::from(input.starts_with('\u{FEFF}'));
+        lexer
+    }
+
+    /// Creates a lexer operating directly on bytes, e.g., of a memory-mapped file, which
+    /// avoids UTF-8 validation and decoding. Each byte is treated as one character, i.e.
+    /// offsets are byte offsets. Since the Newick syntax is ASCII, this only affects labels
+    /// with non-ASCII characters, which are interpreted as Latin-1.
+    pub fn from_bytes(input: &'a [u8]) -> Self {
+        let mut lexer = Self::with_input(
+            Input::Bytes(input.iter().enumerate()),
+            Source::Bytes(input),
+            input.len(),
+        );
+        if input.starts_with(UTF8_BOM) {
+            lexer.bom_chars = UTF8_BOM.len();
+        }
+        lexer
+    }
+
+    /// Creates a lexer reading bytes incrementally from `reader`, i.e. at most the buffer of
+    /// the reader is held in memory. As in [`Lexer::from_bytes`], each byte is one character.
+    /// Spans assume that the input consists of a single line, and the input length is unknown
+    /// in advance, i.e. [`ParserLimits::max_input_bytes`] is not enforced.
+    pub(crate) fn from_reader(reader: &'a mut dyn BufRead) -> Self {
+        // errors are ignored here, since they are reported once the input is read
+        let has_bom = reader
+            .fill_buf()
+            .is_ok_and(|buffer| buffer.starts_with(UTF8_BOM));
+
+        let state = Rc::new(StreamState::default());
+        let mut lexer = Self::with_input(
+            Input::Stream(StreamInput {
+                reader,
+                state: state.clone(),
+                pending: false,
+            }),
+            Source::Stream,
+            0,
+        );
+        lexer.stream = Some(state);
+        if has_bom {
+            lexer.bom_chars = UTF8_BOM.len();
+        }
+        lexer
+    }
+
+    /// Consumes the last character processed by the lexer from the underlying reader (unless
+    /// it was only peeked at), so the reader is positioned directly after the lexed input
+    pub(crate) fn release_reader(&mut self) {
+        if self.input.peeked.is_none()
+            && let Input::Stream(stream) = &mut self.input.input
+        {
+            stream.commit();
+        }
+    }
+
+    /// Returns the first error of the underlying reader, if any; the input ends at such an error
+    pub(crate) fn take_read_error(&mut self) -> Option<io::Error> {
+        self.stream.as_ref()?.error.borrow_mut().take()
+    }
+
+    fn with_input(input: Input<'a>, source: Source<'a>, input_bytes: usize) -> Self {
         Self {
-            input: input.chars().enumerate().peekable(),
+            input: Cursor::new(input),
+            source,
             allow_whitespace: false,
+            allow_inner_labels: false,
+            allow_branch_lengths: false,
+            interner: None,
+            comments: Comments::Reject,
+            input_bytes,
+            limits: ParserLimits::default(),
+            line_starts: RefCell::new(vec![Position::default()]),
+            lines_scanned: Cell::new(Position::default()),
+            stream: None,
+            peeked: VecDeque::new(),
+            nhx: None,
+            bom_chars: 0,
+            allow_line_terminator: false,
+            raw_labels: false,
+            progress: None,
+            num_nodes: 0,
+        }
+    }
+
+    /// Returns the line, column, and byte range of `token`. Lines are located on demand by
+    /// scanning the input up to the requested position, so lexing and parsing valid input does
+    /// not pay for spans and an early error does not scan the remaining input.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::newick::*;
+    ///
+    /// let mut lexer = Lexer::new("(1,\n 'Ä',3);");
+    /// lexer.allow_whitespaces();
+    /// lexer.raw_labels();
+    /// let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+    /// assert_eq!(tokens[5].byte_range(), 10..11);
+    /// assert_eq!(lexer.token_span(&tokens[5]).to_string(), "line 2, column 6");
+    /// ```
+    pub fn token_span(&self, token: &Token) -> Span {
+        self.span(token.begin(), token.byte_range().end)
+    }
+
+    /// Returns the span from the beginning of `first` to the end of `last`
+    pub(crate) fn span_between(&self, first: &Token, last: &Token) -> Span {
+        self.span(first.begin(), last.byte_range().end)
+    }
+
+    /// Returns the span from `begin` to the byte offset `end`
+    fn span(&self, begin: Position, end: usize) -> Span {
+        let mut line_starts = self.line_starts.borrow_mut();
+        let scanned = self.lines_scanned.get();
+        if scanned.byte < begin.byte {
+            let newline = |offset: usize, byte: usize| Position {
+                offset: offset + 1,
+                byte: byte + 1,
+            };
+            let mut end = scanned;
+            match self.source {
+                Source::Text(text) => {
+                    for (byte, c) in text[scanned.byte..begin.byte].char_indices() {
+                        if c == '\n' {
+                            line_starts.push(newline(end.offset, scanned.byte + byte));
+                        }
+                        end.offset += 1;
+                    }
+                }
+                Source::Bytes(bytes) => {
+                    for (i, &byte) in bytes[scanned.byte..begin.byte].iter().enumerate() {
+                        if byte == b'\n' {
+                            line_starts.push(newline(scanned.byte + i, scanned.byte + i));
+                        }
+                    }
+                    end.offset = begin.byte;
+                }
+                Source::Stream => {}
+            }
+            end.byte = begin.byte;
+            self.lines_scanned.set(end);
+        }
+
+        let line = line_starts.partition_point(|start| start.byte <= begin.byte) - 1;
+        Span {
+            line,
+            column: begin.offset - line_starts[line].offset,
+            byte_range: begin.byte..end,
+        }
+    }
+
+    /// Returns the span from `begin` to the next unconsumed character
+    fn span_from(&self, begin: Position) -> Span {
+        self.span(begin, self.input.position.byte)
+    }
+
+    /// Returns the empty span at the end of the input
+    pub(crate) fn end_span(&self) -> Span {
+        let end = self.end_position();
+        self.span(end, end.byte)
+    }
+
+    /// Returns the span of the whole input
+    pub(crate) fn input_span(&self) -> Span {
+        self.span(Position::default(), self.end_position().byte)
+    }
+
+    /// Returns the position after the last character of the input; for streamed inputs,
+    /// after the last character read so far
+    fn end_position(&self) -> Position {
+        let len = match self.source {
+            Source::Text(text) => {
+                return Position {
+                    offset: text.chars().count(),
+                    byte: text.len(),
+                };
+            }
+            Source::Bytes(bytes) => bytes.len(),
+            Source::Stream => self
+                .stream
+                .as_ref()
+                .map_or(0, |state| state.num_bytes.get()),
+        };
+        Position {
+            offset: len,
+            byte: len,
+        }
+    }
+
+    fn token(&self, begin: Position, token_type: TokenType) -> Token {
+        Token {
+            offset: begin.offset,
+            byte_offset: begin.byte,
+            byte_len: self.input.position.byte - begin.byte,
+            token_type,
+        }
+    }
+
+    fn unterminated_comment(&self, begin: Position) -> LexerError {
+        LexerError::UnterminatedComment {
+            offset: begin.offset,
+            span: self.span_from(begin),
+        }
+    }
+
+    /// Sets the limits enforced by the parser consuming this lexer
+    pub fn set_limits(&mut self, limits: ParserLimits) {
+        self.limits = limits;
+    }
+
+    pub(crate) fn limits(&self) -> &ParserLimits {
+        &self.limits
+    }
+
+    /// Returns the length of the whole input in bytes
+    pub(crate) fn input_bytes(&self) -> usize {
+        self.input_bytes
+    }
+
+    /// Tolerates files written on Windows: skips a byte order mark at the beginning of the
+    /// input (U+FEFF, or the bytes `EF BB BF` for byte inputs) and accepts a line terminator
+    /// `\n` or `\r\n` after the tree. Has to be called before the first token is read.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let mut lexer = Lexer::new("\u{FEFF}(1,2);\r\n");
+    /// lexer.tolerate_bom_and_crlf();
+    /// let tree = BinTreeBuilder::default()
+    ///     .parse_newick_from_lexer(&mut lexer, NodeIdx::new(3))
+    ///     .unwrap();
+    /// assert_eq!(tree.top_down().to_newick_string(), "(1,2);");
+    /// ```
+    pub fn tolerate_bom_and_crlf(&mut self) {
+        self.debug_assert_not_peeked();
+        if self.input.peek().is_some_and(|&(offset, _)| offset == 0) {
+            for _ in 0..self.bom_chars {
+                self.input.next();
+            }
+        }
+        self.allow_line_terminator = true;
+    }
+
+    /// Silently skips bracket comments `[...]` between tokens
+    pub fn skip_comments(&mut self) {
+        self.comments = Comments::Skip;
+    }
+
+    /// Skips bracket comments `[...]` between tokens and reports the offset of the opening
+    /// bracket together with the text between the brackets to `on_comment`.
+    pub fn report_comments(&mut self, on_comment: &'a mut dyn FnMut(usize, &str)) {
+        self.comments = Comments::Report(on_comment);
+    }
+
+    /// Skips whitespace (if allowed) and comments (if enabled); returns the position
+    /// of an unterminated comment as error
+    fn skip_ignorable(&mut self) -> Result<(), Position> {
+        loop {
+            if self.allow_whitespace {
+                while self.input.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+            }
+
+            if matches!(self.comments, Comments::Reject) {
+                return Ok(());
+            }
+
+            let begin = self.input.position;
+            if self.input.next_if(|&(_, c)| c == '[').is_none() {
+                return Ok(());
+            }
+
+            let mut text = String::new();
+            loop {
+                match self.input.next() {
+                    None => return Err(begin),
+                    Some((_, ']')) => break,
+                    Some((_, c)) => text.push(c),
+                }
+            }
+
+            if let Some(pending) = self.nhx.as_mut()
+                && text.starts_with(NHX_PREFIX)
+            {
+                pending.push((begin, self.input.position.byte, text));
+                continue;
+            }
+
+            if let Comments::Report(on_comment) = &mut self.comments {
+                on_comment(begin.offset, &text);
+            }
+        }
+    }
+
+    /// Enables [`Lexer::try_nhx`]. NHX annotations are bracket comments, so this also
+    /// skips other comments unless they are reported.
+    pub fn parse_nhx(&mut self) {
+        if matches!(self.comments, Comments::Reject) {
+            self.comments = Comments::Skip;
+        }
+        self.nhx = Some(Vec::new());
+    }
+
+    /// If NHX annotations are parsed, consumes the annotations `[&&NHX:key=value:...]`
+    /// following the current position, e.g. after a node, and returns their tags in order.
+    /// Returns `None` if there is no annotation.
+    pub fn try_nhx(&mut self) -> Option<Result<Vec<(String, String)>, LexerError>> {
+        self.nhx.as_ref()?;
+        self.debug_assert_not_peeked();
+        if let Err(begin) = self.skip_ignorable() {
+            return Some(Err(self.unterminated_comment(begin)));
+        }
+
+        let pending = std::mem::take(self.nhx.as_mut().unwrap());
+        if pending.is_empty() {
+            return None;
+        }
+
+        let mut tags = Vec::new();
+        for (begin, end, text) in pending {
+            for tag in text[NHX_PREFIX.len()..]
+                .split(':')
+                .filter(|t| !t.is_empty())
+            {
+                match tag.split_once('=') {
+                    Some((key, value)) if !key.is_empty() => {
+                        tags.push((key.to_owned(), value.to_owned()))
+                    }
+                    _ => {
+                        return Some(Err(LexerError::InvalidNhx {
+                            offset: begin.offset,
+                            span: self.span(begin, end),
+                        }));
+                    }
+                }
+            }
+        }
+        Some(Ok(tags))
+    }
+
+    /// Drops NHX annotations that were skipped but not consumed by [`Lexer::try_nhx`],
+    /// e.g. since they do not follow a node
+    pub(crate) fn discard_nhx(&mut self) {
+        if let Some(pending) = self.nhx.as_mut() {
+            pending.clear();
+        }
+    }
+
+    /// Interprets each label as taxon name (even if it is numeric) and reports the label
+    /// assigned to it by `interner` instead.
+    pub fn intern_names(&mut self, interner: &'a mut LabelInterner) {
+        self.interner = Some(interner);
+    }
+
+    /// Invokes `on_progress` whenever at least `interval` bytes of the input were consumed
+    /// since its last invocation, and once the input or the tree ends. Since each node
+    /// begins with an opening parenthesis or a label, [`Progress::nodes`] is the number of
+    /// nodes lexed, which allows to show the progress of parsing very large trees.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let mut reports = Vec::new();
+    /// let mut on_progress = |progress: pace26io::progress::Progress| reports.push(progress);
+    ///
+    /// let mut lexer = Lexer::new("((1,2),(3,4));");
+    /// lexer.report_progress(5, &mut on_progress);
+    /// BinTreeBuilder::default()
+    ///     .parse_newick_from_lexer(&mut lexer, NodeIdx::new(4))
+    ///     .unwrap();
+    ///
+    /// drop(lexer);
+    /// let last = reports.last().unwrap();
+    /// assert_eq!((last.bytes, last.nodes), (14, 7));
+    /// assert!(reports.len() > 1);
+    /// ```
+    pub fn report_progress(&mut self, interval: usize, on_progress: &'a mut dyn FnMut(Progress)) {
+        self.progress = Some(ProgressReporter::new(interval, on_progress));
+    }
+
+    /// Reports each label (numeric, name, or quoted) as [`TokenType::RawLabel`] without
+    /// converting or interning it. Consumers can then hash or intern the text of labels, as
+    /// returned by [`Lexer::label_text`], without intermediate allocations. Takes precedence
+    /// over [`Lexer::intern_names`].
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::newick::*;
+    ///
+    /// let mut lexer = Lexer::new("(Homo_sapiens,'Pan ''troglodytes''');");
+    /// lexer.raw_labels();
+    ///
+    /// let labels: Vec<_> = lexer
+    ///     .by_ref()
+    ///     .map(Result::unwrap)
+    ///     .filter(|token| token.token_type == TokenType::RawLabel)
+    ///     .collect();
+    /// assert_eq!(lexer.label_text(&labels[0]).unwrap(), "Homo_sapiens");
+    /// assert_eq!(lexer.label_text(&labels[1]).unwrap(), "Pan 'troglodytes'");
+    /// ```
+    pub fn raw_labels(&mut self) {
+        self.raw_labels = true;
+    }
+
+    /// Returns the text of a [`TokenType::RawLabel`] token returned by this lexer, borrowed
+    /// from the input. Quotes of quoted labels are removed; only labels containing escaped
+    /// quotes (`''`) are copied. Returns `None` for other tokens or if the lexer was not
+    /// created by [`Lexer::new`]; for [`Lexer::from_bytes`], the label is the input within
+    /// [`Token::byte_range`].
+    pub fn label_text(&self, token: &Token) -> Option<Cow<'a, str>> {
+        self.label_slice(token).map(unquote_label)
+    }
+
+    /// Same as [`Lexer::label_text`], but returns the label as written in the input, i.e.
+    /// including the quotes and escapes of quoted labels
+    pub fn label_slice(&self, token: &Token) -> Option<&'a str> {
+        let Source::Text(text) = self.source else {
+            return None;
+        };
+        if token.token_type != TokenType::RawLabel {
+            return None;
         }
+        Some(&text[token.byte_range()])
+    }
+
+    /// Consumes a label without converting it and returns it as [`TokenType::RawLabel`]
+    fn try_raw_label(&mut self) -> Option<Result<Token, LexerError>> {
+        let begin = self.input.position;
+        let (_, first_char) = self.input.next_if(|&(_, c)| is_name_char(c) || c == '\'')?;
+
+        if first_char == '\'' {
+            loop {
+                match self.input.next() {
+                    None => {
+                        return Some(Err(LexerError::UnterminatedQuote {
+                            offset: begin.offset,
+                            span: self.span_from(begin),
+                        }));
+                    }
+                    Some((_, '\'')) if self.input.next_if(|&(_, c)| c == '\'').is_none() => break,
+                    _ => {}
+                }
+            }
+        } else {
+            while self.input.next_if(|&(_, c)| is_name_char(c)).is_some() {}
+        }
+
+        Some(Ok(self.token(begin, TokenType::RawLabel)))
+    }
+
+    /// Enables [`Lexer::try_inner_label`]
+    pub fn allow_inner_labels(&mut self) {
+        self.allow_inner_labels = true;
+    }
+
+    /// If inner labels are allowed, consumes the label following a closing parenthesis,
+    /// e.g. `bootstrap95` in `(1,2)bootstrap95`, and returns it. Fails with
+    /// [`LexerError::UnterminatedQuote`] if a quoted label is not closed.
+    pub fn try_inner_label(&mut self) -> Option<Result<String, LexerError>> {
+        self.debug_assert_not_peeked();
+        if !self.allow_inner_labels {
+            return None;
+        }
+
+        if let Some(quoted) = self.try_read_quoted() {
+            return Some(quoted);
+        }
+
+        let (_, first_char) = self.input.next_if(|&(_, c)| is_name_char(c))?;
+        let mut label = String::from(first_char);
+        while let Some((_, c)) = self.input.next_if(|&(_, c)| is_name_char(c)) {
+            label.push(c);
+        }
+        Some(Ok(label))
+    }
+
+    /// Returns `true` if only (allowed) whitespace remains
+    pub(crate) fn is_exhausted(&mut self) -> bool {
+        self.peeked.is_empty() && self.skip_ignorable().is_ok() && self.input.peek().is_none()
+    }
+
+    fn debug_assert_not_peeked(&self) {
+        debug_assert!(
+            self.peeked.is_empty(),
+            "character-level access while tokens are peeked"
+        );
+    }
+
+    /// Consumes whitespace at the current position and returns its span if the input ends
+    /// afterwards. Fails with [`LexerError::UnexpectedChar`] if further input follows the
+    /// whitespace. Does nothing if whitespace is allowed anyway.
+    pub(crate) fn skip_trailing_whitespace(&mut self) -> Result<Option<Span>, LexerError> {
+        self.debug_assert_not_peeked();
+        if self.allow_whitespace {
+            return Ok(None);
+        }
+
+        self.skip_line_terminator()?;
+
+        let begin = self.input.position;
+        let Some((_, character)) = self.input.next_if(|(_, c)| c.is_whitespace()) else {
+            return Ok(None);
+        };
+        let end = self.input.position.byte;
+        while self.input.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+
+        if self.input.peek().is_some() {
+            return Err(LexerError::UnexpectedChar {
+                character,
+                offset: begin.offset,
+                span: self.span(begin, end),
+            });
+        }
+        Ok(Some(self.span_from(begin)))
+    }
+
+    /// If enabled, consumes a line terminator `\n` or `\r\n`; fails with
+    /// [`LexerError::UnexpectedChar`] if further input follows it
+    fn skip_line_terminator(&mut self) -> Result<(), LexerError> {
+        if !self.allow_line_terminator {
+            return Ok(());
+        }
+
+        let begin = self.input.position;
+        let cr = self.input.next_if(|&(_, c)| c == '\r');
+        let lf = self.input.next_if(|&(_, c)| c == '\n');
+        match cr.or(lf) {
+            Some((_, character)) if self.input.peek().is_some() => {
+                Err(LexerError::UnexpectedChar {
+                    character,
+                    offset: begin.offset,
+                    span: self.span(begin, begin.byte + 1),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the offset (in characters) of the first character not consumed yet, e.g.,
+    /// the number of characters of all trees parsed so far. Since peeked tokens are already
+    /// consumed, this method may only be used if no tokens are peeked (see [`Lexer::peek_n`]).
+    pub fn offset(&mut self) -> usize {
+        self.debug_assert_not_peeked();
+        self.input.position.offset
+    }
+
+    /// Returns the input not consumed yet, starting at [`Lexer::offset`]. This allows to
+    /// parse several expressions with the same lexer and to hand the remaining input back
+    /// to an enclosing parser. Returns `None` unless the lexer was created by [`Lexer::new`].
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let mut builder = BinTreeBuilder::default();
+    /// let mut lexer = Lexer::new("(1,2);(2,1); end");
+    ///
+    /// let first = builder.parse_newick_from_lexer(&mut lexer, NodeIdx::new(3)).unwrap();
+    /// assert_eq!(lexer.offset(), 6);
+    /// let second = builder.parse_newick_from_lexer(&mut lexer, NodeIdx::new(3)).unwrap();
+    /// assert_eq!(lexer.remainder(), Some(" end"));
+    /// assert_eq!(second.top_down().to_newick_string(), "(2,1);");
+    /// ```
+    pub fn remainder(&mut self) -> Option<&'a str> {
+        self.debug_assert_not_peeked();
+        match self.source {
+            Source::Text(text) => Some(&text[self.input.position.byte..]),
+            _ => None,
+        }
+    }
+
+    fn try_parse_name(&mut self) -> Option<LabelValue> {
+        let interner = self.interner.as_mut()?;
+        let (_, first_char) = self.input.next_if(|&(_, c)| is_name_char(c))?;
+
+        let mut name = String::from(first_char);
+        while let Some((_, c)) = self.input.next_if(|&(_, c)| is_name_char(c)) {
+            name.push(c);
+        }
+
+        Some(interner.intern(&name).0)
+    }
+
+    /// Reads a single-quoted label, where two consecutive quotes encode a quote, e.g. `'don''t'`
+    fn try_read_quoted(&mut self) -> Option<Result<String, LexerError>> {
+        let begin = self.input.position;
+        self.input.next_if(|&(_, c)| c == '\'')?;
+
+        let mut name = String::new();
+        loop {
+            match self.input.next() {
+                None => {
+                    return Some(Err(LexerError::UnterminatedQuote {
+                        offset: begin.offset,
+                        span: self.span_from(begin),
+                    }));
+                }
+                Some((_, '\'')) => {
+                    if self.input.next_if(|&(_, c)| c == '\'').is_none() {
+                        return Some(Ok(name));
+                    }
+                    name.push('\'');
+                }
+                Some((_, c)) => name.push(c),
+            }
+        }
+    }
+
+    /// Quoted labels are interned if an interner is available; otherwise, they have to be numeric
+    fn try_parse_quoted(&mut self) -> Option<Result<Token, LexerError>> {
+        let begin = self.input.position;
+        let name = match self.try_read_quoted()? {
+            Ok(x) => x,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let token_type = match self.interner.as_mut() {
+            Some(interner) => TokenType::Name(interner.intern(&name).0),
+            None => match name.parse() {
+                Ok(number) => TokenType::Number(number),
+                Err(_) => {
+                    return Some(Err(LexerError::NameWithoutInterner {
+                        offset: begin.offset,
+                        span: self.span_from(begin),
+                    }));
+                }
+            },
+        };
+
+        Some(Ok(self.token(begin, token_type)))
     }
 
     pub fn allow_whitespaces(&mut self) {
         self.allow_whitespace = true;
     }
 
-    fn try_parse_number(&mut self) -> Option<(usize, u32)> {
+    /// Enables [`Lexer::try_branch_length`]; without it, a colon is an unexpected character
+    pub fn allow_branch_lengths(&mut self) {
+        self.allow_branch_lengths = true;
+    }
+
+    /// If branch lengths are allowed and the next non-whitespace character is a colon,
+    /// consumes the branch length `:<float>` following it and returns the length; otherwise,
+    /// nothing is consumed.
+    pub fn try_branch_length(&mut self) -> Option<Result<f64, LexerError>> {
+        self.try_branch_length_with_range()
+            .map(|(_, length)| length)
+    }
+
+    /// Same as [`Lexer::try_branch_length`], but also returns the byte range of the branch
+    /// length including the colon
+    pub(crate) fn try_branch_length_with_range(
+        &mut self,
+    ) -> Option<(Range<usize>, Result<f64, LexerError>)> {
+        self.debug_assert_not_peeked();
+        if !self.allow_branch_lengths {
+            return None;
+        }
+
+        if let Err(comment) = self.skip_ignorable() {
+            return Some(self.unterminated_comment_with_range(comment));
+        }
+
+        let begin = self.input.position;
+        self.input.next_if(|&(_, c)| c == ':')?;
+        if let Err(comment) = self.skip_ignorable() {
+            return Some(self.unterminated_comment_with_range(comment));
+        }
+
+        let mut text = String::new();
+        while let Some((_, c)) = self
+            .input
+            .next_if(|&(_, c)| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'))
+        {
+            text.push(c);
+        }
+
+        let length = match text.parse::<f64>() {
+            Ok(length) if length.is_finite() => Ok(length),
+            _ => Err(LexerError::InvalidBranchLength {
+                offset: begin.offset,
+                span: self.span_from(begin),
+            }),
+        };
+        Some((begin.byte..self.input.position.byte, length))
+    }
+
+    fn unterminated_comment_with_range<T>(
+        &self,
+        begin: Position,
+    ) -> (Range<usize>, Result<T, LexerError>) {
+        (
+            begin.byte..self.input.position.byte,
+            Err(self.unterminated_comment(begin)),
+        )
+    }
+
+    /// Reads a decimal label; fails with [`LexerError::NumberOverflow`] if it exceeds
+    /// the range of [`LabelValue`]
+    fn try_parse_number(&mut self) -> Option<Result<LabelValue, LexerError>> {
         if self.input.peek().is_none_or(|(_, c)| !c.is_ascii_digit()) {
             return None;
         }
 
-        let (offset, first_char) = self.input.next().unwrap();
-        let mut number = first_char.to_digit(10).unwrap();
+        let begin = self.input.position;
+        let (_, first_char) = self.input.next().unwrap();
+        let mut number = Some(first_char.to_digit(10).unwrap() as LabelValue);
 
         while let Some((_, c)) = self.input.next_if(|(_, c)| c.is_ascii_digit()) {
-            number = number * 10 + c.to_digit(10).unwrap();
+            number = number
+                .and_then(|n| n.checked_mul(10))
+                .and_then(|n| n.checked_add(c.to_digit(10).unwrap() as LabelValue));
         }
 
-        Some((offset, number))
+        Some(number.ok_or_else(|| LexerError::NumberOverflow {
+            offset: begin.offset,
+            span: self.span_from(begin),
+        }))
     }
 }
 
@@ -72,33 +1086,196 @@ impl<'a> Iterator for Lexer<'a> {
     type Item = Result<Token, LexerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // attempt to read a number
-        if let Some((offset, number)) = self.try_parse_number() {
-            return Some(Ok(Token {
-                token_type: TokenType::Number(number),
-                offset,
-            }));
+        if let Some(token) = self.peeked.pop_front() {
+            return Some(token);
+        }
+        self.lex_token()
+    }
+}
+
+impl Lexer<'_> {
+    /// Returns the next token without consuming it; see [`Lexer::peek_n`]
+    pub fn peek(&mut self) -> Option<&Result<Token, LexerError>> {
+        self.peek_n(0)
+    }
+
+    /// Returns the `k`-th next token (0-based, i.e. `peek_n(0)` is the next token) without
+    /// consuming any token. Returns `None` if the input ends before.
+    ///
+    /// Peeked tokens are already read from the input. Hence, methods operating on the
+    /// characters following the current token, e.g. [`Lexer::try_branch_length`] or
+    /// [`Lexer::try_inner_label`], may only be used once all peeked tokens are consumed.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::newick::*;
+    ///
+    /// let mut lexer = Lexer::new("(1,2);");
+    /// assert_eq!(lexer.peek_n(2).unwrap().as_ref().unwrap().token_type, TokenType::Comma);
+    /// assert_eq!(lexer.peek().unwrap().as_ref().unwrap().token_type, TokenType::ParOpen);
+    /// assert_eq!(lexer.next().unwrap().unwrap().token_type, TokenType::ParOpen);
+    /// assert_eq!(lexer.count(), 5);
+    /// ```
+    pub fn peek_n(&mut self, k: usize) -> Option<&Result<Token, LexerError>> {
+        while self.peeked.len() <= k {
+            let token = self.lex_token()?;
+            self.peeked.push_back(token);
+        }
+        self.peeked.get(k)
+    }
+
+    /// Reads the whole remaining input and returns all errors in order of their offsets,
+    /// instead of stopping at the first one. Branch lengths, inner labels and NHX annotations
+    /// are accepted where the parser accepts them (subject to the lexer's settings); the
+    /// nesting of parentheses is not checked. A run of consecutive unexpected characters,
+    /// e.g. an unquoted name without interner, is reported once by its first character. An
+    /// unterminated quote or comment swallows the rest of the input and is hence the last
+    /// error reported.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::newick::*;
+    ///
+    /// let mut lexer = Lexer::new("((1,2)#,3:,4);");
+    /// lexer.allow_branch_lengths();
+    /// let errors = lexer.scan_all();
+    /// assert_eq!(errors.len(), 2);
+    /// assert!(matches!(errors[0], LexerError::UnexpectedChar { character: '#', offset: 6, .. }));
+    /// assert!(matches!(errors[1], LexerError::InvalidBranchLength { offset: 9, .. }));
+    /// ```
+    pub fn scan_all(&mut self) -> Vec<LexerError> {
+        let mut errors = Vec::new();
+        let mut last_unexpected = None;
+        while let Some(token) = self.next() {
+            let token_type = match token {
+                Ok(token) => token.token_type,
+                Err(err) => {
+                    if let LexerError::UnexpectedChar { offset, .. } = err {
+                        let continues_run = last_unexpected == offset.checked_sub(1);
+                        last_unexpected = Some(offset);
+                        if continues_run {
+                            continue;
+                        }
+                    }
+                    errors.push(err);
+                    continue;
+                }
+            };
+
+            // the parser only reads suffixes once no tokens are peeked
+            if !self.peeked.is_empty() {
+                continue;
+            }
+
+            match token_type {
+                TokenType::ParClose => {
+                    if let Some(Err(err)) = self.try_inner_label() {
+                        errors.push(err);
+                        continue;
+                    }
+                }
+                TokenType::Number(_) | TokenType::Name(_) | TokenType::RawLabel => {}
+                _ => continue,
+            }
+
+            if let Some(Err(err)) = self.try_branch_length() {
+                errors.push(err);
+            }
+            if let Some(Err(err)) = self.try_nhx() {
+                errors.push(err);
+            }
+        }
+        errors
+    }
+
+    fn lex_token(&mut self) -> Option<Result<Token, LexerError>> {
+        let token = self.read_token();
+        if self.progress.is_some() {
+            self.update_progress(&token);
+        }
+        token
+    }
+
+    fn update_progress(&mut self, token: &Option<Result<Token, LexerError>>) {
+        let is_end = match token {
+            Some(Ok(token)) => match token.token_type {
+                TokenType::ParOpen
+                | TokenType::Number(_)
+                | TokenType::Name(_)
+                | TokenType::RawLabel => {
+                    self.num_nodes += 1;
+                    false
+                }
+                TokenType::Semicolon => true,
+                _ => false,
+            },
+            Some(Err(_)) => false,
+            None => true,
+        };
+
+        let progress = Progress {
+            bytes: self.input.position.byte,
+            nodes: self.num_nodes,
+            lines: 0,
+        };
+
+        let reporter = self.progress.as_mut().unwrap();
+        if is_end {
+            reporter.finish(progress);
+        } else {
+            reporter.update(progress);
+        }
+    }
+
+    fn read_token(&mut self) -> Option<Result<Token, LexerError>> {
+        if let Err(begin) = self.skip_ignorable() {
+            return Some(Err(self.unterminated_comment(begin)));
+        }
+
+        if let Err(err) = self.skip_line_terminator() {
+            return Some(Err(err));
+        }
+
+        let begin = self.input.position;
+        if self.raw_labels
+            && let Some(token) = self.try_raw_label()
+        {
+            return Some(token);
+        }
+
+        if let Some(token) = self.try_parse_quoted() {
+            return Some(token);
+        }
+
+        // attempt to read a name (if names are interned) or a number
+        if let Some(label) = self.try_parse_name() {
+            return Some(Ok(self.token(begin, TokenType::Name(label))));
+        }
+
+        if let Some(number) = self.try_parse_number() {
+            return Some(number.map(|number| self.token(begin, TokenType::Number(number))));
         }
 
         // otherwise try to match dedicated chars
-        let (offset, next_char) = self.input.next()?;
+        let (_, next_char) = self.input.next()?;
         let token_type = match next_char {
             '(' => TokenType::ParOpen,
             ')' => TokenType::ParClose,
             ',' => TokenType::Comma,
             ';' => TokenType::Semicolon,
             _ if self.allow_whitespace && next_char.is_whitespace() => {
-                return self.next();
+                return self.read_token();
             }
             _ => {
                 return Some(Err(LexerError::UnexpectedChar {
                     character: next_char,
-                    offset,
+                    offset: begin.offset,
+                    span: self.span_from(begin),
                 }));
             }
         };
 
-        Some(Ok(Token { token_type, offset }))
+        Some(Ok(self.token(begin, token_type)))
     }
 }
 
@@ -109,32 +1286,49 @@ mod test {
 
     use super::*;
 
-    macro_rules! token_at {
-        ($offset:expr, $token:expr) => {
-            Some(Ok(Token {
-                offset: $offset,
-                token_type: $token,
-            }))
+    macro_rules! assert_token {
+        ($next:expr, $offset:expr, $token:expr) => {
+            let next = $next.map(|t| t.map(|t| (t.offset, t.token_type)));
+            assert_eq!(next, Some(Ok(($offset, $token))));
         };
     }
 
+    #[test]
+    fn peek() {
+        let mut lexer = Lexer::new("(1,x");
+        assert_token!(lexer.peek().cloned(), 0, TokenType::ParOpen);
+        assert_token!(lexer.peek_n(1).cloned(), 1, TokenType::Number(1));
+        assert!(matches!(
+            lexer.peek_n(3),
+            Some(Err(LexerError::UnexpectedChar { character: 'x', .. }))
+        ));
+        assert!(lexer.peek_n(4).is_none());
+
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        assert_token!(lexer.peek().cloned(), 1, TokenType::Number(1));
+        assert_token!(lexer.next(), 1, TokenType::Number(1));
+        assert_token!(lexer.next(), 2, TokenType::Comma);
+        assert!(lexer.next().unwrap().is_err());
+        assert!(lexer.next().is_none());
+    }
+
     #[test]
     fn strict_correct() {
         let mut lexer = Lexer::new(")(10(;23,");
-        assert_eq!(lexer.next(), token_at!(0, TokenType::ParClose));
-        assert_eq!(lexer.next(), token_at!(1, TokenType::ParOpen));
-        assert_eq!(lexer.next(), token_at!(2, TokenType::Number(10)));
-        assert_eq!(lexer.next(), token_at!(4, TokenType::ParOpen));
-        assert_eq!(lexer.next(), token_at!(5, TokenType::Semicolon));
-        assert_eq!(lexer.next(), token_at!(6, TokenType::Number(23)));
-        assert_eq!(lexer.next(), token_at!(8, TokenType::Comma));
+        assert_token!(lexer.next(), 0, TokenType::ParClose);
+        assert_token!(lexer.next(), 1, TokenType::ParOpen);
+        assert_token!(lexer.next(), 2, TokenType::Number(10));
+        assert_token!(lexer.next(), 4, TokenType::ParOpen);
+        assert_token!(lexer.next(), 5, TokenType::Semicolon);
+        assert_token!(lexer.next(), 6, TokenType::Number(23));
+        assert_token!(lexer.next(), 8, TokenType::Comma);
     }
 
     #[test]
     fn strict_with_spaces() {
         let mut lexer = Lexer::new(")( 10(;23");
-        assert_eq!(lexer.next(), token_at!(0, TokenType::ParClose));
-        assert_eq!(lexer.next(), token_at!(1, TokenType::ParOpen));
+        assert_token!(lexer.next(), 0, TokenType::ParClose);
+        assert_token!(lexer.next(), 1, TokenType::ParOpen);
         assert!(lexer.next().unwrap().is_err());
     }
 
@@ -142,12 +1336,376 @@ mod test {
     fn nonstrict_with_spaces() {
         let mut lexer = Lexer::new(")( 10(;23");
         lexer.allow_whitespaces();
-        assert_eq!(lexer.next(), token_at!(0, TokenType::ParClose));
-        assert_eq!(lexer.next(), token_at!(1, TokenType::ParOpen));
-        assert_eq!(lexer.next(), token_at!(3, TokenType::Number(10)));
-        assert_eq!(lexer.next(), token_at!(5, TokenType::ParOpen));
-        assert_eq!(lexer.next(), token_at!(6, TokenType::Semicolon));
-        assert_eq!(lexer.next(), token_at!(7, TokenType::Number(23)));
+        assert_token!(lexer.next(), 0, TokenType::ParClose);
+        assert_token!(lexer.next(), 1, TokenType::ParOpen);
+        assert_token!(lexer.next(), 3, TokenType::Number(10));
+        assert_token!(lexer.next(), 5, TokenType::ParOpen);
+        assert_token!(lexer.next(), 6, TokenType::Semicolon);
+        assert_token!(lexer.next(), 7, TokenType::Number(23));
+    }
+
+    #[test]
+    fn interned_names() {
+        let mut interner = LabelInterner::default();
+        let mut lexer = Lexer::new("(Pan_troglodytes, 42x)ü;42x");
+        lexer.allow_whitespaces();
+        lexer.intern_names(&mut interner);
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        assert_token!(lexer.next(), 1, TokenType::Name(1));
+        assert_token!(lexer.next(), 16, TokenType::Comma);
+        assert_token!(lexer.next(), 18, TokenType::Name(2));
+        assert_token!(lexer.next(), 21, TokenType::ParClose);
+        assert_token!(lexer.next(), 22, TokenType::Name(3));
+        assert_token!(lexer.next(), 23, TokenType::Semicolon);
+        assert_token!(lexer.next(), 24, TokenType::Name(2));
+        assert_eq!(lexer.next(), None);
+
+        assert_eq!(interner.name(crate::binary_tree::Label(3)), Some("ü"));
+    }
+
+    #[test]
+    fn branch_lengths() {
+        let mut lexer = Lexer::new("1:0.5,:1e-3)");
+        lexer.allow_branch_lengths();
+        assert_eq!(lexer.try_branch_length(), None);
+        assert_token!(lexer.next(), 0, TokenType::Number(1));
+        assert_eq!(lexer.try_branch_length(), Some(Ok(0.5)));
+        assert_token!(lexer.next(), 5, TokenType::Comma);
+        assert_eq!(lexer.try_branch_length(), Some(Ok(1e-3)));
+        assert_token!(lexer.next(), 11, TokenType::ParClose);
+
+        let mut lexer = Lexer::new(":x");
+        lexer.allow_branch_lengths();
+        assert!(matches!(
+            lexer.try_branch_length(),
+            Some(Err(LexerError::InvalidBranchLength { offset: 0, .. }))
+        ));
+
+        let mut lexer = Lexer::new(" : 2 ,");
+        lexer.allow_whitespaces();
+        lexer.allow_branch_lengths();
+        assert_eq!(lexer.try_branch_length(), Some(Ok(2.0)));
+        assert_token!(lexer.next(), 5, TokenType::Comma);
+    }
+
+    #[test]
+    fn inner_labels() {
+        let mut lexer = Lexer::new(")b95:1,");
+        assert_token!(lexer.next(), 0, TokenType::ParClose);
+        assert_eq!(lexer.try_inner_label(), None);
+
+        lexer.allow_inner_labels();
+        lexer.allow_branch_lengths();
+        assert_eq!(lexer.try_inner_label(), Some(Ok("b95".into())));
+        assert_eq!(lexer.try_branch_length(), Some(Ok(1.0)));
+        assert_eq!(lexer.try_inner_label(), None);
+        assert_token!(lexer.next(), 6, TokenType::Comma);
+    }
+
+    #[test]
+    fn quoted_labels() {
+        let mut interner = LabelInterner::default();
+        let mut lexer = Lexer::new("('don''t','a b'),'';'x");
+        lexer.intern_names(&mut interner);
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        assert_token!(lexer.next(), 1, TokenType::Name(1));
+        assert_token!(lexer.next(), 9, TokenType::Comma);
+        assert_token!(lexer.next(), 10, TokenType::Name(2));
+        assert_token!(lexer.next(), 15, TokenType::ParClose);
+        assert_token!(lexer.next(), 16, TokenType::Comma);
+        assert_token!(lexer.next(), 17, TokenType::Name(3));
+        assert_token!(lexer.next(), 19, TokenType::Semicolon);
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError::UnterminatedQuote { offset: 20, .. }))
+        ));
+        assert_eq!(lexer.next(), None);
+
+        let names: Vec<_> = interner.iter().map(|(_, name)| name.to_owned()).collect();
+        assert_eq!(names, vec!["don't", "a b", ""]);
+
+        let mut lexer = Lexer::new("'12','x'");
+        assert_token!(lexer.next(), 0, TokenType::Number(12));
+        assert_token!(lexer.next(), 4, TokenType::Comma);
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError::NameWithoutInterner { offset: 5, .. }))
+        ));
+    }
+
+    #[test]
+    fn comments() {
+        let mut lexer = Lexer::new("([c]1[x]:[y]2,3)");
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError::UnexpectedChar {
+                character: '[',
+                offset: 1,
+                ..
+            }))
+        ));
+
+        let mut lexer = Lexer::new("([c]1[x]:[y]2,3)[");
+        lexer.skip_comments();
+        lexer.allow_branch_lengths();
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        assert_token!(lexer.next(), 4, TokenType::Number(1));
+        assert_eq!(lexer.try_branch_length(), Some(Ok(2.0)));
+        assert_token!(lexer.next(), 13, TokenType::Comma);
+        assert_token!(lexer.next(), 14, TokenType::Number(3));
+        assert_token!(lexer.next(), 15, TokenType::ParClose);
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError::UnterminatedComment { offset: 16, .. }))
+        ));
+
+        let mut comments = Vec::new();
+        let mut on_comment = |offset, text: &str| comments.push((offset, text.to_owned()));
+        let mut lexer = Lexer::new("[a] 1[&&NHX:S=b];");
+        lexer.allow_whitespaces();
+        lexer.report_comments(&mut on_comment);
+        assert_token!(lexer.next(), 4, TokenType::Number(1));
+        assert_token!(lexer.next(), 16, TokenType::Semicolon);
+        assert_eq!(lexer.next(), None);
+        assert_eq!(
+            comments,
+            vec![(0, "a".to_owned()), (5, "&&NHX:S=b".to_owned())]
+        );
+    }
+
+    #[test]
+    fn from_bytes() {
+        let text = "((1,23),\n 4);";
+        let mut lexer = Lexer::new(text);
+        lexer.allow_whitespaces();
+        let expected: Vec<_> = lexer.map(|t| t.unwrap()).collect();
+
+        let mut lexer = Lexer::from_bytes(text.as_bytes());
+        lexer.allow_whitespaces();
+        let tokens: Vec<_> = lexer.map(|t| t.unwrap()).collect();
+        assert_eq!(tokens, expected);
+
+        let mut interner = LabelInterner::default();
+        let mut lexer = Lexer::from_bytes(b"(caf\xe9,x)\xff");
+        lexer.intern_names(&mut interner);
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        assert_token!(lexer.next(), 1, TokenType::Name(1));
+        assert_token!(lexer.next(), 5, TokenType::Comma);
+        assert_token!(lexer.next(), 6, TokenType::Name(2));
+        assert_token!(lexer.next(), 7, TokenType::ParClose);
+        assert_token!(lexer.next(), 8, TokenType::Name(3));
+        assert_eq!(interner.name(crate::binary_tree::Label(1)), Some("café"));
+    }
+
+    #[test]
+    fn bom_and_crlf() {
+        let mut lexer = Lexer::new("\u{FEFF}(1,2);\r\n");
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError::UnexpectedChar {
+                character: '\u{FEFF}',
+                ..
+            }))
+        ));
+
+        let mut lexer = Lexer::new("\u{FEFF}(1,2);\r\n");
+        lexer.tolerate_bom_and_crlf();
+        let tokens: Vec<_> = lexer.map(|t| t.unwrap().token_type).collect();
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[0], TokenType::ParOpen);
+
+        let mut lexer = Lexer::from_bytes(b"\xEF\xBB\xBF(1,2);\n");
+        lexer.tolerate_bom_and_crlf();
+        assert_token!(lexer.next(), 3, TokenType::ParOpen);
+        assert!(lexer.all(|t| t.is_ok()));
+
+        // line terminators are only accepted at the end of the input
+        let mut lexer = Lexer::new("(1,\r\n2);");
+        lexer.tolerate_bom_and_crlf();
+        assert!(lexer.any(|t| t.is_err()));
+    }
+
+    #[test]
+    fn remainder() {
+        let mut lexer = Lexer::new("(ä,b) rest");
+        assert_eq!(lexer.offset(), 0);
+        assert_eq!(lexer.remainder(), Some("(ä,b) rest"));
+        let mut interner = LabelInterner::default();
+        lexer.intern_names(&mut interner);
+        assert_eq!(lexer.by_ref().take(5).count(), 5);
+        assert_eq!(lexer.offset(), 5);
+        assert_eq!(lexer.remainder(), Some(" rest"));
+        // an error for the space and the name `rest`
+        assert_eq!(lexer.by_ref().count(), 2);
+        assert_eq!(lexer.offset(), 10);
+        assert_eq!(lexer.remainder(), Some(""));
+
+        let mut lexer = Lexer::from_bytes(b"(1,2);");
+        lexer.next();
+        assert_eq!(lexer.offset(), 1);
+        assert_eq!(lexer.remainder(), None);
+    }
+
+    #[test]
+    fn scan_all() {
+        let mut lexer = Lexer::new("((1,2):0.5,3:1e-3);");
+        lexer.allow_branch_lengths();
+        assert_eq!(lexer.scan_all(), vec![]);
+
+        // branch lengths are rejected unless allowed
+        let mut lexer = Lexer::new("(1,2:0.5);");
+        assert!(matches!(
+            lexer.scan_all()[..],
+            [
+                LexerError::UnexpectedChar {
+                    character: ':',
+                    offset: 4,
+                    ..
+                },
+                ..
+            ]
+        ));
+
+        let mut lexer = Lexer::new("(a,\n(1,%)x:1)!;[open");
+        lexer.allow_branch_lengths();
+        let errors: Vec<_> = lexer
+            .scan_all()
+            .into_iter()
+            .map(|err| (err.span().line, err.span().column))
+            .collect();
+        // `a`, `\n`, `%`, `x:` (branch lengths are accepted after labels only), `!`, `[open`
+        assert_eq!(errors, [(0, 1), (0, 3), (1, 3), (1, 5), (1, 9), (1, 11)]);
+
+        let mut lexer = Lexer::new("(1,2)x:1[&&NHX:a=1];");
+        lexer.allow_inner_labels();
+        lexer.allow_branch_lengths();
+        lexer.parse_nhx();
+        assert_eq!(lexer.scan_all(), vec![]);
+
+        let mut lexer = Lexer::new("(1,2)[&&NHX:a]:1;");
+        lexer.parse_nhx();
+        lexer.allow_branch_lengths();
+        assert!(matches!(
+            lexer.scan_all()[..],
+            [LexerError::InvalidNhx { offset: 5, .. }]
+        ));
+    }
+
+    #[test]
+    fn raw_labels() {
+        let input = "(12,[c]äb:1.5,'x''y','z');";
+        let mut lexer = Lexer::new(input);
+        lexer.raw_labels();
+        lexer.skip_comments();
+        lexer.allow_branch_lengths();
+
+        let mut labels = Vec::new();
+        while let Some(token) = lexer.next() {
+            let token = token.unwrap();
+            if token.token_type == TokenType::RawLabel {
+                labels.push(lexer.label_text(&token).unwrap());
+                lexer.try_branch_length();
+            } else {
+                assert_eq!(lexer.label_text(&token), None);
+            }
+        }
+        assert_eq!(labels, ["12", "äb", "x'y", "z"]);
+        assert!(matches!(labels[1], Cow::Borrowed(_)));
+        assert!(matches!(labels[2], Cow::Owned(_)));
+
+        let mut lexer = Lexer::new("('ab");
+        lexer.raw_labels();
+        lexer.next();
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError::UnterminatedQuote { offset: 1, .. }))
+        ));
+
+        let mut lexer = Lexer::from_bytes(b"abc;");
+        lexer.raw_labels();
+        let token = lexer.next().unwrap().unwrap();
+        assert_eq!(token.byte_range(), 0..3);
+        assert_eq!(lexer.label_text(&token), None);
+    }
+
+    #[test]
+    fn lines_located_on_demand() {
+        let text = "(1,\n2);\n(3,\n4);";
+        let mut lexers = [Lexer::new(text), Lexer::from_bytes(text.as_bytes())];
+        for lexer in &mut lexers {
+            lexer.tolerate_bom_and_crlf();
+            lexer.allow_whitespaces();
+            let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+            assert_eq!(tokens.len(), 12);
+            assert_eq!(lexer.line_starts.borrow().len(), 1);
+
+            let span = lexer.token_span(&tokens[10]);
+            assert_eq!((span.line, span.column, span.byte_range), (3, 1, 13..14));
+            assert_eq!(lexer.line_starts.borrow().len(), 4);
+
+            // earlier spans do not scan again
+            let span = lexer.token_span(&tokens[1]);
+            assert_eq!((span.line, span.column), (0, 1));
+            assert_eq!(lexer.line_starts.borrow().len(), 4);
+        }
+    }
+
+    #[test]
+    fn spans() {
+        let mut lexer = Lexer::new("(1,\n  äb);");
+        lexer.allow_whitespaces();
+        let mut interner = LabelInterner::default();
+        lexer.intern_names(&mut interner);
+
+        let tokens: Vec<_> = lexer.by_ref().map(Result::unwrap).collect();
+        let spans: Vec<_> = tokens.iter().map(|t| lexer.token_span(t)).collect();
+        let expected = [
+            (0, 0, 0..1),
+            (0, 1, 1..2),
+            (0, 2, 2..3),
+            (1, 2, 6..9),
+            (1, 4, 9..10),
+        ];
+        assert_eq!(spans.len(), 6);
+        for (span, (line, column, byte_range)) in spans.iter().zip(expected) {
+            assert_eq!(
+                span,
+                &Span {
+                    line,
+                    column,
+                    byte_range
+                }
+            );
+        }
+        assert_eq!(spans[5].to_string(), "line 2, column 6");
+
+        let mut lexer = Lexer::new("(1,\n#)");
+        lexer.allow_whitespaces();
+        let err = lexer.nth(3).unwrap().unwrap_err();
+        assert_eq!(err.span().byte_range, 4..5);
+        assert_eq!(
+            err.to_string(),
+            "unexpected character # at line 2, column 1"
+        );
+    }
+
+    #[test]
+    fn number_overflow() {
+        let max = LabelValue::MAX;
+        let text = format!("({max},1)");
+        let mut lexer = Lexer::new(&text);
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        assert_token!(lexer.next(), 1, TokenType::Number(max));
+
+        let too_large = format!("{}0", max / 10 + 1);
+        let text = format!("({too_large},1)");
+        let mut lexer = Lexer::new(&text);
+        assert_token!(lexer.next(), 0, TokenType::ParOpen);
+        let err = lexer.next().unwrap().unwrap_err();
+        assert!(matches!(err, LexerError::NumberOverflow { offset: 1, .. }));
+        assert_eq!(err.span().byte_range, 1..too_large.len() + 1);
+        assert_token!(lexer.next(), too_large.len() + 1, TokenType::Comma);
     }
 
     #[test]
@@ -161,14 +1719,18 @@ mod test {
             if rng.random_bool(0.5) {
                 expected.push(Token {
                     offset: text.len(),
+                    byte_offset: text.len(),
+                    byte_len: 1,
                     token_type: TokenType::ParOpen,
                 });
                 text.push('(');
             }
 
-            let rand_num = rng.random_range(0..u32::MAX);
+            let rand_num = rng.random_range(0..LabelValue::MAX);
             expected.push(Token {
                 offset: text.len(),
+                byte_offset: text.len(),
+                byte_len: rand_num.to_string().len(),
                 token_type: TokenType::Number(rand_num),
             });
             text.push_str(format!("{rand_num}").as_str());
@@ -176,6 +1738,8 @@ mod test {
             if rng.random_bool(0.5) {
                 expected.push(Token {
                     offset: text.len(),
+                    byte_offset: text.len(),
+                    byte_len: 1,
                     token_type: TokenType::ParClose,
                 });
                 text.push(')');
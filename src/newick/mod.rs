@@ -1,7 +1,31 @@
+pub mod balanced_parentheses;
 pub mod binary_tree_parser;
 pub mod binary_tree_writer;
-mod lexer;
+pub mod borrowed_bin_tree;
+pub mod enewick_writer;
+pub mod events;
+pub mod highlight;
+pub mod label_interner;
+pub(crate) mod lexer;
+#[cfg(feature = "rayon")]
+pub mod parallel_parser;
+#[cfg(feature = "rayon")]
+pub mod parallel_writer;
+pub mod stats;
+pub mod streaming_writer;
 pub mod writer;
 
+pub use balanced_parentheses::parse_newick_to_bp;
 pub use binary_tree_parser::*;
+pub use borrowed_bin_tree::parse_newick_borrowed;
+pub use enewick_writer::{
+    ENewickWriterError, to_enewick_string, write_enewick, write_enewick_styled,
+};
+pub use label_interner::LabelInterner;
+pub use lexer::{Lexer, LexerError, Span, Token, TokenType};
+#[cfg(feature = "rayon")]
+pub use parallel_parser::*;
+#[cfg(feature = "rayon")]
+pub use parallel_writer::*;
+pub use streaming_writer::*;
 pub use writer::*;
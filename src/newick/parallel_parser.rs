@@ -0,0 +1,141 @@
+use super::{BinaryTreeParser, NewickParserOptions, ParserError};
+use crate::{
+    binary_tree::{NodeIdx, TreeBuilder},
+    cancellation::{CancellationToken, Cancelled},
+};
+use rayon::prelude::*;
+
+/// Parses the Newick strings `lines` in parallel, where the `i`-th string receives the
+/// root id `root_id(i)`. The results are returned in the order of `lines`, independently of
+/// the scheduling. Each worker thread uses its own default-constructed builder, so `B` must
+/// not keep state shared between trees, and its own options obtained from `options`, e.g.
+/// [`NewickParserOptions::default`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*, pace::numbering::NodeNumbering};
+///
+/// let lines = ["((1,2),3);", "(1,(2,3));", "(1,2,3);"];
+/// let numbering = NodeNumbering::new(3);
+/// let trees = parse_newick_batch_par::<IndexedBinTreeBuilder>(
+///     &lines,
+///     |i| numbering.root_id(i),
+///     NewickParserOptions::default,
+/// );
+///
+/// assert_eq!(trees[1].as_ref().unwrap().node_idx(), numbering.root_id(1));
+/// assert!(trees[2].is_err());
+/// ```
+pub fn parse_newick_batch_par<B>(
+    lines: &[impl AsRef<str> + Sync],
+    root_id: impl Fn(usize) -> NodeIdx + Sync,
+    options: impl Fn() -> NewickParserOptions<'static> + Sync,
+) -> Vec<Result<B::Node, ParserError>>
+where
+    B: TreeBuilder + Default,
+    B::Node: Send,
+{
+    parse_newick_batch_par_cancellable::<B>(lines, root_id, options, &CancellationToken::new())
+        .expect("token is never cancelled")
+}
+
+/// Same as [`parse_newick_batch_par`], but checks `token` before parsing each string and
+/// returns [`Cancelled`] once it is cancelled; strings that are already being parsed are
+/// finished first.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, cancellation::*, newick::*};
+///
+/// let lines = vec!["((1,2),3);"; 1000];
+/// let token = CancellationToken::new();
+/// let parse = |token: &CancellationToken| {
+///     parse_newick_batch_par_cancellable::<BinTreeBuilder>(
+///         &lines,
+///         |_| NodeIdx::new(4),
+///         NewickParserOptions::default,
+///         token,
+///     )
+/// };
+/// assert_eq!(parse(&token).unwrap().len(), 1000);
+///
+/// token.cancel();
+/// assert_eq!(parse(&token).unwrap_err(), Cancelled);
+/// ```
+pub fn parse_newick_batch_par_cancellable<B>(
+    lines: &[impl AsRef<str> + Sync],
+    root_id: impl Fn(usize) -> NodeIdx + Sync,
+    options: impl Fn() -> NewickParserOptions<'static> + Sync,
+    token: &CancellationToken,
+) -> Result<Vec<Result<B::Node, ParserError>>, Cancelled>
+where
+    B: TreeBuilder + Default,
+    B::Node: Send,
+{
+    lines
+        .par_iter()
+        .enumerate()
+        .map_init(
+            || (B::default(), options()),
+            |(builder, options), (i, line)| {
+                (!token.is_cancelled())
+                    .then(|| builder.parse_newick_from_str_with(line.as_ref(), root_id(i), options))
+            },
+        )
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Cancelled)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binary_tree::*, newick::*};
+
+    #[test]
+    fn same_as_sequential() {
+        let lines: Vec<String> = (0..500)
+            .map(|i| match i % 4 {
+                0 => "((1,2),3);".to_owned(),
+                1 => format!("({i},(2,3));"),
+                2 => "(1,(2,3)".to_owned(),
+                _ => "(((4,2),(7,1)),8);".to_owned(),
+            })
+            .collect();
+
+        let root_id = |i: usize| NodeIdx::new(1000 + 10 * i as u32);
+        let parallel = parse_newick_batch_par::<IndexedBinTreeBuilder>(
+            &lines,
+            root_id,
+            NewickParserOptions::default,
+        );
+
+        let mut builder = IndexedBinTreeBuilder::default();
+        assert_eq!(parallel.len(), lines.len());
+        for (i, (line, tree)) in lines.iter().zip(parallel).enumerate() {
+            let expected = builder.parse_newick_from_str(line, root_id(i));
+            assert_eq!(tree, expected);
+        }
+    }
+
+    #[test]
+    fn cancelled_while_parsing() {
+        let lines = vec!["(((4,2),(7,1)),8);"; 100_000];
+        let token = CancellationToken::new();
+        let parsed = std::sync::atomic::AtomicUsize::new(0);
+
+        // cancel from within the workers once some trees were parsed
+        let result = parse_newick_batch_par_cancellable::<BinTreeBuilder>(
+            &lines,
+            |i| {
+                if parsed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) == 100 {
+                    token.cancel();
+                }
+                NodeIdx::new(10 * i as u32)
+            },
+            NewickParserOptions::default,
+            &token,
+        );
+        assert_eq!(result.unwrap_err(), Cancelled);
+        assert!(parsed.into_inner() < lines.len());
+    }
+}
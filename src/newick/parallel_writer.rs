@@ -0,0 +1,87 @@
+use super::{NewickStyle, NewickWriter};
+use rayon::prelude::*;
+use std::io::Write;
+
+/// Serializes all trees into independent buffers in parallel and writes them
+/// in their original order, each terminated by a semicolon and a newline.
+/// This corresponds to the tree lines of a PACE instance.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let trees = vec![
+///     builder.parse_newick_from_str("(1,(2,3));", NodeIdx::new(0)).unwrap(),
+///     builder.parse_newick_from_str("((1,2),3);", NodeIdx::new(0)).unwrap(),
+/// ];
+///
+/// let mut buffer: Vec<u8> = Vec::new();
+/// write_trees_parallel(&trees, &mut buffer).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "(1,(2,3));\n((1,2),3);\n");
+/// ```
+pub fn write_trees_parallel<T>(trees: &[T], writer: &mut impl Write) -> std::io::Result<()>
+where
+    T: Sync,
+    for<'a> &'a T: NewickWriter,
+{
+    write_trees_parallel_styled(trees, &NewickStyle::COMPACT, writer)
+}
+
+/// Same as [`write_trees_parallel`], but emits whitespace according to `style`; each tree is
+/// followed by exactly one newline regardless of [`NewickStyle::trailing_newline`]
+pub fn write_trees_parallel_styled<T>(
+    trees: &[T],
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> std::io::Result<()>
+where
+    T: Sync,
+    for<'a> &'a T: NewickWriter,
+{
+    let style = NewickStyle {
+        trailing_newline: true,
+        ..*style
+    };
+    let buffers = trees
+        .par_iter()
+        .map(|tree| {
+            let mut buffer: Vec<u8> = Vec::new();
+            tree.write_newick_styled(&style, &mut buffer)?;
+            Ok(buffer)
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    for buffer in buffers {
+        writer.write_all(&buffer)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binary_tree::*, newick::*};
+
+    #[test]
+    fn same_as_sequential() {
+        let mut builder = IndexedBinTreeBuilder::default();
+        let trees: Vec<_> = ["1;", "(1,2);", "(((4,2),(7,1)),8);", "(1,(5,91234));"]
+            .into_iter()
+            .cycle()
+            .take(100)
+            .map(|s| builder.parse_newick_from_str(s, NodeIdx::new(0)).unwrap())
+            .collect();
+
+        let mut expected = String::new();
+        for tree in &trees {
+            expected.push_str(&tree.top_down().to_newick_string());
+            expected.push('\n');
+        }
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write_trees_parallel(&trees, &mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+    }
+}
@@ -0,0 +1,93 @@
+use super::{
+    binary_tree_parser::ParserError,
+    events::{NewickEventHandler, parse_newick_events},
+};
+use crate::binary_tree::Label;
+
+/// Summary of a Newick tree computed by [`NewickStats::scan`] without constructing the tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NewickStats {
+    pub num_leaves: usize,
+    pub num_inner: usize,
+
+    /// Maximum number of edges on a path from the root to a leaf
+    pub depth: usize,
+
+    /// Smallest and largest leaf label
+    pub min_label: Option<Label>,
+    pub max_label: Option<Label>,
+}
+
+impl NewickStats {
+    /// Scans the tree in `text` (strict Newick format) in a single pass with memory
+    /// independent of its size.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::Label, newick::stats::*};
+    ///
+    /// let stats = NewickStats::scan("((3,(1,7)),2);").unwrap();
+    /// assert_eq!((stats.num_leaves, stats.num_inner, stats.depth), (4, 3, 3));
+    /// assert_eq!((stats.min_label, stats.max_label), (Some(Label(1)), Some(Label(7))));
+    /// ```
+    pub fn scan(text: &str) -> Result<Self, ParserError> {
+        let mut scanner = Scanner::default();
+        parse_newick_events(text, &mut scanner)?;
+        Ok(scanner.stats)
+    }
+}
+
+#[derive(Default)]
+struct Scanner {
+    stats: NewickStats,
+    current_depth: usize,
+}
+
+impl NewickEventHandler for Scanner {
+    fn on_open(&mut self) {
+        self.stats.num_inner += 1;
+        self.current_depth += 1;
+    }
+
+    fn on_leaf(&mut self, label: Label) {
+        let stats = &mut self.stats;
+        stats.num_leaves += 1;
+        stats.depth = stats.depth.max(self.current_depth);
+        stats.min_label = Some(stats.min_label.map_or(label, |l| l.min(label)));
+        stats.max_label = Some(stats.max_label.map_or(label, |l| l.max(label)));
+    }
+
+    fn on_close(&mut self) {
+        self.current_depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scan() {
+        assert_eq!(
+            NewickStats::scan("5;").unwrap(),
+            NewickStats {
+                num_leaves: 1,
+                num_inner: 0,
+                depth: 0,
+                min_label: Some(Label(5)),
+                max_label: Some(Label(5)),
+            }
+        );
+
+        let caterpillar =
+            "(".repeat(999) + "0" + &(1..1000).map(|i| format!(",{i})")).collect::<String>() + ";";
+        let stats = NewickStats::scan(&caterpillar).unwrap();
+        assert_eq!(
+            (stats.num_leaves, stats.num_inner, stats.depth),
+            (1000, 999, 999)
+        );
+        assert_eq!(stats.max_label, Some(Label(999)));
+
+        assert!(NewickStats::scan("(1,2)").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use super::NewickStyle;
+use crate::binary_tree::{NodeType, TopDownCursor};
+use std::io::{self, BufWriter, Write};
+
+/// Capacity of the buffer [`write_newick_streaming`] places in front of the writer
+pub const STREAMING_BUFFER_CAPACITY: usize = 1 << 16;
+
+enum Step<C> {
+    Visit(C),
+    Comma,
+    Close,
+}
+
+/// Writes the tree below `root` in Newick format (followed by a semicolon) directly into
+/// `writer` without materializing the output in memory. The writer is wrapped into a
+/// [`BufWriter`], so passing a [`std::fs::File`] or socket is efficient, and the traversal uses
+/// an explicit stack rather than recursion, so arbitrarily deep trees (e.g. caterpillars with
+/// millions of leaves) do not overflow the call stack. The output is identical to
+/// [`NewickWriter::write_newick`](super::NewickWriter::write_newick).
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let tree: BinTree = "((1,2),(3,4));".parse().unwrap();
+///
+/// let mut buffer = Vec::new();
+/// write_newick_streaming(tree.top_down(), &mut buffer).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "((1,2),(3,4));");
+/// ```
+pub fn write_newick_streaming<C: TopDownCursor>(root: C, writer: impl Write) -> io::Result<()> {
+    write_newick_streaming_styled(root, &NewickStyle::COMPACT, writer)
+}
+
+/// Same as [`write_newick_streaming`], but emits whitespace according to `style`
+pub fn write_newick_streaming_styled<C: TopDownCursor>(
+    root: C,
+    style: &NewickStyle,
+    writer: impl Write,
+) -> io::Result<()> {
+    let mut writer = BufWriter::with_capacity(STREAMING_BUFFER_CAPACITY, writer);
+    write_streaming_inner(root, style, &mut writer)?;
+    style.write_end(&mut writer)?;
+    writer.flush()
+}
+
+/// Writes all `trees` as in [`write_newick_streaming`], each followed by a newline, sharing a
+/// single buffer. This corresponds to the tree lines of a PACE instance.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let trees: Vec<BinTree> = vec!["(1,(2,3));".parse().unwrap(), "3;".parse().unwrap()];
+///
+/// let mut buffer = Vec::new();
+/// write_trees_streaming(trees.iter().map(|t| t.top_down()), &mut buffer).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "(1,(2,3));\n3;\n");
+/// ```
+pub fn write_trees_streaming<C: TopDownCursor>(
+    trees: impl IntoIterator<Item = C>,
+    writer: impl Write,
+) -> io::Result<()> {
+    write_trees_streaming_styled(trees, &NewickStyle::COMPACT, writer)
+}
+
+/// Same as [`write_trees_streaming`], but emits whitespace according to `style`; each tree is
+/// followed by exactly one newline regardless of [`NewickStyle::trailing_newline`]
+pub fn write_trees_streaming_styled<C: TopDownCursor>(
+    trees: impl IntoIterator<Item = C>,
+    style: &NewickStyle,
+    writer: impl Write,
+) -> io::Result<()> {
+    let mut writer = BufWriter::with_capacity(STREAMING_BUFFER_CAPACITY, writer);
+    for root in trees {
+        write_streaming_inner(root, style, &mut writer)?;
+        writer.write_all(b";\n")?;
+    }
+    writer.flush()
+}
+
+fn write_streaming_inner<C: TopDownCursor>(
+    root: C,
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let mut stack = vec![Step::Visit(root)];
+    while let Some(step) = stack.pop() {
+        match step {
+            Step::Visit(node) => match node.visit() {
+                NodeType::Inner(left, right) => {
+                    style.write_open(writer)?;
+                    stack.push(Step::Close);
+                    stack.push(Step::Visit(right));
+                    stack.push(Step::Comma);
+                    stack.push(Step::Visit(left));
+                }
+                NodeType::Leaf(label) => write!(writer, "{}", label.0)?,
+            },
+            Step::Comma => style.write_comma(writer)?,
+            Step::Close => style.write_close(writer)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binary_tree::*, newick::NewickWriter};
+
+    #[test]
+    fn same_as_recursive() {
+        for text in ["1;", "(1,2);", "(((4,2),(7,1)),8);", "(1,(5,(91234,3)));"] {
+            let tree: IndexedBinTree = text.parse().unwrap();
+            let mut buffer = Vec::new();
+            write_newick_streaming(tree.top_down(), &mut buffer).unwrap();
+            assert_eq!(String::from_utf8(buffer).unwrap(), text);
+            assert_eq!(tree.top_down().to_newick_string(), text);
+        }
+    }
+
+    #[test]
+    fn deep_caterpillar() {
+        // node u < N is inner with children (u + 1, N + 1 + u); nodes u >= N are leaves
+        const N: LabelValue = 1_000_000;
+        let children_of = |&u: &LabelValue| (u < N).then(|| (u + 1, N + 1 + u));
+        let label_of = |&u: &LabelValue| (u >= N).then(|| Label(u - N + 1));
+        let cursor = FnCursor::new(0, &children_of, &label_of);
+
+        let mut buffer = Vec::new();
+        write_newick_streaming(cursor, &mut buffer).unwrap();
+
+        assert_eq!(buffer.iter().filter(|&&c| c == b'(').count(), N as usize);
+        assert!(buffer.starts_with(b"(((("));
+        assert!(buffer.ends_with(b",3),2);"));
+        assert!(String::from_utf8(buffer).unwrap().contains("(1,1000001)"));
+    }
+}
@@ -1,4 +1,79 @@
-use std::io::Write;
+use super::LabelInterner;
+use crate::binary_tree::{Label, NodeType, TopDownCursor, TreeWithNodeIdx};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+};
+
+/// Whitespace emitted by the Newick writers. The default produces the minimal representation
+/// `((1,2),3);`, which is required by the PACE format; the Newick parser only accepts the other
+/// styles with [`NewickParserOptions::whitespace`](super::NewickParserOptions::whitespace).
+///
+/// Writers producing one tree per line (e.g. [`Forest`](crate::forest::Forest) or
+/// [`InstanceWriter`](crate::pace::writer::InstanceWriter)) terminate each line themselves and
+/// ignore [`NewickStyle::trailing_newline`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let tree: BinTree = "((1,2),3);".parse().unwrap();
+/// let style = NewickStyle {
+///     space_after_comma: true,
+///     spaces_around_parentheses: true,
+///     trailing_newline: true,
+/// };
+///
+/// let mut buffer = Vec::new();
+/// tree.top_down().write_newick_styled(&style, &mut buffer).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "( ( 1, 2 ), 3 );\n");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NewickStyle {
+    /// Writes `(1, 2)` instead of `(1,2)`
+    pub space_after_comma: bool,
+
+    /// Writes `( 1,2 )` instead of `(1,2)`
+    pub spaces_around_parentheses: bool,
+
+    /// Writes a newline after the finishing semicolon
+    pub trailing_newline: bool,
+}
+
+impl NewickStyle {
+    /// The minimal representation without any whitespace; same as [`NewickStyle::default`]
+    pub const COMPACT: Self = Self {
+        space_after_comma: false,
+        spaces_around_parentheses: false,
+        trailing_newline: false,
+    };
+
+    pub(crate) fn write_open(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(if self.spaces_around_parentheses {
+            b"( "
+        } else {
+            b"("
+        })
+    }
+
+    pub(crate) fn write_comma(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(if self.space_after_comma { b", " } else { b"," })
+    }
+
+    pub(crate) fn write_close(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(if self.spaces_around_parentheses {
+            b" )"
+        } else {
+            b")"
+        })
+    }
+
+    /// Writes the finishing semicolon and, if requested, the newline
+    pub(crate) fn write_end(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(if self.trailing_newline { b";\n" } else { b";" })
+    }
+}
 
 pub trait NewickWriter {
     /// Produces minimal Newick representation of a binary without any whitespace characters
@@ -17,8 +92,25 @@ pub trait NewickWriter {
     /// assert_eq!(String::from_utf8(buffer).unwrap(), "(1,2);");
     /// ```
     fn write_newick(&self, writer: &mut impl Write) -> std::io::Result<()> {
-        self.write_newick_inner(writer)?;
-        write!(writer, ";")
+        self.write_newick_styled(&NewickStyle::COMPACT, writer)
+    }
+
+    /// Same as [NewickWriter::write_newick], but emits whitespace according to `style`
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let tree: BinTree = "((1,2),3);".parse().unwrap();
+    /// let style = NewickStyle { space_after_comma: true, ..Default::default() };
+    ///
+    /// let mut buffer : Vec<u8> = Vec::new();
+    /// tree.top_down().write_newick_styled(&style, &mut buffer).unwrap();
+    /// assert_eq!(String::from_utf8(buffer).unwrap(), "((1, 2), 3);");
+    /// ```
+    fn write_newick_styled(&self, style: &NewickStyle, writer: &mut impl Write) -> io::Result<()> {
+        self.write_newick_inner_styled(style, writer)?;
+        style.write_end(writer)
     }
 
     /// Produces a Newick string representation of self by calling [NewickWriter::write_newick]
@@ -41,7 +133,664 @@ pub trait NewickWriter {
         String::from_utf8(buffer).expect("The writer should not produce invalid strings")
     }
 
+    /// Same as [NewickWriter::write_newick], but writes into a [`fmt::Write`], e.g. a `String`
+    /// or a [`fmt::Formatter`], without an intermediate byte buffer
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    ///
+    /// let tree: BinTree = "((1,2),3);".parse().unwrap();
+    ///
+    /// let mut text = String::from("tree: ");
+    /// tree.top_down().write_newick_fmt(&mut text).unwrap();
+    /// assert_eq!(text, "tree: ((1,2),3);");
+    /// assert_eq!(format!("{tree}"), "((1,2),3);");
+    /// ```
+    fn write_newick_fmt(&self, writer: &mut impl fmt::Write) -> fmt::Result {
+        self.write_newick(&mut FmtAdapter(writer))
+            .map_err(|_| fmt::Error)
+    }
+
+    /// Same as [NewickWriter::write_newick], but writes each leaf as the taxon name assigned to
+    /// its label by `names`, e.g. a [`LabelInterner`] filled while parsing or a map from labels
+    /// to names; see [`LabelFormatter`] for the available mappings. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if a label has no name.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*};
+    /// use std::collections::HashMap;
+    ///
+    /// let tree: BinTree = "((1,2),3);".parse().unwrap();
+    /// let names = HashMap::from([(Label(1), "Pan"), (Label(2), "Homo sapiens"), (Label(3), "Gorilla")]);
+    ///
+    /// let mut buffer : Vec<u8> = Vec::new();
+    /// tree.top_down().write_newick_named(&names, &mut buffer).unwrap();
+    /// assert_eq!(String::from_utf8(buffer).unwrap(), "((Pan,'Homo sapiens'),Gorilla);");
+    ///
+    /// let names = vec!["a", "b"]; // names of labels 1 and 2, but not 3
+    /// assert!(tree.top_down().to_newick_string_named(&names).is_err());
+    /// ```
+    fn write_newick_named(
+        &self,
+        names: &impl LabelFormatter,
+        writer: &mut impl Write,
+    ) -> io::Result<()>
+    where
+        Self: TopDownCursor + Copy,
+    {
+        write_newick_formatted(*self, names, &(), writer)
+    }
+
+    /// Produces a Newick string by calling [NewickWriter::write_newick_named]
+    fn to_newick_string_named(&self, names: &impl LabelFormatter) -> io::Result<String>
+    where
+        Self: TopDownCursor + Copy,
+    {
+        let mut buffer: Vec<u8> = Vec::new();
+        self.write_newick_named(names, &mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("The writer should not produce invalid strings"))
+    }
+
     /// Produces minimal Newick representation of a binary without any whitespace characters
     /// Same as [NewickWriter::write_newick], but omits the finishing semicolon.
-    fn write_newick_inner(&self, writer: &mut impl Write) -> std::io::Result<()>;
+    fn write_newick_inner(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        self.write_newick_inner_styled(&NewickStyle::COMPACT, writer)
+    }
+
+    /// Same as [NewickWriter::write_newick_styled], but omits the finishing semicolon (and the
+    /// trailing newline).
+    fn write_newick_inner_styled(
+        &self,
+        style: &NewickStyle,
+        writer: &mut impl Write,
+    ) -> std::io::Result<()>;
+}
+
+/// Forwards the output of the Newick writers to a [`fmt::Write`]; this relies on the writers
+/// passing complete UTF-8 sequences to each call of [`Write::write`]
+struct FmtAdapter<'a, W: fmt::Write>(&'a mut W);
+
+impl<W: fmt::Write> Write for FmtAdapter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text =
+            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.0.write_str(text).map_err(io::Error::other)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the label of a leaf; see [`write_newick_formatted`]
+pub trait LabelFormatter {
+    fn write_label(&self, label: Label, writer: &mut impl Write) -> io::Result<()>;
+}
+
+/// Writes leaves as numeric PACE labels, as [`NewickWriter`] does
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NumericLabels;
+
+impl LabelFormatter for NumericLabels {
+    fn write_label(&self, label: Label, writer: &mut impl Write) -> io::Result<()> {
+        write!(writer, "{}", label.0)
+    }
+}
+
+/// Writes each leaf as the name assigned to its label, quoted if necessary (e.g.
+/// `'Homo sapiens'`). Fails with [`io::ErrorKind::InvalidInput`] if a label has no name.
+impl LabelFormatter for LabelInterner {
+    fn write_label(&self, label: Label, writer: &mut impl Write) -> io::Result<()> {
+        write_name(self.name(label), label, writer)
+    }
+}
+
+/// Writes each leaf as the name stored for its label, quoted if necessary. Fails with
+/// [`io::ErrorKind::InvalidInput`] if a label has no name.
+impl<S: AsRef<str>> LabelFormatter for HashMap<Label, S> {
+    fn write_label(&self, label: Label, writer: &mut impl Write) -> io::Result<()> {
+        write_name(self.get(&label).map(AsRef::as_ref), label, writer)
+    }
+}
+
+/// Writes each leaf with label `i` as the name at position `i - 1`, quoted if necessary (i.e.
+/// the names of labels `1..=n` in order). Fails with [`io::ErrorKind::InvalidInput`] if a
+/// label is out of range.
+impl<S: AsRef<str>> LabelFormatter for Vec<S> {
+    fn write_label(&self, label: Label, writer: &mut impl Write) -> io::Result<()> {
+        let name = (label.0 as usize)
+            .checked_sub(1)
+            .and_then(|i| self.get(i))
+            .map(AsRef::as_ref);
+        write_name(name, label, writer)
+    }
+}
+
+fn write_name(name: Option<&str>, label: Label, writer: &mut impl Write) -> io::Result<()> {
+    let name = name.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("label {} has no name", label.0),
+        )
+    })?;
+
+    let needs_quotes = name.is_empty()
+        || name.chars().any(|c| {
+            c.is_whitespace() || matches!(c, '(' | ')' | ',' | ';' | ':' | '[' | ']' | '\'' | '"')
+        });
+    if needs_quotes {
+        write!(writer, "'{}'", name.replace('\'', "''"))
+    } else {
+        write!(writer, "{name}")
+    }
+}
+
+/// Writes the annotation of the edge above a node, e.g. a branch length `:0.5`, directly after
+/// the node; see [`write_newick_formatted`]. The edge above the root is annotated as well.
+pub trait EdgeFormatter<C> {
+    fn write_edge(&self, node: &C, writer: &mut impl Write) -> io::Result<()>;
+}
+
+/// Writes no edge annotations
+impl<C> EdgeFormatter<C> for () {
+    fn write_edge(&self, _node: &C, _writer: &mut impl Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the branch length `:<length>` returned for a node, if any
+impl<C, F: Fn(&C) -> Option<f64>> EdgeFormatter<C> for F {
+    fn write_edge(&self, node: &C, writer: &mut impl Write) -> io::Result<()> {
+        match self(node) {
+            Some(length) => write!(writer, ":{length}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes the internal label of an inner node, e.g. a node index or support value, directly
+/// after its closing parenthesis and before the edge annotation; see
+/// [`write_newick_annotated`]. Leaves are written by [`LabelFormatter`] instead.
+pub trait InnerLabelFormatter<C> {
+    fn write_inner_label(&self, node: &C, writer: &mut impl Write) -> io::Result<()>;
+}
+
+/// Writes no internal labels
+impl<C> InnerLabelFormatter<C> for () {
+    fn write_inner_label(&self, _node: &C, _writer: &mut impl Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the [`NodeIdx`](crate::binary_tree::NodeIdx) of each inner node as internal label,
+/// e.g. `((1,2)8,3)7;`, which shows the node indices assigned by the builder. Such trees can
+/// be read again with
+/// [`NewickParserOptions::inner_labels`](super::NewickParserOptions::inner_labels) enabled.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let tree = IndexedBinTreeBuilder::default()
+///     .parse_newick_from_str("((1,2),3);", NodeIdx::new(4))
+///     .unwrap();
+///
+/// let mut buffer = Vec::new();
+/// write_newick_annotated(tree.top_down(), &NumericLabels, &InnerNodeIndices, &(), &mut buffer)
+///     .unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "((1,2)5,3)4;");
+/// assert_eq!(tree.to_newick_string_with_indices(), "((1,2)5,3)4;");
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InnerNodeIndices;
+
+impl<T: TreeWithNodeIdx> InnerLabelFormatter<&T> for InnerNodeIndices
+where
+    for<'a> &'a T: TopDownCursor,
+{
+    fn write_inner_label(&self, node: &&T, writer: &mut impl Write) -> io::Result<()> {
+        write!(writer, "{}", node.node_idx().0)
+    }
+}
+
+/// Writes the tree below `root` in Newick format, where leaves are written by `labels` and
+/// each node is followed by the annotation written by `edges`. This allows to write trees
+/// whose names or branch lengths are stored outside of the tree, e.g. indexed by
+/// [`TreeWithNodeIdx::node_idx`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+/// use std::collections::HashMap;
+///
+/// let mut interner = LabelInterner::default();
+/// let mut options = NewickParserOptions {
+///     labels: LabelKind::Names(&mut interner),
+///     ..Default::default()
+/// };
+/// let tree = IndexedBinTreeBuilder::default()
+///     .parse_newick_from_str_with("((Pan,Homo),Gorilla);", NodeIdx::new(4), &mut options)
+///     .unwrap();
+///
+/// let lengths = HashMap::from([(NodeIdx::new(5), 0.5), (NodeIdx::new(2), 1.25)]);
+/// let branch_length = |node: &&IndexedBinTree| lengths.get(&node.node_idx()).copied();
+///
+/// let mut buffer = Vec::new();
+/// write_newick_formatted(tree.top_down(), &NumericLabels, &(), &mut buffer).unwrap();
+/// write_newick_formatted(tree.top_down(), &interner, &branch_length, &mut buffer).unwrap();
+/// assert_eq!(
+///     String::from_utf8(buffer).unwrap(),
+///     "((1,2),3);((Pan,Homo:1.25):0.5,Gorilla);"
+/// );
+/// ```
+pub fn write_newick_formatted<C: TopDownCursor>(
+    root: C,
+    labels: &impl LabelFormatter,
+    edges: &impl EdgeFormatter<C>,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_newick_annotated(root, labels, &(), edges, writer)
+}
+
+/// Same as [`write_newick_formatted`], but emits whitespace according to `style`
+pub fn write_newick_formatted_styled<C: TopDownCursor>(
+    root: C,
+    labels: &impl LabelFormatter,
+    edges: &impl EdgeFormatter<C>,
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_newick_annotated_styled(root, labels, &(), edges, style, writer)
+}
+
+/// Same as [`write_newick_formatted`], but additionally writes the internal label of each
+/// inner node by `inner_labels`, followed by its edge annotation, e.g. `((1,2)5:0.5,3)4;`.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let tree = IndexedBinTreeBuilder::default()
+///     .parse_newick_from_str("((1,2),3);", NodeIdx::new(4))
+///     .unwrap();
+/// let branch_length = |node: &&IndexedBinTree| (node.node_idx() == NodeIdx::new(5)).then_some(0.5);
+///
+/// let mut buffer = Vec::new();
+/// write_newick_annotated(
+///     tree.top_down(),
+///     &NumericLabels,
+///     &InnerNodeIndices,
+///     &branch_length,
+///     &mut buffer,
+/// )
+/// .unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "((1,2)5:0.5,3)4;");
+/// ```
+pub fn write_newick_annotated<C: TopDownCursor>(
+    root: C,
+    labels: &impl LabelFormatter,
+    inner_labels: &impl InnerLabelFormatter<C>,
+    edges: &impl EdgeFormatter<C>,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_newick_annotated_styled(
+        root,
+        labels,
+        inner_labels,
+        edges,
+        &NewickStyle::COMPACT,
+        writer,
+    )
+}
+
+/// Same as [`write_newick_annotated`], but emits whitespace according to `style`
+pub fn write_newick_annotated_styled<C: TopDownCursor>(
+    root: C,
+    labels: &impl LabelFormatter,
+    inner_labels: &impl InnerLabelFormatter<C>,
+    edges: &impl EdgeFormatter<C>,
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_formatted_inner(root, labels, inner_labels, edges, style, writer)?;
+    style.write_end(writer)
+}
+
+fn write_formatted_inner<C: TopDownCursor>(
+    node: C,
+    labels: &impl LabelFormatter,
+    inner_labels: &impl InnerLabelFormatter<C>,
+    edges: &impl EdgeFormatter<C>,
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    match node.visit() {
+        NodeType::Inner(left, right) => {
+            style.write_open(writer)?;
+            write_formatted_inner(left, labels, inner_labels, edges, style, writer)?;
+            style.write_comma(writer)?;
+            write_formatted_inner(right, labels, inner_labels, edges, style, writer)?;
+            style.write_close(writer)?;
+            inner_labels.write_inner_label(&node, writer)?;
+        }
+        NodeType::Leaf(label) => labels.write_label(label, writer)?,
+    }
+    edges.write_edge(&node, writer)
+}
+
+/// Writes the tree below `root` in Newick format. If `names` is provided, each leaf is written
+/// as the name assigned to its label, quoted if necessary (e.g. `'Homo sapiens'`); otherwise,
+/// as numeric PACE label. Fails with [`io::ErrorKind::InvalidInput`] if a label has no name.
+/// See [`write_newick_formatted`] for further customization.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let mut interner = LabelInterner::default();
+/// let mut options = NewickParserOptions {
+///     labels: LabelKind::Names(&mut interner),
+///     ..Default::default()
+/// };
+/// let tree = BinTreeBuilder::default()
+///     .parse_newick_from_str_with("((Pan,'Homo sapiens'),Gorilla);", NodeIdx::new(0), &mut options)
+///     .unwrap();
+///
+/// let mut buffer = Vec::new();
+/// write_newick_with_names(tree.top_down(), None, &mut buffer).unwrap();
+/// write_newick_with_names(tree.top_down(), Some(&interner), &mut buffer).unwrap();
+/// assert_eq!(String::from_utf8(buffer).unwrap(), "((1,2),3);((Pan,'Homo sapiens'),Gorilla);");
+/// ```
+pub fn write_newick_with_names<C: TopDownCursor>(
+    root: C,
+    names: Option<&LabelInterner>,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    match names {
+        Some(names) => write_newick_formatted(root, names, &(), writer),
+        None => write_newick_formatted(root, &NumericLabels, &(), writer),
+    }
+}
+
+/// Writes the tree below `root` in Newick format, where the children of each inner node are
+/// ordered such that the left subtree contains the smallest leaf label (as in the `normalize`
+/// example). Hence, two trees that only differ in the order of children produce byte-identical
+/// output, which is useful for diffing and deduplication.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*};
+///
+/// let a: BinTree = "((4,(3,1)),2);".parse().unwrap();
+/// let b: BinTree = "(2,((1,3),4));".parse().unwrap();
+///
+/// let mut buffer_a = Vec::new();
+/// let mut buffer_b = Vec::new();
+/// write_newick_canonical(a.top_down(), &mut buffer_a).unwrap();
+/// write_newick_canonical(b.top_down(), &mut buffer_b).unwrap();
+/// assert_eq!(buffer_a, buffer_b);
+/// assert_eq!(String::from_utf8(buffer_a).unwrap(), "(((1,3),4),2);");
+/// ```
+pub fn write_newick_canonical<C: TopDownCursor>(
+    root: C,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    write_newick_canonical_styled(root, &NewickStyle::COMPACT, writer)
+}
+
+/// Same as [`write_newick_canonical`], but emits whitespace according to `style`
+pub fn write_newick_canonical_styled<C: TopDownCursor>(
+    root: C,
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    let mut nodes = Vec::new();
+    let root = collect_canonical(root, &mut nodes);
+    write_canonical_inner(&nodes, root, style, writer)?;
+    style.write_end(writer)
+}
+
+/// Node of the tree copied by [`write_newick_canonical`] with the smallest label below it
+struct CanonicalNode {
+    min_label: Label,
+    children: Option<(usize, usize)>,
+}
+
+fn collect_canonical<C: TopDownCursor>(node: C, nodes: &mut Vec<CanonicalNode>) -> usize {
+    let canonical = match node.visit() {
+        NodeType::Inner(left, right) => {
+            let left = collect_canonical(left, nodes);
+            let right = collect_canonical(right, nodes);
+            let (left, right) = if nodes[left].min_label <= nodes[right].min_label {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            CanonicalNode {
+                min_label: nodes[left].min_label,
+                children: Some((left, right)),
+            }
+        }
+        NodeType::Leaf(label) => CanonicalNode {
+            min_label: label,
+            children: None,
+        },
+    };
+    nodes.push(canonical);
+    nodes.len() - 1
+}
+
+fn write_canonical_inner(
+    nodes: &[CanonicalNode],
+    node: usize,
+    style: &NewickStyle,
+    writer: &mut impl Write,
+) -> io::Result<()> {
+    match nodes[node].children {
+        Some((left, right)) => {
+            style.write_open(writer)?;
+            write_canonical_inner(nodes, left, style, writer)?;
+            style.write_comma(writer)?;
+            write_canonical_inner(nodes, right, style, writer)?;
+            style.write_close(writer)
+        }
+        None => write!(writer, "{}", nodes[node].min_label.0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        binary_tree::*,
+        newick::{BinaryTreeParser, LabelKind, NewickParserOptions},
+    };
+
+    #[test]
+    fn names_roundtrip() {
+        let mut interner = LabelInterner::default();
+        let mut builder = BinTreeBuilder::default();
+        let text = "(('it''s',Pan),('a b',''));";
+        let tree = builder
+            .parse_newick_from_str_with(
+                text,
+                NodeIdx::new(0),
+                &mut NewickParserOptions {
+                    labels: LabelKind::Names(&mut interner),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        write_newick_with_names(tree.top_down(), Some(&interner), &mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap(), text);
+
+        let err = write_newick_with_names(
+            tree.top_down(),
+            Some(&LabelInterner::default()),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn styles_agree_across_writers() {
+        let tree: IndexedBinTree = "(((4,2),(7,1)),8);".parse().unwrap();
+        let style = NewickStyle {
+            space_after_comma: true,
+            spaces_around_parentheses: true,
+            trailing_newline: false,
+        };
+
+        let mut plain = Vec::new();
+        tree.top_down()
+            .write_newick_styled(&style, &mut plain)
+            .unwrap();
+        let plain = String::from_utf8(plain).unwrap();
+        assert_eq!(plain, "( ( ( 4, 2 ), ( 7, 1 ) ), 8 );");
+
+        let mut formatted = Vec::new();
+        write_newick_formatted_styled(tree.top_down(), &NumericLabels, &(), &style, &mut formatted)
+            .unwrap();
+        assert_eq!(String::from_utf8(formatted).unwrap(), plain);
+
+        let mut streamed = Vec::new();
+        crate::newick::write_newick_streaming_styled(tree.top_down(), &style, &mut streamed)
+            .unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), plain);
+
+        let mut canonical = Vec::new();
+        write_newick_canonical_styled(tree.top_down(), &style, &mut canonical).unwrap();
+        assert_eq!(
+            String::from_utf8(canonical).unwrap(),
+            "( ( ( 1, 7 ), ( 2, 4 ) ), 8 );"
+        );
+
+        let parsed = IndexedBinTreeBuilder::default()
+            .parse_newick_from_str_with(
+                &plain,
+                NodeIdx::new(6),
+                &mut NewickParserOptions {
+                    whitespace: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(parsed, tree);
+    }
+
+    #[test]
+    fn inner_node_indices_roundtrip() {
+        let tree: IndexedBinTree = "(((4,2),(7,1)),8);".parse().unwrap();
+        let text = tree.to_newick_string_with_indices();
+        assert_eq!(text, "(((4,2)8,(7,1)9)7,8)6;");
+
+        let mut builder = IndexedBinTreeBuilder::default();
+        let parsed = builder
+            .parse_newick_from_str_with(
+                &text,
+                NodeIdx::new(6),
+                &mut NewickParserOptions {
+                    inner_labels: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(parsed, tree);
+        assert_eq!(
+            IndexedBinTree::Leaf(Label(3)).to_newick_string_with_indices(),
+            "3;"
+        );
+
+        let branch_length = |node: &&IndexedBinTree| Some(node.node_idx().0 as f64 / 2.0);
+        let mut buffer = Vec::new();
+        write_newick_annotated(
+            &tree,
+            &NumericLabels,
+            &InnerNodeIndices,
+            &branch_length,
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "(((4:2,2:1)8:4,(7:3.5,1:0.5)9:4.5)7:3.5,8:4)6:3;"
+        );
+    }
+
+    #[test]
+    fn canonical_ignores_child_order() {
+        let canonical = |text: &str| {
+            let tree: IndexedBinTree = text.parse().unwrap();
+            let mut buffer = Vec::new();
+            write_newick_canonical(tree.top_down(), &mut buffer).unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+
+        assert_eq!(canonical("5;"), "5;");
+        for text in [
+            "(((4,2),(7,1)),8);",
+            "(8,((1,7),(2,4)));",
+            "(((7,1),(4,2)),8);",
+        ] {
+            assert_eq!(canonical(text), "(((1,7),(2,4)),8);");
+        }
+        assert_ne!(canonical("((1,2),3);"), canonical("((1,3),2);"));
+    }
+
+    #[test]
+    fn name_maps() {
+        let tree: BinTree = "((3,1),2);".parse().unwrap();
+        let names = vec!["x y", "Pan", "Homo"];
+        assert_eq!(
+            tree.top_down().to_newick_string_named(&names).unwrap(),
+            "((Homo,'x y'),Pan);"
+        );
+
+        let map: HashMap<Label, String> = (1..=3).map(|l| (Label(l), format!("t{l}"))).collect();
+        assert_eq!(
+            tree.top_down().to_newick_string_named(&map).unwrap(),
+            "((t3,t1),t2);"
+        );
+
+        let leaf = BinTree::Leaf(Label(0));
+        let err = leaf.top_down().to_newick_string_named(&names).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn display_matches_writer() {
+        for text in ["7;", "(1,2);", "(((4,2),(7,1)),8);"] {
+            let bin: BinTree = text.parse().unwrap();
+            let indexed: IndexedBinTree = text.parse().unwrap();
+            assert_eq!(bin.to_string(), text);
+            assert_eq!(format!("{indexed}"), text);
+            assert_eq!(indexed.top_down().to_newick_string(), text);
+        }
+    }
+
+    #[test]
+    fn branch_lengths_roundtrip() {
+        let tree: IndexedBinTree = "(((4,2),(7,1)),8);".parse().unwrap();
+        let length = |node: &&IndexedBinTree| Some(node.node_idx().0 as f64 / 4.0);
+
+        let mut buffer = Vec::new();
+        write_newick_formatted(tree.top_down(), &NumericLabels, &length, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text, "(((4:1,2:0.5):2,(7:1.75,1:0.25):2.25):1.75,8:2):1.5;");
+
+        let mut options = NewickParserOptions {
+            branch_lengths: true,
+            ..Default::default()
+        };
+        let parsed = IndexedBinTreeBuilder::default()
+            .parse_newick_from_str_with(&text, NodeIdx::new(6), &mut options)
+            .unwrap();
+        assert_eq!(parsed, tree);
+    }
 }
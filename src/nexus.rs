@@ -0,0 +1,427 @@
+//! Reading and writing the TREES block of NEXUS files, in which much published phylogenetic
+//! data is distributed. Other blocks (e.g. TAXA or CHARACTERS) are skipped when reading.
+use crate::{
+    binary_tree::{Label, LabelValue, NodeIdx, TopDownCursor, TreeBuilder},
+    newick::{BinaryTreeParser, LabelFormatter, LabelInterner, Lexer, NewickWriter, ParserError},
+};
+use std::io::{self, BufRead, Write};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum NexusError {
+    #[error("input does not start with #NEXUS")]
+    MissingHeader,
+
+    #[error("input contains no TREES block")]
+    NoTreesBlock,
+
+    #[error("unterminated comment")]
+    UnterminatedComment,
+
+    #[error("unterminated quoted word")]
+    UnterminatedQuote,
+
+    #[error("invalid TRANSLATE entry {0:?}")]
+    InvalidTranslate(String),
+
+    #[error("invalid TREE command {0:?}")]
+    InvalidTree(String),
+
+    #[error("tree {index}: {source}")]
+    Newick {
+        index: usize,
+        #[source]
+        source: ParserError,
+    },
+
+    #[error(transparent)]
+    IO(#[from] io::Error),
+}
+
+/// Trees of the TREES blocks of a NEXUS file; see [`parse_nexus_trees`]
+#[derive(Debug, Clone)]
+pub struct NexusTrees<N> {
+    /// Names given by the `TREE <name> = ...` commands
+    pub tree_names: Vec<String>,
+    pub trees: Vec<N>,
+
+    /// Taxon names of the leaf labels, unless all leaves are numeric and no TRANSLATE
+    /// command is given
+    pub label_names: Option<LabelInterner>,
+}
+
+/// Reads all trees of the TREES blocks of a NEXUS file, where the `i`-th tree receives the
+/// root id `root_id(i)`. Comments (including rooting hints such as `[&R]`) and branch lengths
+/// are ignored. Leaves are labelled as follows:
+///  - With a `TRANSLATE` command, the `i`-th entry receives label `i`; leaves not listed
+///    receive the next free labels.
+///  - Otherwise, if all leaves are integers, they are used as labels (as in PACE instances).
+///  - Otherwise, leaf names receive labels `1, 2, ...` in order of their first appearance.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*, nexus::*};
+///
+/// let input = "#NEXUS
+/// BEGIN TREES;
+///     TRANSLATE
+///         1 Gorilla,
+///         2 'Homo sapiens',
+///         3 Pan;
+///     TREE first = [&R] ((2,3),1);
+///     TREE second = (1,(2:0.5,3:0.5));
+/// END;";
+///
+/// let nexus = parse_nexus_trees(input, &mut BinTreeBuilder::default(), |_| NodeIdx::new(4)).unwrap();
+/// assert_eq!(nexus.tree_names, ["first", "second"]);
+/// assert_eq!(nexus.trees[0].top_down().to_newick_string(), "((2,3),1);");
+/// assert_eq!(nexus.label_names.unwrap().name(Label(2)), Some("Homo sapiens"));
+/// ```
+pub fn parse_nexus_trees<B: TreeBuilder>(
+    text: &str,
+    builder: &mut B,
+    root_id: impl Fn(usize) -> NodeIdx,
+) -> Result<NexusTrees<B::Node>, NexusError> {
+    let text = text.trim_start_matches('\u{FEFF}').trim_start();
+    if !text
+        .get(..6)
+        .is_some_and(|header| header.eq_ignore_ascii_case("#NEXUS"))
+    {
+        return Err(NexusError::MissingHeader);
+    }
+
+    let mut translate: Vec<(String, String)> = Vec::new();
+    let mut commands_of_trees: Vec<(String, String)> = Vec::new();
+    let mut in_trees_block = false;
+    let mut found_trees_block = false;
+
+    for command in split_commands(&text[6..])? {
+        let (keyword, rest) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((&command, ""));
+
+        match keyword.to_ascii_uppercase().as_str() {
+            "BEGIN" => {
+                in_trees_block = rest.trim().eq_ignore_ascii_case("TREES");
+                found_trees_block |= in_trees_block;
+            }
+            "END" | "ENDBLOCK" => in_trees_block = false,
+            "TRANSLATE" if in_trees_block => {
+                for entry in split_outside_quotes(rest, ',') {
+                    translate.push(parse_translate_entry(entry)?);
+                }
+            }
+            "TREE" | "UTREE" if in_trees_block => {
+                let rest = rest.trim_start();
+                let rest = rest.strip_prefix('*').unwrap_or(rest);
+                let parts = split_outside_quotes(rest, '=');
+                let (name, newick) = parts.split_first().expect("splitting yields a part");
+                if newick.is_empty() {
+                    return Err(NexusError::InvalidTree(command.clone()));
+                }
+                commands_of_trees.push((unquote(name.trim()), format!("{};", newick.join("="))));
+            }
+            _ => {}
+        }
+    }
+
+    if !found_trees_block {
+        return Err(NexusError::NoTreesBlock);
+    }
+
+    // labels are assigned by interning the words of the leaves unless they are all numeric
+    let mut keys = LabelInterner::default();
+    for (key, _) in &translate {
+        keys.intern(key);
+    }
+    let numeric = translate.is_empty()
+        && commands_of_trees
+            .iter()
+            .all(|(_, newick)| has_numeric_leaves(newick));
+
+    let mut tree_names = Vec::with_capacity(commands_of_trees.len());
+    let mut trees = Vec::with_capacity(commands_of_trees.len());
+    for (index, (name, newick)) in commands_of_trees.into_iter().enumerate() {
+        let mut lexer = Lexer::new(&newick);
+        lexer.allow_whitespaces();
+        lexer.allow_branch_lengths();
+        if !numeric {
+            lexer.intern_names(&mut keys);
+        }
+
+        let tree = builder
+            .parse_newick_from_lexer(&mut lexer, root_id(index))
+            .map_err(|source| NexusError::Newick { index, source })?;
+        tree_names.push(name);
+        trees.push(tree);
+    }
+
+    let label_names = (!numeric).then(|| {
+        if translate.is_empty() {
+            return keys;
+        }
+
+        let mut names = LabelInterner::default();
+        for (_, name) in &translate {
+            names.intern(name);
+        }
+        for label in translate.len() + 1..=keys.len() {
+            names.intern(keys.name(Label(label as LabelValue)).unwrap());
+        }
+        names
+    });
+
+    Ok(NexusTrees {
+        tree_names,
+        trees,
+        label_names,
+    })
+}
+
+/// Same as [`parse_nexus_trees`], but reads the whole input from `reader` first
+pub fn read_nexus_trees<B: TreeBuilder>(
+    mut reader: impl BufRead,
+    builder: &mut B,
+    root_id: impl Fn(usize) -> NodeIdx,
+) -> Result<NexusTrees<B::Node>, NexusError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    parse_nexus_trees(&text, builder, root_id)
+}
+
+/// Writes a NEXUS file with a single TREES block containing `trees`, named `tree1, tree2, ...`.
+/// If `names` is provided, a `TRANSLATE` command maps each label to its name (quoted if
+/// necessary), so the file can be read back by [`parse_nexus_trees`] with the same labels.
+/// For an [`Instance`](crate::pace::simplified::Instance), pass its trees and
+/// [`label_names`](crate::pace::simplified::Instance::label_names).
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, nexus::*};
+///
+/// let trees: Vec<BinTree> = vec!["((1,2),3);".parse().unwrap(), "(1,(2,3));".parse().unwrap()];
+/// let mut buffer = Vec::new();
+/// write_nexus(&trees, None, &mut buffer).unwrap();
+///
+/// let text = String::from_utf8(buffer).unwrap();
+/// assert!(text.contains("\tTREE tree2 = (1,(2,3));\n"));
+///
+/// let nexus = parse_nexus_trees(&text, &mut BinTreeBuilder::default(), |_| NodeIdx::new(4)).unwrap();
+/// assert_eq!(nexus.trees, trees);
+/// ```
+pub fn write_nexus<T>(
+    trees: &[T],
+    names: Option<&LabelInterner>,
+    writer: &mut impl Write,
+) -> io::Result<()>
+where
+    for<'a> &'a T: TopDownCursor,
+{
+    writeln!(writer, "#NEXUS")?;
+    writeln!(writer, "BEGIN TREES;")?;
+
+    if let Some(names) = names.filter(|names| !names.is_empty()) {
+        writeln!(writer, "\tTRANSLATE")?;
+        for label in 1..=names.len() {
+            let label = Label(label as LabelValue);
+            write!(writer, "\t\t{} ", label.0)?;
+            names.write_label(label, writer)?;
+            writeln!(
+                writer,
+                "{}",
+                if label.0 as usize == names.len() {
+                    ";"
+                } else {
+                    ","
+                }
+            )?;
+        }
+    }
+
+    for (index, tree) in trees.iter().enumerate() {
+        write!(writer, "\tTREE tree{} = ", index + 1)?;
+        tree.write_newick(writer)?;
+        writeln!(writer)?;
+    }
+
+    writeln!(writer, "END;")
+}
+
+/// Removes comments `[...]` and splits the remaining text at semicolons outside of quotes
+/// into trimmed, non-empty commands
+fn split_commands(text: &str) -> Result<Vec<String>, NexusError> {
+    let mut commands = Vec::new();
+    let mut command = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next().ok_or(NexusError::UnterminatedComment)? {
+                        '[' => depth += 1,
+                        ']' => depth -= 1,
+                        _ => {}
+                    }
+                }
+            }
+            '\'' => {
+                command.push(c);
+                loop {
+                    let c = chars.next().ok_or(NexusError::UnterminatedQuote)?;
+                    command.push(c);
+                    // an escaped quote `''` is read as two quoted words
+                    if c == '\'' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                let trimmed = command.trim();
+                if !trimmed.is_empty() {
+                    commands.push(trimmed.to_owned());
+                }
+                command.clear();
+            }
+            _ => command.push(c),
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Splits `text` at each `separator` that is not quoted
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut begin = 0;
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        if c == '\'' {
+            quoted = !quoted;
+        } else if c == separator && !quoted {
+            parts.push(&text[begin..i]);
+            begin = i + 1;
+        }
+    }
+    parts.push(&text[begin..]);
+    parts
+}
+
+fn unquote(word: &str) -> String {
+    match word.strip_prefix('\'').and_then(|w| w.strip_suffix('\'')) {
+        Some(quoted) => quoted.replace("''", "'"),
+        None => word.to_owned(),
+    }
+}
+
+fn parse_translate_entry(entry: &str) -> Result<(String, String), NexusError> {
+    let entry = entry.trim();
+    match entry.split_once(char::is_whitespace) {
+        Some((key, name)) if !name.trim().is_empty() => Ok((unquote(key), unquote(name.trim()))),
+        _ => Err(NexusError::InvalidTranslate(entry.to_owned())),
+    }
+}
+
+/// Returns `true` if each leaf of the (syntactically valid) tree is an integer label
+fn has_numeric_leaves(newick: &str) -> bool {
+    let mut lexer = Lexer::new(newick);
+    lexer.allow_whitespaces();
+    lexer.allow_branch_lengths();
+    lexer.raw_labels();
+
+    let mut numeric = true;
+    while let Some(token) = lexer.next() {
+        let Ok(token) = token else {
+            // the error is reported when parsing the tree
+            continue;
+        };
+        if let Some(text) = lexer.label_text(&token) {
+            numeric &= text.parse::<LabelValue>().is_ok();
+            lexer.try_branch_length();
+        }
+    }
+    numeric
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binary_tree::*;
+
+    fn parse(text: &str) -> Result<NexusTrees<BinTree>, NexusError> {
+        parse_nexus_trees(text, &mut BinTreeBuilder::default(), |_| NodeIdx::new(0))
+    }
+
+    #[test]
+    fn names_without_translate() {
+        let input = "#nexus\n[generated]\nbegin taxa; dimensions ntax=3; end;\n\
+            begin trees;\n tree 't 1' = ((Pan,'Homo sapiens'):1,Gorilla);\n\
+            utree * u = (Gorilla,(Pan,'Homo sapiens'));\nend;";
+        let nexus = parse(input).unwrap();
+        assert_eq!(nexus.tree_names, ["t 1", "u"]);
+        assert_eq!(nexus.trees[1].top_down().to_newick_string(), "(3,(1,2));");
+
+        let names = nexus.label_names.unwrap();
+        assert_eq!(names.name(Label(2)), Some("Homo sapiens"));
+        assert_eq!(names.len(), 3);
+    }
+
+    #[test]
+    fn numeric_leaves() {
+        let nexus = parse("#NEXUS\nBEGIN TREES;\nTREE a = ((7,3),5);\nEND;").unwrap();
+        assert_eq!(nexus.trees[0].top_down().to_newick_string(), "((7,3),5);");
+        assert!(nexus.label_names.is_none());
+    }
+
+    #[test]
+    fn translate_roundtrip() {
+        let input = "#NEXUS\nBEGIN TREES;\nTRANSLATE a Pan, b 'it''s', c Gorilla;\n\
+            TREE x = ((b,a),(c,Homo));\nEND;";
+        let nexus = parse(input).unwrap();
+        assert_eq!(
+            nexus.trees[0].top_down().to_newick_string(),
+            "((2,1),(3,4));"
+        );
+        let names = nexus.label_names.unwrap();
+        assert_eq!(names.name(Label(2)), Some("it's"));
+        assert_eq!(names.name(Label(4)), Some("Homo"));
+
+        let mut buffer = Vec::new();
+        write_nexus(&nexus.trees, Some(&names), &mut buffer).unwrap();
+        let written = String::from_utf8(buffer).unwrap();
+        assert!(written.contains("\t\t2 'it''s',\n"));
+
+        let reread = parse(&written).unwrap();
+        assert_eq!(reread.trees, nexus.trees);
+        assert_eq!(reread.label_names.unwrap().name(Label(2)), Some("it's"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            parse("BEGIN TREES;"),
+            Err(NexusError::MissingHeader)
+        ));
+        assert!(matches!(
+            parse("#NEXUS\nBEGIN TAXA;\nEND;"),
+            Err(NexusError::NoTreesBlock)
+        ));
+        assert!(matches!(
+            parse("#NEXUS\nBEGIN TREES; [open\nEND;"),
+            Err(NexusError::UnterminatedComment)
+        ));
+        assert!(matches!(
+            parse("#NEXUS\nBEGIN TREES;\nTREE a ((1,2),3);\nEND;"),
+            Err(NexusError::InvalidTree(_))
+        ));
+        assert!(matches!(
+            parse("#NEXUS\nBEGIN TREES;\nTRANSLATE 1;\nEND;"),
+            Err(NexusError::InvalidTranslate(_))
+        ));
+        assert!(matches!(
+            parse("#NEXUS\nBEGIN TREES;\nTREE a = (1,2;\nTREE b = (1,2);\nEND;"),
+            Err(NexusError::Newick { index: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use super::reader::{Action, InstanceReader, InstanceVisitor, ReadState, ReaderResult};
+use crate::{cancellation::CancellationToken, progress::Progress};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+/// Asynchronous counterpart of [`InstanceReader`] reading from a [`tokio::io::AsyncBufRead`].
+/// Only the input is awaited; the visitor is invoked synchronously for each line and behaves
+/// exactly as with [`InstanceReader::read`].
+///
+/// # Example
+/// ```
+/// use pace26io::pace::{async_reader::AsyncInstanceReader, reader::*};
+///
+/// #[derive(Default)]
+/// struct CountTrees(usize);
+///
+/// impl InstanceVisitor for CountTrees {
+///     fn visit_tree(&mut self, _lineno: usize, _line: &str) -> Action {
+///         self.0 += 1;
+///         Action::Continue
+///     }
+/// }
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut visitor = CountTrees::default();
+/// AsyncInstanceReader::new(&mut visitor)
+///     .read("#p 2 3\n((1,2),3);\n(1,(2,3));\n".as_bytes())
+///     .await
+///     .unwrap();
+/// assert_eq!(visitor.0, 2);
+/// # });
+/// ```
+pub struct AsyncInstanceReader<'a, V: InstanceVisitor> {
+    inner: InstanceReader<'a, V>,
+}
+
+impl<'a, V: InstanceVisitor> AsyncInstanceReader<'a, V> {
+    pub fn new(visitor: &'a mut V) -> Self {
+        Self {
+            inner: InstanceReader::new(visitor),
+        }
+    }
+
+    /// See [`InstanceReader::ignore_unknown_parameters`]
+    pub fn ignore_unknown_parameters(&mut self) {
+        self.inner.ignore_unknown_parameters();
+    }
+
+    /// See [`InstanceReader::reject_empty_instances`]
+    pub fn reject_empty_instances(&mut self) {
+        self.inner.reject_empty_instances();
+    }
+
+    /// See [`InstanceReader::skip_bom`]
+    pub fn skip_bom(&mut self) {
+        self.inner.skip_bom();
+    }
+
+    /// See [`InstanceReader::report_progress`]
+    pub fn report_progress(&mut self, interval: usize, on_progress: &'a mut dyn FnMut(Progress)) {
+        self.inner.report_progress(interval, on_progress);
+    }
+
+    /// See [`InstanceReader::set_cancellation_token`]
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.inner.set_cancellation_token(token);
+    }
+
+    pub async fn read<R: AsyncBufRead + Unpin>(&mut self, reader: R) -> ReaderResult<()> {
+        let mut state = ReadState::default();
+        let mut lines = reader.lines();
+        let mut lineno = 0;
+        while let Some(line) = lines.next_line().await? {
+            self.inner.check_cancellation(lineno)?;
+            if self.inner.process_line(&mut state, lineno, line)? == Action::Terminate {
+                return Ok(());
+            }
+            lineno += 1;
+        }
+        self.inner.finish(&state)
+    }
+}
+
+impl<'a, V: InstanceVisitor> From<InstanceReader<'a, V>> for AsyncInstanceReader<'a, V> {
+    /// Keeps all settings of the synchronous reader
+    fn from(inner: InstanceReader<'a, V>) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pace::reader::ReaderError;
+
+    #[derive(Default)]
+    struct Collect {
+        lines: Vec<(usize, String)>,
+        stop_after: Option<usize>,
+    }
+
+    impl InstanceVisitor for Collect {
+        fn visit_header(&mut self, lineno: usize, num_trees: usize, num_leaves: usize) -> Action {
+            self.lines
+                .push((lineno, format!("#p {num_trees} {num_leaves}")));
+            Action::Continue
+        }
+
+        fn visit_tree(&mut self, lineno: usize, line: &str) -> Action {
+            self.lines.push((lineno, line.to_owned()));
+            if self.stop_after.is_some_and(|n| self.lines.len() >= n) {
+                Action::Terminate
+            } else {
+                Action::Continue
+            }
+        }
+    }
+
+    fn read_sync(
+        input: &str,
+        stop_after: Option<usize>,
+    ) -> (ReaderResult<()>, Vec<(usize, String)>) {
+        let mut visitor = Collect {
+            stop_after,
+            ..Default::default()
+        };
+        let mut reader = InstanceReader::new(&mut visitor);
+        reader.reject_empty_instances();
+        let result = reader.read(input.as_bytes());
+        (result, visitor.lines)
+    }
+
+    fn read_async(
+        input: &str,
+        stop_after: Option<usize>,
+    ) -> (ReaderResult<()>, Vec<(usize, String)>) {
+        let mut visitor = Collect {
+            stop_after,
+            ..Default::default()
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(async {
+            let mut reader = AsyncInstanceReader::new(&mut visitor);
+            reader.reject_empty_instances();
+            reader.read(input.as_bytes()).await
+        });
+        (result, visitor.lines)
+    }
+
+    #[test]
+    fn same_as_sync() {
+        let input = "c comment\r\n#p 3 4\n((1,2),(3,4));\n\n((1,3),(2,4));\r\n(1,(2,(3,4)));";
+        for stop_after in [None, Some(2)] {
+            let (result, lines) = read_async(input, stop_after);
+            assert!(result.is_ok());
+            assert_eq!(lines, read_sync(input, stop_after).1);
+        }
+
+        for input in ["", "#p 0 3\n", "#p 1 3\n#x unknown 1\n(1,(2,3));\n"] {
+            let sync = read_sync(input, None).0.unwrap_err();
+            let asynchronous = read_async(input, None).0.unwrap_err();
+            assert_eq!(sync.to_string(), asynchronous.to_string());
+        }
+    }
+
+    #[test]
+    fn cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let mut visitor = Collect::default();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(async {
+            let mut reader = AsyncInstanceReader::new(&mut visitor);
+            reader.set_cancellation_token(token);
+            reader.read("#p 1 2\n(1,2);\n".as_bytes()).await
+        });
+        assert!(matches!(result, Err(ReaderError::Cancelled { lineno: 0 })));
+    }
+}
@@ -0,0 +1,625 @@
+//! Cluster reduction splits an instance along the clusters (i.e. leaf sets of subtrees) shared
+//! by all of its trees. Each shared cluster, together with the root, yields an independent
+//! subinstance (called a *part*): the trees restricted to the cluster, where every maximal shared
+//! sub-cluster is contracted into a single placeholder leaf. Parts can be solved on different
+//! machines; the [`SplitManifest`] records which original leaf or which other part each leaf of
+//! a part stands for, so that the solutions can be recombined.
+use super::{
+    parameters::multiplicities::MULTIPLICITIES_KEY, simplified::Instance, writer::InstanceWriter,
+};
+use crate::{
+    binary_tree::*,
+    consensus::{self, ConsensusError},
+    multi_tree::MultifurcatingTreeBuilder,
+};
+use serde_core::de::Error as _;
+use serde_json::{Value, json};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ClusterSplitError {
+    #[error(transparent)]
+    Consensus(#[from] ConsensusError),
+
+    #[error(transparent)]
+    IO(#[from] io::Error),
+}
+
+/// Meaning of a leaf of a part
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitLeaf {
+    /// Leaf of the original instance
+    Original(Label),
+
+    /// Placeholder for the cluster solved in the part with the given index
+    Part(usize),
+}
+
+/// Subinstance of a [`SplitManifest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitPart {
+    /// Index of the part containing the placeholder of this part and the placeholder's label
+    /// there; `None` for the root part
+    pub parent: Option<(usize, Label)>,
+
+    /// The leaf labelled `i + 1` in the subinstance corresponds to `leaves[i]`
+    pub leaves: Vec<SplitLeaf>,
+}
+
+/// Describes how the parts written by [`write_cluster_split`] relate to the original instance.
+/// Part 0 is the root part; every other part occurs as a placeholder leaf in exactly one part
+/// with a smaller index. A solution of the original instance is recombined from the solutions
+/// of the parts by replacing, top-down, each placeholder by the solution of its part and mapping
+/// the remaining labels back to the original leaves.
+///
+/// In JSON (see [`SplitManifest::to_json_value`]) an original leaf is written as its label
+/// and a placeholder as `{"part": i}`:
+/// ```text
+/// {"num_leaves":5,"parts":[
+///   {"leaves":[{"part":1},{"part":2}],"parent":null},
+///   {"leaves":[1,2,3],"parent":{"label":1,"part":0}},
+///   {"leaves":[4,5],"parent":{"label":2,"part":0}}]}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitManifest {
+    /// Number of leaves of the original instance
+    pub num_leaves: usize,
+    pub parts: Vec<SplitPart>,
+}
+
+impl SplitManifest {
+    /// Returns the manifest as JSON value; the format is shown in [`SplitManifest`]
+    pub fn to_json_value(&self) -> Value {
+        let parts: Vec<Value> = self
+            .parts
+            .iter()
+            .map(|part| {
+                let parent = part
+                    .parent
+                    .map(|(p, label)| json!({ "part": p, "label": label.0 }));
+                let leaves: Vec<Value> = part
+                    .leaves
+                    .iter()
+                    .map(|leaf| match leaf {
+                        SplitLeaf::Original(label) => json!(label.0),
+                        SplitLeaf::Part(p) => json!({ "part": p }),
+                    })
+                    .collect();
+                json!({ "parent": parent, "leaves": leaves })
+            })
+            .collect();
+
+        json!({ "num_leaves": self.num_leaves, "parts": parts })
+    }
+
+    /// Inverse of [`SplitManifest::to_json_value`]; fails if `value` is not a valid manifest
+    pub fn from_json_value(value: &Value) -> Result<Self, serde_json::Error> {
+        let invalid = |what: &str| serde_json::Error::custom(format!("invalid manifest: {what}"));
+        let as_usize = |v: &Value| v.as_u64().and_then(|x| usize::try_from(x).ok());
+        let as_label = |v: &Value| {
+            v.as_u64()
+                .and_then(|x| LabelValue::try_from(x).ok())
+                .map(Label)
+        };
+
+        let num_leaves = value
+            .get("num_leaves")
+            .and_then(as_usize)
+            .ok_or_else(|| invalid("num_leaves"))?;
+
+        let parts = value
+            .get("parts")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("parts"))?
+            .iter()
+            .map(|part| {
+                let parent = match part.get("parent") {
+                    None | Some(Value::Null) => None,
+                    Some(parent) => Some(
+                        parent
+                            .get("part")
+                            .and_then(as_usize)
+                            .zip(parent.get("label").and_then(as_label))
+                            .ok_or_else(|| invalid("parent"))?,
+                    ),
+                };
+
+                let leaves = part
+                    .get("leaves")
+                    .and_then(Value::as_array)
+                    .ok_or_else(|| invalid("leaves"))?
+                    .iter()
+                    .map(|leaf| match leaf.get("part") {
+                        Some(p) => as_usize(p).map(SplitLeaf::Part),
+                        None => as_label(leaf).map(SplitLeaf::Original),
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| invalid("leaves"))?;
+
+                Ok(SplitPart { parent, leaves })
+            })
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+
+        Ok(Self { num_leaves, parts })
+    }
+
+    /// Writes the manifest as a single line of JSON
+    pub fn write_json(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "{}", self.to_json_value())
+    }
+}
+
+/// Splits `instance` along the clusters shared by all trees (see the [module docs](self)) and
+/// writes each part as a complete instance (with its own header and labels `1..=k`) into the
+/// writer returned by `open(part_index)`. Writers are requested one after another, and each is
+/// dropped before the next is opened, so parts can be streamed into files. Multiplicities are
+/// copied into every part; other stride parameters and the approximation line are not.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, pace::{cluster_split::*, simplified::Instance}};
+///
+/// let input = "#p 2 5\n(((1,2),3),(4,5));\n((1,(2,3)),(5,4));\n";
+/// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+///
+/// let mut outputs = vec![Vec::new(); 3];
+/// let mut writers = outputs.iter_mut();
+/// let manifest = write_cluster_split(&instance, |_| Ok(writers.next().unwrap())).unwrap();
+///
+/// assert_eq!(manifest.parts.len(), 3);
+/// assert_eq!(manifest.parts[1].leaves, [1, 2, 3].map(|l| SplitLeaf::Original(Label(l))));
+/// assert_eq!(outputs[1], b"#p 2 3\n((1,2),3);\n(1,(2,3));\n");
+/// ```
+pub fn write_cluster_split<B, W>(
+    instance: &Instance<B>,
+    mut open: impl FnMut(usize) -> io::Result<W>,
+) -> Result<SplitManifest, ClusterSplitError>
+where
+    B: TreeBuilder,
+    for<'a> &'a B::Node: TopDownCursor,
+    W: Write,
+{
+    let clusters = SharedClusters::new(&instance.trees)?;
+    let trees: Vec<_> = instance
+        .trees
+        .iter()
+        .map(|tree| SplitTree::new(tree, &clusters))
+        .collect();
+
+    for (part, &node) in clusters.part_roots.iter().enumerate() {
+        let mut writer = InstanceWriter::new(open(part)?);
+        if let Some(multiplicities) = &instance.multiplicities {
+            writer.write_stride_param(MULTIPLICITIES_KEY, &json!(multiplicities))?;
+        }
+        writer.write_header(trees.len(), clusters.children[node].len().max(1))?;
+        for tree in &trees {
+            tree.write_part(part, &clusters, &mut writer)?;
+        }
+        writer.into_inner()?.flush()?;
+    }
+
+    Ok(clusters.manifest(instance.num_leaves))
+}
+
+/// Strict consensus tree stored as an arena; built via [`MultifurcatingTreeBuilder`]
+#[derive(Default)]
+struct SharedClusters {
+    children: Vec<Vec<usize>>,
+    labels: Vec<Option<Label>>,
+
+    parent: Vec<Option<usize>>,
+    depth: Vec<usize>,
+    size: Vec<usize>,
+
+    /// Label of a node within the part of its parent; 1 for the root
+    label_in_parent: Vec<LabelValue>,
+    part_of: Vec<Option<usize>>,
+    part_roots: Vec<usize>,
+    leaf_of: HashMap<Label, usize>,
+}
+
+impl MultifurcatingTreeBuilder for SharedClusters {
+    type Node = usize;
+
+    fn new_inner(&mut self, children: Vec<usize>) -> usize {
+        self.children.push(children);
+        self.labels.push(None);
+        self.children.len() - 1
+    }
+
+    fn new_leaf(&mut self, label: Label) -> usize {
+        self.children.push(Vec::new());
+        self.labels.push(Some(label));
+        self.children.len() - 1
+    }
+}
+
+impl SharedClusters {
+    fn new<C: TopDownCursor>(trees: impl IntoIterator<Item = C>) -> Result<Self, ConsensusError> {
+        let mut clusters = Self::default();
+        let root = consensus::strict(trees, &mut clusters)?;
+
+        let n = clusters.children.len();
+        clusters.parent = vec![None; n];
+        clusters.depth = vec![0; n];
+        clusters.size = vec![0; n];
+        clusters.label_in_parent = vec![1; n];
+        clusters.part_of = vec![None; n];
+
+        // preorder, so parts are numbered top-down and parents precede their children
+        let mut order = Vec::with_capacity(n);
+        let mut stack = vec![root];
+        while let Some(u) = stack.pop() {
+            order.push(u);
+            if u == root || !clusters.children[u].is_empty() {
+                clusters.part_of[u] = Some(clusters.part_roots.len());
+                clusters.part_roots.push(u);
+            }
+            for (i, &v) in clusters.children[u].iter().enumerate().rev() {
+                clusters.parent[v] = Some(u);
+                clusters.depth[v] = clusters.depth[u] + 1;
+                clusters.label_in_parent[v] = (i + 1) as LabelValue;
+                stack.push(v);
+            }
+        }
+
+        for &u in order.iter().rev() {
+            clusters.size[u] = match clusters.labels[u] {
+                Some(label) => {
+                    clusters.leaf_of.insert(label, u);
+                    1
+                }
+                None => clusters.children[u].iter().map(|&v| clusters.size[v]).sum(),
+            };
+        }
+
+        Ok(clusters)
+    }
+
+    /// Lowest common ancestor by climbing; each cluster is left at most once per tree (namely at
+    /// the parent of the tree node spanning it), so this takes amortized constant time
+    fn lca(&self, mut u: usize, mut v: usize) -> usize {
+        while self.depth[u] > self.depth[v] {
+            u = self.parent[u].unwrap();
+        }
+        while self.depth[v] > self.depth[u] {
+            v = self.parent[v].unwrap();
+        }
+        while u != v {
+            u = self.parent[u].unwrap();
+            v = self.parent[v].unwrap();
+        }
+        u
+    }
+
+    fn manifest(&self, num_leaves: usize) -> SplitManifest {
+        let parts = self
+            .part_roots
+            .iter()
+            .map(|&u| {
+                let parent = self.parent[u]
+                    .map(|p| (self.part_of[p].unwrap(), Label(self.label_in_parent[u])));
+                let leaves = if self.children[u].is_empty() {
+                    vec![SplitLeaf::Original(self.labels[u].unwrap())]
+                } else {
+                    self.children[u]
+                        .iter()
+                        .map(|&v| match self.part_of[v] {
+                            Some(part) => SplitLeaf::Part(part),
+                            None => SplitLeaf::Original(self.labels[v].unwrap()),
+                        })
+                        .collect()
+                };
+                SplitPart { parent, leaves }
+            })
+            .collect();
+
+        SplitManifest { num_leaves, parts }
+    }
+}
+
+/// Input tree stored as an arena, where every node spanning a shared cluster knows it
+struct SplitTree {
+    children: Vec<Option<(usize, usize)>>,
+
+    /// Node of the [`SharedClusters`] spanned exactly by this node, if any
+    cluster: Vec<Option<usize>>,
+
+    /// Tree node spanning the root of each part
+    part_roots: Vec<usize>,
+}
+
+impl SplitTree {
+    fn new(root: impl TopDownCursor, clusters: &SharedClusters) -> Self {
+        let mut children = Vec::new();
+        let mut lowest = Vec::new();
+        let mut stack = vec![(root, None::<(usize, bool)>)];
+
+        // preorder; children are patched into their parent once they are assigned an index
+        while let Some((node, parent)) = stack.pop() {
+            let u = children.len();
+            children.push(None);
+            lowest.push(usize::MAX);
+            if let Some((p, is_right)) = parent {
+                let (left, right) = children[p].get_or_insert((usize::MAX, usize::MAX));
+                *if is_right { right } else { left } = u;
+            }
+            match node.visit() {
+                NodeType::Inner(left, right) => {
+                    stack.push((right, Some((u, true))));
+                    stack.push((left, Some((u, false))));
+                }
+                NodeType::Leaf(label) => lowest[u] = clusters.leaf_of[&label],
+            }
+        }
+
+        let mut size = vec![1; children.len()];
+        let mut cluster = vec![None; children.len()];
+        let mut part_roots = vec![usize::MAX; clusters.part_roots.len()];
+        for u in (0..children.len()).rev() {
+            if let Some((left, right)) = children[u] {
+                size[u] = size[left] + size[right];
+                lowest[u] = clusters.lca(lowest[left], lowest[right]);
+            }
+            if clusters.size[lowest[u]] == size[u] {
+                cluster[u] = Some(lowest[u]);
+                if let Some(part) = clusters.part_of[lowest[u]] {
+                    part_roots[part] = u;
+                }
+            }
+        }
+
+        Self {
+            children,
+            cluster,
+            part_roots,
+        }
+    }
+
+    fn write_part<W: Write>(
+        &self,
+        part: usize,
+        clusters: &SharedClusters,
+        writer: &mut InstanceWriter<W>,
+    ) -> io::Result<()> {
+        // nodes are (index, is_part_root); shared clusters below the part root become leaves
+        let children_of = |&(u, is_root): &(usize, bool)| {
+            if !is_root && self.cluster[u].is_some() {
+                return None;
+            }
+            self.children[u].map(|(l, r)| ((l, false), (r, false)))
+        };
+        let label_of = |node: &(usize, bool)| match children_of(node) {
+            Some(_) => None,
+            None => Some(Label(clusters.label_in_parent[self.cluster[node.0]?])),
+        };
+
+        let root = (self.part_roots[part], true);
+        writer.write_tree(FnCursor::new(root, &children_of, &label_of))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::NewickWriter;
+    use rand::{Rng, SeedableRng, seq::SliceRandom};
+    use rand_pcg::Pcg64Mcg;
+
+    fn split(input: &str) -> (SplitManifest, Vec<String>) {
+        let instance =
+            Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+        // there is at most one part per inner node of the consensus tree
+        let mut outputs = vec![Vec::new(); instance.num_leaves.max(1)];
+        let mut writers = outputs.iter_mut();
+        let manifest = write_cluster_split(&instance, |_| Ok(writers.next().unwrap())).unwrap();
+
+        let outputs = outputs
+            .into_iter()
+            .filter(|o| !o.is_empty())
+            .map(|o| String::from_utf8(o).unwrap())
+            .collect();
+        (manifest, outputs)
+    }
+
+    #[test]
+    fn nested_clusters() {
+        let (manifest, outputs) = split(concat!(
+            "#p 2 7\n#s multiplicities [2,1]\n",
+            "((((1,2),3),(4,5)),(6,7));\n",
+            "((6,7),((5,4),(1,(2,3))));\n"
+        ));
+
+        let original = |l| SplitLeaf::Original(Label(l));
+        assert_eq!(
+            manifest.parts,
+            vec![
+                SplitPart {
+                    parent: None,
+                    leaves: vec![SplitLeaf::Part(1), SplitLeaf::Part(4)],
+                },
+                SplitPart {
+                    parent: Some((0, Label(1))),
+                    leaves: vec![SplitLeaf::Part(2), SplitLeaf::Part(3)],
+                },
+                SplitPart {
+                    parent: Some((1, Label(1))),
+                    leaves: vec![original(1), original(2), original(3)],
+                },
+                SplitPart {
+                    parent: Some((1, Label(2))),
+                    leaves: vec![original(4), original(5)],
+                },
+                SplitPart {
+                    parent: Some((0, Label(2))),
+                    leaves: vec![original(6), original(7)],
+                },
+            ]
+        );
+
+        let header = "#s multiplicities [2,1]\n#p 2";
+        assert_eq!(outputs[0], format!("{header} 2\n(1,2);\n(2,1);\n"));
+        assert_eq!(outputs[1], format!("{header} 2\n(1,2);\n(2,1);\n"));
+        assert_eq!(outputs[2], format!("{header} 3\n((1,2),3);\n(1,(2,3));\n"));
+        assert_eq!(outputs[3], format!("{header} 2\n(1,2);\n(2,1);\n"));
+        assert_eq!(outputs[4], format!("{header} 2\n(1,2);\n(1,2);\n"));
+    }
+
+    #[test]
+    fn no_shared_clusters() {
+        let (manifest, outputs) = split("#p 2 4\n((1,2),(3,4));\n((1,3),(2,4));\n");
+        assert_eq!(manifest.parts.len(), 1);
+        assert_eq!(outputs[0], "#p 2 4\n((1,2),(3,4));\n((1,3),(2,4));\n");
+    }
+
+    #[test]
+    fn single_leaf() {
+        let (manifest, outputs) = split("#p 1 1\n1;\n");
+        assert_eq!(
+            manifest.parts,
+            vec![SplitPart {
+                parent: None,
+                leaves: vec![SplitLeaf::Original(Label(1))],
+            }]
+        );
+        assert_eq!(outputs, ["#p 1 1\n1;\n"]);
+    }
+
+    #[test]
+    fn manifest_json() {
+        let (manifest, _) = split("#p 2 5\n(((1,2),3),(4,5));\n((1,(2,3)),(5,4));\n");
+        let json = manifest.to_json_value().to_string();
+        assert_eq!(
+            json,
+            concat!(
+                r#"{"num_leaves":5,"parts":["#,
+                r#"{"leaves":[{"part":1},{"part":2}],"parent":null},"#,
+                r#"{"leaves":[1,2,3],"parent":{"label":1,"part":0}},"#,
+                r#"{"leaves":[4,5],"parent":{"label":2,"part":0}}]}"#
+            )
+        );
+
+        let parsed = SplitManifest::from_json_value(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(parsed, manifest);
+
+        assert!(SplitManifest::from_json_value(&json!({ "num_leaves": 1 })).is_err());
+    }
+
+    #[test]
+    fn different_leaf_sets() {
+        let mut builder = BinTreeBuilder::default();
+        let mut instance =
+            Instance::try_read("#p 1 3\n((1,2),3);\n".as_bytes(), &mut builder).unwrap();
+        instance.trees.push("(1,2);".parse().unwrap());
+
+        let result = write_cluster_split(&instance, |_| Ok(Vec::new()));
+        assert!(matches!(
+            result,
+            Err(ClusterSplitError::Consensus(
+                ConsensusError::DifferentLeafSets
+            ))
+        ));
+    }
+
+    /// Random Newick string (without semicolon) on `leaves` obtained by merging random pairs
+    fn random_subtree(rng: &mut impl Rng, leaves: &[LabelValue]) -> String {
+        let mut subtrees: Vec<String> = leaves.iter().map(|l| l.to_string()).collect();
+        while subtrees.len() > 1 {
+            subtrees.shuffle(rng);
+            let (a, b) = (subtrees.pop().unwrap(), subtrees.pop().unwrap());
+            subtrees.push(format!("({a},{b})"));
+        }
+        subtrees.pop().unwrap()
+    }
+
+    /// Keeps the clusters of `tree`, except for subtrees that are re-randomized with
+    /// probability `p`
+    fn randomize_subtrees(rng: &mut impl Rng, tree: &BinTree, p: f64) -> String {
+        if rng.random_bool(p) {
+            let mut leaves: Vec<_> = tree
+                .top_down()
+                .dfs()
+                .filter_map(|u| u.leaf_label())
+                .map(|l| l.0)
+                .collect();
+            leaves.sort_unstable();
+            return random_subtree(rng, &leaves);
+        }
+        match tree.top_down().visit() {
+            NodeType::Inner(left, right) => format!(
+                "({},{})",
+                randomize_subtrees(rng, left, p),
+                randomize_subtrees(rng, right, p)
+            ),
+            NodeType::Leaf(label) => label.0.to_string(),
+        }
+    }
+
+    /// Replaces the placeholders of `part` in `tree` recursively by the trees of their parts
+    fn recombine(
+        manifest: &SplitManifest,
+        parts: &[Vec<BinTree>],
+        part: usize,
+        tree: usize,
+    ) -> String {
+        fn expand(
+            manifest: &SplitManifest,
+            parts: &[Vec<BinTree>],
+            part: usize,
+            tree: usize,
+            node: &BinTree,
+        ) -> String {
+            match node.top_down().visit() {
+                NodeType::Inner(left, right) => format!(
+                    "({},{})",
+                    expand(manifest, parts, part, tree, left),
+                    expand(manifest, parts, part, tree, right)
+                ),
+                NodeType::Leaf(label) => match manifest.parts[part].leaves[label.0 as usize - 1] {
+                    SplitLeaf::Original(label) => label.0.to_string(),
+                    SplitLeaf::Part(child) => recombine(manifest, parts, child, tree),
+                },
+            }
+        }
+        expand(manifest, parts, part, tree, &parts[part][tree])
+    }
+
+    #[test]
+    fn random_recombination() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0x5eed);
+        for _ in 0..100 {
+            let n = rng.random_range(1..40);
+            let leaves: Vec<LabelValue> = (1..=n).collect();
+            let first: BinTree = format!("{};", random_subtree(&mut rng, &leaves))
+                .parse()
+                .unwrap();
+
+            let mut input = format!("#p 3 {n}\n{}\n", first.top_down().to_newick_string());
+            for _ in 0..2 {
+                input += &format!("{};\n", randomize_subtrees(&mut rng, &first, 0.1));
+            }
+
+            let (manifest, outputs) = split(&input);
+            assert_eq!(manifest.parts.len(), outputs.len());
+            assert_eq!(
+                manifest.parts.iter().map(|p| p.leaves.len()).sum::<usize>(),
+                n as usize + manifest.parts.len() - 1
+            );
+
+            let parts: Vec<Vec<BinTree>> = outputs
+                .iter()
+                .map(|o| o.lines().skip(1).map(|t| t.parse().unwrap()).collect())
+                .collect();
+            for (tree, original) in input.lines().skip(1).enumerate() {
+                assert_eq!(
+                    format!("{};", recombine(&manifest, &parts, 0, tree)),
+                    original
+                );
+            }
+        }
+    }
+}
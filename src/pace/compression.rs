@@ -0,0 +1,282 @@
+use std::{
+    io::{self, BufRead, Read},
+    path::Path,
+};
+
+/// Length of the longest magic byte sequence recognized by [`Compression::from_magic`]
+const MAX_MAGIC_LEN: usize = 6;
+
+/// Compression formats recognized by [`decompress`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Identifies the compression format by the magic bytes at the beginning of `bytes`
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::pace::compression::Compression;
+    ///
+    /// assert_eq!(Compression::from_magic(b"\x1f\x8b\x08\x00"), Some(Compression::Gzip));
+    /// assert_eq!(Compression::from_magic(b"#p 2 3\n"), None);
+    /// ```
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        const MAGICS: [(&[u8], Compression); 3] = [
+            (b"\x1f\x8b", Compression::Gzip),
+            (b"\xfd7zXZ\x00", Compression::Xz),
+            (b"\x28\xb5\x2f\xfd", Compression::Zstd),
+        ];
+
+        MAGICS
+            .iter()
+            .find(|(magic, _)| bytes.starts_with(magic))
+            .map(|&(_, format)| format)
+    }
+
+    /// Identifies the compression format by the extension of `path` (`.gz`, `.xz`, `.zst`,
+    /// or `.zstd`, ignoring case)
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::pace::compression::Compression;
+    ///
+    /// assert_eq!(Compression::from_path("instances/tiny01.nw.gz"), Some(Compression::Gzip));
+    /// assert_eq!(Compression::from_path("instances/tiny01.nw"), None);
+    /// ```
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gz" => Some(Compression::Gzip),
+            "xz" => Some(Compression::Xz),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Name of the crate feature required to decompress this format
+    pub fn feature(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Xz => "xz",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// Returns `true` if the crate was compiled with the decoder of this format
+    pub fn is_supported(self) -> bool {
+        match self {
+            Compression::Gzip => cfg!(feature = "gzip"),
+            Compression::Xz => cfg!(feature = "xz"),
+            Compression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+}
+
+/// Returns a reader yielding the decompressed content of `reader` if it starts with the magic
+/// bytes of a [`Compression`] format, and `reader` itself otherwise. Fails with
+/// [`io::ErrorKind::Unsupported`] if the format is recognized, but its feature (`gzip`, `xz`, or
+/// `zstd`) is disabled. Concatenated gzip members and xz streams are decompressed in sequence.
+///
+/// [`InstanceReader::read`](super::reader::InstanceReader::read), and hence
+/// [`Instance::try_read`](super::simplified::Instance::try_read), apply this function to
+/// their input.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::compression::decompress;
+/// use std::io::BufRead;
+///
+/// let reader = decompress("#p 1 1\n1;\n".as_bytes()).unwrap();
+/// assert_eq!(reader.lines().count(), 2);
+/// ```
+pub fn decompress<'r, R: BufRead + 'r>(reader: R) -> io::Result<Box<dyn BufRead + 'r>> {
+    decompress_with_fallback(reader, None)
+}
+
+/// Same as [`decompress`], but decompresses non-empty inputs without known magic bytes as
+/// `fallback`, e.g. the format derived from the file name by [`Compression::from_path`].
+/// Hence, a corrupted `.gz` file fails with the error of the decoder instead of being read
+/// as plain text.
+pub fn decompress_with_fallback<'r, R: BufRead + 'r>(
+    mut reader: R,
+    fallback: Option<Compression>,
+) -> io::Result<Box<dyn BufRead + 'r>> {
+    let buffered = reader.fill_buf()?;
+    if buffered.is_empty() {
+        return Ok(Box::new(reader));
+    }
+    if buffered.len() >= MAX_MAGIC_LEN {
+        let format = Compression::from_magic(buffered).or(fallback);
+        return decode(reader, format);
+    }
+
+    // the buffer may hold fewer bytes than a magic, so collect them explicitly
+    let mut prefix = Vec::with_capacity(MAX_MAGIC_LEN);
+    while prefix.len() < MAX_MAGIC_LEN {
+        let buffered = match reader.fill_buf() {
+            Ok(buffered) => buffered,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buffered.is_empty() {
+            break;
+        }
+        let len = buffered.len().min(MAX_MAGIC_LEN - prefix.len());
+        prefix.extend_from_slice(&buffered[..len]);
+        reader.consume(len);
+    }
+
+    let format = Compression::from_magic(&prefix).or(fallback);
+    decode(io::Cursor::new(prefix).chain(reader), format)
+}
+
+fn decode<'r, R: BufRead + 'r>(
+    reader: R,
+    format: Option<Compression>,
+) -> io::Result<Box<dyn BufRead + 'r>> {
+    let Some(format) = format else {
+        return Ok(Box::new(reader));
+    };
+
+    match format {
+        #[cfg(feature = "gzip")]
+        Compression::Gzip => Ok(Box::new(io::BufReader::new(
+            flate2::bufread::MultiGzDecoder::new(reader),
+        ))),
+
+        #[cfg(feature = "xz")]
+        Compression::Xz => Ok(Box::new(io::BufReader::new(lzma_rust2::XzReader::new(
+            reader, true,
+        )))),
+
+        #[cfg(feature = "zstd")]
+        Compression::Zstd => Ok(Box::new(io::BufReader::new(
+            ruzstd::decoding::StreamingDecoder::new(reader)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        ))),
+
+        #[allow(unreachable_patterns)]
+        _ => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "input is {format:?}-compressed, but the `{}` feature of pace26io is disabled",
+                format.feature()
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const INSTANCE: &str = "#p 2 3\n((1,2),3);\n(1,(2,3));\n";
+
+    fn read_all(input: &[u8]) -> io::Result<String> {
+        let mut text = String::new();
+        decompress(input)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn uncompressed() {
+        assert_eq!(read_all(INSTANCE.as_bytes()).unwrap(), INSTANCE);
+        assert_eq!(read_all(b"").unwrap(), "");
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip() {
+        use std::io::Write;
+
+        let compress = |text: &str| {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(text.as_bytes()).unwrap();
+            encoder.finish().unwrap()
+        };
+
+        let mut compressed = compress(&INSTANCE[..7]);
+        compressed.extend(compress(&INSTANCE[7..]));
+        assert_eq!(read_all(&compressed).unwrap(), INSTANCE);
+
+        let mut builder = crate::binary_tree::BinTreeBuilder::default();
+        let instance =
+            crate::pace::simplified::Instance::try_read(compressed.as_slice(), &mut builder)
+                .unwrap();
+        assert_eq!(instance.trees.len(), 2);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn short_reads() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(INSTANCE.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        // the buffer holds a single byte, i.e. less than the magic
+        let mut text = String::new();
+        decompress(io::BufReader::with_capacity(1, compressed.as_slice()))
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, INSTANCE);
+
+        let mut text = String::new();
+        decompress(io::BufReader::with_capacity(1, INSTANCE.as_bytes()))
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, INSTANCE);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn path_fallback() {
+        let dir = std::env::temp_dir().join(format!("pace26io-fallback-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("corrupted.nw.gz");
+        std::fs::write(&path, INSTANCE).unwrap();
+
+        let mut builder = crate::binary_tree::BinTreeBuilder::default();
+        let result = crate::pace::simplified::Instance::try_read_path(&path, &mut builder);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "xz")]
+    #[test]
+    fn xz() {
+        use std::io::Write;
+
+        let mut encoder =
+            lzma_rust2::XzWriter::new(Vec::new(), lzma_rust2::XzOptions::default()).unwrap();
+        encoder.write_all(INSTANCE.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(read_all(&compressed).unwrap(), INSTANCE);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd() {
+        let compressed = ruzstd::encoding::compress_to_vec(
+            INSTANCE.as_bytes(),
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        assert_eq!(read_all(&compressed).unwrap(), INSTANCE);
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    #[test]
+    fn unsupported() {
+        let err = read_all(b"\x1f\x8b\x08\x00\x00").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("`gzip` feature"));
+    }
+}
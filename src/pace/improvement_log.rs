@@ -0,0 +1,232 @@
+use crate::pace::reader::{Action, InstanceReader, InstanceVisitor, ReaderError};
+use serde_core::de::Error as _;
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+use thiserror::Error;
+
+/// Key of the stride lines `#s improvement {"time":1.5,"score":42}` carrying log entries
+pub const IMPROVEMENT_KEY: &str = "improvement";
+
+/// A single entry of an anytime log: the solver found a solution of size `score`
+/// after `time` seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Improvement {
+    pub time: f64,
+    pub score: u64,
+}
+
+impl Improvement {
+    /// Returns the JSON object `{"time":1.5,"score":42}`
+    pub fn to_json_string(&self) -> String {
+        format!(r#"{{"time":{},"score":{}}}"#, json!(self.time), self.score)
+    }
+
+    /// Parses a JSON object with the fields `time` and `score`; other fields are ignored.
+    pub fn from_json_str(text: &str) -> Result<Self, serde_json::Error> {
+        let value: Value = serde_json::from_str(text)?;
+        let time = value.get("time").and_then(Value::as_f64);
+        let score = value.get("score").and_then(Value::as_u64);
+        match (time, score) {
+            (Some(time), Some(score)) => Ok(Self { time, score }),
+            _ => Err(serde_json::Error::custom(format!(
+                "expected a number `time` and an unsigned integer `score` in {value}"
+            ))),
+        }
+    }
+}
+
+/// Log of improvements emitted by a heuristic solver alongside its solution.
+///
+/// The log can be stored in two ways:
+///  - As stride lines `#s improvement {"time":1.5,"score":42}` interleaved with the solver output.
+///    Since they start with `#`, they are ignored by tools unaware of them.
+///  - As a separate JSONL file containing one object `{"time":1.5,"score":42}` per line.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::improvement_log::*;
+///
+/// let mut log = ImprovementLog::default();
+/// log.push(Improvement { time: 0.5, score: 10 });
+/// log.push(Improvement { time: 2.0, score: 7 });
+///
+/// let mut buffer: Vec<u8> = Vec::new();
+/// log.write_stride_lines(&mut buffer).unwrap();
+/// let output = String::from_utf8(buffer).unwrap();
+/// assert_eq!(output.lines().next(), Some(r#"#s improvement {"time":0.5,"score":10}"#));
+///
+/// let solver_output = format!("{output}(1,(2,3));\n");
+/// let read = ImprovementLog::read_stride_lines(solver_output.as_bytes()).unwrap();
+/// assert_eq!(read, log);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImprovementLog {
+    pub entries: Vec<Improvement>,
+}
+
+#[derive(Error, Debug)]
+pub enum ImprovementLogError {
+    #[error(transparent)]
+    Reader(#[from] ReaderError),
+
+    #[error("Invalid improvement in line {}: {err}", lineno + 1)]
+    InvalidJSON {
+        lineno: usize,
+        err: serde_json::Error,
+    },
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
+
+impl ImprovementLog {
+    pub fn push(&mut self, improvement: Improvement) {
+        self.entries.push(improvement);
+    }
+
+    /// Returns the best (i.e. smallest) score logged
+    pub fn best_score(&self) -> Option<u64> {
+        self.entries.iter().map(|e| e.score).min()
+    }
+
+    /// Writes a single entry as a stride line; intended to be called by solvers
+    /// whenever they find an improved solution.
+    pub fn write_stride_line(
+        writer: &mut impl Write,
+        improvement: &Improvement,
+    ) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "#s {IMPROVEMENT_KEY} {}",
+            improvement.to_json_string()
+        )
+    }
+
+    /// Writes all entries as stride lines
+    pub fn write_stride_lines(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        for entry in &self.entries {
+            Self::write_stride_line(writer, entry)?;
+        }
+        Ok(())
+    }
+
+    /// Collects all `#s improvement` lines of a solver output; all other lines are ignored.
+    pub fn read_stride_lines(reader: impl BufRead) -> Result<Self, ImprovementLogError> {
+        let mut visitor = StrideLineVisitor {
+            log: Self::default(),
+            error: None,
+        };
+        InstanceReader::new(&mut visitor).read(reader)?;
+
+        match visitor.error {
+            Some(err) => Err(err),
+            None => Ok(visitor.log),
+        }
+    }
+
+    /// Writes all entries as JSONL, i.e. one JSON object per line
+    pub fn write_jsonl(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        for entry in &self.entries {
+            writeln!(writer, "{}", entry.to_json_string())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a JSONL log; empty lines are skipped.
+    pub fn read_jsonl(reader: impl BufRead) -> Result<Self, ImprovementLogError> {
+        let mut log = Self::default();
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry = Improvement::from_json_str(&line)
+                .map_err(|err| ImprovementLogError::InvalidJSON { lineno, err })?;
+            log.push(entry);
+        }
+        Ok(log)
+    }
+}
+
+struct StrideLineVisitor {
+    log: ImprovementLog,
+    error: Option<ImprovementLogError>,
+}
+
+impl InstanceVisitor for StrideLineVisitor {
+    fn visit_stride_line(&mut self, lineno: usize, _line: &str, key: &str, value: &str) -> Action {
+        if key != IMPROVEMENT_KEY {
+            return Action::Continue;
+        }
+
+        match Improvement::from_json_str(value) {
+            Ok(entry) => {
+                self.log.push(entry);
+                Action::Continue
+            }
+            Err(err) => {
+                self.error = Some(ImprovementLogError::InvalidJSON { lineno, err });
+                Action::Terminate
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn example() -> ImprovementLog {
+        ImprovementLog {
+            entries: vec![
+                Improvement {
+                    time: 0.25,
+                    score: 100,
+                },
+                Improvement {
+                    time: 1.5,
+                    score: 42,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn jsonl_roundtrip() {
+        let mut buffer: Vec<u8> = Vec::new();
+        example().write_jsonl(&mut buffer).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.clone()).unwrap(),
+            "{\"time\":0.25,\"score\":100}\n{\"time\":1.5,\"score\":42}\n"
+        );
+
+        let log = ImprovementLog::read_jsonl(buffer.as_slice()).unwrap();
+        assert_eq!(log, example());
+        assert_eq!(log.best_score(), Some(42));
+    }
+
+    #[test]
+    fn stride_lines_among_solution() {
+        let input = "# comment\n#s improvement {\"time\":0.25,\"score\":100}\n(1,2);\n#s other 3\n#s improvement {\"score\":42,\"time\":1.5}\n(3,4);\n";
+        let log = ImprovementLog::read_stride_lines(input.as_bytes()).unwrap();
+        assert_eq!(log, example());
+    }
+
+    #[test]
+    fn invalid_entry() {
+        let input = "(1,2);\n#s improvement {\"time\":0.25}\n";
+        let res = ImprovementLog::read_stride_lines(input.as_bytes());
+        assert!(matches!(
+            res,
+            Err(ImprovementLogError::InvalidJSON { lineno: 1, .. })
+        ));
+
+        let res = ImprovementLog::read_jsonl("\n{\"time\":1}".as_bytes());
+        assert!(matches!(
+            res,
+            Err(ImprovementLogError::InvalidJSON { lineno: 1, .. })
+        ));
+    }
+}
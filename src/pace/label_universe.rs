@@ -0,0 +1,150 @@
+use crate::binary_tree::{Label, LabelValue};
+
+/// Set of leaf labels occurring in an instance. It is maintained while reading an
+/// [`Instance`](super::simplified::Instance) and allows to detect 0-based or sparse
+/// labelings without traversing the trees again.
+///
+/// Labels are stored in a bitset, i.e. the memory consumption is proportional to the
+/// largest label.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, pace::label_universe::*};
+///
+/// let universe: LabelUniverse = [0, 1, 4].into_iter().map(Label).collect();
+/// assert_eq!(universe.len(), 3);
+/// assert_eq!(universe.max_label(), Some(Label(4)));
+/// assert!(universe.is_zero_based());
+/// assert!(!universe.is_contiguous());
+/// assert_eq!(universe.gaps().collect::<Vec<_>>(), vec![Label(2), Label(3)]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LabelUniverse {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl LabelUniverse {
+    /// Adds `label` and returns `true` if it was not present before
+    pub fn insert(&mut self, label: Label) -> bool {
+        let (word, bit) = Self::position(label);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+
+        let is_new = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        self.len += is_new as usize;
+        is_new
+    }
+
+    pub fn contains(&self, label: Label) -> bool {
+        let (word, bit) = Self::position(label);
+        self.words.get(word).is_some_and(|w| w & bit != 0)
+    }
+
+    /// Returns the number of distinct labels
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn min_label(&self) -> Option<Label> {
+        self.iter().next()
+    }
+
+    pub fn max_label(&self) -> Option<Label> {
+        let (word, &bits) = self.words.iter().enumerate().rfind(|(_, w)| **w != 0)?;
+        Some(Label(
+            (word * 64 + 63 - bits.leading_zeros() as usize) as LabelValue,
+        ))
+    }
+
+    /// Returns `true` if label 0 is present, which violates the PACE format
+    pub fn is_zero_based(&self) -> bool {
+        self.contains(Label(0))
+    }
+
+    /// Returns `true` if the labels are exactly `1..=max_label`, as required by the PACE format
+    pub fn is_contiguous(&self) -> bool {
+        !self.is_zero_based()
+            && self
+                .max_label()
+                .is_none_or(|Label(max)| max as usize == self.len)
+    }
+
+    /// Iterates over all present labels in increasing order
+    pub fn iter(&self) -> impl Iterator<Item = Label> + '_ {
+        (0..self.words.len() as u64 * 64)
+            .map(|label| Label(label as LabelValue))
+            .filter(|&label| self.contains(label))
+    }
+
+    /// Iterates over all missing labels in `1..max_label` in increasing order
+    pub fn gaps(&self) -> impl Iterator<Item = Label> + '_ {
+        let max = self.max_label().map_or(0, |Label(l)| l);
+        (1..max).map(Label).filter(|&label| !self.contains(label))
+    }
+
+    /// Returns the number of bytes allocated on the heap
+    pub fn heap_size(&self) -> usize {
+        self.words.capacity() * size_of::<u64>()
+    }
+
+    fn position(Label(label): Label) -> (usize, u64) {
+        ((label / 64) as usize, 1 << (label % 64))
+    }
+}
+
+impl Extend<Label> for LabelUniverse {
+    fn extend<T: IntoIterator<Item = Label>>(&mut self, iter: T) {
+        for label in iter {
+            self.insert(label);
+        }
+    }
+}
+
+impl FromIterator<Label> for LabelUniverse {
+    fn from_iter<T: IntoIterator<Item = Label>>(iter: T) -> Self {
+        let mut universe = Self::default();
+        universe.extend(iter);
+        universe
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insert_and_query() {
+        let mut universe = LabelUniverse::default();
+        assert!(universe.is_empty());
+        assert!(universe.is_contiguous());
+        assert_eq!(universe.max_label(), None);
+
+        for l in [3, 1, 2, 130, 3] {
+            universe.insert(Label(l));
+        }
+        assert_eq!(universe.len(), 4);
+        assert_eq!(universe.min_label(), Some(Label(1)));
+        assert_eq!(universe.max_label(), Some(Label(130)));
+        assert!(universe.contains(Label(130)));
+        assert!(!universe.contains(Label(129)));
+        assert!(!universe.contains(Label(1000)));
+        assert!(!universe.is_zero_based());
+        assert!(!universe.is_contiguous());
+        assert_eq!(universe.gaps().count(), 126);
+        assert_eq!(
+            universe.iter().collect::<Vec<_>>(),
+            vec![Label(1), Label(2), Label(3), Label(130)]
+        );
+
+        let universe: LabelUniverse = (1..=64).map(Label).collect();
+        assert!(universe.is_contiguous());
+        assert_eq!(universe.gaps().next(), None);
+    }
+}
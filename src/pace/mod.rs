@@ -1,3 +1,18 @@
+#[cfg(feature = "async")]
+pub mod async_reader;
+pub mod cluster_split;
+pub mod compression;
+pub mod improvement_log;
+pub mod label_universe;
+pub mod numbering;
 pub mod parameters;
+pub mod path;
+pub mod peek;
 pub mod reader;
+pub mod repair;
 pub mod simplified;
+pub mod solver_output;
+pub mod visitors;
+pub mod writer;
+
+pub use peek::*;
@@ -0,0 +1,131 @@
+use crate::binary_tree::{Label, LabelValue, NodeIdx};
+
+/// Node described by a PACE node id; see [`NodeNumbering`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Leaf(Label),
+
+    /// Inner node with preorder rank `rank` (0 for the root) in the `tree`-th tree (0-based)
+    Inner {
+        tree: usize,
+        rank: usize,
+    },
+}
+
+/// Numbering of nodes in PACE instances with `n` leaves: leaves have the ids `1..=n`
+/// (i.e. their labels), and the `n - 1` inner nodes of the `k`-th tree (0-based) have
+/// consecutive ids in preorder starting with the root id `(k + 1)(n - 1) + 2`.
+/// This is the numbering assigned by [`Instance::try_read`](super::simplified::Instance::try_read)
+/// and used by the tree decomposition.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, pace::numbering::*};
+///
+/// // inner nodes of the first tree are 5..=7, those of the second tree 8..=10
+/// let numbering = NodeNumbering::new(4);
+/// assert_eq!(numbering.root_id(1), NodeIdx::new(8));
+/// assert_eq!(numbering.inner_node_id(0, 2), NodeIdx::new(7));
+/// assert_eq!(numbering.classify(NodeIdx::new(3)), Some(NodeKind::Leaf(Label(3))));
+/// assert_eq!(
+///     numbering.classify(NodeIdx::new(9)),
+///     Some(NodeKind::Inner { tree: 1, rank: 1 })
+/// );
+/// assert_eq!(numbering.classify(NodeIdx::new(0)), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeNumbering {
+    num_leaves: usize,
+}
+
+impl NodeNumbering {
+    /// Creates the numbering for instances with `num_leaves` leaves; only [`NodeNumbering::classify`]
+    /// and [`NodeNumbering::leaf_id`] may be used if there are no leaves
+    pub fn new(num_leaves: usize) -> Self {
+        Self { num_leaves }
+    }
+
+    pub fn num_leaves(&self) -> usize {
+        self.num_leaves
+    }
+
+    /// Returns the number of inner nodes of each tree, i.e. `n - 1`
+    pub fn num_inner_per_tree(&self) -> usize {
+        self.num_leaves.saturating_sub(1)
+    }
+
+    /// Returns the id of the root of the `tree`-th tree (0-based). For single-leaf
+    /// instances, this id is not assigned to any node.
+    pub fn root_id(&self, tree: usize) -> NodeIdx {
+        NodeIdx(((tree + 1) * (self.num_leaves - 1) + 2) as u32)
+    }
+
+    /// Returns the id of the inner node with preorder rank `rank` in the `tree`-th tree
+    pub fn inner_node_id(&self, tree: usize, rank: usize) -> NodeIdx {
+        NodeIdx(self.root_id(tree).0 + rank as u32)
+    }
+
+    /// Returns the id of the leaf with label `label`, i.e. the label itself
+    ///
+    /// # Panics
+    /// If the label exceeds the range of [`NodeIdx`], which is only possible if the feature
+    /// `u64-labels` is enabled
+    pub fn leaf_id(&self, label: Label) -> NodeIdx {
+        NodeIdx::try_from(label).expect("leaf label exceeds the range of node ids")
+    }
+
+    /// Inverse of [`NodeNumbering::leaf_id`] and [`NodeNumbering::inner_node_id`];
+    /// returns `None` for the unused id 0 and, in single-leaf instances, for all ids
+    /// other than 1
+    pub fn classify(&self, id: NodeIdx) -> Option<NodeKind> {
+        let id = id.0 as usize;
+        if (1..=self.num_leaves).contains(&id) {
+            return Some(NodeKind::Leaf(Label(id as LabelValue)));
+        }
+
+        let num_inner = self.num_inner_per_tree();
+        let offset = id.checked_sub(self.num_leaves + 1)?;
+        (num_inner > 0).then(|| NodeKind::Inner {
+            tree: offset / num_inner,
+            rank: offset % num_inner,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for num_leaves in 1..6 {
+            let numbering = NodeNumbering::new(num_leaves);
+            for label in 1..=num_leaves as LabelValue {
+                let id = numbering.leaf_id(Label(label));
+                assert_eq!(numbering.classify(id), Some(NodeKind::Leaf(Label(label))));
+            }
+
+            for tree in 0..4 {
+                for rank in 0..num_leaves - 1 {
+                    let id = numbering.inner_node_id(tree, rank);
+                    assert_eq!(numbering.classify(id), Some(NodeKind::Inner { tree, rank }));
+                }
+            }
+        }
+
+        let single = NodeNumbering::new(1);
+        assert_eq!(single.classify(NodeIdx::new(2)), None);
+    }
+
+    #[test]
+    fn label_conversion() {
+        assert_eq!(NodeIdx::try_from(Label(7)), Ok(NodeIdx::new(7)));
+
+        #[cfg(feature = "u64-labels")]
+        {
+            use crate::binary_tree::LabelOutOfRange;
+            let large = u32::MAX as LabelValue + 1;
+            assert_eq!(NodeIdx::try_from(Label(large)), Err(LabelOutOfRange(large)));
+        }
+    }
+}
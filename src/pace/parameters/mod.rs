@@ -1 +1,3 @@
+pub mod multiplicities;
 pub mod tree_decomposition;
+pub mod weights;
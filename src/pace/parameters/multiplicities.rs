@@ -0,0 +1,71 @@
+use serde_json::Value;
+use thiserror::Error;
+
+/// Key of the stride line `#s multiplicities [1,3,2]` assigning each input tree the number
+/// of times it occurs, e.g., in deduplicated or weighted instances
+pub const MULTIPLICITIES_KEY: &str = "multiplicities";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MultiplicitiesError {
+    #[error("expected an array of non-negative integers")]
+    NotAnArray,
+
+    #[error("entry {index} ({text}) is not a non-negative integer")]
+    InvalidEntry { index: usize, text: String },
+
+    #[error("found {found} multiplicities for {num_trees} trees")]
+    LengthMismatch { num_trees: usize, found: usize },
+}
+
+/// Parses the value of the stride parameter [`MULTIPLICITIES_KEY`], which has to be a JSON
+/// array of non-negative integers.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::parameters::multiplicities::*;
+/// use serde_json::json;
+///
+/// assert_eq!(parse_multiplicities(&json!([1, 3, 0])), Ok(vec![1, 3, 0]));
+/// assert!(parse_multiplicities(&json!([1, -3])).is_err());
+/// assert!(parse_multiplicities(&json!("1 3")).is_err());
+/// ```
+pub fn parse_multiplicities(value: &Value) -> Result<Vec<u64>, MultiplicitiesError> {
+    let Value::Array(entries) = value else {
+        return Err(MultiplicitiesError::NotAnArray);
+    };
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            entry
+                .as_u64()
+                .ok_or_else(|| MultiplicitiesError::InvalidEntry {
+                    index,
+                    text: entry.to_string(),
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn invalid_entries() {
+        assert_eq!(
+            parse_multiplicities(&json!([2, 1.5])),
+            Err(MultiplicitiesError::InvalidEntry {
+                index: 1,
+                text: "1.5".into()
+            })
+        );
+        assert_eq!(
+            parse_multiplicities(&json!({"a": 1})),
+            Err(MultiplicitiesError::NotAnArray)
+        );
+        assert_eq!(parse_multiplicities(&json!([])), Ok(vec![]));
+    }
+}
@@ -1,6 +1,6 @@
-use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
-use serde::ser::SerializeSeq;
-use serde::{Serialize, Serializer};
+use serde_core::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde_core::ser::SerializeSeq;
+use serde_core::{Serialize, Serializer};
 use std::fmt;
 
 type Node = u32;
@@ -15,6 +15,71 @@ pub struct TreeDecomposition {
     pub edges: Vec<(Node, Node)>,
 }
 
+impl TreeDecomposition {
+    /// Roots the decomposition at bag `root` and returns the sorted union of all bags in the
+    /// subtree of bag `bag` (both 1-indexed, as in `edges`). Returns `None` if either index is
+    /// out of range or `bag` is not connected to `root`.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::pace::parameters::tree_decomposition::TreeDecomposition;
+    ///
+    /// let td = TreeDecomposition {
+    ///     treewidth: 1,
+    ///     bags: vec![vec![1, 2], vec![2, 3], vec![3, 4]],
+    ///     edges: vec![(1, 2), (2, 3)],
+    /// };
+    /// assert_eq!(td.subtree_vertices(2, 1), Some(vec![2, 3, 4]));
+    /// assert_eq!(td.subtree_vertices(2, 3), Some(vec![1, 2, 3]));
+    /// ```
+    pub fn subtree_vertices(&self, bag: usize, root: usize) -> Option<Vec<Node>> {
+        let num_bags = self.bags.len();
+        if !(1..=num_bags).contains(&bag) || !(1..=num_bags).contains(&root) {
+            return None;
+        }
+
+        let mut neighbors = vec![Vec::new(); num_bags];
+        for &(u, v) in &self.edges {
+            let (u, v) = (u as usize - 1, v as usize - 1);
+            if u < num_bags && v < num_bags {
+                neighbors[u].push(v);
+                neighbors[v].push(u);
+            }
+        }
+
+        // the subtree of `bag` consists of all bags that are reachable without passing
+        // its parent, i.e. the predecessor on the path from `root`
+        let mut parent = vec![None; num_bags];
+        let mut visited = vec![false; num_bags];
+        let mut stack = vec![root - 1];
+        visited[root - 1] = true;
+        while let Some(u) = stack.pop() {
+            for &v in &neighbors[u] {
+                if !visited[v] {
+                    visited[v] = true;
+                    parent[v] = Some(u);
+                    stack.push(v);
+                }
+            }
+        }
+
+        if !visited[bag - 1] {
+            return None;
+        }
+
+        let mut vertices = Vec::new();
+        let mut stack = vec![bag - 1];
+        while let Some(u) = stack.pop() {
+            vertices.extend_from_slice(&self.bags[u]);
+            stack.extend(neighbors[u].iter().filter(|&&v| parent[v] == Some(u)));
+        }
+
+        vertices.sort_unstable();
+        vertices.dedup();
+        Some(vertices)
+    }
+}
+
 impl Serialize for TreeDecomposition {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -87,6 +152,24 @@ mod test {
         assert_eq!(td.edges.len(), 10);
     }
 
+    #[test]
+    fn subtree_vertices() {
+        let td: TreeDecomposition = serde_json::from_str(JSON).unwrap();
+
+        assert_eq!(td.subtree_vertices(3, 1), Some(vec![1, 11, 15]));
+        assert_eq!(
+            td.subtree_vertices(2, 1),
+            Some(vec![1, 2, 7, 8, 11, 15, 16])
+        );
+        assert_eq!(td.subtree_vertices(1, 1).unwrap().len(), 16);
+        assert_eq!(
+            td.subtree_vertices(1, 3),
+            Some(vec![3, 4, 5, 6, 8, 9, 10, 12, 13, 14, 16])
+        );
+        assert_eq!(td.subtree_vertices(0, 1), None);
+        assert_eq!(td.subtree_vertices(1, 12), None);
+    }
+
     #[test]
     fn serialize() {
         let td: TreeDecomposition = serde_json::from_str(JSON).unwrap();
@@ -0,0 +1,156 @@
+use serde_json::Value;
+use thiserror::Error;
+
+/// Key of the stride lines `#s weights [0.5,1.0,0.75]` carrying per-tree weights
+pub const WEIGHTS_KEY: &str = "weights";
+
+/// Specifies which non-finite values are accepted by [`parse_weights`]. By default, neither
+/// `NaN` nor infinite values are accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FloatPolicy {
+    pub allow_nan: bool,
+    pub allow_infinite: bool,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum WeightsError {
+    #[error("expected an array of numbers")]
+    NotAnArray,
+
+    #[error("entry {index} ({text:?}) is not a number")]
+    InvalidNumber { index: usize, text: String },
+
+    #[error("entry {index} is {value}, which is rejected by the float policy")]
+    NonFinite { index: usize, value: f64 },
+}
+
+/// Parses an array of floats from the value of a stride parameter. Besides JSON arrays of
+/// numbers, the parser accepts the entries `NaN`, `inf`, and `-inf` (also as JSON strings),
+/// which JSON cannot express. Since such values do not form valid JSON, the stride parameter is
+/// stored as [`Value::String`] and parsed as list of floats separated by commas and/or
+/// whitespace, optionally enclosed in brackets. Non-finite values are subject to `policy`.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::parameters::weights::*;
+/// use serde_json::json;
+///
+/// assert_eq!(parse_weights(&json!([0.5, 1, 2.5]), FloatPolicy::default()), Ok(vec![0.5, 1.0, 2.5]));
+///
+/// let text = json!("[0.5, inf]");
+/// assert!(parse_weights(&text, FloatPolicy::default()).is_err());
+///
+/// let policy = FloatPolicy { allow_infinite: true, ..Default::default() };
+/// assert_eq!(parse_weights(&text, policy), Ok(vec![0.5, f64::INFINITY]));
+/// ```
+pub fn parse_weights(value: &Value, policy: FloatPolicy) -> Result<Vec<f64>, WeightsError> {
+    let weights = match value {
+        Value::Array(entries) => entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| match entry {
+                Value::Number(number) => number.as_f64().ok_or_else(|| invalid(index, entry)),
+                Value::String(text) => parse_float(index, text),
+                _ => Err(invalid(index, entry)),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Value::String(text) => {
+            let text = text.trim();
+            let text = text
+                .strip_prefix('[')
+                .and_then(|t| t.strip_suffix(']'))
+                .unwrap_or(text);
+            text.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|t| !t.is_empty())
+                .enumerate()
+                .map(|(index, text)| parse_float(index, text))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        _ => return Err(WeightsError::NotAnArray),
+    };
+
+    for (index, &value) in weights.iter().enumerate() {
+        if (value.is_nan() && !policy.allow_nan) || (value.is_infinite() && !policy.allow_infinite)
+        {
+            return Err(WeightsError::NonFinite { index, value });
+        }
+    }
+
+    Ok(weights)
+}
+
+fn parse_float(index: usize, text: &str) -> Result<f64, WeightsError> {
+    text.trim_matches('"')
+        .parse()
+        .map_err(|_| WeightsError::InvalidNumber {
+            index,
+            text: text.to_owned(),
+        })
+}
+
+fn invalid(index: usize, entry: &Value) -> WeightsError {
+    WeightsError::InvalidNumber {
+        index,
+        text: entry.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn policies() {
+        let strict = FloatPolicy::default();
+        let lenient = FloatPolicy {
+            allow_nan: true,
+            allow_infinite: true,
+        };
+
+        assert_eq!(parse_weights(&json!([]), strict), Ok(vec![]));
+        assert_eq!(
+            parse_weights(&json!("1.5 -2e3,\t0"), strict),
+            Ok(vec![1.5, -2000.0, 0.0])
+        );
+
+        let weights = parse_weights(&json!(["NaN", "-inf", 3]), lenient).unwrap();
+        assert!(weights[0].is_nan());
+        assert_eq!(weights[1..], [f64::NEG_INFINITY, 3.0]);
+
+        assert!(matches!(
+            parse_weights(&json!("[1, NaN]"), strict),
+            Err(WeightsError::NonFinite { index: 1, .. })
+        ));
+        assert_eq!(
+            parse_weights(&json!("[1, -inf]"), strict),
+            Err(WeightsError::NonFinite {
+                index: 1,
+                value: f64::NEG_INFINITY
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_values() {
+        let policy = FloatPolicy::default();
+        assert_eq!(
+            parse_weights(&json!(3), policy),
+            Err(WeightsError::NotAnArray)
+        );
+        assert_eq!(
+            parse_weights(&json!([1, null]), policy),
+            Err(WeightsError::InvalidNumber {
+                index: 1,
+                text: "null".into()
+            })
+        );
+        assert_eq!(
+            parse_weights(&json!("1 x"), policy),
+            Err(WeightsError::InvalidNumber {
+                index: 1,
+                text: "x".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+
+/// Opens the file at `path` for buffered reading. Paths are never converted to strings,
+/// so non-UTF8 file names are supported. On Windows, absolute paths are transparently
+/// rewritten into extended-length paths (`\\?\C:\...` or `\\?\UNC\server\share\...`), lifting
+/// the `MAX_PATH` limit of 260 characters.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::path::open_buffered;
+/// use std::io::BufRead;
+///
+/// let first_line = open_buffered("examples/tiny01.nw").unwrap().lines().next();
+/// assert!(first_line.is_some());
+/// ```
+pub fn open_buffered(path: impl AsRef<Path>) -> io::Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(long_path(path.as_ref())?)?))
+}
+
+/// Returns `path` as extended-length path on Windows; on other platforms `path` is unchanged
+#[cfg(windows)]
+pub(crate) fn long_path(path: &Path) -> io::Result<Cow<'_, Path>> {
+    use std::{
+        ffi::OsString,
+        path::{Component, Prefix},
+    };
+
+    // extended-length paths are not normalized by Windows, so resolve `.` and `..` first
+    let absolute = std::path::absolute(path)?;
+    let mut components = absolute.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return Ok(Cow::Owned(absolute));
+    };
+
+    let mut long = OsString::from(r"\\?\");
+    match prefix.kind() {
+        Prefix::Disk(_) => long.push(prefix.as_os_str()),
+        Prefix::UNC(server, share) => {
+            long.push(r"UNC\");
+            long.push(server);
+            long.push(r"\");
+            long.push(share);
+        }
+        // verbatim and device paths are left untouched
+        _ => return Ok(Cow::Owned(absolute)),
+    }
+    long.push(components.as_path());
+
+    Ok(Cow::Owned(long.into()))
+}
+
+/// Returns `path` as extended-length path on Windows; on other platforms `path` is unchanged
+#[cfg(not(windows))]
+pub(crate) fn long_path(path: &Path) -> io::Result<Cow<'_, Path>> {
+    Ok(Cow::Borrowed(path))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, Write};
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_path() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let name = OsStr::from_bytes(b"pace26io-\xff\xfe.nw");
+        let path = std::env::temp_dir().join(name);
+        assert!(path.to_str().is_none());
+
+        File::create(&path)
+            .unwrap()
+            .write_all(b"#p 1 1\n1;\n")
+            .unwrap();
+        let lines: Vec<String> = open_buffered(&path)
+            .unwrap()
+            .lines()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(lines, vec!["#p 1 1", "1;"]);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_long_path() {
+        let convert = |p: &str| long_path(Path::new(p)).unwrap().into_owned();
+        assert_eq!(convert(r"C:\data\x.nw"), Path::new(r"\\?\C:\data\x.nw"));
+        assert_eq!(
+            convert(r"\\server\share\x.nw"),
+            Path::new(r"\\?\UNC\server\share\x.nw")
+        );
+        assert_eq!(convert(r"\\?\C:\x.nw"), Path::new(r"\\?\C:\x.nw"));
+        assert_eq!(convert(r"C:\a\..\b.nw"), Path::new(r"\\?\C:\b.nw"));
+    }
+}
@@ -0,0 +1,127 @@
+use crate::pace::reader::{Action, InstanceReader, InstanceVisitor, ReaderError};
+use std::io::{BufRead, Seek, SeekFrom};
+
+/// Information available before the first tree of an instance, as returned by [`peek_header`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeaderInfo {
+    pub num_trees: usize,
+    pub num_leaves: usize,
+
+    /// Stride lines `#s {key} {value}` preceding the first tree
+    pub stride_params: Vec<(String, String)>,
+}
+
+/// Reads the input only up to the first tree (or its end) and returns the header together
+/// with all stride lines found so far. Returns `Ok(None)` if no header precedes the first tree.
+/// This allows to, e.g., choose a solver configuration before committing to a full parse.
+///
+/// The reader is left at an unspecified position; use [`peek_header_and_rewind`] to read
+/// the instance afterwards.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::*;
+///
+/// let input = "#s source random\n#p 2 3\n((1,2),3);\n(1,(2,3));\n";
+/// let header = peek_header(input.as_bytes()).unwrap().unwrap();
+/// assert_eq!((header.num_trees, header.num_leaves), (2, 3));
+/// assert_eq!(header.stride_params, vec![("source".to_string(), "random".to_string())]);
+/// ```
+pub fn peek_header(reader: impl BufRead) -> Result<Option<HeaderInfo>, ReaderError> {
+    let mut visitor = PeekVisitor::default();
+    InstanceReader::new(&mut visitor).read(reader)?;
+
+    Ok(visitor.header.map(|(num_trees, num_leaves)| HeaderInfo {
+        num_trees,
+        num_leaves,
+        stride_params: visitor.stride_params,
+    }))
+}
+
+/// Same as [`peek_header`], but seeks back to the initial position of the reader afterwards.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, pace::{*, simplified::Instance}};
+/// use std::io::Cursor;
+///
+/// let mut input = Cursor::new("#p 2 3\n((1,2),3);\n(1,(2,3));\n");
+/// let header = peek_header_and_rewind(&mut input).unwrap().unwrap();
+///
+/// let instance = Instance::try_read(input, &mut BinTreeBuilder::default()).unwrap();
+/// assert_eq!(instance.trees.len(), header.num_trees);
+/// ```
+pub fn peek_header_and_rewind<R: BufRead + Seek>(
+    reader: &mut R,
+) -> Result<Option<HeaderInfo>, ReaderError> {
+    let start = reader.stream_position()?;
+    let header = peek_header(&mut *reader);
+    reader.seek(SeekFrom::Start(start))?;
+    header
+}
+
+#[derive(Default)]
+struct PeekVisitor {
+    header: Option<(usize, usize)>,
+    stride_params: Vec<(String, String)>,
+}
+
+impl InstanceVisitor for PeekVisitor {
+    fn visit_header(&mut self, _lineno: usize, num_trees: usize, num_leaves: usize) -> Action {
+        self.header = Some((num_trees, num_leaves));
+        Action::Continue
+    }
+
+    fn visit_tree(&mut self, _lineno: usize, _line: &str) -> Action {
+        Action::Terminate
+    }
+
+    fn visit_stride_line(&mut self, _lineno: usize, _line: &str, key: &str, value: &str) -> Action {
+        self.stride_params.push((key.to_owned(), value.to_owned()));
+        Action::Continue
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        fs::File,
+        io::{BufReader, Read},
+    };
+
+    #[test]
+    fn stops_at_first_tree() {
+        // the invalid header after the first tree is never read
+        let input = "#p 2 3\n#s a 1\n(1,(2,3));\n#s b 2\n#p x\n";
+        let header = peek_header(input.as_bytes()).unwrap().unwrap();
+        assert_eq!(
+            header,
+            HeaderInfo {
+                num_trees: 2,
+                num_leaves: 3,
+                stride_params: vec![("a".into(), "1".into())]
+            }
+        );
+    }
+
+    #[test]
+    fn no_header() {
+        assert_eq!(peek_header("(1,2);\n#p 1 2\n".as_bytes()).unwrap(), None);
+        assert_eq!(peek_header("".as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn rewind() {
+        let mut reader = BufReader::new(File::open("examples/tiny01.nw").unwrap());
+        let header = peek_header_and_rewind(&mut reader).unwrap().unwrap();
+        assert_eq!(header.num_leaves, 6);
+
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(
+            content,
+            std::fs::read_to_string("examples/tiny01.nw").unwrap()
+        );
+    }
+}
@@ -1,4 +1,8 @@
-use crate::pace::parameters::tree_decomposition::TreeDecomposition;
+use crate::{
+    cancellation::CancellationToken,
+    pace::{compression::decompress, parameters::tree_decomposition::TreeDecomposition},
+    progress::{Progress, ProgressReporter},
+};
 use std::io::BufRead;
 use thiserror::Error;
 
@@ -37,6 +41,12 @@ use thiserror::Error;
 /// ```
 pub struct InstanceReader<'a, V: InstanceVisitor> {
     visitor: &'a mut V,
+    cancellation: Option<CancellationToken>,
+    ignore_unknown_parameters: bool,
+    reject_empty_instances: bool,
+    skip_bom: bool,
+    progress: Option<ProgressReporter<'a>>,
+    on_line_error: Option<&'a mut dyn FnMut(ReaderError)>,
 }
 
 /// Visitor trait for processing elements of a PACE 2026 instance.
@@ -62,6 +72,10 @@ pub trait InstanceVisitor {
     fn visit_unrecognized_hash_line(&mut self, _lineno: usize, _line: &str) -> Action {
         Action::Continue
     }
+    /// Invoked for comment lines `# ...`
+    fn visit_comment(&mut self, _lineno: usize, _line: &str) -> Action {
+        Action::Continue
+    }
     fn visit_unrecognized_line(&mut self, _lineno: usize, _line: &str) -> Action {
         Action::Continue
     }
@@ -108,10 +122,40 @@ pub enum ReaderError {
     #[error("Found multiple headers. Lines {} and {}", lineno0+1, lineno1+1)]
     MultipleHeaders { lineno0: usize, lineno1: usize },
 
+    #[error("Input contains neither a header nor a tree")]
+    EmptyInput,
+
+    #[error("Header in line {} is not followed by any tree", lineno + 1)]
+    NoTrees { lineno: usize },
+
+    #[error("Reading was cancelled before line {}", lineno + 1)]
+    Cancelled { lineno: usize },
+
     #[error(transparent)]
     IO(#[from] std::io::Error),
 }
 
+impl ReaderError {
+    /// Returns the line (0-based) causing the error, if any; for
+    /// [`ReaderError::MultipleHeaders`], this is the second header
+    pub fn lineno(&self) -> Option<usize> {
+        match self {
+            ReaderError::InvalidHeaderLine { lineno }
+            | ReaderError::InvalidStrideLine { lineno }
+            | ReaderError::InvalidParameterLine { lineno }
+            | ReaderError::InvalidApproxLine { lineno }
+            | ReaderError::UnknownParameter { lineno, .. }
+            | ReaderError::InvalidJSON { lineno, .. }
+            | ReaderError::MultipleHeaders {
+                lineno1: lineno, ..
+            }
+            | ReaderError::NoTrees { lineno }
+            | ReaderError::Cancelled { lineno } => Some(*lineno),
+            ReaderError::EmptyInput | ReaderError::IO(_) => None,
+        }
+    }
+}
+
 fn try_parse_header(line: &str) -> Option<(usize, usize)> {
     let mut parts = line.split(' ');
     if parts.next()? != "#p" {
@@ -141,7 +185,7 @@ fn try_parse_approx(line: &str) -> Option<(f64, usize)> {
 
 /// Expects a line `#X {key} {value}` and returns ({key}, {value}) if found
 fn try_split_key_value(line: &str) -> Option<(&str, &str)> {
-    let split = line[3..].find(' ')? + 3;
+    let split = line.get(3..)?.find(' ')? + 3;
 
     let key = line[2..split].trim();
     let value = line[split + 1..].trim();
@@ -155,112 +199,273 @@ pub enum Action {
     Terminate,
 }
 
-type ReaderResult<T> = std::result::Result<T, ReaderError>;
+pub(super) type ReaderResult<T> = std::result::Result<T, ReaderError>;
+
+/// Progress of [`InstanceReader::read`] carried from line to line
+#[derive(Default)]
+pub(super) struct ReadState {
+    header_line: Option<usize>,
+    has_trees: bool,
+    progress: Progress,
+}
 
 impl<'a, V: InstanceVisitor> InstanceReader<'a, V> {
     pub fn new(visitor: &'a mut V) -> Self {
-        Self { visitor }
+        Self {
+            visitor,
+            cancellation: None,
+            ignore_unknown_parameters: false,
+            reject_empty_instances: false,
+            skip_bom: false,
+            progress: None,
+            on_line_error: None,
+        }
+    }
+
+    /// Passes `#x` lines with unknown keys to [`InstanceVisitor::visit_unrecognized_hash_line`]
+    /// instead of returning [`ReaderError::UnknownParameter`].
+    pub fn ignore_unknown_parameters(&mut self) {
+        self.ignore_unknown_parameters = true;
     }
 
+    /// Makes [`InstanceReader::read`] return [`ReaderError::EmptyInput`] if the input consists
+    /// only of blank and comment lines, and [`ReaderError::NoTrees`] if the header is not
+    /// followed by any tree (e.g. `#p 0 3`). By default, such inputs are read without error.
+    /// Neither error is raised if the visitor terminates the reading early.
+    pub fn reject_empty_instances(&mut self) {
+        self.reject_empty_instances = true;
+    }
+
+    /// Instead of returning the error of an invalid `#` line (i.e. any error with a
+    /// [`ReaderError::lineno`]), passes it to `on_error` and continues with the next line.
+    /// IO errors, cancellation, and the checks of [`InstanceReader::reject_empty_instances`]
+    /// still end reading.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::pace::reader::*;
+    ///
+    /// let mut errors = Vec::new();
+    /// let mut on_error = |error: ReaderError| errors.push(error.lineno());
+    ///
+    /// let mut visitor = ();
+    /// let mut reader = InstanceReader::new(&mut visitor);
+    /// reader.report_line_errors(&mut on_error);
+    /// reader.read("#p 2\n#a x 1\n(1,2);\n".as_bytes()).unwrap();
+    ///
+    /// drop(reader);
+    /// assert_eq!(errors, vec![Some(0), Some(1)]);
+    /// ```
+    pub fn report_line_errors(&mut self, on_error: &'a mut dyn FnMut(ReaderError)) {
+        self.on_line_error = Some(on_error);
+    }
+
+    /// Skips a byte order mark U+FEFF at the beginning of the input, as written by many
+    /// Windows tools. Windows line endings `\r\n` are accepted regardless of this setting.
+    pub fn skip_bom(&mut self) {
+        self.skip_bom = true;
+    }
+
+    /// Invokes `on_progress` whenever at least `interval` bytes were read since its last
+    /// invocation, and once the reading ends. Each line terminator is counted as one byte.
+    /// For compressed inputs, bytes refer to the decompressed input.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{pace::reader::*, progress::Progress};
+    ///
+    /// let mut reports = Vec::new();
+    /// let mut on_progress = |progress: Progress| reports.push(progress);
+    ///
+    /// let mut visitor = ();
+    /// let mut reader = InstanceReader::new(&mut visitor);
+    /// reader.report_progress(10, &mut on_progress);
+    /// reader.read("#p 2 3\n((1,2),3);\n(1,(2,3));\n".as_bytes()).unwrap();
+    ///
+    /// drop(reader);
+    /// assert_eq!(reports.len(), 3);
+    /// assert_eq!(reports.last().unwrap().lines, 3);
+    /// assert_eq!(reports.last().unwrap().bytes, 29);
+    /// ```
+    pub fn report_progress(&mut self, interval: usize, on_progress: &'a mut dyn FnMut(Progress)) {
+        self.progress = Some(ProgressReporter::new(interval, on_progress));
+    }
+
+    /// Makes [`InstanceReader::read`] check the token before processing each line
+    /// and return [`ReaderError::Cancelled`] once it is cancelled.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+
+    /// Reads the instance from `reader` and passes its lines to the visitor. Compressed input
+    /// is transparently decompressed; see [`decompress`].
     pub fn read<R: BufRead>(&mut self, reader: R) -> ReaderResult<()> {
+        let mut state = ReadState::default();
+        for (lineno, line) in decompress(reader)?.lines().enumerate() {
+            self.check_cancellation(lineno)?;
+            if self.process_line(&mut state, lineno, line?)? == Action::Terminate {
+                return Ok(());
+            }
+        }
+        self.finish(&state)
+    }
+
+    pub(super) fn check_cancellation(&self, lineno: usize) -> ReaderResult<()> {
+        if self.cancellation.as_ref().is_some_and(|c| c.is_cancelled()) {
+            return Err(ReaderError::Cancelled { lineno });
+        }
+        Ok(())
+    }
+
+    /// Processes a single line and returns [`Action::Terminate`] if the visitor requests so
+    pub(super) fn process_line(
+        &mut self,
+        state: &mut ReadState,
+        lineno: usize,
+        line: String,
+    ) -> ReaderResult<Action> {
+        state.progress.bytes += line.len() + 1;
+        state.progress.lines = lineno + 1;
+
+        let action = match self.visit_line(state, lineno, line) {
+            Err(error) if error.lineno().is_some() && self.on_line_error.is_some() => {
+                (self.on_line_error.as_mut().unwrap())(error);
+                Action::Continue
+            }
+            result => result?,
+        };
+        if let Some(reporter) = self.progress.as_mut() {
+            match action {
+                Action::Continue => reporter.update(state.progress),
+                Action::Terminate => reporter.finish(state.progress),
+            }
+        }
+        Ok(action)
+    }
+
+    fn visit_line(
+        &mut self,
+        state: &mut ReadState,
+        lineno: usize,
+        mut line: String,
+    ) -> ReaderResult<Action> {
         macro_rules! visit {
             ($method : ident, $( $args:expr ),* $(,)? ) => {
                 if self.visitor.$method( $( $args ),*) == Action::Terminate
                 {
-                    return Ok(());
+                    return Ok(Action::Terminate);
                 }
             };
         }
 
-        let mut header_line = None;
-        for (lineno, line) in reader.lines().enumerate() {
-            let line = line?;
-            let content = line.trim();
+        if lineno == 0 && self.skip_bom && line.starts_with('\u{FEFF}') {
+            line.remove(0);
+        }
+        let content = line.trim();
 
-            if content.len() != line.len() {
-                // line has extra whitespace
-                visit!(visit_line_with_extra_whitespace, lineno, &line);
-            }
+        if content.len() != line.len() {
+            // line has extra whitespace
+            visit!(visit_line_with_extra_whitespace, lineno, &line);
+        }
 
-            // empty line
-            if content.is_empty() {
-                continue;
-            }
+        // empty line
+        if content.is_empty() {
+            return Ok(Action::Continue);
+        }
 
-            if content.starts_with("#") {
-                if content.starts_with("# ") {
-                    // comment, nothing to do
-                } else if content.starts_with("#p") {
-                    // header line
-
-                    // make sure header is unique
-                    if let Some(lineno0) = header_line {
-                        return Err(ReaderError::MultipleHeaders {
-                            lineno0,
-                            lineno1: lineno,
-                        });
-                    } else {
-                        header_line = Some(lineno);
-                    }
+        if content.starts_with("#") {
+            if content.starts_with("# ") {
+                visit!(visit_comment, lineno, content);
+            } else if content.starts_with("#p") {
+                // header line
+
+                // make sure header is unique
+                if let Some(lineno0) = state.header_line {
+                    return Err(ReaderError::MultipleHeaders {
+                        lineno0,
+                        lineno1: lineno,
+                    });
+                } else {
+                    state.header_line = Some(lineno);
+                }
 
-                    if let Some((num_trees, num_leaves)) = try_parse_header(content) {
-                        visit!(visit_header, lineno, num_trees, num_leaves);
-                    } else {
-                        return Err(ReaderError::InvalidHeaderLine { lineno });
-                    }
-                } else if content.starts_with("#s") {
-                    // stride line in the format "#s key: value"
-                    if let Some((key, value)) = try_split_key_value(content) {
-                        visit!(visit_stride_line, lineno, content, key, value);
-                    } else {
-                        return Err(ReaderError::InvalidStrideLine { lineno });
-                    }
-                } else if content.starts_with("#a") {
-                    // stride line in the format "#s key: value"
-                    if let Some((a, b)) = try_parse_approx(content) {
-                        visit!(visit_approx_line, lineno, a, b);
-                    } else {
-                        return Err(ReaderError::InvalidApproxLine { lineno });
-                    }
-                } else if content.starts_with("#x") {
-                    if let Some((key, value)) = try_split_key_value(content) {
-                        match key {
-                            "treedecomp" => {
-                                if V::VISIT_PARAM_TREE_DECOMPOSITION {
-                                    match serde_json::from_str::<TreeDecomposition>(value) {
-                                        Ok(td) => {
-                                            visit!(visit_param_tree_decomposition, lineno, td);
-                                        }
-                                        Err(err) => {
-                                            return Err(ReaderError::InvalidJSON { lineno, err });
-                                        }
-                                    };
-                                }
+                if let Some((num_trees, num_leaves)) = try_parse_header(content) {
+                    visit!(visit_header, lineno, num_trees, num_leaves);
+                } else {
+                    return Err(ReaderError::InvalidHeaderLine { lineno });
+                }
+            } else if content.starts_with("#s") {
+                // stride line in the format "#s key: value"
+                if let Some((key, value)) = try_split_key_value(content) {
+                    visit!(visit_stride_line, lineno, content, key, value);
+                } else {
+                    return Err(ReaderError::InvalidStrideLine { lineno });
+                }
+            } else if content.starts_with("#a") {
+                // stride line in the format "#s key: value"
+                if let Some((a, b)) = try_parse_approx(content) {
+                    visit!(visit_approx_line, lineno, a, b);
+                } else {
+                    return Err(ReaderError::InvalidApproxLine { lineno });
+                }
+            } else if content.starts_with("#x") {
+                if let Some((key, value)) = try_split_key_value(content) {
+                    match key {
+                        "treedecomp" => {
+                            if V::VISIT_PARAM_TREE_DECOMPOSITION {
+                                match serde_json::from_str::<TreeDecomposition>(value) {
+                                    Ok(td) => {
+                                        visit!(visit_param_tree_decomposition, lineno, td);
+                                    }
+                                    Err(err) => {
+                                        return Err(ReaderError::InvalidJSON { lineno, err });
+                                    }
+                                };
                             }
+                        }
 
-                            _ => {
-                                return Err(ReaderError::UnknownParameter {
-                                    lineno,
-                                    key: key.into(),
-                                });
-                            }
+                        _ if self.ignore_unknown_parameters => {
+                            visit!(visit_unrecognized_hash_line, lineno, content);
+                        }
+
+                        _ => {
+                            return Err(ReaderError::UnknownParameter {
+                                lineno,
+                                key: key.into(),
+                            });
                         }
-                    } else {
-                        return Err(ReaderError::InvalidParameterLine { lineno });
                     }
                 } else {
-                    // unrecognized line
-                    visit!(visit_unrecognized_hash_line, lineno, content);
+                    return Err(ReaderError::InvalidParameterLine { lineno });
                 }
-                continue;
+            } else {
+                // unrecognized line
+                visit!(visit_unrecognized_hash_line, lineno, content);
             }
+            return Ok(Action::Continue);
+        }
 
-            if content.ends_with(";") {
-                visit!(visit_tree, lineno, content);
-                continue;
-            }
+        if content.ends_with(";") {
+            state.has_trees = true;
+            visit!(visit_tree, lineno, content);
+            return Ok(Action::Continue);
+        }
+
+        visit!(visit_unrecognized_line, lineno, content);
+        Ok(Action::Continue)
+    }
 
-            visit!(visit_unrecognized_line, lineno, content);
+    /// Performs the checks after the last line was processed
+    pub(super) fn finish(&mut self, state: &ReadState) -> ReaderResult<()> {
+        if let Some(reporter) = self.progress.as_mut() {
+            reporter.finish(state.progress);
+        }
+
+        if self.reject_empty_instances && !state.has_trees {
+            return Err(match state.header_line {
+                Some(lineno) => ReaderError::NoTrees { lineno },
+                None => ReaderError::EmptyInput,
+            });
         }
 
         Ok(())
@@ -487,6 +692,102 @@ mod tests {
         assert!(matches!(res, Err(ReaderError::InvalidJSON { .. })));
     }
 
+    #[test]
+    fn cancelled() {
+        struct CancellingVisitor(CancellationToken, usize);
+        impl InstanceVisitor for CancellingVisitor {
+            fn visit_tree(&mut self, _lineno: usize, _line: &str) -> Action {
+                self.1 += 1;
+                self.0.cancel();
+                Action::Continue
+            }
+        }
+
+        let input = "#p 2 3\n(1);\n(2);\n";
+        let token = CancellationToken::new();
+        let mut visitor = CancellingVisitor(token.clone(), 0);
+        let mut reader = InstanceReader::new(&mut visitor);
+        reader.set_cancellation_token(token);
+        let res = reader.read(input.as_bytes());
+
+        assert!(matches!(res, Err(ReaderError::Cancelled { lineno: 2 })));
+        assert_eq!(visitor.1, 1);
+    }
+
+    #[test]
+    fn progress() {
+        struct StopAfterFirstTree;
+        impl InstanceVisitor for StopAfterFirstTree {
+            fn visit_tree(&mut self, _lineno: usize, _line: &str) -> Action {
+                Action::Terminate
+            }
+        }
+
+        let input = "#p 2 3\n((1,2),3);\n(1,(2,3));\n";
+        let mut reports = Vec::new();
+        let mut on_progress = |progress: Progress| reports.push((progress.lines, progress.bytes));
+        let mut visitor = StopAfterFirstTree;
+        let mut reader = InstanceReader::new(&mut visitor);
+        reader.report_progress(1000, &mut on_progress);
+        reader.read(input.as_bytes()).unwrap();
+        drop(reader);
+
+        // the first line is always reported, the last one since the visitor terminates
+        assert_eq!(reports, vec![(1, 7), (2, 18)]);
+    }
+
+    #[test]
+    fn empty_instances() {
+        for input in ["", "\n  \n", "# comment\n"] {
+            let mut visitor = TestVisitor::default();
+            InstanceReader::new(&mut visitor)
+                .read(input.as_bytes())
+                .unwrap();
+
+            let mut reader = InstanceReader::new(&mut visitor);
+            reader.reject_empty_instances();
+            let res = reader.read(input.as_bytes());
+            assert!(matches!(res, Err(ReaderError::EmptyInput)), "{input:?}");
+        }
+
+        for input in ["#p 0 3\n", "# comment\n#p 2 3\n\n"] {
+            let mut visitor = TestVisitor::default();
+            let mut reader = InstanceReader::new(&mut visitor);
+            reader.reject_empty_instances();
+            let res = reader.read(input.as_bytes());
+            assert!(matches!(res, Err(ReaderError::NoTrees { .. })), "{input:?}");
+        }
+
+        let mut visitor = TestVisitor::default();
+        let mut reader = InstanceReader::new(&mut visitor);
+        reader.reject_empty_instances();
+        reader.read("(1);\n".as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn bom_and_crlf() {
+        let input = "\u{FEFF}#p 2 3\r\n(1);\r\n(2);\r\n";
+
+        let mut visitor = TestVisitor::default();
+        InstanceReader::new(&mut visitor)
+            .read(input.as_bytes())
+            .unwrap();
+        assert!(visitor.headers.is_empty());
+        assert_eq!(visitor.unrecognized_lines.len(), 1);
+
+        let mut visitor = TestVisitor::default();
+        let mut reader = InstanceReader::new(&mut visitor);
+        reader.skip_bom();
+        reader.read(input.as_bytes()).unwrap();
+        assert_eq!(visitor.headers, vec![(0, 2, 3)]);
+        assert_eq!(
+            visitor.trees,
+            vec![(1, "(1);".to_string()), (2, "(2);".to_string())]
+        );
+        assert!(visitor.extra_whitespace_lines.is_empty());
+        assert!(visitor.unrecognized_lines.is_empty());
+    }
+
     #[test]
     fn input_with_tree_decomp() {
         let input = "#p 2 3\n#s stride_key somevalue\n(1);\n#x treedecomp [42,[[1,2],[3,4,5]],[[1,2],[3,4],[5,6]]]\n";
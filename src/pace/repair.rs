@@ -0,0 +1,203 @@
+//! Detection of common defects in instance files together with machine-applicable fixes.
+use std::io::{BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairKind {
+    /// A tree line lacks its terminating `;`
+    MissingSemicolon,
+
+    /// Leading or trailing whitespace, or whitespace within a tree
+    StrayWhitespace,
+
+    /// The header reads `#p {num_leaves} {num_trees}` instead of `#p {num_trees} {num_leaves}`
+    SwappedHeader,
+
+    /// A further `#p` line after the first header
+    DuplicateHeader,
+}
+
+/// A fix for a single line of the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// Line number (0-based) of the defect
+    pub lineno: usize,
+    pub kind: RepairKind,
+
+    /// The line after applying this fix (and all previous fixes of the same line),
+    /// or `None` if the line is to be removed
+    pub replacement: Option<String>,
+}
+
+/// Result of [`suggest`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    pub suggestions: Vec<Suggestion>,
+
+    /// Input with all suggestions applied; lines are terminated by `\n`
+    pub repaired: String,
+}
+
+impl RepairReport {
+    /// Returns `true` if no defects were found
+    pub fn is_clean(&self) -> bool {
+        self.suggestions.is_empty()
+    }
+
+    pub fn write_repaired(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(self.repaired.as_bytes())
+    }
+}
+
+/// Scans an instance for common defects and suggests fixes. In contrast to
+/// [`crate::pace::reader::InstanceReader`], the scan does not stop at the first problem.
+///
+/// The following defects are detected:
+///  - [`RepairKind::StrayWhitespace`]: whitespace around any line, or within tree lines
+///  - [`RepairKind::MissingSemicolon`]: a line starting like a tree (`(` or a digit) without trailing `;`
+///  - [`RepairKind::DuplicateHeader`]: all but the first `#p` line are removed
+///  - [`RepairKind::SwappedHeader`]: the first header's tree count does not match the number of
+///    tree lines, but its leaf count does
+///
+/// # Example
+/// ```
+/// use pace26io::pace::repair::*;
+///
+/// let input = "#p 3 2\n(1, 2);\n(2,1)\n#p 2 3\n";
+/// let report = suggest(input.as_bytes()).unwrap();
+///
+/// let kinds: Vec<_> = report.suggestions.iter().map(|s| (s.lineno, s.kind)).collect();
+/// assert_eq!(kinds, vec![
+///     (0, RepairKind::SwappedHeader),
+///     (1, RepairKind::StrayWhitespace),
+///     (2, RepairKind::MissingSemicolon),
+///     (3, RepairKind::DuplicateHeader),
+/// ]);
+/// assert_eq!(report.repaired, "#p 2 3\n(1,2);\n(2,1);\n");
+/// ```
+pub fn suggest(reader: impl BufRead) -> std::io::Result<RepairReport> {
+    let lines = reader.lines().collect::<std::io::Result<Vec<_>>>()?;
+
+    let mut suggestions = Vec::new();
+    let mut repaired: Vec<Option<String>> = Vec::with_capacity(lines.len());
+    let mut header: Option<(usize, usize, usize)> = None;
+    let mut num_trees = 0;
+
+    for (lineno, line) in lines.into_iter().enumerate() {
+        let mut line = line;
+        let mut fix = |kind, replacement: Option<String>| {
+            suggestions.push(Suggestion {
+                lineno,
+                kind,
+                replacement,
+            })
+        };
+
+        let is_tree = line
+            .trim_start()
+            .starts_with(|c: char| c == '(' || c.is_ascii_digit());
+
+        let trimmed = if is_tree {
+            line.split_whitespace().collect::<String>()
+        } else {
+            line.trim().to_owned()
+        };
+        if trimmed != line {
+            line = trimmed;
+            fix(RepairKind::StrayWhitespace, Some(line.clone()));
+        }
+
+        if is_tree {
+            if !line.ends_with(';') {
+                line.push(';');
+                fix(RepairKind::MissingSemicolon, Some(line.clone()));
+            }
+            num_trees += 1;
+        } else if let Some((a, b)) = parse_header(&line) {
+            if header.is_some() {
+                fix(RepairKind::DuplicateHeader, None);
+                repaired.push(None);
+                continue;
+            }
+            header = Some((repaired.len(), a, b));
+        }
+
+        repaired.push(Some(line));
+    }
+
+    // the tree count is only known after the full scan
+    if let Some((lineno, a, b)) = header
+        && a != num_trees
+        && b == num_trees
+    {
+        let line = format!("#p {b} {a}");
+        let pos = suggestions.partition_point(|s| s.lineno <= lineno);
+        suggestions.insert(
+            pos,
+            Suggestion {
+                lineno,
+                kind: RepairKind::SwappedHeader,
+                replacement: Some(line.clone()),
+            },
+        );
+        repaired[lineno] = Some(line);
+    }
+
+    let mut output = String::new();
+    for line in repaired.into_iter().flatten() {
+        output.push_str(&line);
+        output.push('\n');
+    }
+
+    Ok(RepairReport {
+        suggestions,
+        repaired: output,
+    })
+}
+
+fn parse_header(line: &str) -> Option<(usize, usize)> {
+    let mut parts = line.strip_prefix("#p")?.split_whitespace();
+    let a = parts.next()?.parse().ok()?;
+    let b = parts.next()?.parse().ok()?;
+    Some((a, b))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binary_tree::BinTreeBuilder, pace::simplified::Instance};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    #[test]
+    fn valid_instance_is_clean() {
+        let report = suggest(BufReader::new(File::open("examples/tiny01.nw").unwrap())).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(
+            report.repaired,
+            std::fs::read_to_string("examples/tiny01.nw").unwrap()
+        );
+    }
+
+    #[test]
+    fn repaired_output_is_readable() {
+        let input = "  #p 6 2\n (1,( 2,3)) \n((1,2),3)\n#p 2 3\n";
+        let report = suggest(input.as_bytes()).unwrap();
+        assert_eq!(report.suggestions.len(), 6);
+
+        // the swapped header line is reported before whitespace fixes of later lines
+        assert_eq!(report.suggestions[1].kind, RepairKind::SwappedHeader);
+        assert_eq!(
+            report.suggestions[2].replacement.as_deref(),
+            Some("(1,(2,3))")
+        );
+        assert_eq!(
+            report.suggestions[3].replacement.as_deref(),
+            Some("(1,(2,3));")
+        );
+
+        let instance =
+            Instance::try_read(report.repaired.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+        assert_eq!(instance.trees.len(), 2);
+        assert_eq!(instance.num_leaves, 6);
+    }
+}
@@ -1,12 +1,39 @@
+#[cfg(feature = "async")]
+use crate::pace::async_reader::AsyncInstanceReader;
 use crate::{
-    binary_tree::{NodeIdx, TreeBuilder},
-    newick::{BinaryTreeParser, ParserError},
+    binary_tree::{
+        Label, LabelValue, MemoryFootprint, NodeIdx, TopDownCursor, TreeBuilder,
+        flat_tree::FlatTree,
+    },
+    cancellation::CancellationToken,
+    compat::{CompatFlags, FORMAT_VERSION_KEY, FormatVersion, FormatVersionError},
+    metrics::{MetricsError, quartet_distance},
+    newick::{
+        LabelInterner, ParserError,
+        binary_tree_parser::{LeafPathHook, parse_tree},
+        lexer::Lexer,
+    },
     pace::{
-        parameters::tree_decomposition::TreeDecomposition,
+        compression::{Compression, decompress_with_fallback},
+        label_universe::LabelUniverse,
+        numbering::{NodeKind, NodeNumbering},
+        parameters::{
+            multiplicities::{MULTIPLICITIES_KEY, MultiplicitiesError, parse_multiplicities},
+            tree_decomposition::TreeDecomposition,
+            weights::{FloatPolicy, WeightsError, parse_weights},
+        },
+        path::open_buffered,
         reader::{Action, InstanceReader, InstanceVisitor, ReaderError},
     },
+    seed::Fingerprint,
+};
+use serde_core::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    io::BufRead,
+    path::Path,
+    time::{Duration, Instant},
 };
-use std::io::BufRead;
 
 use thiserror::Error;
 
@@ -20,10 +47,31 @@ use thiserror::Error;
 pub struct Instance<B: TreeBuilder> {
     pub num_leaves: usize,
     pub trees: Vec<B::Node>,
+
+    /// Line numbers (0-based) of the trees in the input, i.e. `trees[i]` was read from line `tree_linenos[i]`
+    pub tree_linenos: Vec<usize>,
     pub tree_decomposition: Option<TreeDecomposition>,
 
     /// Represents parameters (a, b) where an approximate solution of size at most `a * opt + b` is allowable
     pub approx: Option<(f64, usize)>,
+
+    /// Stride parameters `#s {key} {value}` in input order. Values that are not valid JSON
+    /// are stored as [`serde_json::Value::String`]. See also [`Instance::param`].
+    pub stride_params: Vec<(String, serde_json::Value)>,
+
+    /// Number of occurrences of each tree as declared by the first `#s multiplicities` line;
+    /// if present, it has one entry per tree. See also [`Instance::multiplicity`].
+    pub multiplicities: Option<Vec<u64>>,
+
+    /// Leaf labels occurring in any tree; maintained while reading
+    pub labels: LabelUniverse,
+
+    /// Version declared by `#s format-version`; reading fails if it is not supported
+    pub format_version: Option<FormatVersion>,
+
+    /// Taxon names of the leaf labels; never set by the reader, but may be attached by the
+    /// caller to write trees with names (see [`crate::newick::write_newick_with_names`])
+    pub label_names: Option<LabelInterner>,
 }
 
 impl<B: TreeBuilder> Instance<B> {
@@ -31,29 +79,991 @@ impl<B: TreeBuilder> Instance<B> {
         reader: impl BufRead,
         tree_builder: &mut B,
     ) -> Result<Self, SimplifiedReaderError> {
-        let mut instance = Instance {
-            num_leaves: 0,
-            trees: Vec::with_capacity(2),
-            tree_decomposition: None,
-            approx: None,
+        Self::try_read_impl(reader, tree_builder, ReadOptions::default())
+    }
+
+    /// Same as [`Instance::try_read`], but accepts the extensions of the format enabled by
+    /// `compat`, e.g. [`CompatProfile::Lenient`](crate::compat::CompatProfile::Lenient).
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, compat::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 3\n((1, 2), 3);\n(1,(2,3))[&R];\n";
+    /// let mut builder = BinTreeBuilder::default();
+    /// assert!(Instance::try_read(input.as_bytes(), &mut builder).is_err());
+    ///
+    /// let instance = Instance::try_read_compat(input.as_bytes(), &mut builder, CompatProfile::Lenient).unwrap();
+    /// assert_eq!(instance.trees.len(), 2);
+    /// ```
+    pub fn try_read_compat(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+        compat: impl Into<CompatFlags>,
+    ) -> Result<Self, SimplifiedReaderError> {
+        let options = ReadOptions {
+            compat: compat.into(),
+            ..Default::default()
         };
+        Self::try_read_impl(reader, tree_builder, options)
+    }
 
-        let mut visitor = Visitor {
-            builder: tree_builder,
-            instance: &mut instance,
-            num_leaves: None,
-            error: None,
+    /// Same as [`Instance::try_read`], but reads the file at `path`; see
+    /// [`open_buffered`] for the handling of non-UTF8 and long paths. Besides the magic
+    /// bytes, the compression format is derived from the extension of `path`; see
+    /// [`decompress_with_fallback`].
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let instance = Instance::try_read_path("examples/tiny01.nw", &mut BinTreeBuilder::default()).unwrap();
+    /// assert_eq!(instance.trees.len(), 2);
+    /// ```
+    pub fn try_read_path(
+        path: impl AsRef<Path>,
+        tree_builder: &mut B,
+    ) -> Result<Self, SimplifiedReaderError> {
+        let fallback = Compression::from_path(&path);
+        let reader = decompress_with_fallback(open_buffered(path)?, fallback)?;
+        Self::try_read(reader, tree_builder)
+    }
+
+    /// Same as [`Instance::try_read`], but does not stop at the first error. Instead, all
+    /// errors of the input are returned ordered by line. After an invalid tree, reading
+    /// resumes behind the next `;` following the error, so further trees on the same line
+    /// are still checked. Invalid `#` lines (see [`ReaderError`]) are skipped as well.
+    /// Only errors that prevent further reading (e.g. IO errors) are reported as last
+    /// error without line number.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 4 3\n#a x 1\n((1,2),3;(1,(2,3));\n(1,2,3);\n(1,(2,3));\n";
+    /// let errors = Instance::try_read_collecting_errors(input.as_bytes(), &mut BinTreeBuilder::default())
+    ///     .unwrap_err();
+    ///
+    /// let linenos: Vec<_> = errors.iter().map(|e| e.lineno).collect();
+    /// assert_eq!(linenos, vec![Some(1), Some(2), Some(3)]);
+    /// ```
+    pub fn try_read_collecting_errors(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+    ) -> Result<Self, Vec<LineError>> {
+        let mut errors = Vec::new();
+        let mut reader_errors = Vec::new();
+        let options = ReadOptions {
+            errors: Some(&mut errors),
+            reader_errors: Some(&mut reader_errors),
+            ..Default::default()
         };
 
+        let result = Self::try_read_impl(reader, tree_builder, options);
+        errors.extend(reader_errors.into_iter().map(|error| LineError {
+            lineno: error.lineno(),
+            error: error.into(),
+        }));
+        errors.sort_by_key(|error| error.lineno);
+
+        match result {
+            Ok(instance) if errors.is_empty() => return Ok(instance),
+            Ok(_) => {}
+            Err(error) => errors.push(LineError {
+                lineno: None,
+                error,
+            }),
+        }
+        Err(errors)
+    }
+
+    /// Same as [`Instance::try_read`], but stops with [`ReaderError::Cancelled`]
+    /// (wrapped into [`SimplifiedReaderError::ReaderError`]) once `token` is cancelled.
+    pub fn try_read_cancellable(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+        token: &CancellationToken,
+    ) -> Result<Self, SimplifiedReaderError> {
+        let options = ReadOptions {
+            token: Some(token.clone()),
+            ..Default::default()
+        };
+        Self::try_read_impl(reader, tree_builder, options)
+    }
+
+    /// Same as [`Instance::try_read`], but additionally verifies that each tree contains
+    /// every leaf `1..=n` exactly once; otherwise, reading fails with
+    /// [`SimplifiedReaderError::InconsistentLeafSet`] listing the missing and duplicated
+    /// labels of the first offending tree.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 4\n((1,2),(3,4));\n((1,2),(1,4));\n";
+    /// let mut builder = BinTreeBuilder::default();
+    /// assert!(Instance::try_read(input.as_bytes(), &mut builder).is_ok());
+    ///
+    /// let err = Instance::try_read_checked(input.as_bytes(), &mut builder).unwrap_err();
+    /// assert_eq!(
+    ///     err.to_string(),
+    ///     "Tree 1 does not contain each leaf 1..=n exactly once (missing: 3; duplicated: 1)"
+    /// );
+    /// ```
+    pub fn try_read_checked(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+    ) -> Result<Self, SimplifiedReaderError> {
+        let options = ReadOptions {
+            check_leaf_sets: true,
+            ..Default::default()
+        };
+        Self::try_read_impl(reader, tree_builder, options)
+    }
+
+    /// Same as [`Instance::try_read`], but rejects inputs without any tree, which
+    /// [`Instance::try_read`] reads as instances without trees: empty inputs and inputs
+    /// consisting only of blank and comment lines fail with [`ReaderError::EmptyInput`], and
+    /// header-only inputs such as `#p 0 3` with [`ReaderError::NoTrees`].
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::{reader::ReaderError, simplified::*}};
+    ///
+    /// let mut builder = BinTreeBuilder::default();
+    /// let instance = Instance::try_read("#p 0 3\n".as_bytes(), &mut builder).unwrap();
+    /// assert!(instance.trees.is_empty());
+    ///
+    /// assert!(matches!(
+    ///     Instance::try_read_nonempty("#p 0 3\n".as_bytes(), &mut builder),
+    ///     Err(SimplifiedReaderError::ReaderError(ReaderError::NoTrees { lineno: 0 }))
+    /// ));
+    /// ```
+    pub fn try_read_nonempty(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+    ) -> Result<Self, SimplifiedReaderError> {
+        let options = ReadOptions {
+            reject_empty: true,
+            ..Default::default()
+        };
+        Self::try_read_impl(reader, tree_builder, options)
+    }
+
+    /// Iterates over `(index, lineno, tree)` in the order of the input file, where `index` is
+    /// the position in [`Instance::trees`] and `lineno` the (0-based) line of the tree.
+    /// This order is part of the API contract: the `i`-th tree of the file is always
+    /// `trees[i]`, and no method of [`Instance`] reorders trees.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 2\n(1,2);\n# comment\n(2,1);\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// let lines: Vec<_> = instance.ordered_trees().map(|(i, lineno, _)| (i, lineno)).collect();
+    /// assert_eq!(lines, vec![(0, 1), (1, 3)]);
+    /// ```
+    pub fn ordered_trees(&self) -> impl Iterator<Item = (usize, usize, &B::Node)> {
+        self.trees
+            .iter()
+            .zip(&self.tree_linenos)
+            .enumerate()
+            .map(|(i, (tree, &lineno))| (i, lineno, tree))
+    }
+
+    /// Returns the numbering of leaves and inner nodes used for the trees and the tree decomposition
+    pub fn node_numbering(&self) -> NodeNumbering {
+        NodeNumbering::new(self.num_leaves)
+    }
+
+    /// Iterates over all stride parameters `#s {key} {value}` in the order of the input file
+    /// (including repeated keys); see also [`Instance::param`].
+    pub fn ordered_params(&self) -> impl Iterator<Item = (&str, &serde_json::Value)> {
+        self.stride_params.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Deserializes the value of the first stride parameter `#s {key} {value}` with the given key.
+    /// Returns `None` if there is no such parameter.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 2\n#s budget {\"seconds\":30}\n#s note hello world\n(1,2);\n(1,2);\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// let budget: serde_json::Value = instance.param("budget").unwrap().unwrap();
+    /// assert_eq!(budget["seconds"], 30);
+    /// assert_eq!(instance.param::<String>("note").unwrap().unwrap(), "hello world");
+    /// assert!(instance.param::<u32>("missing").is_none());
+    /// ```
+    pub fn param<T: DeserializeOwned>(&self, key: &str) -> Option<Result<T, serde_json::Error>> {
+        self.stride_params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| T::deserialize(value))
+    }
+
+    /// Parses the first stride parameter with the given key as array of floats, e.g.,
+    /// per-tree weights `#s weights [0.5,1,NaN]`; see [`parse_weights`] for the accepted
+    /// syntax. Returns `None` if there is no such parameter.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::{parameters::weights::*, simplified::*}};
+    ///
+    /// let input = "#p 2 2\n#s weights [0.5, inf]\n(1,2);\n(1,2);\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// assert!(instance.weights(WEIGHTS_KEY, FloatPolicy::default()).unwrap().is_err());
+    /// let policy = FloatPolicy { allow_infinite: true, ..Default::default() };
+    /// assert_eq!(
+    ///     instance.weights(WEIGHTS_KEY, policy),
+    ///     Some(Ok(vec![0.5, f64::INFINITY]))
+    /// );
+    /// ```
+    pub fn weights(
+        &self,
+        key: &str,
+        policy: FloatPolicy,
+    ) -> Option<Result<Vec<f64>, WeightsError>> {
+        self.stride_params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| parse_weights(value, policy))
+    }
+
+    /// Returns the multiplicity of the `tree`-th tree as declared by `#s multiplicities`,
+    /// or 1 if the instance declares no multiplicities.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 2\n#s multiplicities [3,1]\n(1,2);\n(2,1);\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// assert_eq!(instance.multiplicity(0), 3);
+    /// assert_eq!(instance.total_multiplicity(), 4);
+    /// ```
+    pub fn multiplicity(&self, tree: usize) -> u64 {
+        self.multiplicities.as_ref().map_or(1, |m| m[tree])
+    }
+
+    /// Returns the number of trees, where each tree is counted with its multiplicity
+    pub fn total_multiplicity(&self) -> u64 {
+        (0..self.trees.len()).map(|i| self.multiplicity(i)).sum()
+    }
+
+    /// Same as [`Instance::try_read`], but additionally records the parse duration, size, and
+    /// depth of each tree, e.g., to identify trees dominating the load time.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 4\n((1,2),(3,4));\n(((1,3),2),4);\n";
+    /// let (instance, profile) =
+    ///     Instance::try_read_profiled(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// assert_eq!(profile.trees.len(), instance.trees.len());
+    /// assert_eq!(profile.trees[1].num_nodes, 7);
+    /// assert_eq!(profile.trees[1].depth, 3);
+    /// ```
+    pub fn try_read_profiled(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+    ) -> Result<(Self, ParseProfile), SimplifiedReaderError> {
+        let mut profile = ParseProfile::default();
+        let options = ReadOptions {
+            profile: Some(&mut profile),
+            ..Default::default()
+        };
+        let instance = Self::try_read_impl(reader, tree_builder, options)?;
+        Ok((instance, profile))
+    }
+
+    /// Same as [`Instance::try_read_compat`], but additionally reports all content that was
+    /// skipped while reading, e.g., to assert that nothing unexpected was dropped.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, compat::*, pace::simplified::*};
+    ///
+    /// let input = "# generated\n#p 2 2\n#q 1\n(1,2)[&R];\n(1,2);\nend\n";
+    /// let (instance, ignored) = Instance::try_read_accounting(
+    ///     input.as_bytes(),
+    ///     &mut BinTreeBuilder::default(),
+    ///     CompatProfile::Lenient,
+    /// )
+    /// .unwrap();
+    ///
+    /// assert_eq!(instance.trees.len(), 2);
+    /// assert_eq!(ignored.comment_lines, vec![(0, "# generated".to_string())]);
+    /// assert_eq!(ignored.tree_comments, vec![(3, "&R".to_string())]);
+    /// assert_eq!(ignored.unrecognized_hash_lines, vec![(2, "#q 1".to_string())]);
+    /// assert_eq!(ignored.unrecognized_lines, vec![(5, "end".to_string())]);
+    /// ```
+    pub fn try_read_accounting(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+        compat: impl Into<CompatFlags>,
+    ) -> Result<(Self, IgnoredContent), SimplifiedReaderError> {
+        let mut ignored = IgnoredContent::default();
+        let options = ReadOptions {
+            compat: compat.into(),
+            ignored: Some(&mut ignored),
+            ..Default::default()
+        };
+        let instance = Self::try_read_impl(reader, tree_builder, options)?;
+        Ok((instance, ignored))
+    }
+
+    fn try_read_impl(
+        reader: impl BufRead,
+        tree_builder: &mut B,
+        mut options: ReadOptions,
+    ) -> Result<Self, SimplifiedReaderError> {
+        let mut instance = Self::new_empty();
+        let token = options.token.take();
+        let mut reader_errors = options.reader_errors.take();
+        let collect_reader_errors = reader_errors.is_some();
+        let mut on_reader_error = |error| {
+            if let Some(errors) = reader_errors.as_mut() {
+                errors.push(error);
+            }
+        };
+        let compat = options.compat;
+        let reject_empty = options.reject_empty;
+        let mut visitor = Visitor::new(tree_builder, &mut instance, options);
+
         let mut instance_reader = InstanceReader::new(&mut visitor);
+        configure_reader(&mut instance_reader, token, compat, reject_empty);
+        if collect_reader_errors {
+            instance_reader.report_line_errors(&mut on_reader_error);
+        }
         instance_reader.read(reader)?;
 
         if let Some(err) = visitor.error {
             return Err(err);
         }
+        instance.check_multiplicities()?;
+        Ok(instance)
+    }
+
+    /// Same as [`Instance::try_read_compat`], but reads asynchronously from `reader`; only
+    /// the input is read asynchronously, i.e. each line is parsed in the calling task.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, compat::*, pace::simplified::*};
+    ///
+    /// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+    /// let input = "#p 2 3\n((1,2),3);\n(1,(2,3));\n";
+    /// let instance = Instance::try_read_async(
+    ///     input.as_bytes(),
+    ///     &mut BinTreeBuilder::default(),
+    ///     CompatProfile::Pace26Strict,
+    /// )
+    /// .await
+    /// .unwrap();
+    /// assert_eq!(instance.trees.len(), 2);
+    /// # });
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn try_read_async(
+        reader: impl tokio::io::AsyncBufRead + Unpin,
+        tree_builder: &mut B,
+        compat: impl Into<CompatFlags>,
+    ) -> Result<Self, SimplifiedReaderError> {
+        let compat = compat.into();
+        let options = ReadOptions {
+            compat,
+            ..Default::default()
+        };
 
+        let mut instance = Self::new_empty();
+        let mut visitor = Visitor::new(tree_builder, &mut instance, options);
+
+        let mut instance_reader = InstanceReader::new(&mut visitor);
+        configure_reader(&mut instance_reader, None, compat, false);
+        AsyncInstanceReader::from(instance_reader)
+            .read(reader)
+            .await?;
+
+        if let Some(err) = visitor.error {
+            return Err(err);
+        }
+        instance.check_multiplicities()?;
         Ok(instance)
     }
+
+    fn new_empty() -> Self {
+        Instance {
+            num_leaves: 0,
+            trees: Vec::with_capacity(2),
+            tree_decomposition: None,
+            approx: None,
+            stride_params: Vec::new(),
+            multiplicities: None,
+            tree_linenos: Vec::new(),
+            labels: LabelUniverse::default(),
+            format_version: None,
+            label_names: None,
+        }
+    }
+
+    /// Fails if the number of multiplicities does not match the number of trees
+    fn check_multiplicities(&self) -> Result<(), SimplifiedReaderError> {
+        if let Some(multiplicities) = &self.multiplicities
+            && multiplicities.len() != self.trees.len()
+        {
+            return Err(MultiplicitiesError::LengthMismatch {
+                num_trees: self.trees.len(),
+                found: multiplicities.len(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Applies the reader-level settings of [`Instance::try_read_impl`]
+fn configure_reader<V: InstanceVisitor>(
+    reader: &mut InstanceReader<V>,
+    token: Option<CancellationToken>,
+    compat: CompatFlags,
+    reject_empty: bool,
+) {
+    if let Some(token) = token {
+        reader.set_cancellation_token(token);
+    }
+    if compat.unknown_parameters {
+        reader.ignore_unknown_parameters();
+    }
+    if compat.bom_and_crlf {
+        reader.skip_bom();
+    }
+    if reject_empty {
+        reader.reject_empty_instances();
+    }
+}
+
+impl<B: TreeBuilder> Instance<B>
+where
+    B::Node: MemoryFootprint,
+{
+    /// Returns the number of bytes occupied by the instance including all heap allocations.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 4\n((1,2),(3,4));\n(((1,3),2),4);\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    /// assert!(instance.memory_footprint() >= 2 * BinTree::estimate_heap_size(4));
+    /// ```
+    pub fn memory_footprint(&self) -> usize {
+        let trees = self.trees.capacity() * size_of::<B::Node>()
+            + self.trees.iter().map(|t| t.heap_size()).sum::<usize>();
+
+        let tree_decomposition = self.tree_decomposition.as_ref().map_or(0, |td| {
+            td.bags.capacity() * size_of::<Vec<u32>>()
+                + td.bags
+                    .iter()
+                    .map(|b| b.capacity() * size_of::<u32>())
+                    .sum::<usize>()
+                + td.edges.capacity() * size_of::<(u32, u32)>()
+        });
+
+        let stride_params = self.stride_params.capacity()
+            * size_of::<(String, serde_json::Value)>()
+            + self
+                .stride_params
+                .iter()
+                .map(|(k, _)| k.capacity())
+                .sum::<usize>();
+
+        let tree_linenos = self.tree_linenos.capacity() * size_of::<usize>();
+        let multiplicities = self
+            .multiplicities
+            .as_ref()
+            .map_or(0, |m| m.capacity() * size_of::<u64>());
+
+        size_of::<Self>()
+            + trees
+            + tree_linenos
+            + tree_decomposition
+            + stride_params
+            + multiplicities
+            + self.labels.heap_size()
+    }
+}
+
+impl<B: TreeBuilder> Instance<B>
+where
+    for<'a> &'a B::Node: TopDownCursor,
+{
+    /// Returns a fingerprint of the number of leaves and the trees (in order) to derive
+    /// reproducible seeds with [`derive_seed`](crate::seed::derive_seed); parameters, the tree
+    /// decomposition, and inner node ids are not taken into account.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*, seed::derive_seed};
+    ///
+    /// let input = "#p 2 3\n((1,2),3);\n(1,(2,3));\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    /// let shuffle_seed = derive_seed(instance.fingerprint(), "shuffle");
+    /// assert_ne!(shuffle_seed, derive_seed(instance.fingerprint(), "perturb"));
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        let mut fingerprint = Fingerprint::default();
+        fingerprint.write_u64(self.num_leaves as u64);
+        for tree in &self.trees {
+            fingerprint.write_tree(tree);
+        }
+        fingerprint.finish()
+    }
+
+    /// Relabels the leaves of all trees onto `1..=n`, where `n` is the number of distinct
+    /// labels, while preserving their relative order. This turns trees with arbitrary (e.g.
+    /// sparse or 0-based) labels into a valid PACE instance. `num_leaves` is set to `n`, the
+    /// inner nodes are renumbered accordingly, and the tree decomposition (if any) is updated.
+    ///
+    /// Returns the mapping, i.e. the original label of the new label `i + 1` is stored at index `i`.
+    ///
+    /// A vertex of the tree decomposition is considered a leaf if its id occurs as label in
+    /// some tree; all other ids are interpreted as inner nodes w.r.t. the previous `num_leaves`.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, compat::*, newick::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 3\n((0,20),7);\n(0,(7,20));\n";
+    /// let mut builder = BinTreeBuilder::default();
+    /// let compat = CompatFlags { out_of_range_labels: true, ..Default::default() };
+    /// let mut instance = Instance::try_read_compat(input.as_bytes(), &mut builder, compat).unwrap();
+    ///
+    /// let mapping = instance.compact_labels(&mut builder);
+    /// assert_eq!(mapping, vec![Label(0), Label(7), Label(20)]);
+    /// assert_eq!(instance.trees[0].top_down().to_newick_string(), "((1,3),2);");
+    /// assert_eq!(instance.trees[1].top_down().to_newick_string(), "(1,(2,3));");
+    /// ```
+    pub fn compact_labels(&mut self, builder: &mut B) -> Vec<Label> {
+        let mut flat_trees: Vec<FlatTree> = self.trees.iter().map(FlatTree::new).collect();
+
+        let mut mapping: Vec<Label> = flat_trees
+            .iter()
+            .flat_map(|t| t.leaves.iter().map(|&(label, _)| label))
+            .collect();
+        mapping.sort_unstable();
+        mapping.dedup();
+
+        let new_label = |label: Label| {
+            Label(mapping.binary_search(&label).expect("label was collected") as LabelValue + 1)
+        };
+
+        let old_numbering = NodeNumbering::new(self.num_leaves);
+        let num_leaves = mapping.len();
+        let numbering = NodeNumbering::new(num_leaves);
+
+        for (tree_idx, (tree, flat)) in self.trees.iter_mut().zip(&mut flat_trees).enumerate() {
+            for &(label, u) in &flat.leaves {
+                flat.nodes[u].label = Some(new_label(label));
+            }
+            *tree = flat
+                .build(builder, numbering.root_id(tree_idx), |_| false)
+                .0;
+        }
+
+        if let Some(td) = self.tree_decomposition.as_mut() {
+            for node in td.bags.iter_mut().flatten() {
+                let label = Label(*node as LabelValue);
+                if mapping.binary_search(&label).is_ok() {
+                    // compaction never increases labels, so the new one fits as well
+                    *node = NodeIdx::try_from(new_label(label))
+                        .expect("label does not exceed the original id")
+                        .0;
+                } else if let Some(NodeKind::Inner { tree, rank }) =
+                    old_numbering.classify(NodeIdx(*node))
+                {
+                    *node = numbering.inner_node_id(tree, rank).0;
+                }
+            }
+        }
+
+        self.num_leaves = num_leaves;
+        self.labels = (1..=num_leaves as LabelValue).map(Label).collect();
+        mapping
+    }
+
+    /// Extracts the subforest of all trees induced by the vertices in the subtree of bag `bag`
+    /// of the tree decomposition rooted at bag `root` (both 1-indexed). This is the part of the
+    /// instance a dynamic program over the tree decomposition has processed once it reaches
+    /// `bag`. Returns `None` if there is no tree decomposition or the indices are invalid.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 3\n((1,2),3);\n(1,(2,3));\n#x treedecomp [2,[[4,6,7],[1,5,7],[2,3,6]],[[1,2],[1,3]]]\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// let sub = instance.induced_subforest(2, 1).unwrap();
+    /// assert_eq!(sub.vertices, vec![1, 5, 7]);
+    /// assert_eq!(sub.tree_edges, vec![vec![(5, 1)], vec![]]);
+    /// ```
+    pub fn induced_subforest(&self, bag: usize, root: usize) -> Option<InducedSubforest> {
+        let vertices = self
+            .tree_decomposition
+            .as_ref()?
+            .subtree_vertices(bag, root)?;
+        Some(self.induced_by(vertices))
+    }
+
+    /// Returns the subgraph of the display graph (i.e. the union of all trees, where leaves
+    /// with the same label are identified) induced by the vertices of bag `bag` (1-indexed) of
+    /// the tree decomposition. Each edge is reported together with the tree it originates from.
+    /// Returns `None` if there is no tree decomposition or the index is invalid.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 3\n((1,2),3);\n(1,(2,3));\n#x treedecomp [2,[[4,6,7],[1,5,7],[2,3,6]],[[1,2],[1,3]]]\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// let sub = instance.bag_subgraph(1).unwrap();
+    /// assert_eq!(sub.vertices, vec![4, 6, 7]);
+    /// assert_eq!(sub.tree_edges, vec![vec![], vec![(6, 7)]]);
+    /// assert!(instance.bag_subgraph(4).is_none());
+    /// ```
+    pub fn bag_subgraph(&self, bag: usize) -> Option<InducedSubforest> {
+        let mut vertices = self
+            .tree_decomposition
+            .as_ref()?
+            .bags
+            .get(bag.checked_sub(1)?)?
+            .clone();
+        vertices.sort_unstable();
+        vertices.dedup();
+        Some(self.induced_by(vertices))
+    }
+
+    /// Returns the subforest induced by `vertices`, which have to be sorted
+    fn induced_by(&self, vertices: Vec<u32>) -> InducedSubforest {
+        let tree_edges = self
+            .trees
+            .iter()
+            .enumerate()
+            .map(|(tree_idx, tree)| {
+                let flat = FlatTree::new(tree);
+
+                // inner nodes are numbered in preorder, which is the order of `flat.nodes`
+                let mut ids = Vec::with_capacity(flat.nodes.len());
+                let mut next_id = self.node_numbering().root_id(tree_idx).0;
+                for node in &flat.nodes {
+                    // leaves whose labels exceed the range of ids cannot be selected
+                    ids.push(match node.label {
+                        Some(label) => NodeIdx::try_from(label).ok().map(|id| id.0),
+                        None => {
+                            next_id += 1;
+                            Some(next_id - 1)
+                        }
+                    });
+                }
+
+                let selected = |id: u32| vertices.binary_search(&id).is_ok();
+                flat.nodes
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(u, node)| Some((ids[node.parent?]?, ids[u]?)))
+                    .filter(|&(p, c)| selected(p) && selected(c))
+                    .collect()
+            })
+            .collect();
+
+        InducedSubforest {
+            vertices,
+            tree_edges,
+        }
+    }
+
+    /// Returns groups of trees that are identical up to rotations (i.e. swapping the children
+    /// of inner nodes). Each group contains the indices of its trees in increasing order and
+    /// occurs at least twice, counting each tree with its [`Instance::multiplicity`]; hence,
+    /// a single tree with multiplicity at least 2 forms a group on its own. Groups are sorted
+    /// by their first index.
+    ///
+    /// Subtrees are identified by canonical ids, which are assigned bottom-up to each distinct
+    /// pair of (unordered) child ids. Hence, trees are grouped exactly, without hash collisions.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 4 3\n((1,2),3);\n(1,(2,3));\n(3,(2,1));\n((2,1),3);\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    /// assert_eq!(instance.find_duplicate_trees(), vec![vec![0, 2, 3]]);
+    ///
+    /// let input = "#p 2 3\n#s multiplicities [1,2]\n((1,2),3);\n(1,(2,3));\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    /// assert_eq!(instance.find_duplicate_trees(), vec![vec![1]]);
+    /// ```
+    pub fn find_duplicate_trees(&self) -> Vec<Vec<usize>> {
+        #[derive(PartialEq, Eq, Hash)]
+        enum Subtree {
+            Leaf(Label),
+            Inner(usize, usize),
+        }
+
+        let mut canonical_ids: HashMap<Subtree, usize> = HashMap::new();
+        let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        for (tree_idx, tree) in self.trees.iter().enumerate() {
+            let flat = FlatTree::new(tree);
+            let mut ids = vec![0; flat.nodes.len()];
+            for u in flat.post_order() {
+                let subtree = match (flat.nodes[u].children, flat.nodes[u].label) {
+                    (Some((l, r)), _) => Subtree::Inner(ids[l].min(ids[r]), ids[l].max(ids[r])),
+                    (None, Some(label)) => Subtree::Leaf(label),
+                    (None, None) => unreachable!("each node is either an inner node or a leaf"),
+                };
+                let next_id = canonical_ids.len();
+                ids[u] = *canonical_ids.entry(subtree).or_insert(next_id);
+            }
+            groups.entry(ids[flat.root]).or_default().push(tree_idx);
+        }
+
+        let mut groups: Vec<Vec<usize>> = groups
+            .into_values()
+            .filter(|group| group.iter().map(|&i| self.multiplicity(i)).sum::<u64>() > 1)
+            .collect();
+        groups.sort_unstable();
+        groups
+    }
+
+    /// Keeps only the first tree of each group of [`Instance::find_duplicate_trees`] and adds
+    /// the multiplicities of the removed trees to it, such that [`Instance::multiplicities`]
+    /// is set and [`Instance::total_multiplicity`] is unchanged. Inner nodes of the remaining
+    /// trees are renumbered according to their new positions. The tree decomposition refers
+    /// to the removed trees and is hence dropped. Returns the number of removed trees.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 4 3\n#s multiplicities [2,1,1,3]\n((1,2),3);\n(1,(2,3));\n(3,(2,1));\n((2,1),3);\n";
+    /// let mut builder = BinTreeBuilder::default();
+    /// let mut instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+    ///
+    /// assert_eq!(instance.merge_duplicate_trees(&mut builder), 2);
+    /// assert_eq!(instance.trees.len(), 2);
+    /// assert_eq!(instance.multiplicities, Some(vec![6, 1]));
+    /// assert_eq!(instance.total_multiplicity(), 7);
+    /// ```
+    pub fn merge_duplicate_trees(&mut self, builder: &mut B) -> usize {
+        let mut multiplicities: Vec<u64> = (0..self.trees.len())
+            .map(|i| self.multiplicity(i))
+            .collect();
+        let mut removed = vec![false; self.trees.len()];
+        for group in self.find_duplicate_trees() {
+            for &i in &group[1..] {
+                multiplicities[group[0]] += multiplicities[i];
+                removed[i] = true;
+            }
+        }
+
+        let num_removed = removed.iter().filter(|&&r| r).count();
+        if num_removed == 0 {
+            return 0;
+        }
+
+        let numbering = self.node_numbering();
+        let trees = std::mem::take(&mut self.trees);
+        let linenos = std::mem::take(&mut self.tree_linenos);
+        let mut kept_multiplicities = Vec::with_capacity(trees.len() - num_removed);
+        for (i, (tree, lineno)) in trees.into_iter().zip(linenos).enumerate() {
+            if removed[i] {
+                continue;
+            }
+
+            let new_idx = self.trees.len();
+            let tree = if new_idx == i {
+                tree
+            } else {
+                FlatTree::new(&tree)
+                    .build(builder, numbering.root_id(new_idx), |_| false)
+                    .0
+            };
+            self.trees.push(tree);
+            self.tree_linenos.push(lineno);
+            kept_multiplicities.push(multiplicities[i]);
+        }
+
+        self.multiplicities = Some(kept_multiplicities);
+        self.tree_decomposition = None;
+        num_removed
+    }
+
+    /// Returns the sum of the quartet distances (see [`quartet_distance()`]) between each tree
+    /// and `reference`, where each tree is counted with its [`Instance::multiplicity`].
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    ///
+    /// let input = "#p 2 4\n#s multiplicities [1,3]\n((1,2),(3,4));\n((1,3),(2,4));\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// assert_eq!(instance.total_quartet_distance(&instance.trees[0]), Ok(3));
+    /// assert_eq!(instance.total_quartet_distance(&instance.trees[1]), Ok(1));
+    /// ```
+    pub fn total_quartet_distance(&self, reference: &B::Node) -> Result<u128, MetricsError> {
+        self.trees
+            .iter()
+            .enumerate()
+            .try_fold(0u128, |sum, (i, tree)| {
+                let distance = quartet_distance(tree, reference)?;
+                Ok(sum + distance * self.multiplicity(i) as u128)
+            })
+    }
+
+    /// Randomly swaps the children of each inner node in every tree. This does not change the
+    /// semantics of the instance, but only its presentation, and hence can be used to test that
+    /// solvers are invariant to it. Inner nodes are renumbered in preorder, as done by
+    /// [`Instance::try_read`], and the tree decomposition (if any) is updated accordingly.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::simplified::*};
+    /// use rand::SeedableRng;
+    ///
+    /// let input = "#p 2 4\n((1,2),(3,4));\n(((1,3),2),4);\n";
+    /// let mut builder = BinTreeBuilder::default();
+    /// let mut instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+    ///
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    /// instance.shuffle_rotations(&mut rng, &mut builder);
+    /// assert_eq!(instance.trees.len(), 2);
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn shuffle_rotations(&mut self, rng: &mut impl rand::Rng, builder: &mut B) {
+        let numbering = self.node_numbering();
+
+        // new_ranks[k][r] is the new preorder rank of the inner node with old rank r in tree k
+        let mut new_ranks = Vec::with_capacity(self.trees.len());
+        for (tree_idx, tree) in self.trees.iter_mut().enumerate() {
+            let flat = FlatTree::new(&*tree);
+
+            let mut old_rank = vec![0; flat.nodes.len()];
+            let mut num_inner_seen = 0;
+            for (u, node) in flat.nodes.iter().enumerate() {
+                if node.children.is_some() {
+                    old_rank[u] = num_inner_seen;
+                    num_inner_seen += 1;
+                }
+            }
+
+            // `build` decides on the rotation of inner nodes in their new preorder
+            let mut ranks = vec![0; num_inner_seen];
+            let mut next_rank = 0;
+            let (shuffled, _) = flat.build(builder, numbering.root_id(tree_idx), |u| {
+                ranks[old_rank[u]] = next_rank;
+                next_rank += 1;
+                rng.random_bool(0.5)
+            });
+
+            *tree = shuffled;
+            new_ranks.push(ranks);
+        }
+
+        if let Some(td) = self.tree_decomposition.as_mut() {
+            for node in td.bags.iter_mut().flatten() {
+                if let Some(NodeKind::Inner { tree, rank }) = numbering.classify(NodeIdx(*node))
+                    && let Some(&new_rank) = new_ranks.get(tree).and_then(|r| r.get(rank))
+                {
+                    *node = numbering.inner_node_id(tree, new_rank).0;
+                }
+            }
+        }
+    }
+}
+
+/// Input content skipped by [`Instance::try_read_accounting`]; all entries are pairs
+/// of line number (0-based) and content in input order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IgnoredContent {
+    /// Comment lines `# ...`
+    pub comment_lines: Vec<(usize, String)>,
+
+    /// Text of bracket comments `[...]` within trees (only if enabled by [`CompatFlags::comments`])
+    pub tree_comments: Vec<(usize, String)>,
+
+    /// Lines starting with `#` that are neither comments nor known directives, including
+    /// unknown `#x` parameters (only if enabled by [`CompatFlags::unknown_parameters`])
+    pub unrecognized_hash_lines: Vec<(usize, String)>,
+
+    /// Lines that are neither trees nor start with `#`
+    pub unrecognized_lines: Vec<(usize, String)>,
+}
+
+impl IgnoredContent {
+    /// Returns `true` if nothing was ignored
+    pub fn is_empty(&self) -> bool {
+        self.comment_lines.is_empty()
+            && self.tree_comments.is_empty()
+            && self.unrecognized_hash_lines.is_empty()
+            && self.unrecognized_lines.is_empty()
+    }
+}
+
+/// Per-tree statistics recorded by [`Instance::try_read_profiled`]
+#[derive(Debug, Clone, Default)]
+pub struct ParseProfile {
+    /// One entry per tree in input order
+    pub trees: Vec<TreeProfile>,
+}
+
+impl ParseProfile {
+    /// Returns the tree that took longest to parse
+    pub fn slowest(&self) -> Option<&TreeProfile> {
+        self.trees.iter().max_by_key(|t| t.duration)
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.trees.iter().map(|t| t.duration).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeProfile {
+    /// Line number (0-based) of the tree
+    pub lineno: usize,
+
+    /// Time spent parsing and building the tree
+    pub duration: Duration,
+
+    /// Number of nodes (inner nodes and leaves)
+    pub num_nodes: usize,
+
+    /// Maximum number of edges on a path from the root to a leaf
+    pub depth: usize,
+}
+
+/// Subforest of an instance induced by a set of vertices, as returned by [`Instance::induced_subforest`]
+/// and [`Instance::bag_subgraph`].
+/// Leaves are identified by their label and inner nodes by their id (see [`Instance::try_read`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InducedSubforest {
+    /// Sorted ids of the selected vertices
+    pub vertices: Vec<u32>,
+
+    /// For each tree, all edges `(parent, child)` with both endpoints selected in preorder of `child`
+    pub tree_edges: Vec<Vec<(u32, u32)>>,
 }
 
 struct Visitor<'a, B: TreeBuilder> {
@@ -61,62 +1071,285 @@ struct Visitor<'a, B: TreeBuilder> {
     instance: &'a mut Instance<B>,
     num_leaves: Option<usize>,
     error: Option<SimplifiedReaderError>,
+    errors: Option<&'a mut Vec<LineError>>,
+    ignored: Option<&'a mut IgnoredContent>,
+    profile: Option<&'a mut ParseProfile>,
+    compat: CompatFlags,
+
+    /// Number of occurrences of each leaf label in the current tree if leaf sets are checked
+    leaf_counts: Option<Vec<u32>>,
 }
 
-impl<'a, B: TreeBuilder> InstanceVisitor for Visitor<'a, B> {
-    fn visit_header(&mut self, _lineno: usize, _num_trees: usize, num_leaves: usize) -> Action {
-        if self.num_leaves.is_some() {
-            self.error = Some(SimplifiedReaderError::MultipleHeaders);
-            return Action::Terminate;
+impl<'a, B: TreeBuilder> Visitor<'a, B> {
+    /// Creates a visitor filling `instance`; the cancellation token of `options` is ignored
+    fn new(builder: &'a mut B, instance: &'a mut Instance<B>, options: ReadOptions<'a>) -> Self {
+        Self {
+            builder,
+            instance,
+            num_leaves: None,
+            error: None,
+            errors: options.errors,
+            ignored: options.ignored,
+            profile: options.profile,
+            compat: options.compat,
+            leaf_counts: options.check_leaf_sets.then(Vec::new),
         }
+    }
 
-        if num_leaves == 0 {
-            self.error = Some(SimplifiedReaderError::NoLeaves);
-            return Action::Terminate;
+    /// Records `error`; reading terminates unless errors are collected
+    fn fail(&mut self, lineno: usize, error: SimplifiedReaderError) -> Action {
+        match self.errors.as_mut() {
+            Some(errors) => {
+                errors.push(LineError {
+                    lineno: Some(lineno),
+                    error,
+                });
+                Action::Continue
+            }
+            None => {
+                self.error = Some(error);
+                Action::Terminate
+            }
         }
-
-        self.num_leaves = Some(num_leaves);
-        self.instance.num_leaves = num_leaves;
-        Action::Continue
     }
 
-    fn visit_tree(&mut self, _lineno: usize, line: &str) -> Action {
+    /// Parses the first tree of `line`; if errors are collected and parsing fails, also returns
+    /// the remainder of the line behind the next `;`, where parsing can resume
+    fn parse_tree_prefix<'l>(&mut self, lineno: usize, line: &'l str) -> (Action, Option<&'l str>) {
         let num_leaves = match self.num_leaves {
             Some(x) => x,
-            None => {
-                self.error = Some(SimplifiedReaderError::NoHeader);
-                return Action::Terminate;
+            None => return (self.fail(lineno, SimplifiedReaderError::NoHeader), None),
+        };
+
+        let root_id = NodeNumbering::new(num_leaves).root_id(self.instance.trees.len());
+
+        let mut tree_comments = Vec::new();
+        let mut on_comment = |_, text: &str| tree_comments.push((lineno, text.to_owned()));
+
+        let mut lexer = Lexer::new(line);
+        self.compat.configure_lexer(&mut lexer);
+        if self.ignored.is_some() && self.compat.comments {
+            lexer.report_comments(&mut on_comment);
+        }
+
+        if let Some(counts) = self.leaf_counts.as_mut() {
+            counts.clear();
+            counts.resize(num_leaves + 1, 0);
+        }
+
+        let labels = &mut self.instance.labels;
+        let mut leaf_counts = self.leaf_counts.as_mut();
+        let mut out_of_range = None;
+        let mut visit_label = |label: Label| {
+            labels.insert(label);
+            if !(1..=num_leaves).contains(&(label.0 as usize)) {
+                out_of_range.get_or_insert(label);
+            } else if let Some(counts) = leaf_counts.as_mut() {
+                counts[label.0 as usize] += 1;
             }
         };
 
-        let root_id = (self.instance.trees.len() + 1) * (num_leaves - 1) + 2;
+        let result = match self.profile.as_mut() {
+            None => {
+                let mut hook = LeafPathHook {
+                    path: Vec::new(),
+                    on_leaf: |label, _: &[NodeIdx]| visit_label(label),
+                };
+                parse_tree(self.builder, &mut lexer, root_id, &mut hook)
+            }
+            Some(profile) => {
+                let (mut num_leaves, mut depth) = (0usize, 0);
+                let start = Instant::now();
+                let mut hook = LeafPathHook {
+                    path: Vec::new(),
+                    on_leaf: |label, path: &[NodeIdx]| {
+                        visit_label(label);
+                        num_leaves += 1;
+                        depth = depth.max(path.len());
+                    },
+                };
+                let result = parse_tree(self.builder, &mut lexer, root_id, &mut hook);
 
-        let tree = match self
-            .builder
-            .parse_newick_from_str(line, NodeIdx(root_id as u32))
-        {
+                profile.trees.push(TreeProfile {
+                    lineno,
+                    duration: start.elapsed(),
+                    num_nodes: (2 * num_leaves).saturating_sub(1),
+                    depth,
+                });
+                result
+            }
+        };
+
+        let tree = match result {
             Ok(t) => t,
             Err(e) => {
-                self.error = Some(SimplifiedReaderError::NewickError(e));
-                return Action::Terminate;
+                // resynchronize behind the next `;` if errors are collected
+                let resume = self.errors.is_some().then(|| {
+                    let offset = e.span().byte_range.start.min(line.len());
+                    line[offset..]
+                        .find(';')
+                        .map(|i| line[offset + i + 1..].trim())
+                });
+                let action = self.fail(lineno, SimplifiedReaderError::NewickError(e));
+                return (action, resume.flatten().filter(|rest| !rest.is_empty()));
             }
         };
 
+        if let Some(label) = out_of_range
+            && !self.compat.out_of_range_labels
+        {
+            let tree = self.instance.trees.len();
+            let action = self.fail(
+                lineno,
+                SimplifiedReaderError::LabelOutOfRange { tree, label },
+            );
+            return (action, None);
+        }
+
+        if let Some(counts) = self.leaf_counts.as_ref() {
+            let labels_with = |pred: fn(u32) -> bool| -> Vec<Label> {
+                (1..counts.len())
+                    .filter(|&label| pred(counts[label]))
+                    .map(|label| Label(label as LabelValue))
+                    .collect()
+            };
+            let missing = labels_with(|count| count == 0);
+            let duplicated = labels_with(|count| count > 1);
+
+            if !missing.is_empty() || !duplicated.is_empty() {
+                let tree = self.instance.trees.len();
+                let action = self.fail(
+                    lineno,
+                    SimplifiedReaderError::InconsistentLeafSet {
+                        tree,
+                        missing,
+                        duplicated,
+                    },
+                );
+                return (action, None);
+            }
+        }
+
         self.instance.trees.push(tree);
+        self.instance.tree_linenos.push(lineno);
+        if let Some(ignored) = self.ignored.as_mut() {
+            ignored.tree_comments.append(&mut tree_comments);
+        }
+
+        (Action::Continue, None)
+    }
+}
+
+/// Maximum number of inner nodes and of leaves announced to [`TreeBuilder::reserve`]; the
+/// counts of the header are untrusted, so larger instances grow their storage on demand
+const MAX_RESERVED_NODES: usize = 1 << 20;
+
+/// Optional settings of [`Instance::try_read_impl`]
+#[derive(Default)]
+struct ReadOptions<'a> {
+    token: Option<CancellationToken>,
+    profile: Option<&'a mut ParseProfile>,
+    errors: Option<&'a mut Vec<LineError>>,
+    ignored: Option<&'a mut IgnoredContent>,
+    compat: CompatFlags,
+    check_leaf_sets: bool,
+    reject_empty: bool,
+    reader_errors: Option<&'a mut Vec<ReaderError>>,
+}
+
+impl<'a, B: TreeBuilder> InstanceVisitor for Visitor<'a, B> {
+    fn visit_header(&mut self, lineno: usize, num_trees: usize, num_leaves: usize) -> Action {
+        if self.num_leaves.is_some() {
+            return self.fail(lineno, SimplifiedReaderError::MultipleHeaders);
+        }
 
+        if num_leaves == 0 {
+            self.error = Some(SimplifiedReaderError::NoLeaves);
+            return Action::Terminate;
+        }
+
+        self.num_leaves = Some(num_leaves);
+        self.instance.num_leaves = num_leaves;
+        self.builder.reserve(
+            num_trees
+                .saturating_mul(num_leaves - 1)
+                .min(MAX_RESERVED_NODES),
+            num_trees.saturating_mul(num_leaves).min(MAX_RESERVED_NODES),
+        );
         Action::Continue
     }
 
-    fn visit_approx_line(&mut self, _lineno: usize, param_a: f64, param_b: usize) -> Action {
+    fn visit_tree(&mut self, lineno: usize, line: &str) -> Action {
+        // a loop rather than recursion, as a line may contain arbitrarily many broken trees
+        let mut rest = line;
+        loop {
+            match self.parse_tree_prefix(lineno, rest) {
+                (_, Some(next)) => rest = next,
+                (action, None) => return action,
+            }
+        }
+    }
+
+    fn visit_approx_line(&mut self, lineno: usize, param_a: f64, param_b: usize) -> Action {
         if self.instance.approx.is_some() {
-            self.error = Some(SimplifiedReaderError::MultipleApprox);
-            return Action::Terminate;
+            return self.fail(lineno, SimplifiedReaderError::MultipleApprox);
         }
 
         self.instance.approx = Some((param_a, param_b));
         Action::Continue
     }
 
+    fn visit_comment(&mut self, lineno: usize, line: &str) -> Action {
+        if let Some(ignored) = self.ignored.as_mut() {
+            ignored.comment_lines.push((lineno, line.to_owned()));
+        }
+        Action::Continue
+    }
+
+    fn visit_unrecognized_hash_line(&mut self, lineno: usize, line: &str) -> Action {
+        if let Some(ignored) = self.ignored.as_mut() {
+            ignored
+                .unrecognized_hash_lines
+                .push((lineno, line.to_owned()));
+        }
+        Action::Continue
+    }
+
+    fn visit_unrecognized_line(&mut self, lineno: usize, line: &str) -> Action {
+        if let Some(ignored) = self.ignored.as_mut() {
+            ignored.unrecognized_lines.push((lineno, line.to_owned()));
+        }
+        Action::Continue
+    }
+
+    fn visit_stride_line(&mut self, lineno: usize, _line: &str, key: &str, value: &str) -> Action {
+        if key == FORMAT_VERSION_KEY {
+            let version = value
+                .parse::<FormatVersion>()
+                .and_then(|version| Ok((version, version.profile()?)));
+            match version {
+                Ok((version, profile)) => {
+                    self.instance.format_version = Some(version);
+                    self.compat = self.compat.union(profile.flags());
+                }
+                Err(e) => return self.fail(lineno, SimplifiedReaderError::FormatVersion(e)),
+            }
+        }
+
+        let value = serde_json::from_str(value)
+            .unwrap_or_else(|_| serde_json::Value::String(value.to_owned()));
+
+        if key == MULTIPLICITIES_KEY && self.instance.multiplicities.is_none() {
+            match parse_multiplicities(&value) {
+                Ok(multiplicities) => self.instance.multiplicities = Some(multiplicities),
+                Err(e) => return self.fail(lineno, SimplifiedReaderError::Multiplicities(e)),
+            }
+        }
+
+        self.instance.stride_params.push((key.to_owned(), value));
+        Action::Continue
+    }
+
     const VISIT_PARAM_TREE_DECOMPOSITION: bool = true;
     fn visit_param_tree_decomposition(
         &mut self,
@@ -128,6 +1361,15 @@ impl<'a, B: TreeBuilder> InstanceVisitor for Visitor<'a, B> {
     }
 }
 
+/// Error reported by [`Instance::try_read_collecting_errors`]
+#[derive(Debug, Error)]
+#[error("{}: {error}", lineno.map_or("?".to_owned(), |l| format!("line {}", l + 1)))]
+pub struct LineError {
+    /// Line (0-based) of the error, if known
+    pub lineno: Option<usize>,
+    pub error: SimplifiedReaderError,
+}
+
 #[derive(Debug, Error)]
 pub enum SimplifiedReaderError {
     #[error(transparent)]
@@ -153,11 +1395,39 @@ pub enum SimplifiedReaderError {
 
     #[error("Multiple approx lines (#a) found")]
     MultipleApprox,
+
+    #[error(transparent)]
+    FormatVersion(#[from] FormatVersionError),
+
+    #[error("Invalid multiplicities: {0}")]
+    Multiplicities(#[from] MultiplicitiesError),
+
+    #[error("Leaf label {} of tree {tree} is not in the range 1..=n declared in the header", label.0)]
+    LabelOutOfRange { tree: usize, label: Label },
+
+    #[error(
+        "Tree {tree} does not contain each leaf 1..=n exactly once (missing: {}; duplicated: {})",
+        format_labels(missing),
+        format_labels(duplicated)
+    )]
+    InconsistentLeafSet {
+        tree: usize,
+        missing: Vec<Label>,
+        duplicated: Vec<Label>,
+    },
+}
+
+fn format_labels(labels: &[Label]) -> String {
+    if labels.is_empty() {
+        return "none".into();
+    }
+    let labels: Vec<_> = labels.iter().map(|label| label.0.to_string()).collect();
+    labels.join(", ")
 }
 
 #[cfg(test)]
 mod test {
-    use crate::binary_tree::IndexedBinTreeBuilder;
+    use crate::binary_tree::{BinTreeBuilder, IndexedBinTreeBuilder};
 
     use super::*;
     use std::{fs::File, io::BufReader};
@@ -176,4 +1446,515 @@ mod test {
         assert_eq!(instance.tree_decomposition.unwrap().treewidth, 2);
         assert_eq!(instance.approx, Some((1.2, 1337)));
     }
+
+    #[test]
+    fn duplicate_trees() {
+        let input = "#p 5 4\n((1,2),(3,4));\n(((1,2),3),4);\n((4,3),(2,1));\n((1,3),(2,4));\n(4,(3,(2,1)));\n";
+        let instance =
+            Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+        assert_eq!(
+            instance.find_duplicate_trees(),
+            vec![vec![0, 2], vec![1, 4]]
+        );
+
+        let input = "#p 2 3\n((1,2),3);\n((1,3),2);\n";
+        let instance =
+            Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+        assert!(instance.find_duplicate_trees().is_empty());
+    }
+
+    #[test]
+    fn merge_duplicate_trees() {
+        use crate::{binary_tree::TreeWithNodeIdx, newick::NewickWriter};
+
+        let input = "#p 4 3\n((1,2),3);\n(1,(2,3));\n(2,(1,3));\n(3,(2,1));\n#x treedecomp [2,[[1,2,3]],[]]\n";
+        let mut builder = IndexedBinTreeBuilder::default();
+        let mut instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+
+        assert_eq!(instance.merge_duplicate_trees(&mut builder), 1);
+        assert_eq!(instance.multiplicities, Some(vec![2, 1, 1]));
+        assert_eq!(instance.tree_linenos, vec![1, 2, 3]);
+        assert!(instance.tree_decomposition.is_none());
+
+        // inner nodes are numbered as if the instance was read without the removed tree
+        let numbering = instance.node_numbering();
+        for (i, tree) in instance.trees.iter().enumerate() {
+            assert_eq!(tree.top_down().node_idx(), numbering.root_id(i));
+        }
+        assert_eq!(
+            instance.trees[2].top_down().to_newick_string(),
+            "(2,(1,3));"
+        );
+
+        assert_eq!(instance.merge_duplicate_trees(&mut builder), 0);
+        assert_eq!(instance.multiplicities, Some(vec![2, 1, 1]));
+    }
+
+    #[test]
+    fn stride_params() {
+        let input = "#p 2 2\n#s seed 42\n#s tags [\"a\",\"b\"]\n(1,2);\n#s seed 43\n(1,2);\n";
+        let instance =
+            Instance::try_read(input.as_bytes(), &mut IndexedBinTreeBuilder::default()).unwrap();
+
+        assert_eq!(instance.stride_params.len(), 3);
+        assert_eq!(instance.stride_params[2].0, "seed");
+        assert_eq!(instance.param::<u64>("seed").unwrap().unwrap(), 42);
+        assert_eq!(
+            instance.param::<Vec<String>>("tags").unwrap().unwrap(),
+            vec!["a", "b"]
+        );
+        assert!(instance.param::<u64>("tags").unwrap().is_err());
+    }
+
+    #[test]
+    fn reserve_from_header() {
+        #[derive(Default)]
+        struct CountingBuilder {
+            reserved: Option<(usize, usize)>,
+            created: (usize, usize),
+        }
+
+        impl TreeBuilder for CountingBuilder {
+            type Node = ();
+
+            fn new_inner(&mut self, _id: NodeIdx, _left: (), _right: ()) {
+                self.created.0 += 1;
+            }
+
+            fn new_leaf(&mut self, _label: crate::binary_tree::Label) {
+                self.created.1 += 1;
+            }
+
+            fn reserve(&mut self, num_inner: usize, num_leaves: usize) {
+                self.reserved = Some((num_inner, num_leaves));
+            }
+        }
+
+        let mut builder = CountingBuilder::default();
+        let input = BufReader::new(File::open("examples/tiny01.nw").unwrap());
+        Instance::try_read(input, &mut builder).unwrap();
+        assert_eq!(builder.reserved, Some((10, 12)));
+        assert_eq!(builder.reserved, Some(builder.created));
+
+        // huge counts in the header must not translate into huge allocations, regardless of
+        // whether the remaining input matches them
+        let mut builder = CountingBuilder::default();
+        let input = format!("#p {} {}\n(1,2);\n", usize::MAX, 1usize << 40);
+        let _ = Instance::try_read(input.as_bytes(), &mut builder);
+        assert_eq!(
+            builder.reserved,
+            Some((MAX_RESERVED_NODES, MAX_RESERVED_NODES))
+        );
+    }
+
+    #[test]
+    fn induced_subforest() {
+        let input = BufReader::new(File::open("examples/tiny01.nw").unwrap());
+        let instance = Instance::try_read(input, &mut IndexedBinTreeBuilder::default()).unwrap();
+
+        let sub = instance.induced_subforest(3, 1).unwrap();
+        assert_eq!(sub.vertices, vec![1, 11, 15]);
+        assert_eq!(sub.tree_edges, vec![vec![(11, 1)], vec![(15, 1)]]);
+
+        let sub = instance.induced_subforest(2, 1).unwrap();
+        assert_eq!(
+            sub.tree_edges,
+            vec![
+                vec![(7, 8), (7, 11), (11, 1), (11, 2)],
+                vec![(15, 16), (16, 2), (15, 1)]
+            ]
+        );
+
+        assert!(instance.induced_subforest(12, 1).is_none());
+    }
+
+    /// API contract: trees and parameters are reported in file order
+    #[test]
+    fn ordered_accessors() {
+        let input = "#s b 1\n#p 3 3\n((1,2),3);\n#s a 2\n\n(1,(2,3));\n#s b 3\n((1,3),2);\n";
+        let mut builder = IndexedBinTreeBuilder::default();
+        let mut instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+
+        let check = |instance: &Instance<IndexedBinTreeBuilder>| {
+            let trees: Vec<_> = instance
+                .ordered_trees()
+                .map(|(i, lineno, tree)| (i, lineno, crate::binary_tree::leaf_order(tree)))
+                .collect();
+            let labels = |x: [LabelValue; 3]| x.map(crate::binary_tree::Label).to_vec();
+            assert_eq!(
+                trees,
+                vec![
+                    (0, 2, labels([1, 2, 3])),
+                    (1, 5, labels([1, 2, 3])),
+                    (2, 7, labels([1, 3, 2]))
+                ]
+            );
+
+            let params: Vec<_> = instance
+                .ordered_params()
+                .map(|(k, v)| (k.to_owned(), v.as_u64().unwrap()))
+                .collect();
+            assert_eq!(
+                params,
+                vec![("b".into(), 1), ("a".into(), 2), ("b".into(), 3)]
+            );
+        };
+
+        check(&instance);
+        instance.compact_labels(&mut builder);
+        check(&instance);
+    }
+
+    #[test]
+    fn profiled_read() {
+        let input = BufReader::new(File::open("examples/tiny01.nw").unwrap());
+        let (instance, profile) =
+            Instance::try_read_profiled(input, &mut IndexedBinTreeBuilder::default()).unwrap();
+
+        let stats: Vec<_> = profile
+            .trees
+            .iter()
+            .map(|t| (t.lineno, t.num_nodes, t.depth))
+            .collect();
+        assert_eq!(stats, vec![(5, 11, 3), (6, 11, 5)]);
+        assert_eq!(instance.tree_linenos, vec![5, 6]);
+        assert!(profile.slowest().is_some());
+        assert!(profile.total_duration() >= profile.slowest().unwrap().duration);
+    }
+
+    #[test]
+    fn cancelled_read() {
+        let input = BufReader::new(File::open("examples/tiny01.nw").unwrap());
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let res =
+            Instance::try_read_cancellable(input, &mut IndexedBinTreeBuilder::default(), &token);
+        assert!(matches!(
+            res,
+            Err(SimplifiedReaderError::ReaderError(ReaderError::Cancelled {
+                lineno: 0
+            }))
+        ));
+    }
+
+    #[test]
+    fn collecting_errors() {
+        let input = "(1,2);\n#p 2 2\n#a 1.0 0\n(1,2);\n#a 2.0 0\n(1,x);\n#x unknown 1\n(2,1);\n";
+        let errors =
+            Instance::try_read_collecting_errors(input.as_bytes(), &mut BinTreeBuilder::default())
+                .unwrap_err();
+
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(
+            errors[0],
+            LineError {
+                lineno: Some(0),
+                error: SimplifiedReaderError::NoHeader
+            }
+        ));
+        assert!(matches!(
+            errors[1].error,
+            SimplifiedReaderError::MultipleApprox
+        ));
+        assert!(matches!(
+            errors[2].error,
+            SimplifiedReaderError::NewickError(_)
+        ));
+        assert!(matches!(
+            errors[3],
+            LineError {
+                lineno: Some(6),
+                error: SimplifiedReaderError::ReaderError(ReaderError::UnknownParameter {
+                    lineno: 6,
+                    ..
+                })
+            }
+        ));
+
+        // reading continues after invalid `#` lines and behind the `;` of invalid trees
+        let input = "#p 3 3\n#s\n#a x 1\n((1,2),3;(1,x);(1,(2,3));\n(3,(1,2));\n";
+        let errors =
+            Instance::try_read_collecting_errors(input.as_bytes(), &mut BinTreeBuilder::default())
+                .unwrap_err();
+        let summary: Vec<_> = errors
+            .iter()
+            .map(|e| {
+                (
+                    e.lineno,
+                    matches!(e.error, SimplifiedReaderError::NewickError(_)),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Some(1), false),
+                (Some(2), false),
+                (Some(3), true),
+                (Some(3), true)
+            ]
+        );
+
+        let input = "#p 2 2\n(1,2);\n(2,1);\n";
+        let instance =
+            Instance::try_read_collecting_errors(input.as_bytes(), &mut BinTreeBuilder::default())
+                .unwrap();
+        assert_eq!(instance.trees.len(), 2);
+    }
+
+    #[test]
+    fn many_invalid_trees_on_one_line() {
+        // each `(;` is an invalid tree; resynchronizing must not recurse once per tree
+        const N: usize = 2_000_000;
+        let input = format!("#p 1 1\n{}\n", "(;".repeat(N));
+        let errors =
+            Instance::try_read_collecting_errors(input.as_bytes(), &mut BinTreeBuilder::default())
+                .unwrap_err();
+        assert_eq!(errors.len(), N);
+        assert!(
+            errors
+                .iter()
+                .all(|e| matches!(e.error, SimplifiedReaderError::NewickError(_)))
+        );
+    }
+
+    #[test]
+    fn empty_instances() {
+        let mut builder = BinTreeBuilder::default();
+        for input in ["", " \n\n", "# only a comment\n"] {
+            let instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+            assert_eq!(instance.num_leaves, 0);
+            assert!(instance.trees.is_empty());
+
+            assert!(matches!(
+                Instance::try_read_nonempty(input.as_bytes(), &mut builder),
+                Err(SimplifiedReaderError::ReaderError(ReaderError::EmptyInput))
+            ));
+        }
+
+        for input in ["#p 0 3\n", "#p 2 3\n"] {
+            let instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+            assert_eq!(instance.num_leaves, 3);
+            assert!(instance.trees.is_empty());
+
+            assert!(matches!(
+                Instance::try_read_nonempty(input.as_bytes(), &mut builder),
+                Err(SimplifiedReaderError::ReaderError(ReaderError::NoTrees {
+                    lineno: 0
+                }))
+            ));
+        }
+
+        // the header error takes precedence
+        assert!(matches!(
+            Instance::try_read_nonempty("#p 0 0\n".as_bytes(), &mut builder),
+            Err(SimplifiedReaderError::NoLeaves)
+        ));
+    }
+
+    #[test]
+    fn bom_and_crlf() {
+        let input = "\u{FEFF}#p 2 3\r\n((1,2),3);\r\n(1,(2,3));\r\n";
+        let mut builder = BinTreeBuilder::default();
+        assert!(Instance::try_read(input.as_bytes(), &mut builder).is_err());
+
+        let compat = CompatFlags {
+            bom_and_crlf: true,
+            ..Default::default()
+        };
+        let instance = Instance::try_read_compat(input.as_bytes(), &mut builder, compat).unwrap();
+        assert_eq!(instance.num_leaves, 3);
+        assert_eq!(instance.trees.len(), 2);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn read_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut builder = BinTreeBuilder::default();
+
+        let input = "\u{FEFF}#p 2 3\r\n#s multiplicities [2,1]\r\n((1,2),3);\r\n(1,(2,3));\r\n";
+        let compat = CompatFlags {
+            bom_and_crlf: true,
+            ..Default::default()
+        };
+        let expected = Instance::try_read_compat(input.as_bytes(), &mut builder, compat).unwrap();
+        let instance = runtime
+            .block_on(Instance::try_read_async(
+                input.as_bytes(),
+                &mut builder,
+                compat,
+            ))
+            .unwrap();
+        assert_eq!(instance.trees, expected.trees);
+        assert_eq!(instance.multiplicities, Some(vec![2, 1]));
+
+        assert!(
+            runtime
+                .block_on(Instance::try_read_async(
+                    "#p 1 3\n((1,2),3;\n".as_bytes(),
+                    &mut builder,
+                    compat
+                ))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn checked_leaf_sets() {
+        let mut builder = BinTreeBuilder::default();
+        let valid = "#p 2 4\n((1,2),(3,4));\n(4,(3,(2,1)));\n";
+        assert!(Instance::try_read_checked(valid.as_bytes(), &mut builder).is_ok());
+
+        let input = "#p 3 4\n((1,2),(3,4));\n(1,(2,4));\n((3,3),(3,1));\n";
+        let err = Instance::try_read_checked(input.as_bytes(), &mut builder).unwrap_err();
+        assert!(matches!(
+            err,
+            SimplifiedReaderError::InconsistentLeafSet { tree: 1, ref missing, ref duplicated }
+                if missing == &[Label(3)] && duplicated.is_empty()
+        ));
+
+        let input = "#p 1 4\n((3,3),(3,1));\n";
+        let err = Instance::try_read_checked(input.as_bytes(), &mut builder).unwrap_err();
+        assert!(matches!(
+            err,
+            SimplifiedReaderError::InconsistentLeafSet { tree: 0, ref missing, ref duplicated }
+                if missing == &[Label(2), Label(4)] && duplicated == &[Label(3)]
+        ));
+    }
+
+    #[test]
+    fn multiplicities() {
+        let mut builder = BinTreeBuilder::default();
+        let instance =
+            Instance::try_read("#p 2 2\n(1,2);\n(2,1);\n".as_bytes(), &mut builder).unwrap();
+        assert_eq!(instance.multiplicities, None);
+        assert_eq!(instance.total_multiplicity(), 2);
+
+        let input = "#p 2 2\n#s multiplicities [2,0]\n#s multiplicities [1]\n(1,2);\n(2,1);\n";
+        let instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+        assert_eq!(instance.multiplicities, Some(vec![2, 0]));
+        assert_eq!(instance.total_multiplicity(), 2);
+
+        assert!(matches!(
+            Instance::try_read(
+                "#p 2 2\n#s multiplicities [2]\n(1,2);\n(2,1);\n".as_bytes(),
+                &mut builder
+            ),
+            Err(SimplifiedReaderError::Multiplicities(
+                MultiplicitiesError::LengthMismatch {
+                    num_trees: 2,
+                    found: 1
+                }
+            ))
+        ));
+        assert!(matches!(
+            Instance::try_read(
+                "#p 1 2\n#s multiplicities [-1]\n(1,2);\n".as_bytes(),
+                &mut builder
+            ),
+            Err(SimplifiedReaderError::Multiplicities(
+                MultiplicitiesError::InvalidEntry { index: 0, .. }
+            ))
+        ));
+    }
+
+    #[test]
+    fn compact_labels() {
+        use crate::{binary_tree::*, newick::NewickWriter};
+
+        // header claims 4 leaves, but labels are 0-based; inner ids are 5..=7 and 8..=10
+        let input = "#p 2 4\n((0,1),(2,3));\n(0,(1,(2,3)));\n#x treedecomp [2,[[0,5],[6,9],[10,3]],[[1,2],[2,3]]]\n";
+        let mut builder = IndexedBinTreeBuilder::default();
+        assert!(matches!(
+            Instance::try_read(input.as_bytes(), &mut builder),
+            Err(SimplifiedReaderError::LabelOutOfRange {
+                tree: 0,
+                label: Label(0)
+            })
+        ));
+
+        let compat = CompatFlags {
+            out_of_range_labels: true,
+            ..Default::default()
+        };
+        let mut instance =
+            Instance::try_read_compat(input.as_bytes(), &mut builder, compat).unwrap();
+        assert!(instance.labels.is_zero_based());
+        assert_eq!(instance.labels.max_label(), Some(Label(3)));
+
+        let mapping = instance.compact_labels(&mut builder);
+        assert_eq!(mapping, (0..4).map(Label).collect::<Vec<_>>());
+        assert_eq!(instance.num_leaves, 4);
+        assert!(instance.labels.is_contiguous());
+        assert_eq!(instance.labels.max_label(), Some(Label(4)));
+        assert_eq!(
+            instance.trees[0].top_down().to_newick_string(),
+            "((1,2),(3,4));"
+        );
+        assert_eq!(instance.trees[1].node_idx(), NodeIdx::new(8));
+
+        let td = instance.tree_decomposition.unwrap();
+        assert_eq!(td.bags, vec![vec![1, 5], vec![6, 9], vec![10, 4]]);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn shuffle_rotations() {
+        use crate::binary_tree::*;
+        use rand::SeedableRng;
+        use std::collections::HashMap;
+
+        fn clusters(
+            node: &IndexedBinTree,
+            out: &mut HashMap<u32, Vec<LabelValue>>,
+        ) -> Vec<LabelValue> {
+            match node.top_down().visit() {
+                NodeType::Leaf(Label(l)) => vec![l],
+                NodeType::Inner(l, r) => {
+                    let mut cluster = clusters(l, out);
+                    cluster.extend(clusters(r, out));
+                    cluster.sort();
+                    out.insert(node.node_idx().0, cluster.clone());
+                    cluster
+                }
+            }
+        }
+
+        // every inner node is its own bag
+        let input = "#p 2 5\n(((1,2),3),(4,5));\n(1,(2,(3,(4,5))));\n#x treedecomp [1,[[6],[7],[8],[9],[10],[11],[12],[13]],[]]\n";
+        let mut builder = IndexedBinTreeBuilder::default();
+        let mut instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+
+        let mut before = HashMap::new();
+        for tree in &instance.trees {
+            clusters(tree, &mut before);
+        }
+        let bags_before = instance.tree_decomposition.clone().unwrap().bags;
+
+        let mut rng = rand_pcg::Pcg64Mcg::seed_from_u64(3);
+        let mut changed = false;
+        for _ in 0..10 {
+            instance.shuffle_rotations(&mut rng, &mut builder);
+
+            let mut after = HashMap::new();
+            for tree in &instance.trees {
+                clusters(tree, &mut after);
+            }
+
+            let mut ids: Vec<_> = after.keys().copied().collect();
+            ids.sort();
+            assert_eq!(ids, (6..=13).collect::<Vec<_>>());
+
+            let bags_after = &instance.tree_decomposition.as_ref().unwrap().bags;
+            for (old, new) in bags_before.iter().zip(bags_after) {
+                assert_eq!(before[&old[0]], after[&new[0]]);
+                changed |= old != new;
+            }
+        }
+        assert!(changed);
+    }
 }
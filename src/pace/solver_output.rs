@@ -0,0 +1,212 @@
+//! Separation of the standard output of a solver into log lines and the solution payload.
+//!
+//! By the PACE output convention, lines starting with `#` (comments and stride lines such as
+//! `#s improvement ...`) are ignored by the verifier, while all other non-empty lines form the
+//! solution.
+use crate::{
+    binary_tree::{NodeIdx, TopDownCursor, TreeBuilder},
+    forest::{Forest, ForestError},
+};
+use std::io::{BufRead, Write};
+
+/// Channel a line of solver output belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputChannel {
+    /// Lines starting with `#`
+    Log,
+
+    /// All other non-empty lines
+    Payload,
+}
+
+impl OutputChannel {
+    /// Classifies a single line (without line terminator); returns `None` for lines
+    /// consisting of whitespace only
+    pub fn of_line(line: &str) -> Option<Self> {
+        let line = line.trim_start();
+        if line.is_empty() {
+            None
+        } else if line.starts_with('#') {
+            Some(Self::Log)
+        } else {
+            Some(Self::Payload)
+        }
+    }
+}
+
+/// Lines of a solver output split by [`OutputChannel`]; each line is stored with its
+/// line number (0-based) in the combined stream and without line terminator.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SplitOutput {
+    pub log: Vec<(usize, String)>,
+    pub payload: Vec<(usize, String)>,
+}
+
+impl SplitOutput {
+    /// Reads the combined output from `reader` and splits it into both channels.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::pace::solver_output::*;
+    ///
+    /// let stdout = "# starting\n(1,(2,3));\n#s improvement {\"time\":0.5,\"score\":1}\n\n4;\n";
+    /// let split = SplitOutput::read(stdout.as_bytes()).unwrap();
+    ///
+    /// assert_eq!(split.payload_text(), "(1,(2,3));\n4;\n");
+    /// assert_eq!(split.log[1], (2, "#s improvement {\"time\":0.5,\"score\":1}".to_string()));
+    /// ```
+    pub fn read(reader: impl BufRead) -> std::io::Result<Self> {
+        let mut result = Self::default();
+        for_each_line(reader, |lineno, channel, line| {
+            let target = match channel {
+                OutputChannel::Log => &mut result.log,
+                OutputChannel::Payload => &mut result.payload,
+            };
+            target.push((lineno, line.to_owned()));
+            Ok(())
+        })?;
+        Ok(result)
+    }
+
+    /// Returns the payload lines, each terminated by `\n`, e.g. to pass them to a verifier
+    pub fn payload_text(&self) -> String {
+        self.payload
+            .iter()
+            .flat_map(|(_, line)| [line.as_str(), "\n"])
+            .collect()
+    }
+
+    /// Parses the payload as a forest over `1..=num_leaves` (see [`Forest::parse_newick`]) and
+    /// checks that the leaf sets of its trees partition the universe, as required of a solution.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, forest::*, pace::solver_output::*};
+    ///
+    /// let stdout = "# starting\n(1,3);\n#s improvement {\"time\":0.5,\"score\":1}\n2;\n";
+    /// let split = SplitOutput::read(stdout.as_bytes()).unwrap();
+    /// let mut builder = BinTreeBuilder::default();
+    ///
+    /// let forest = split.parse_forest(3, &mut builder, |_| NodeIdx::new(0)).unwrap();
+    /// assert_eq!(forest.to_newick_string(), "(1,3);\n2;\n");
+    /// assert_eq!(
+    ///     split.parse_forest(4, &mut builder, |_| NodeIdx::new(0)).unwrap_err(),
+    ///     ForestError::MissingLabel(Label(4))
+    /// );
+    /// ```
+    pub fn parse_forest<B: TreeBuilder>(
+        &self,
+        num_leaves: usize,
+        builder: &mut B,
+        root_id: impl FnMut(usize) -> NodeIdx,
+    ) -> Result<Forest<B>, ForestError>
+    where
+        for<'a> &'a B::Node: TopDownCursor,
+    {
+        let forest = Forest::parse_newick(&self.payload_text(), num_leaves, builder, root_id)?;
+        forest.validate_partition()?;
+        Ok(forest)
+    }
+}
+
+/// Copies the lines of `reader` into `log` and `payload` according to their [`OutputChannel`]
+/// without buffering the whole output; whitespace-only lines are dropped and lines are
+/// terminated by `\n`.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::solver_output::*;
+///
+/// let (mut log, mut payload) = (Vec::new(), Vec::new());
+/// split_solver_output("# t=0.1s\r\n(1,2);\r\n".as_bytes(), &mut log, &mut payload).unwrap();
+/// assert_eq!(log, b"# t=0.1s\n");
+/// assert_eq!(payload, b"(1,2);\n");
+/// ```
+pub fn split_solver_output(
+    reader: impl BufRead,
+    log: &mut impl Write,
+    payload: &mut impl Write,
+) -> std::io::Result<()> {
+    for_each_line(reader, |_, channel, line| match channel {
+        OutputChannel::Log => writeln!(log, "{line}"),
+        OutputChannel::Payload => writeln!(payload, "{line}"),
+    })
+}
+
+/// Invokes `callback` with line number, channel, and content of each non-empty line;
+/// `\r\n` line terminators are accepted
+fn for_each_line(
+    mut reader: impl BufRead,
+    mut callback: impl FnMut(usize, OutputChannel, &str) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut line = String::new();
+    let mut lineno = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+
+        let content = line.trim_end_matches(['\n', '\r']);
+        if let Some(channel) = OutputChannel::of_line(content) {
+            callback(lineno, channel, content)?;
+        }
+        lineno += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binary_tree::{BinTreeBuilder, Label};
+
+    #[test]
+    fn channels() {
+        assert_eq!(OutputChannel::of_line(""), None);
+        assert_eq!(OutputChannel::of_line(" \t"), None);
+        assert_eq!(OutputChannel::of_line("#"), Some(OutputChannel::Log));
+        assert_eq!(
+            OutputChannel::of_line("  # indented"),
+            Some(OutputChannel::Log)
+        );
+        assert_eq!(OutputChannel::of_line("1;"), Some(OutputChannel::Payload));
+        assert_eq!(
+            OutputChannel::of_line("((1,2),3);"),
+            Some(OutputChannel::Payload)
+        );
+    }
+
+    #[test]
+    fn split() {
+        let stdout = "#s a 1\n(1,2);\n  \n# done\n3;";
+        let split = SplitOutput::read(stdout.as_bytes()).unwrap();
+        assert_eq!(
+            split.log,
+            vec![(0, "#s a 1".to_string()), (3, "# done".to_string())]
+        );
+        assert_eq!(
+            split.payload,
+            vec![(1, "(1,2);".to_string()), (4, "3;".to_string())]
+        );
+    }
+
+    #[test]
+    fn forest() {
+        let mut builder = BinTreeBuilder::default();
+        let split = SplitOutput::read("#s a 1\n(1,\n# interleaved\n2);\n3;".as_bytes()).unwrap();
+        let forest = split
+            .parse_forest(3, &mut builder, |_| NodeIdx::new(0))
+            .unwrap();
+        assert_eq!(forest.to_newick_string(), "(1,2);\n3;\n");
+
+        assert!(matches!(
+            split.parse_forest(2, &mut builder, |_| NodeIdx::new(0)),
+            Err(ForestError::LabelOutOfRange(Label(3)))
+        ));
+        let split = SplitOutput::read("(1,2".as_bytes()).unwrap();
+        assert!(matches!(
+            split.parse_forest(2, &mut builder, |_| NodeIdx::new(0)),
+            Err(ForestError::Parser(_))
+        ));
+    }
+}
@@ -0,0 +1,382 @@
+//! Combinators composing [`InstanceVisitor`]s, so that, e.g., statistics can be collected
+//! while trees are built in the same pass over the input.
+use crate::pace::{
+    parameters::tree_decomposition::TreeDecomposition,
+    reader::{Action, InstanceVisitor},
+};
+
+/// Ignores all elements
+impl InstanceVisitor for () {}
+
+impl<V: InstanceVisitor> InstanceVisitor for &mut V {
+    fn visit_header(&mut self, lineno: usize, num_trees: usize, num_leaves: usize) -> Action {
+        (**self).visit_header(lineno, num_trees, num_leaves)
+    }
+    fn visit_approx_line(&mut self, lineno: usize, param_a: f64, param_b: usize) -> Action {
+        (**self).visit_approx_line(lineno, param_a, param_b)
+    }
+    fn visit_tree(&mut self, lineno: usize, line: &str) -> Action {
+        (**self).visit_tree(lineno, line)
+    }
+    fn visit_line_with_extra_whitespace(&mut self, lineno: usize, line: &str) -> Action {
+        (**self).visit_line_with_extra_whitespace(lineno, line)
+    }
+    fn visit_unrecognized_hash_line(&mut self, lineno: usize, line: &str) -> Action {
+        (**self).visit_unrecognized_hash_line(lineno, line)
+    }
+    fn visit_comment(&mut self, lineno: usize, line: &str) -> Action {
+        (**self).visit_comment(lineno, line)
+    }
+    fn visit_unrecognized_line(&mut self, lineno: usize, line: &str) -> Action {
+        (**self).visit_unrecognized_line(lineno, line)
+    }
+    fn visit_stride_line(&mut self, lineno: usize, line: &str, key: &str, value: &str) -> Action {
+        (**self).visit_stride_line(lineno, line, key, value)
+    }
+
+    const VISIT_PARAM_TREE_DECOMPOSITION: bool = V::VISIT_PARAM_TREE_DECOMPOSITION;
+    fn visit_param_tree_decomposition(&mut self, lineno: usize, td: TreeDecomposition) -> Action {
+        (**self).visit_param_tree_decomposition(lineno, td)
+    }
+}
+
+/// Passes each element to two visitors. A visitor that terminates receives no further
+/// elements; reading terminates once both visitors did. Nest tees to combine more visitors.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::{reader::*, visitors::*};
+///
+/// let mut first = CountingVisitor::new(());
+/// let mut second = CountingVisitor::new(());
+/// let mut tee = TeeVisitor::new(&mut first, FilterVisitor::new(&mut second, |_, line: &str| line.len() > 4));
+///
+/// InstanceReader::new(&mut tee).read("#p 2 3\n((1,2),3);\n(1);\n".as_bytes()).unwrap();
+/// assert_eq!(first.counts.trees, 2);
+/// assert_eq!(second.counts.trees, 1);
+/// ```
+pub struct TeeVisitor<A, B> {
+    pub first: A,
+    pub second: B,
+    first_done: bool,
+    second_done: bool,
+}
+
+impl<A: InstanceVisitor, B: InstanceVisitor> TeeVisitor<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            first_done: false,
+            second_done: false,
+        }
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+
+    fn forward(
+        &mut self,
+        first: impl FnOnce(&mut A) -> Action,
+        second: impl FnOnce(&mut B) -> Action,
+    ) -> Action {
+        if !self.first_done {
+            self.first_done = first(&mut self.first) == Action::Terminate;
+        }
+        if !self.second_done {
+            self.second_done = second(&mut self.second) == Action::Terminate;
+        }
+
+        if self.first_done && self.second_done {
+            Action::Terminate
+        } else {
+            Action::Continue
+        }
+    }
+}
+
+macro_rules! tee {
+    ($self:ident, $method:ident, $( $args:expr ),* $(,)? ) => {
+        $self.forward(|v| v.$method($( $args ),*), |v| v.$method($( $args ),*))
+    };
+}
+
+impl<A: InstanceVisitor, B: InstanceVisitor> InstanceVisitor for TeeVisitor<A, B> {
+    fn visit_header(&mut self, lineno: usize, num_trees: usize, num_leaves: usize) -> Action {
+        tee!(self, visit_header, lineno, num_trees, num_leaves)
+    }
+    fn visit_approx_line(&mut self, lineno: usize, param_a: f64, param_b: usize) -> Action {
+        tee!(self, visit_approx_line, lineno, param_a, param_b)
+    }
+    fn visit_tree(&mut self, lineno: usize, line: &str) -> Action {
+        tee!(self, visit_tree, lineno, line)
+    }
+    fn visit_line_with_extra_whitespace(&mut self, lineno: usize, line: &str) -> Action {
+        tee!(self, visit_line_with_extra_whitespace, lineno, line)
+    }
+    fn visit_unrecognized_hash_line(&mut self, lineno: usize, line: &str) -> Action {
+        tee!(self, visit_unrecognized_hash_line, lineno, line)
+    }
+    fn visit_comment(&mut self, lineno: usize, line: &str) -> Action {
+        tee!(self, visit_comment, lineno, line)
+    }
+    fn visit_unrecognized_line(&mut self, lineno: usize, line: &str) -> Action {
+        tee!(self, visit_unrecognized_line, lineno, line)
+    }
+    fn visit_stride_line(&mut self, lineno: usize, line: &str, key: &str, value: &str) -> Action {
+        tee!(self, visit_stride_line, lineno, line, key, value)
+    }
+
+    const VISIT_PARAM_TREE_DECOMPOSITION: bool =
+        A::VISIT_PARAM_TREE_DECOMPOSITION || B::VISIT_PARAM_TREE_DECOMPOSITION;
+    fn visit_param_tree_decomposition(&mut self, lineno: usize, td: TreeDecomposition) -> Action {
+        match (
+            A::VISIT_PARAM_TREE_DECOMPOSITION,
+            B::VISIT_PARAM_TREE_DECOMPOSITION,
+        ) {
+            (true, true) => {
+                let copy = td.clone();
+                self.forward(
+                    |v| v.visit_param_tree_decomposition(lineno, copy),
+                    |v| v.visit_param_tree_decomposition(lineno, td),
+                )
+            }
+            (true, false) => self.forward(
+                |v| v.visit_param_tree_decomposition(lineno, td),
+                |_| Action::Continue,
+            ),
+            _ => self.forward(
+                |_| Action::Continue,
+                |v| v.visit_param_tree_decomposition(lineno, td),
+            ),
+        }
+    }
+}
+
+/// Passes only the tree lines `(lineno, line)` satisfying the predicate to the inner visitor;
+/// all other elements are passed unconditionally.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::{reader::*, visitors::*};
+///
+/// // only consider the first two trees
+/// let mut visitor = CountingVisitor::new(());
+/// let mut num_trees = 0;
+/// let mut filter = FilterVisitor::new(&mut visitor, |_, _: &str| {
+///     num_trees += 1;
+///     num_trees <= 2
+/// });
+///
+/// let input = "#p 3 2\n(1,2);\n(2,1);\n(1,2);\n";
+/// InstanceReader::new(&mut filter).read(input.as_bytes()).unwrap();
+/// assert_eq!(visitor.counts.trees, 2);
+/// ```
+pub struct FilterVisitor<V, F> {
+    pub inner: V,
+    predicate: F,
+}
+
+impl<V: InstanceVisitor, F: FnMut(usize, &str) -> bool> FilterVisitor<V, F> {
+    pub fn new(inner: V, predicate: F) -> Self {
+        Self { inner, predicate }
+    }
+
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl<V: InstanceVisitor, F: FnMut(usize, &str) -> bool> InstanceVisitor for FilterVisitor<V, F> {
+    fn visit_header(&mut self, lineno: usize, num_trees: usize, num_leaves: usize) -> Action {
+        self.inner.visit_header(lineno, num_trees, num_leaves)
+    }
+    fn visit_approx_line(&mut self, lineno: usize, param_a: f64, param_b: usize) -> Action {
+        self.inner.visit_approx_line(lineno, param_a, param_b)
+    }
+    fn visit_tree(&mut self, lineno: usize, line: &str) -> Action {
+        if (self.predicate)(lineno, line) {
+            self.inner.visit_tree(lineno, line)
+        } else {
+            Action::Continue
+        }
+    }
+    fn visit_line_with_extra_whitespace(&mut self, lineno: usize, line: &str) -> Action {
+        self.inner.visit_line_with_extra_whitespace(lineno, line)
+    }
+    fn visit_unrecognized_hash_line(&mut self, lineno: usize, line: &str) -> Action {
+        self.inner.visit_unrecognized_hash_line(lineno, line)
+    }
+    fn visit_comment(&mut self, lineno: usize, line: &str) -> Action {
+        self.inner.visit_comment(lineno, line)
+    }
+    fn visit_unrecognized_line(&mut self, lineno: usize, line: &str) -> Action {
+        self.inner.visit_unrecognized_line(lineno, line)
+    }
+    fn visit_stride_line(&mut self, lineno: usize, line: &str, key: &str, value: &str) -> Action {
+        self.inner.visit_stride_line(lineno, line, key, value)
+    }
+
+    const VISIT_PARAM_TREE_DECOMPOSITION: bool = V::VISIT_PARAM_TREE_DECOMPOSITION;
+    fn visit_param_tree_decomposition(&mut self, lineno: usize, td: TreeDecomposition) -> Action {
+        self.inner.visit_param_tree_decomposition(lineno, td)
+    }
+}
+
+/// Number of elements of each kind passed to a [`CountingVisitor`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VisitCounts {
+    pub headers: usize,
+    pub approx_lines: usize,
+    pub trees: usize,
+    pub lines_with_extra_whitespace: usize,
+    pub unrecognized_hash_lines: usize,
+    pub comments: usize,
+    pub unrecognized_lines: usize,
+    pub stride_lines: usize,
+
+    /// Only counted if the inner visitor requests tree decompositions
+    pub tree_decompositions: usize,
+}
+
+/// Counts the elements of each kind and passes them to the inner visitor; use `()` as inner
+/// visitor to only count.
+///
+/// # Example
+/// ```
+/// use pace26io::pace::{reader::*, visitors::*};
+///
+/// let mut visitor = CountingVisitor::new(());
+/// let input = "# comment\n#p 2 3\n#s source random\n((1,2),3);\n(1,(2,3));\n";
+/// InstanceReader::new(&mut visitor).read(input.as_bytes()).unwrap();
+///
+/// assert_eq!(visitor.counts.headers, 1);
+/// assert_eq!(visitor.counts.trees, 2);
+/// assert_eq!(visitor.counts.comments, 1);
+/// assert_eq!(visitor.counts.stride_lines, 1);
+/// ```
+pub struct CountingVisitor<V> {
+    pub inner: V,
+    pub counts: VisitCounts,
+}
+
+impl<V: InstanceVisitor> CountingVisitor<V> {
+    pub fn new(inner: V) -> Self {
+        Self {
+            inner,
+            counts: VisitCounts::default(),
+        }
+    }
+
+    pub fn into_inner(self) -> V {
+        self.inner
+    }
+}
+
+impl<V: InstanceVisitor> InstanceVisitor for CountingVisitor<V> {
+    fn visit_header(&mut self, lineno: usize, num_trees: usize, num_leaves: usize) -> Action {
+        self.counts.headers += 1;
+        self.inner.visit_header(lineno, num_trees, num_leaves)
+    }
+    fn visit_approx_line(&mut self, lineno: usize, param_a: f64, param_b: usize) -> Action {
+        self.counts.approx_lines += 1;
+        self.inner.visit_approx_line(lineno, param_a, param_b)
+    }
+    fn visit_tree(&mut self, lineno: usize, line: &str) -> Action {
+        self.counts.trees += 1;
+        self.inner.visit_tree(lineno, line)
+    }
+    fn visit_line_with_extra_whitespace(&mut self, lineno: usize, line: &str) -> Action {
+        self.counts.lines_with_extra_whitespace += 1;
+        self.inner.visit_line_with_extra_whitespace(lineno, line)
+    }
+    fn visit_unrecognized_hash_line(&mut self, lineno: usize, line: &str) -> Action {
+        self.counts.unrecognized_hash_lines += 1;
+        self.inner.visit_unrecognized_hash_line(lineno, line)
+    }
+    fn visit_comment(&mut self, lineno: usize, line: &str) -> Action {
+        self.counts.comments += 1;
+        self.inner.visit_comment(lineno, line)
+    }
+    fn visit_unrecognized_line(&mut self, lineno: usize, line: &str) -> Action {
+        self.counts.unrecognized_lines += 1;
+        self.inner.visit_unrecognized_line(lineno, line)
+    }
+    fn visit_stride_line(&mut self, lineno: usize, line: &str, key: &str, value: &str) -> Action {
+        self.counts.stride_lines += 1;
+        self.inner.visit_stride_line(lineno, line, key, value)
+    }
+
+    const VISIT_PARAM_TREE_DECOMPOSITION: bool = V::VISIT_PARAM_TREE_DECOMPOSITION;
+    fn visit_param_tree_decomposition(&mut self, lineno: usize, td: TreeDecomposition) -> Action {
+        self.counts.tree_decompositions += 1;
+        self.inner.visit_param_tree_decomposition(lineno, td)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pace::reader::InstanceReader;
+
+    /// Terminates after `limit` trees and records whether it saw a tree decomposition
+    struct Limited {
+        limit: usize,
+        trees: usize,
+        td: bool,
+    }
+
+    impl InstanceVisitor for Limited {
+        fn visit_tree(&mut self, _lineno: usize, _line: &str) -> Action {
+            self.trees += 1;
+            if self.trees == self.limit {
+                Action::Terminate
+            } else {
+                Action::Continue
+            }
+        }
+
+        const VISIT_PARAM_TREE_DECOMPOSITION: bool = true;
+        fn visit_param_tree_decomposition(
+            &mut self,
+            _lineno: usize,
+            _td: TreeDecomposition,
+        ) -> Action {
+            self.td = true;
+            Action::Continue
+        }
+    }
+
+    #[test]
+    fn tee_terminates_once_both_terminated() {
+        let input = "#p 4 2\n(1,2);\n(2,1);\n(1,2);\n#x treedecomp [1,[[1]],[]]\n(2,1);\n";
+        let limited = |limit| Limited {
+            limit,
+            trees: 0,
+            td: false,
+        };
+
+        let mut counting = CountingVisitor::new(limited(1));
+        let mut tee = TeeVisitor::new(&mut counting, limited(3));
+        InstanceReader::new(&mut tee)
+            .read(input.as_bytes())
+            .unwrap();
+
+        let (_, second) = tee.into_inner();
+        assert_eq!(second.trees, 3);
+        assert!(!second.td);
+        assert_eq!(counting.counts.trees, 1);
+        assert_eq!(counting.inner.trees, 1);
+
+        let mut tee = TeeVisitor::new(limited(10), CountingVisitor::new(()));
+        InstanceReader::new(&mut tee)
+            .read(input.as_bytes())
+            .unwrap();
+        let (first, second) = tee.into_inner();
+        assert_eq!(first.trees, 4);
+        assert!(first.td);
+        assert_eq!(second.counts.trees, 4);
+        assert_eq!(second.counts.tree_decompositions, 0);
+    }
+}
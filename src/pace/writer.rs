@@ -0,0 +1,500 @@
+use super::{
+    improvement_log::{IMPROVEMENT_KEY, Improvement},
+    parameters::tree_decomposition::TreeDecomposition,
+    simplified::Instance,
+};
+use crate::{
+    binary_tree::{TopDownCursor, TreeBuilder},
+    forest::Forest,
+    newick::{LabelFormatter, NewickStyle, NewickWriter, write_newick_formatted_styled},
+};
+use std::io::{self, IoSlice, Write};
+
+/// Size of the chunks in which [`InstanceWriter`] and [`SolutionWriter`] assemble their output
+pub const WRITER_CHUNK_SIZE: usize = 1 << 16;
+
+/// Number of chunks collected before they are passed to the underlying writer in a single
+/// [`Write::write_vectored`] call
+const MAX_PENDING_CHUNKS: usize = 16;
+
+/// Collects the many short writes of a line-based format into chunks of about
+/// [`WRITER_CHUNK_SIZE`] bytes and hands them to the underlying writer with vectored writes.
+/// Pending output is written on [`Write::flush`], on `into_inner`, and (ignoring errors) on
+/// drop.
+struct BatchedWriter<W: Write> {
+    // only `None` after `into_inner`
+    inner: Option<W>,
+    chunks: Vec<Vec<u8>>,
+    current: usize,
+}
+
+impl<W: Write> BatchedWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            chunks: vec![Vec::new()],
+            current: 0,
+        }
+    }
+
+    fn into_inner(mut self) -> io::Result<W> {
+        self.write_chunks()?;
+        Ok(self.inner.take().expect("the writer is only taken once"))
+    }
+
+    /// Passes all pending chunks to the underlying writer; the chunks are kept for reuse
+    /// (also if writing fails, in which case the pending output is discarded)
+    fn write_chunks(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().expect("the writer is only taken once");
+        let mut slices: Vec<_> = self.chunks[..=self.current]
+            .iter()
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| IoSlice::new(chunk))
+            .collect();
+        let result = write_all_vectored(inner, &mut slices);
+
+        for chunk in &mut self.chunks[..=self.current] {
+            chunk.clear();
+        }
+        self.current = 0;
+        result
+    }
+}
+
+impl<W: Write> Write for BatchedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() >= WRITER_CHUNK_SIZE {
+            self.write_chunks()?;
+            let inner = self.inner.as_mut().expect("the writer is only taken once");
+            return inner.write(buf);
+        }
+
+        if self.chunks[self.current].len() + buf.len() > WRITER_CHUNK_SIZE {
+            self.current += 1;
+            if self.current == MAX_PENDING_CHUNKS {
+                self.current -= 1;
+                self.write_chunks()?;
+            } else if self.current == self.chunks.len() {
+                self.chunks.push(Vec::with_capacity(WRITER_CHUNK_SIZE));
+            }
+        }
+
+        self.chunks[self.current].extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_chunks()?;
+        self.inner
+            .as_mut()
+            .expect("the writer is only taken once")
+            .flush()
+    }
+}
+
+impl<W: Write> Drop for BatchedWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_chunks();
+        }
+    }
+}
+
+fn write_all_vectored(writer: &mut impl Write, mut slices: &mut [IoSlice<'_>]) -> io::Result<()> {
+    while !slices.is_empty() {
+        match writer.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(written) => IoSlice::advance_slices(&mut slices, written),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+/// Writes instances in the PACE 2026 format line by line. The writer does not check the
+/// order of lines; a valid instance has exactly one header `#p`, which precedes all trees.
+/// [`InstanceWriter::write_instance`] writes a whole [`Instance`] in a valid order.
+///
+/// The output is assembled in chunks and passed to the underlying writer in few vectored
+/// writes, so no [`io::BufWriter`] is needed in front of, e.g., a pipe. Call
+/// [`InstanceWriter::flush`] or [`InstanceWriter::into_inner`] to observe errors of the final
+/// write; on drop, pending output is written but errors are ignored.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, pace::writer::InstanceWriter};
+///
+/// let trees: Vec<BinTree> = vec!["((1,2),3);".parse().unwrap(), "(1,(2,3));".parse().unwrap()];
+///
+/// let mut writer = InstanceWriter::new(Vec::new());
+/// writer.write_comment("generated by a test").unwrap();
+/// writer.write_header(trees.len(), 3).unwrap();
+/// writer.write_stride_param("seed", &serde_json::json!(42)).unwrap();
+/// for tree in &trees {
+///     writer.write_tree(tree).unwrap();
+/// }
+///
+/// assert_eq!(
+///     String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+///     "# generated by a test\n#p 2 3\n#s seed 42\n((1,2),3);\n(1,(2,3));\n"
+/// );
+/// ```
+pub struct InstanceWriter<W: Write> {
+    writer: BatchedWriter<W>,
+    style: NewickStyle,
+}
+
+impl<W: Write> InstanceWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BatchedWriter::new(writer),
+            style: NewickStyle::COMPACT,
+        }
+    }
+
+    /// Writes trees with whitespace according to `style`; each tree is followed by exactly one
+    /// newline regardless of [`NewickStyle::trailing_newline`]. Observe that the PACE format
+    /// does not allow whitespace within trees, so instances written with spaces can only be
+    /// read with [`CompatFlags::whitespace`](crate::compat::CompatFlags::whitespace) enabled.
+    pub fn set_style(&mut self, style: NewickStyle) {
+        self.style = NewickStyle {
+            trailing_newline: false,
+            ..style
+        };
+    }
+
+    /// Writes all pending output and returns the underlying writer
+    pub fn into_inner(self) -> io::Result<W> {
+        self.writer.into_inner()
+    }
+
+    /// Writes each line of `comment` as a comment line `# ...`
+    pub fn write_comment(&mut self, comment: &str) -> io::Result<()> {
+        for line in comment.lines() {
+            writeln!(self.writer, "# {line}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the header `#p {num_trees} {num_leaves}`
+    pub fn write_header(&mut self, num_trees: usize, num_leaves: usize) -> io::Result<()> {
+        writeln!(self.writer, "#p {num_trees} {num_leaves}")
+    }
+
+    /// Writes the approximation line `#a {a} {b}`
+    pub fn write_approx(&mut self, a: f64, b: usize) -> io::Result<()> {
+        writeln!(self.writer, "#a {a} {b}")
+    }
+
+    /// Writes the stride line `#s {key} {value}` with `value` as compact JSON. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if `key` is empty or contains whitespace, since it
+    /// could not be read back.
+    pub fn write_stride_param(&mut self, key: &str, value: &serde_json::Value) -> io::Result<()> {
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid stride parameter key {key:?}"),
+            ));
+        }
+        writeln!(self.writer, "#s {key} {value}")
+    }
+
+    /// Writes the tree decomposition line `#x treedecomp {...}`
+    pub fn write_tree_decomposition(&mut self, td: &TreeDecomposition) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "#x treedecomp {}",
+            serde_json::to_string(td).map_err(io::Error::other)?
+        )
+    }
+
+    /// Writes the tree in Newick format followed by a newline
+    pub fn write_tree(&mut self, tree: impl TopDownCursor) -> io::Result<()> {
+        tree.write_newick_styled(&self.style, &mut self.writer)?;
+        writeln!(self.writer)
+    }
+
+    /// Same as [`InstanceWriter::write_tree`], but writes each leaf as the taxon name assigned
+    /// to its label by `names`; see [`NewickWriter::write_newick_named`]. Observe that names
+    /// are not part of the PACE format, so the result is meant for other tools.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, newick::*, pace::writer::InstanceWriter};
+    ///
+    /// let mut interner = LabelInterner::default();
+    /// let mut options = NewickParserOptions {
+    ///     labels: LabelKind::Names(&mut interner),
+    ///     ..Default::default()
+    /// };
+    /// let tree = BinTreeBuilder::default()
+    ///     .parse_newick_from_str_with("((Pan,Homo),Gorilla);", NodeIdx::new(4), &mut options)
+    ///     .unwrap();
+    ///
+    /// let mut writer = InstanceWriter::new(Vec::new());
+    /// writer.write_tree(tree.top_down()).unwrap();
+    /// writer.write_tree_named(tree.top_down(), &interner).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+    ///     "((1,2),3);\n((Pan,Homo),Gorilla);\n"
+    /// );
+    /// ```
+    pub fn write_tree_named(
+        &mut self,
+        tree: impl TopDownCursor,
+        names: &impl LabelFormatter,
+    ) -> io::Result<()> {
+        write_newick_formatted_styled(tree, names, &(), &self.style, &mut self.writer)?;
+        writeln!(self.writer)
+    }
+
+    /// Writes all information of `instance` that the reader retains, in the order: stride
+    /// parameters (in input order), header, approximation line, trees, and tree decomposition.
+    /// Comments and unknown lines are not part of an [`Instance`] and hence not written.
+    ///
+    /// # Example
+    /// ```
+    /// use pace26io::{binary_tree::*, pace::{simplified::Instance, writer::InstanceWriter}};
+    ///
+    /// let input = "#p 2 3\n# comment\n#s name \"x\"\n((1,2),3);\n(1,(2,3));\n#a 1.5 2\n";
+    /// let instance = Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+    ///
+    /// let mut writer = InstanceWriter::new(Vec::new());
+    /// writer.write_instance(&instance).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+    ///     "#s name \"x\"\n#p 2 3\n#a 1.5 2\n((1,2),3);\n(1,(2,3));\n"
+    /// );
+    /// ```
+    pub fn write_instance<B: TreeBuilder>(&mut self, instance: &Instance<B>) -> io::Result<()>
+    where
+        for<'a> &'a B::Node: TopDownCursor,
+    {
+        for (key, value) in &instance.stride_params {
+            self.write_stride_param(key, value)?;
+        }
+
+        self.write_header(instance.trees.len(), instance.num_leaves)?;
+
+        if let Some((a, b)) = instance.approx {
+            self.write_approx(a, b)?;
+        }
+
+        for tree in &instance.trees {
+            self.write_tree(tree)?;
+        }
+
+        if let Some(td) = &instance.tree_decomposition {
+            self.write_tree_decomposition(td)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes all pending output and flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes solutions, i.e. the trees of an agreement forest one per line, optionally
+/// interleaved with comments and `#s improvement` lines (see
+/// [`ImprovementLog`](super::improvement_log::ImprovementLog)). As [`InstanceWriter`], it
+/// assembles the output in chunks and passes them to the underlying writer in few vectored
+/// writes.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, forest::Forest, pace::{improvement_log::Improvement, writer::SolutionWriter}};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let forest: Forest<BinTreeBuilder> =
+///     Forest::parse_newick("((1,3),4);\n2;\n", 4, &mut builder, |_| NodeIdx::new(0)).unwrap();
+///
+/// let mut writer = SolutionWriter::new(Vec::new());
+/// writer.write_improvement(&Improvement { time: 0.5, score: 2 }).unwrap();
+/// writer.write_forest(&forest).unwrap();
+/// assert_eq!(
+///     String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+///     "#s improvement {\"time\":0.5,\"score\":2}\n((1,3),4);\n2;\n"
+/// );
+/// ```
+pub struct SolutionWriter<W: Write> {
+    writer: BatchedWriter<W>,
+    style: NewickStyle,
+}
+
+impl<W: Write> SolutionWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: BatchedWriter::new(writer),
+            style: NewickStyle::COMPACT,
+        }
+    }
+
+    /// Writes trees with whitespace according to `style`; see [`InstanceWriter::set_style`]
+    pub fn set_style(&mut self, style: NewickStyle) {
+        self.style = NewickStyle {
+            trailing_newline: false,
+            ..style
+        };
+    }
+
+    /// Writes all pending output and returns the underlying writer
+    pub fn into_inner(self) -> io::Result<W> {
+        self.writer.into_inner()
+    }
+
+    /// Writes each line of `comment` as a comment line `# ...`
+    pub fn write_comment(&mut self, comment: &str) -> io::Result<()> {
+        for line in comment.lines() {
+            writeln!(self.writer, "# {line}")?;
+        }
+        Ok(())
+    }
+
+    /// Writes the stride line `#s improvement {...}`
+    pub fn write_improvement(&mut self, improvement: &Improvement) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "#s {IMPROVEMENT_KEY} {}",
+            improvement.to_json_string()
+        )
+    }
+
+    /// Writes the tree in Newick format followed by a newline
+    pub fn write_tree(&mut self, tree: impl TopDownCursor) -> io::Result<()> {
+        tree.write_newick_styled(&self.style, &mut self.writer)?;
+        writeln!(self.writer)
+    }
+
+    /// Writes all trees of `forest`, each on its own line
+    pub fn write_forest<B: TreeBuilder>(&mut self, forest: &Forest<B>) -> io::Result<()>
+    where
+        for<'a> &'a B::Node: TopDownCursor,
+    {
+        for tree in &forest.trees {
+            self.write_tree(tree)?;
+        }
+        Ok(())
+    }
+
+    /// Writes all pending output and flushes the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::binary_tree::{IndexedBinTree, IndexedBinTreeBuilder};
+
+    #[test]
+    fn roundtrip() {
+        let input = std::fs::read_to_string("examples/tiny01.nw").unwrap();
+        let mut builder = IndexedBinTreeBuilder::default();
+        let instance = Instance::try_read(input.as_bytes(), &mut builder).unwrap();
+
+        let mut writer = InstanceWriter::new(Vec::new());
+        writer.write_instance(&instance).unwrap();
+        let written = writer.into_inner().unwrap();
+
+        let reread = Instance::try_read(written.as_slice(), &mut builder).unwrap();
+        assert_eq!(reread.trees, instance.trees);
+        assert_eq!(reread.approx, instance.approx);
+        assert_eq!(reread.stride_params, instance.stride_params);
+        assert_eq!(reread.tree_decomposition, instance.tree_decomposition);
+    }
+
+    #[test]
+    fn styled_trees() {
+        let tree: IndexedBinTree = "((1,2),3);".parse().unwrap();
+        let mut writer = InstanceWriter::new(Vec::new());
+        writer.set_style(NewickStyle {
+            space_after_comma: true,
+            trailing_newline: true,
+            ..Default::default()
+        });
+        writer.write_tree(&tree).unwrap();
+        writer.write_tree(&tree).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            "((1, 2), 3);\n((1, 2), 3);\n"
+        );
+    }
+
+    #[test]
+    fn invalid_stride_key() {
+        let mut writer = InstanceWriter::new(Vec::new());
+        for key in ["", "two words"] {
+            let err = writer
+                .write_stride_param(key, &serde_json::Value::Null)
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(writer.into_inner().unwrap().is_empty());
+    }
+
+    /// Records the number of calls of `write` and `write_vectored`
+    #[derive(Default)]
+    struct CountingWriter {
+        data: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            // accept at most two slices to exercise partial vectored writes
+            let mut written = 0;
+            for buf in bufs.iter().take(2) {
+                self.data.extend_from_slice(buf);
+                written += buf.len();
+            }
+            Ok(written)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn batched_writes() {
+        const NUM_TREES: usize = 100_000;
+        let tree: IndexedBinTree = "((1,2),3);".parse().unwrap();
+
+        let mut writer = InstanceWriter::new(CountingWriter::default());
+        writer.write_header(NUM_TREES, 3).unwrap();
+        for _ in 0..NUM_TREES {
+            writer.write_tree(&tree).unwrap();
+        }
+        let counting = writer.into_inner().unwrap();
+
+        let expected = format!("#p {NUM_TREES} 3\n{}", "((1,2),3);\n".repeat(NUM_TREES));
+        assert_eq!(counting.data, expected.as_bytes());
+        assert!(counting.calls <= expected.len() / WRITER_CHUNK_SIZE + 2);
+
+        // chunks are written on flush and drop, and long writes bypass the chunks
+        let mut buffer = Vec::new();
+        {
+            let mut writer = SolutionWriter::new(&mut buffer);
+            writer.write_comment("short").unwrap();
+            writer.flush().unwrap();
+            writer
+                .write_comment(&"x".repeat(WRITER_CHUNK_SIZE))
+                .unwrap();
+            writer.write_tree(&tree).unwrap();
+        }
+        let expected = format!("# short\n# {}\n((1,2),3);\n", "x".repeat(WRITER_CHUNK_SIZE));
+        assert_eq!(String::from_utf8(buffer).unwrap(), expected);
+    }
+}
@@ -0,0 +1,191 @@
+//! Random modifications of trees, e.g., to generate instances of tunable difficulty.
+use crate::binary_tree::{flat_tree::FlatTree, *};
+use rand::Rng;
+
+/// Applies `k` random subtree prune and regraft (SPR) moves to `tree` and builds the result
+/// with inner nodes numbered in preorder starting with `root_id`. Hence, the rooted SPR
+/// distance between input and output is at most `k`.
+///
+/// Each move prunes the subtree below a uniformly chosen non-root node and regrafts it onto a
+/// uniformly chosen edge of the remaining tree (including the edge above its root).
+/// The original position is excluded, so every single move changes the tree.
+/// Trees with fewer than three leaves do not admit such moves and are returned unchanged.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*, perturbation::*};
+/// use rand::SeedableRng;
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let tree = builder.parse_newick_from_str("(((1,2),3),(4,5));", NodeIdx::new(0)).unwrap();
+///
+/// let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+/// let perturbed = perturb_spr(tree.top_down(), 2, &mut rng, &mut builder, NodeIdx::new(0));
+/// assert_ne!(perturbed, tree);
+/// ```
+pub fn perturb_spr<C, B>(
+    tree: C,
+    k: usize,
+    rng: &mut impl Rng,
+    builder: &mut B,
+    root_id: NodeIdx,
+) -> B::Node
+where
+    C: TopDownCursor,
+    B: TreeBuilder,
+{
+    let mut tree = FlatTree::new(tree);
+
+    if tree.leaves.len() >= 3 {
+        for _ in 0..k {
+            random_spr_move(&mut tree, rng);
+        }
+    }
+
+    tree.build(builder, root_id, |_| false).0
+}
+
+fn random_spr_move(tree: &mut FlatTree, rng: &mut impl Rng) {
+    let num_nodes = tree.nodes.len();
+
+    // prune subtree below `u`; its parent `p` is spliced out and reused for regrafting.
+    // If `p` is the root and the sibling of `u` a leaf, there is no other edge to regraft onto.
+    let (u, p, sibling) = loop {
+        let u = rng.random_range(0..num_nodes);
+        let Some(p) = tree.nodes[u].parent else {
+            continue;
+        };
+
+        let (pl, pr) = tree.nodes[p].children.unwrap();
+        let sibling = if pl == u { pr } else { pl };
+        if p != tree.root || tree.nodes[sibling].children.is_some() {
+            break (u, p, sibling);
+        }
+    };
+    replace_child(tree, p, sibling);
+
+    // candidates are all nodes of the remaining tree except for the sibling (original position)
+    let mut candidates = Vec::with_capacity(num_nodes);
+    let mut stack = vec![tree.root];
+    while let Some(v) = stack.pop() {
+        if v != sibling {
+            candidates.push(v);
+        }
+        if let Some((l, r)) = tree.nodes[v].children {
+            stack.push(l);
+            stack.push(r);
+        }
+    }
+
+    // regraft onto the edge above `target`
+    let target = candidates[rng.random_range(0..candidates.len())];
+    replace_child(tree, target, p);
+    tree.nodes[p].children = Some(if rng.random_bool(0.5) {
+        (target, u)
+    } else {
+        (u, target)
+    });
+    tree.nodes[target].parent = Some(p);
+}
+
+/// Puts `new` at the position of `old` (i.e. below the parent of `old`, or as root)
+fn replace_child(tree: &mut FlatTree, old: usize, new: usize) {
+    let grandparent = tree.nodes[old].parent;
+    tree.nodes[new].parent = grandparent;
+
+    match grandparent {
+        Some(g) => {
+            let (l, r) = tree.nodes[g].children.unwrap();
+            tree.nodes[g].children = Some(if l == old { (new, r) } else { (l, new) });
+        }
+        None => tree.root = new,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::*;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    /// Returns the sorted leaf labels below `cursor` and appends all clusters to `out`
+    fn clusters<C: TopDownCursor>(cursor: C, out: &mut Vec<Vec<LabelValue>>) -> Vec<LabelValue> {
+        match cursor.visit() {
+            NodeType::Leaf(Label(l)) => vec![l],
+            NodeType::Inner(l, r) => {
+                let mut cluster = clusters(l, out);
+                cluster.extend(clusters(r, out));
+                cluster.sort();
+                out.push(cluster.clone());
+                cluster
+            }
+        }
+    }
+
+    fn cluster_set(tree: &IndexedBinTree) -> Vec<Vec<LabelValue>> {
+        let mut out = Vec::new();
+        clusters(tree.top_down(), &mut out);
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn single_move_changes_tree() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0x5b7);
+        let mut builder = IndexedBinTreeBuilder::default();
+        let tree = builder
+            .parse_newick_from_str("((((1,2),3),(4,5)),((6,7),8));", NodeIdx::new(9))
+            .unwrap();
+
+        for _ in 0..100 {
+            let perturbed =
+                perturb_spr(tree.top_down(), 1, &mut rng, &mut builder, NodeIdx::new(9));
+
+            let mut labels = Vec::new();
+            let all = clusters(perturbed.top_down(), &mut labels);
+            assert_eq!(all, (1..=8).collect::<Vec<_>>());
+            assert_ne!(cluster_set(&perturbed), cluster_set(&tree));
+
+            // inner nodes are numbered in preorder
+            let (left, _) = perturbed.top_down().children().unwrap();
+            assert_eq!(perturbed.node_idx(), NodeIdx::new(9));
+            if left.is_inner() {
+                assert_eq!(left.node_idx(), NodeIdx::new(10));
+            }
+        }
+    }
+
+    #[test]
+    fn small_trees() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0x5b7);
+        let mut builder = BinTreeBuilder::default();
+        for text in ["1;", "(1,2);"] {
+            let tree = builder
+                .parse_newick_from_str(text, NodeIdx::new(0))
+                .unwrap();
+            let perturbed =
+                perturb_spr(tree.top_down(), 5, &mut rng, &mut builder, NodeIdx::new(0));
+            assert_eq!(perturbed, tree);
+        }
+    }
+
+    #[test]
+    fn many_moves_on_small_trees() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0x5b8);
+        let mut builder = IndexedBinTreeBuilder::default();
+        for text in ["((1,2),3);", "((1,2),(3,4));", "(1,(2,(3,(4,5))));"] {
+            let tree = builder
+                .parse_newick_from_str(text, NodeIdx::new(0))
+                .unwrap();
+            let n = clusters(tree.top_down(), &mut Vec::new()).len() as LabelValue;
+            for _ in 0..100 {
+                let perturbed =
+                    perturb_spr(tree.top_down(), 20, &mut rng, &mut builder, NodeIdx::new(0));
+                let mut labels = Vec::new();
+                let all = clusters(perturbed.top_down(), &mut labels);
+                assert_eq!(all, (1..=n).collect::<Vec<_>>());
+            }
+        }
+    }
+}
@@ -0,0 +1,54 @@
+/// Progress of a long-running read, as reported to the hooks set by
+/// [`Lexer::report_progress`](crate::newick::Lexer::report_progress) and
+/// [`InstanceReader::report_progress`](crate::pace::reader::InstanceReader::report_progress)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of bytes of the (decompressed) input consumed so far
+    pub bytes: usize,
+
+    /// Number of tree nodes lexed so far; always `0` for the instance reader, which does
+    /// not parse trees itself
+    pub nodes: usize,
+
+    /// Number of lines read so far; always `0` for the lexer
+    pub lines: usize,
+}
+
+/// Invokes a progress hook whenever at least `interval` bytes were consumed since the last
+/// invocation, and once more at the end
+pub(crate) struct ProgressReporter<'a> {
+    hook: &'a mut dyn FnMut(Progress),
+    interval: usize,
+    next_report: usize,
+    last: Option<Progress>,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub(crate) fn new(interval: usize, hook: &'a mut dyn FnMut(Progress)) -> Self {
+        Self {
+            hook,
+            interval: interval.max(1),
+            next_report: 0,
+            last: None,
+        }
+    }
+
+    pub(crate) fn update(&mut self, progress: Progress) {
+        if progress.bytes >= self.next_report {
+            self.report(progress);
+        }
+    }
+
+    /// Reports `progress` unless it was reported already
+    pub(crate) fn finish(&mut self, progress: Progress) {
+        if self.last != Some(progress) {
+            self.report(progress);
+        }
+    }
+
+    fn report(&mut self, progress: Progress) {
+        (self.hook)(progress);
+        self.last = Some(progress);
+        self.next_report = progress.bytes.saturating_add(self.interval);
+    }
+}
@@ -0,0 +1,126 @@
+//! Reproducible seeds for randomized tooling, e.g., the `perturbation` module or
+//! `Instance::shuffle_rotations` (both require the feature `rand`).
+//!
+//! Seeds are derived from a fingerprint of the input and a user-provided salt. Both are
+//! computed by a fixed hash function (FNV-1a followed by a SplitMix64 finalizer) on a
+//! platform-independent byte representation, so the same inputs yield the same seeds on all
+//! machines and in all future versions of this crate.
+use crate::binary_tree::{NodeType, TopDownCursor};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Incremental fingerprint of trees and further data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    state: u64,
+}
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Self { state: FNV_OFFSET }
+    }
+}
+
+impl Fingerprint {
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state = (self.state ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    /// Adds the structure and leaf labels of the tree below `root`; the fingerprint does not
+    /// depend on the node ids or the label width (see feature `u64-labels`)
+    pub fn write_tree<C: TopDownCursor>(&mut self, root: C) {
+        let mut stack = vec![root];
+        while let Some(node) = stack.pop() {
+            match node.visit() {
+                NodeType::Inner(l, r) => {
+                    self.write_bytes(&[1]);
+                    stack.push(r);
+                    stack.push(l);
+                }
+                // the cast is a no-op if the feature `u64-labels` is enabled
+                #[allow(clippy::unnecessary_cast)]
+                NodeType::Leaf(label) => {
+                    self.write_bytes(&[0]);
+                    self.write_u64(label.0 as u64);
+                }
+            }
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        mix(self.state)
+    }
+}
+
+/// Derives a seed from `fingerprint` and `salt`, e.g., the name of the tool and the index
+/// of a run. Different salts yield independent seeds for the same fingerprint.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*, seed::*};
+///
+/// let tree = BinTreeBuilder::default().parse_newick_from_str("((1,2),3);", NodeIdx::new(4)).unwrap();
+/// let mut fingerprint = Fingerprint::default();
+/// fingerprint.write_tree(tree.top_down());
+///
+/// let seed = derive_seed(fingerprint.finish(), "perturb-0");
+/// assert_eq!(seed, derive_seed(fingerprint.finish(), "perturb-0"));
+/// assert_ne!(seed, derive_seed(fingerprint.finish(), "perturb-1"));
+/// ```
+pub fn derive_seed(fingerprint: u64, salt: &str) -> u64 {
+    let mut hasher = Fingerprint::default();
+    hasher.write_u64(fingerprint);
+    hasher.write_bytes(salt.as_bytes());
+    hasher.finish()
+}
+
+/// Creates a random number generator seeded by [`derive_seed`]. Note that the sequence
+/// produced by `R` is only reproducible if `R` documents a stable algorithm, e.g.,
+/// `rand_pcg::Pcg64Mcg`, in contrast to `rand::rngs::StdRng`.
+#[cfg(feature = "rand")]
+pub fn seeded_rng<R: rand::SeedableRng>(fingerprint: u64, salt: &str) -> R {
+    R::seed_from_u64(derive_seed(fingerprint, salt))
+}
+
+/// SplitMix64 finalizer
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{binary_tree::*, newick::*};
+
+    fn fingerprint(text: &str, root_id: u32) -> u64 {
+        let tree = BinTreeBuilder::default()
+            .parse_newick_from_str(text, NodeIdx::new(root_id))
+            .unwrap();
+        let mut fingerprint = Fingerprint::default();
+        fingerprint.write_tree(tree.top_down());
+        fingerprint.finish()
+    }
+
+    #[test]
+    fn stable() {
+        // guards against accidental changes of the hash function
+        assert_eq!(Fingerprint::default().finish(), mix(FNV_OFFSET));
+        assert_eq!(derive_seed(0, ""), 0x813f_0174_a236_7c13);
+    }
+
+    #[test]
+    fn trees() {
+        assert_eq!(fingerprint("((1,2),3);", 4), fingerprint("((1,2),3);", 100));
+        assert_ne!(fingerprint("((1,2),3);", 4), fingerprint("(1,(2,3));", 4));
+        assert_ne!(fingerprint("((1,2),3);", 4), fingerprint("((2,1),3);", 4));
+    }
+}
@@ -0,0 +1,331 @@
+//! Supertrees combine trees on (possibly) different leaf sets into a single tree
+//! that displays each of them.
+use crate::{
+    binary_tree::{flat_tree::FlatTree, *},
+    consensus::{ClusterCounts, ConsensusError, leaf_ranges},
+    multi_tree::MultifurcatingTreeBuilder,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Checks whether the input trees are compatible using Aho et al.'s BUILD algorithm,
+/// i.e. whether there exists a tree displaying all rooted triplets of all input trees.
+/// If so, the (possibly multifurcating) tree computed by BUILD is returned; each
+/// binary refinement of it is a common supertree as well. Otherwise `None` is returned.
+///
+/// Instead of enumerating all triplets explicitly, the implementation works directly
+/// on the clusters of the input trees, which yields the same Aho graph components.
+/// The leaf labels within each tree are expected to be unique.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, multi_tree::*, newick::*, supertree::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let a = builder.parse_newick_from_str("((1,2),3);", NodeIdx::new(0)).unwrap();
+/// let b = builder.parse_newick_from_str("((2,4),3);", NodeIdx::new(0)).unwrap();
+/// let c = builder.parse_newick_from_str("((1,3),2);", NodeIdx::new(0)).unwrap();
+///
+/// let mut multi_builder = MultiTreeBuilder::default();
+/// let supertree = are_compatible([&a, &b], &mut multi_builder).unwrap();
+/// assert_eq!((&supertree).to_newick_string(), "((1,2,4),3);");
+///
+/// assert!(are_compatible([&a, &c], &mut multi_builder).is_none());
+/// ```
+pub fn are_compatible<C, B>(trees: impl IntoIterator<Item = C>, builder: &mut B) -> Option<B::Node>
+where
+    C: TopDownCursor,
+    B: MultifurcatingTreeBuilder,
+{
+    let trees: Vec<FlatTree> = trees.into_iter().map(FlatTree::new).collect();
+
+    // assign dense indices to the union of all leaf sets
+    let mut labels = Vec::new();
+    let mut dense = HashMap::new();
+    for tree in &trees {
+        for &(label, _) in &tree.leaves {
+            dense.entry(label).or_insert_with(|| {
+                labels.push(label);
+                labels.len() - 1
+            });
+        }
+    }
+
+    if labels.is_empty() {
+        return None;
+    }
+
+    // leaf_nodes[t][e] is the node of leaf `e` in tree `t` (if present)
+    let leaf_nodes: Vec<Vec<Option<usize>>> = trees
+        .iter()
+        .map(|tree| {
+            let mut nodes = vec![None; labels.len()];
+            for &(label, node) in &tree.leaves {
+                nodes[dense[&label]] = Some(node);
+            }
+            nodes
+        })
+        .collect();
+
+    enum Step {
+        Enter(Vec<usize>),
+        Exit(usize),
+    }
+
+    let mut results: Vec<B::Node> = Vec::new();
+    let mut stack = vec![Step::Enter((0..labels.len()).collect())];
+    let mut position = vec![0; labels.len()];
+
+    while let Some(step) = stack.pop() {
+        let leaves = match step {
+            Step::Enter(leaves) => leaves,
+            Step::Exit(num_children) => {
+                let children = results.split_off(results.len() - num_children);
+                let node = builder.new_inner(children);
+                results.push(node);
+                continue;
+            }
+        };
+
+        if leaves.len() == 1 {
+            let node = builder.new_leaf(labels[leaves[0]]);
+            results.push(node);
+            continue;
+        }
+
+        for (i, &e) in leaves.iter().enumerate() {
+            position[e] = i;
+        }
+
+        // Two leaves end up in the same component of the Aho graph iff some tree
+        // places them below the same child of the LCA of the current leaf set.
+        let mut components = UnionFind::new(leaves.len());
+        for (tree, nodes) in trees.iter().zip(&leaf_nodes) {
+            let present: Vec<(usize, usize)> = leaves
+                .iter()
+                .filter_map(|&e| nodes[e].map(|u| (e, u)))
+                .collect();
+
+            if present.len() < 2 {
+                continue;
+            }
+
+            let lca = present
+                .iter()
+                .map(|&(_, u)| u)
+                .reduce(|u, v| tree.lca(u, v))
+                .unwrap();
+
+            let mut representative: HashMap<usize, usize> = HashMap::new();
+            for &(e, mut u) in &present {
+                while tree.nodes[u].parent != Some(lca) {
+                    u = tree.nodes[u].parent.unwrap();
+                }
+                let rep = *representative.entry(u).or_insert(e);
+                components.union(position[rep], position[e]);
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+        for (i, &e) in leaves.iter().enumerate() {
+            let root = components.find(i);
+            let group = *group_of_root.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(e);
+        }
+
+        if groups.len() == 1 {
+            return None;
+        }
+
+        stack.push(Step::Exit(groups.len()));
+        stack.extend(groups.into_iter().rev().map(Step::Enter));
+    }
+
+    let root = results.pop().unwrap();
+    Some(builder.make_root(root))
+}
+
+/// Computes a scaffold for supertrees, i.e. the most resolved tree containing exactly the
+/// clusters common to all input trees after restricting them to the leaves they all share.
+/// Leaves missing in some tree are dropped. For trees on the same leaf set (as in PACE
+/// instances) this is the [strict consensus](crate::consensus::strict); each common supertree
+/// restricted to the shared leaves is a refinement of the scaffold.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, multi_tree::*, newick::*, supertree::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let trees = vec![
+///     builder.parse_newick_from_str("(((1,2),5),((3,4),6));", NodeIdx::new(0)).unwrap(),
+///     builder.parse_newick_from_str("((1,(2,3)),(4,7));", NodeIdx::new(0)).unwrap(),
+/// ];
+///
+/// let scaffold = scaffold_supertree(&trees, &mut MultiTreeBuilder::default()).unwrap();
+/// assert_eq!((&scaffold).to_newick_string(), "(1,2,3,4);");
+/// ```
+pub fn scaffold_supertree<C, B>(
+    trees: impl IntoIterator<Item = C>,
+    builder: &mut B,
+) -> Result<B::Node, ConsensusError>
+where
+    C: TopDownCursor,
+    B: MultifurcatingTreeBuilder,
+{
+    let trees: Vec<_> = trees.into_iter().map(leaf_ranges).collect();
+
+    // count in how many trees each label occurs
+    let mut occurrences: HashMap<Label, usize> = HashMap::new();
+    for (leaves, _) in &trees {
+        let mut seen = HashSet::with_capacity(leaves.len());
+        for &label in leaves {
+            if !seen.insert(label) {
+                return Err(ConsensusError::DuplicateLabel(label));
+            }
+            *occurrences.entry(label).or_default() += 1;
+        }
+    }
+
+    let num_trees = trees.len();
+    if num_trees > 0 && occurrences.values().all(|&count| count < num_trees) {
+        return Err(ConsensusError::NoCommonLeaves);
+    }
+
+    let restricted = trees.into_iter().map(|(leaves, ranges)| {
+        // prefix[i] is the number of common leaves among the first i leaves
+        let mut prefix = Vec::with_capacity(leaves.len() + 1);
+        prefix.push(0);
+        let leaves: Vec<Label> = leaves
+            .into_iter()
+            .inspect(|label| {
+                let is_common = occurrences[label] == num_trees;
+                prefix.push(prefix.last().unwrap() + is_common as usize);
+            })
+            .filter(|label| occurrences[label] == num_trees)
+            .collect();
+
+        // Ranges are in postorder, so nodes with the same restricted cluster are adjacent
+        // once trivial clusters are removed.
+        let mut ranges: Vec<(usize, usize)> = ranges
+            .into_iter()
+            .map(|(begin, end)| (prefix[begin], prefix[end]))
+            .filter(|(begin, end)| end - begin >= 2)
+            .collect();
+        ranges.dedup();
+
+        (leaves, ranges)
+    });
+
+    let counts = ClusterCounts::from_leaf_ranges(restricted)?;
+    Ok(counts.build(builder, |count| count == num_trees))
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+        }
+    }
+
+    fn find(&mut self, mut u: usize) -> usize {
+        while self.parent[u] != u {
+            self.parent[u] = self.parent[self.parent[u]];
+            u = self.parent[u];
+        }
+        u
+    }
+
+    fn union(&mut self, u: usize, v: usize) {
+        let (ru, rv) = (self.find(u), self.find(v));
+        if ru != rv {
+            self.parent[rv] = ru;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{multi_tree::MultiTreeBuilder, newick::*};
+
+    fn build(texts: &[&str]) -> Option<String> {
+        let mut builder = BinTreeBuilder::default();
+        let trees: Vec<_> = texts
+            .iter()
+            .map(|t| builder.parse_newick_from_str(t, NodeIdx::new(0)).unwrap())
+            .collect();
+        are_compatible(&trees, &mut MultiTreeBuilder::default()).map(|t| (&t).to_newick_string())
+    }
+
+    #[test]
+    fn identical_trees() {
+        assert_eq!(
+            build(&["(((4,2),(7,1)),8);", "(8,((2,4),(1,7)));"]).as_deref(),
+            Some("(((4,2),(7,1)),8);")
+        );
+    }
+
+    #[test]
+    fn different_leaf_sets() {
+        assert_eq!(
+            build(&["((1,2),3);", "((3,4),5);", "(1,5);"]).as_deref(),
+            Some("((1,2),(3,4),5);")
+        );
+    }
+
+    #[test]
+    fn incompatible() {
+        assert!(build(&["(((1,2),3),4);", "(((1,2),4),3);"]).is_none());
+        assert!(build(&["((1,2),(3,4));", "((1,3),(2,4));"]).is_none());
+    }
+
+    fn scaffold(texts: &[&str]) -> Result<String, ConsensusError> {
+        let mut builder = BinTreeBuilder::default();
+        let trees: Vec<_> = texts
+            .iter()
+            .map(|t| builder.parse_newick_from_str(t, NodeIdx::new(0)).unwrap())
+            .collect();
+        scaffold_supertree(&trees, &mut MultiTreeBuilder::default())
+            .map(|t| (&t).to_newick_string())
+    }
+
+    #[test]
+    fn scaffold_of_common_clusters() {
+        assert_eq!(
+            scaffold(&["(((1,2),3),(4,5));", "((1,(2,3)),(4,5));"]).as_deref(),
+            Ok("((1,2,3),(4,5));")
+        );
+        assert_eq!(
+            scaffold(&["((((1,2),6),3),(4,5));", "(((1,2),3),(7,(4,5)));"]).as_deref(),
+            Ok("(((1,2),3),(4,5));")
+        );
+        assert_eq!(
+            scaffold(&["((1,2),3);", "((1,4),2);"]).as_deref(),
+            Ok("(1,2);")
+        );
+        assert_eq!(scaffold(&["((1,2),3);", "(3,4);"]).as_deref(), Ok("3;"));
+        assert_eq!(
+            scaffold(&["(1,2);", "(3,4);"]),
+            Err(ConsensusError::NoCommonLeaves)
+        );
+        assert_eq!(
+            scaffold(&["(1,(2,1));"]),
+            Err(ConsensusError::DuplicateLabel(Label(1)))
+        );
+        assert_eq!(scaffold(&[]), Err(ConsensusError::NoTrees));
+    }
+
+    #[test]
+    fn single_leaf() {
+        assert_eq!(build(&["1;", "1;"]).as_deref(), Some("1;"));
+        assert_eq!(build(&["1;", "2;"]).as_deref(), Some("(1,2);"));
+        assert_eq!(build(&[]), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! Utilities to draw several trees next to each other, such that the lines connecting
+//! equally labelled leaves of consecutive trees cross as rarely as possible.
+use crate::binary_tree::{flat_tree::FlatTree, *};
+use std::collections::HashMap;
+
+/// A tree with rotated children as produced by [`optimize_rotations`]
+#[derive(Debug, Clone)]
+pub struct RotatedTree<N> {
+    pub tree: N,
+
+    /// Leaf labels of `tree` from left to right
+    pub leaf_order: Vec<Label>,
+
+    /// Number of crossings with the previous tree (always `0` for the first tree)
+    pub crossings: usize,
+}
+
+/// Processes the trees in order and rotates the children of each tree, such that the number of
+/// crossings with the (already rotated) previous tree is minimized. The first tree is kept as is.
+///
+/// Since the relative order of two leaves only depends on the rotation at their lowest common
+/// ancestor, each step is solved optimally by deciding each inner node independently.
+/// Leaves that do not appear in the previous tree are ignored when counting crossings.
+///
+/// Inner nodes are numbered in preorder starting with `root_id(i)` for the `i`-th tree,
+/// matching the numbering of [`crate::newick::BinaryTreeParser`].
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::*, newick::*, tanglegram::*};
+///
+/// let mut builder = BinTreeBuilder::default();
+/// let a = builder.parse_newick_from_str("((1,2),(3,4));", NodeIdx::new(0)).unwrap();
+/// let b = builder.parse_newick_from_str("((4,3),(1,2));", NodeIdx::new(0)).unwrap();
+///
+/// let rotated = optimize_rotations([&a, &b], &mut builder, |_| NodeIdx::new(0));
+/// assert_eq!(rotated[1].tree.top_down().to_newick_string(), "((1,2),(3,4));");
+/// assert_eq!(rotated[1].leaf_order, vec![Label(1), Label(2), Label(3), Label(4)]);
+/// assert_eq!(rotated[1].crossings, 0);
+/// ```
+pub fn optimize_rotations<C, B>(
+    trees: impl IntoIterator<Item = C>,
+    builder: &mut B,
+    mut root_id: impl FnMut(usize) -> NodeIdx,
+) -> Vec<RotatedTree<B::Node>>
+where
+    C: TopDownCursor,
+    B: TreeBuilder,
+{
+    let mut result: Vec<RotatedTree<B::Node>> = Vec::new();
+    for (i, tree) in trees.into_iter().enumerate() {
+        let reference = result.last().map_or(&[][..], |r| r.leaf_order.as_slice());
+        result.push(rotate_towards(tree, reference, builder, root_id(i)));
+    }
+    result
+}
+
+/// Rotates the children of `tree`, such that the number of crossings between its leaf order
+/// and `reference` is minimized. See [`optimize_rotations`] for details.
+pub fn rotate_towards<C, B>(
+    tree: C,
+    reference: &[Label],
+    builder: &mut B,
+    root_id: NodeIdx,
+) -> RotatedTree<B::Node>
+where
+    C: TopDownCursor,
+    B: TreeBuilder,
+{
+    let tree = FlatTree::new(tree);
+    let position: HashMap<Label, usize> =
+        reference.iter().enumerate().map(|(i, &l)| (l, i)).collect();
+
+    // positions[u] contains the sorted reference positions of all leaves below u;
+    // it is consumed when the parent of u is processed.
+    let mut positions: Vec<Vec<usize>> = vec![Vec::new(); tree.nodes.len()];
+    let mut swap = vec![false; tree.nodes.len()];
+    let mut crossings = 0;
+    for u in tree.post_order() {
+        match tree.nodes[u].children {
+            Some((l, r)) => {
+                let left = std::mem::take(&mut positions[l]);
+                let right = std::mem::take(&mut positions[r]);
+
+                let pairs = left.len() * right.len();
+                let (merged, inversions) = merge_counting_inversions(&left, &right);
+                swap[u] = 2 * inversions > pairs;
+                crossings += inversions.min(pairs - inversions);
+                positions[u] = merged;
+            }
+            None => {
+                if let Some(&p) = position.get(&tree.nodes[u].label.unwrap()) {
+                    positions[u].push(p);
+                }
+            }
+        }
+    }
+
+    let (tree, leaf_order) = tree.build(builder, root_id, |u| swap[u]);
+    RotatedTree {
+        tree,
+        leaf_order,
+        crossings,
+    }
+}
+
+/// Returns the number of pairs of labels (contained in both orders) that appear in
+/// different relative order, i.e. the number of crossings when drawing both orders
+/// on top of each other.
+///
+/// # Example
+/// ```
+/// use pace26io::{binary_tree::Label, tanglegram::count_crossings};
+///
+/// let a = [Label(1), Label(2), Label(3)];
+/// let b = [Label(3), Label(1), Label(2)];
+/// assert_eq!(count_crossings(&a, &b), 2);
+/// ```
+pub fn count_crossings(a: &[Label], b: &[Label]) -> usize {
+    let position: HashMap<Label, usize> = a.iter().enumerate().map(|(i, &l)| (l, i)).collect();
+    let sequence: Vec<usize> = b.iter().filter_map(|l| position.get(l).copied()).collect();
+    count_inversions(&sequence).1
+}
+
+fn count_inversions(sequence: &[usize]) -> (Vec<usize>, usize) {
+    if sequence.len() <= 1 {
+        return (sequence.to_vec(), 0);
+    }
+
+    let (left, right) = sequence.split_at(sequence.len() / 2);
+    let (left, inv_left) = count_inversions(left);
+    let (right, inv_right) = count_inversions(right);
+    let (merged, inv) = merge_counting_inversions(&left, &right);
+    (merged, inv_left + inv_right + inv)
+}
+
+/// Merges two sorted sequences and returns the number of pairs `(x, y)` with `x` from `left`,
+/// `y` from `right`, and `x > y`.
+fn merge_counting_inversions(left: &[usize], right: &[usize]) -> (Vec<usize>, usize) {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut inversions = 0;
+
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if left[i] <= right[j] {
+            merged.push(left[i]);
+            i += 1;
+        } else {
+            merged.push(right[j]);
+            inversions += left.len() - i;
+            j += 1;
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+
+    (merged, inversions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::newick::*;
+    use rand::{Rng, SeedableRng, seq::SliceRandom};
+    use rand_pcg::Pcg64Mcg;
+
+    #[test]
+    fn crossings() {
+        let labels = |x: &[LabelValue]| x.iter().map(|&l| Label(l)).collect::<Vec<_>>();
+        assert_eq!(
+            count_crossings(&labels(&[1, 2, 3, 4]), &labels(&[1, 2, 3, 4])),
+            0
+        );
+        assert_eq!(
+            count_crossings(&labels(&[1, 2, 3, 4]), &labels(&[4, 3, 2, 1])),
+            6
+        );
+        assert_eq!(
+            count_crossings(&labels(&[1, 2, 5, 3]), &labels(&[3, 6, 2, 1])),
+            3
+        );
+    }
+
+    #[test]
+    fn rotations() {
+        let mut builder = IndexedBinTreeBuilder::default();
+        let trees: Vec<_> = [
+            "(((1,2),3),((4,5),6));",
+            "(((6,5),4),(2,(3,1)));",
+            "((1,(2,3)),(4,(6,5)));",
+        ]
+        .iter()
+        .map(|t| builder.parse_newick_from_str(t, NodeIdx::new(0)).unwrap())
+        .collect();
+
+        let rotated = optimize_rotations(&trees, &mut builder, |i| NodeIdx::new(10 * i as u32));
+        assert_eq!(rotated.len(), 3);
+
+        let newick: Vec<_> = rotated
+            .iter()
+            .map(|r| r.tree.top_down().to_newick_string())
+            .collect();
+        assert_eq!(newick[0], "(((1,2),3),((4,5),6));");
+        assert_eq!(newick[1], "((2,(1,3)),(4,(5,6)));");
+        assert_eq!(newick[2], "((1,(2,3)),(4,(5,6)));");
+        assert_eq!(
+            rotated.iter().map(|r| r.crossings).collect::<Vec<_>>(),
+            [0, 1, 1]
+        );
+
+        for r in &rotated {
+            assert_eq!(r.leaf_order, leaf_order(&r.tree));
+        }
+
+        // ids are assigned in preorder
+        assert_eq!(rotated[2].tree.node_idx(), NodeIdx::new(20));
+        let (left, right) = rotated[2].tree.top_down().children().unwrap();
+        assert_eq!(left.node_idx(), NodeIdx::new(21));
+        assert_eq!(right.node_idx(), NodeIdx::new(23));
+    }
+
+    #[test]
+    fn random_against_exhaustive() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0x7a91e);
+        for _ in 0..100 {
+            let n = rng.random_range(2..9);
+            let tree = random_tree(&mut rng, n);
+            let mut reference: Vec<Label> = (1..=n).map(Label).collect();
+            reference.shuffle(&mut rng);
+
+            let rotated = rotate_towards(
+                &tree,
+                &reference,
+                &mut BinTreeBuilder::default(),
+                NodeIdx::new(0),
+            );
+            assert_eq!(
+                count_crossings(&reference, &rotated.leaf_order),
+                rotated.crossings
+            );
+
+            let num_inner = n as usize - 1;
+            let best = (0..1u32 << num_inner)
+                .map(|mask| {
+                    let mut order = Vec::new();
+                    let mut next_inner = 0;
+                    collect_rotated(&tree, mask, &mut next_inner, &mut order);
+                    count_crossings(&reference, &order)
+                })
+                .min()
+                .unwrap();
+            assert_eq!(rotated.crossings, best);
+        }
+    }
+
+    fn random_tree(rng: &mut impl Rng, n: LabelValue) -> BinTree {
+        fn build(rng: &mut impl Rng, labels: &[LabelValue]) -> BinTree {
+            if labels.len() == 1 {
+                return BinTree::Leaf(Label(labels[0]));
+            }
+            let split = rng.random_range(1..labels.len());
+            BinTree::Node(Box::new((
+                build(rng, &labels[..split]),
+                build(rng, &labels[split..]),
+            )))
+        }
+
+        let mut labels: Vec<LabelValue> = (1..=n).collect();
+        labels.shuffle(rng);
+        build(rng, &labels)
+    }
+
+    fn collect_rotated(tree: &BinTree, mask: u32, next_inner: &mut u32, order: &mut Vec<Label>) {
+        match tree.top_down().visit() {
+            NodeType::Inner(l, r) => {
+                let swap = (mask >> *next_inner) & 1 == 1;
+                *next_inner += 1;
+                let (first, second) = if swap { (r, l) } else { (l, r) };
+                collect_rotated(first, mask, next_inner, order);
+                collect_rotated(second, mask, next_inner, order);
+            }
+            NodeType::Leaf(label) => order.push(label),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Helpers for regression tests of instance collections, e.g., benchmark repositories.
+use crate::{
+    binary_tree::BinTreeBuilder,
+    pace::{simplified::Instance, writer::InstanceWriter},
+};
+use std::path::Path;
+
+/// Reads the instance at `path`, writes it canonically (see [`write_canonical`]), reads the
+/// result again, and asserts that both instances carry the same information, i.e. trees,
+/// header, approximation parameters, stride parameters, and tree decomposition. Comments and
+/// the order of lines other than stride lines are not preserved.
+/// Panics with a descriptive message if the instance cannot be read or the round trip fails.
+///
+/// # Example
+/// ```
+/// pace26io::testing::assert_roundtrip("examples/tiny01.nw");
+/// ```
+pub fn assert_roundtrip(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let mut builder = BinTreeBuilder::default();
+    let original = Instance::try_read_path(path, &mut builder)
+        .unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()));
+
+    let canonical = write_canonical(&original);
+    let reread = Instance::try_read(canonical.as_bytes(), &mut builder).unwrap_or_else(|err| {
+        panic!(
+            "failed to re-read canonical form of {}: {err}\n{canonical}",
+            path.display()
+        )
+    });
+
+    let context = |what: &str| format!("{what} of {} changed in round trip", path.display());
+    assert_eq!(
+        reread.num_leaves,
+        original.num_leaves,
+        "{}",
+        context("number of leaves")
+    );
+    assert_eq!(reread.trees, original.trees, "{}", context("trees"));
+    assert_eq!(reread.approx, original.approx, "{}", context("approx line"));
+    assert_eq!(
+        reread.stride_params,
+        original.stride_params,
+        "{}",
+        context("stride parameters")
+    );
+    assert_eq!(
+        reread.multiplicities,
+        original.multiplicities,
+        "{}",
+        context("multiplicities")
+    );
+    assert_eq!(
+        reread.tree_decomposition,
+        original.tree_decomposition,
+        "{}",
+        context("tree decomposition")
+    );
+    assert_eq!(
+        reread.format_version,
+        original.format_version,
+        "{}",
+        context("format version")
+    );
+    assert_eq!(reread.labels, original.labels, "{}", context("leaf labels"));
+}
+
+/// Writes `instance` in the canonical line order: stride lines (in input order), header,
+/// approx line, trees, and tree decomposition
+pub fn write_canonical(instance: &Instance<BinTreeBuilder>) -> String {
+    let mut writer = InstanceWriter::new(Vec::new());
+    writer
+        .write_instance(instance)
+        .expect("writing into a buffer does not fail");
+    let buffer = writer
+        .into_inner()
+        .expect("writing into a buffer does not fail");
+    String::from_utf8(buffer).expect("instances are written as UTF-8")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn canonical() {
+        let input = "#p 2 3\n# comment\n#s name \"x\"\n#s weights [0.5, inf]\n((1,2),3);\n(1,(2,3));\n#a 1.5 2\n";
+        let instance =
+            Instance::try_read(input.as_bytes(), &mut BinTreeBuilder::default()).unwrap();
+        assert_eq!(
+            write_canonical(&instance),
+            "#s name \"x\"\n#s weights \"[0.5, inf]\"\n#p 2 3\n#a 1.5 2\n((1,2),3);\n(1,(2,3));\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to read")]
+    fn missing_file() {
+        assert_roundtrip("examples/does-not-exist.nw");
+    }
+}